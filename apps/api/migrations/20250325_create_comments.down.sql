@@ -0,0 +1,3 @@
+-- Add down migration script for comments
+DROP TABLE IF EXISTS comments;
+DROP TABLE IF EXISTS comment_threads;
@@ -0,0 +1,39 @@
+-- Add up migration script for comments
+-- Comment threads are anchored either to a block id plus text offsets,
+-- or to an opaque CRDT relative position produced by the editor.
+CREATE TABLE "comment_threads" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    document_id UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    block_id VARCHAR(255),
+    start_offset INTEGER,
+    end_offset INTEGER,
+    crdt_position TEXT,
+    quoted_text TEXT NOT NULL,
+    orphaned BOOLEAN NOT NULL DEFAULT FALSE,
+    resolved BOOLEAN NOT NULL DEFAULT FALSE,
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CHECK (
+        (block_id IS NOT NULL AND start_offset IS NOT NULL AND end_offset IS NOT NULL
+            AND start_offset >= 0 AND start_offset <= end_offset)
+        OR crdt_position IS NOT NULL
+    )
+);
+
+-- Create comments table, the earliest comment of a thread is its root
+CREATE TABLE "comments" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    thread_id UUID NOT NULL REFERENCES comment_threads(id) ON DELETE CASCADE,
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    edited BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- Create indexes to improve query performance
+CREATE INDEX comment_threads_document_id_idx ON comment_threads (document_id);
+CREATE INDEX comments_thread_id_idx ON comments (thread_id);
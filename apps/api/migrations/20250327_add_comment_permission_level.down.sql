@@ -0,0 +1,20 @@
+-- Postgres cannot drop an enum value, recreate the type without 'comment'
+-- and downgrade existing commenters to readers
+ALTER TYPE permission_level RENAME TO permission_level_old;
+
+CREATE TYPE permission_level AS ENUM ('read', 'readwrite', 'owner');
+
+ALTER TABLE document_permissions ALTER COLUMN permission_level DROP DEFAULT;
+
+ALTER TABLE document_permissions
+ALTER COLUMN permission_level TYPE permission_level
+USING (
+    CASE permission_level::text
+        WHEN 'comment' THEN 'read'
+        ELSE permission_level::text
+    END
+)::permission_level;
+
+ALTER TABLE document_permissions ALTER COLUMN permission_level SET DEFAULT 'read';
+
+DROP TYPE permission_level_old;
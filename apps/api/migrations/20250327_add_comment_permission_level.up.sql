@@ -0,0 +1,2 @@
+-- Add the comment level between read and readwrite
+ALTER TYPE permission_level ADD VALUE IF NOT EXISTS 'comment' BEFORE 'readwrite';
@@ -0,0 +1,7 @@
+-- Add down migration script for suggestions
+DROP TABLE IF EXISTS suggestions;
+DROP TYPE IF EXISTS suggestion_status;
+DROP TYPE IF EXISTS suggestion_kind;
+
+ALTER TABLE documents
+DROP COLUMN revision;
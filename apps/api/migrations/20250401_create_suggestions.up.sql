@@ -0,0 +1,31 @@
+-- Add up migration script for suggestions
+-- Documents get a revision counter so suggestions can record their base revision
+ALTER TABLE documents
+ADD COLUMN revision BIGINT NOT NULL DEFAULT 1;
+
+CREATE TYPE suggestion_kind AS ENUM ('insert', 'delete', 'replace');
+CREATE TYPE suggestion_status AS ENUM ('pending', 'accepted', 'rejected');
+
+-- Create suggestions table, ranges are character offsets within a block
+CREATE TABLE "suggestions" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    document_id UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind suggestion_kind NOT NULL,
+    block_id VARCHAR(255) NOT NULL,
+    start_offset INTEGER NOT NULL CHECK (start_offset >= 0),
+    end_offset INTEGER NOT NULL,
+    original_text TEXT NOT NULL DEFAULT '',
+    text TEXT NOT NULL DEFAULT '',
+    base_revision BIGINT NOT NULL,
+    status suggestion_status NOT NULL DEFAULT 'pending',
+    reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMP WITH TIME ZONE,
+    applied_revision BIGINT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CHECK (start_offset <= end_offset)
+);
+
+-- Create indexes to improve query performance
+CREATE INDEX suggestions_document_id_status_idx ON suggestions (document_id, status);
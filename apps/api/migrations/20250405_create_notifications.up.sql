@@ -0,0 +1,34 @@
+-- Add up migration script for notifications
+CREATE TYPE notification_type AS ENUM (
+    'document_shared',
+    'permission_changed',
+    'comment_reply',
+    'mention',
+    'access_request'
+);
+
+-- Create notifications table, type specific details (document title,
+-- permission level, comment excerpt...) are stored in data
+CREATE TABLE "notifications" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    notification_type notification_type NOT NULL,
+    document_id UUID REFERENCES documents(id) ON DELETE CASCADE,
+    data JSONB NOT NULL DEFAULT '{}',
+    read_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- Create notification preferences table, a missing row means enabled
+CREATE TABLE "notification_preferences" (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    notification_type notification_type NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (user_id, notification_type)
+);
+
+-- Create indexes to improve query performance
+CREATE INDEX notifications_user_id_created_at_idx ON notifications (user_id, created_at DESC);
+CREATE INDEX notifications_user_id_unread_idx ON notifications (user_id) WHERE read_at IS NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script for audit logs
+DROP TABLE IF EXISTS audit_logs;
+DROP FUNCTION IF EXISTS audit_logs_append_only();
@@ -0,0 +1,2 @@
+-- Add down migration script for refresh tokens
+DROP TABLE IF EXISTS "refresh_tokens";
@@ -0,0 +1,2 @@
+-- Add down migration script for magic links
+DROP TABLE IF EXISTS "magic_links";
@@ -0,0 +1,15 @@
+-- Add up migration script for magic links
+-- Passwordless sign-in links. Only the SHA-256 hash of the token is stored and a link
+-- can be consumed once. Links are kept after use so requests can be rate limited.
+CREATE TABLE "magic_links" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    email VARCHAR(255) NOT NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    ip_address VARCHAR(64),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    consumed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX magic_links_email_created_at_idx ON magic_links (LOWER(email), created_at);
+CREATE INDEX magic_links_ip_address_created_at_idx ON magic_links (ip_address, created_at);
@@ -0,0 +1,2 @@
+-- Add down migration script for email login codes
+DROP TABLE IF EXISTS "login_codes";
@@ -0,0 +1,16 @@
+-- Add up migration script for email login codes
+-- Six-digit one-time codes. Only an HMAC of the code is stored; a code expires quickly
+-- and is invalidated after too many wrong attempts or when a newer code is requested.
+CREATE TABLE "login_codes" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    email VARCHAR(255) NOT NULL,
+    code_hash VARCHAR(64) NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    ip_address VARCHAR(64),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    consumed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX login_codes_email_created_at_idx ON login_codes (LOWER(email), created_at);
+CREATE INDEX login_codes_ip_address_created_at_idx ON login_codes (ip_address, created_at);
@@ -0,0 +1,4 @@
+-- Add down migration script for two-factor authentication
+DROP TABLE IF EXISTS "role_policies";
+DROP TABLE IF EXISTS "recovery_codes";
+DROP TABLE IF EXISTS "user_totp";
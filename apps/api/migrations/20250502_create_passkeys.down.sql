@@ -0,0 +1,3 @@
+-- Add down migration script for WebAuthn passkeys
+DROP TABLE IF EXISTS "webauthn_challenges";
+DROP TABLE IF EXISTS "passkeys";
@@ -0,0 +1,3 @@
+-- Add down migration script for linked login identities
+-- PostgreSQL cannot drop a value from an enum, 'github' stays in auth_provider
+DROP TABLE IF EXISTS "user_identities";
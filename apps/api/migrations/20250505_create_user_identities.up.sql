@@ -0,0 +1,28 @@
+-- Add up migration script for linked login identities
+-- 'github' was missing from the original enum, GitHub sign-ups could not be stored.
+-- The new value cannot be used in this transaction, the backfill below only copies existing values.
+ALTER TYPE auth_provider ADD VALUE IF NOT EXISTS 'github';
+
+-- Login methods linked to a user, one row per provider
+CREATE TABLE "user_identities" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider auth_provider NOT NULL,
+    -- Subject ID at the provider, NULL for the local password identity
+    provider_user_id VARCHAR(255),
+    -- Email reported by the provider when the identity was linked
+    email VARCHAR(255),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    UNIQUE (user_id, provider)
+);
+
+CREATE UNIQUE INDEX user_identities_provider_subject_idx
+    ON user_identities (provider, provider_user_id)
+    WHERE provider_user_id IS NOT NULL;
+
+-- Existing accounts keep the login method they signed up with
+INSERT INTO user_identities (user_id, provider, provider_user_id, email, created_at)
+SELECT id, auth_provider, provider_user_id, email, COALESCE(created_at, NOW())
+FROM users
+WHERE auth_provider = 'local' OR provider_user_id IS NOT NULL;
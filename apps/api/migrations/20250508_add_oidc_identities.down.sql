@@ -0,0 +1,14 @@
+-- Add down migration script for generic OpenID Connect providers
+-- PostgreSQL cannot drop a value from an enum, 'oidc' stays in auth_provider
+DELETE FROM user_identities WHERE provider::text = 'oidc';
+
+DROP INDEX IF EXISTS user_identities_provider_name_subject_idx;
+ALTER TABLE user_identities DROP CONSTRAINT IF EXISTS user_identities_user_id_provider_name_key;
+
+ALTER TABLE user_identities ADD CONSTRAINT user_identities_user_id_provider_key
+    UNIQUE (user_id, provider);
+CREATE UNIQUE INDEX user_identities_provider_subject_idx
+    ON user_identities (provider, provider_user_id)
+    WHERE provider_user_id IS NOT NULL;
+
+ALTER TABLE user_identities DROP COLUMN provider_name;
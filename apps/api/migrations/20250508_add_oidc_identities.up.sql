@@ -0,0 +1,18 @@
+-- Add up migration script for generic OpenID Connect providers
+ALTER TYPE auth_provider ADD VALUE IF NOT EXISTS 'oidc';
+
+-- Several OIDC providers share the 'oidc' type, identities are told apart by the configured provider name.
+-- Built-in providers use their type as the name.
+ALTER TABLE user_identities ADD COLUMN provider_name VARCHAR(100);
+UPDATE user_identities SET provider_name = provider::text;
+ALTER TABLE user_identities ALTER COLUMN provider_name SET NOT NULL;
+
+ALTER TABLE user_identities DROP CONSTRAINT user_identities_user_id_provider_key;
+DROP INDEX user_identities_provider_subject_idx;
+
+ALTER TABLE user_identities ADD CONSTRAINT user_identities_user_id_provider_name_key
+    UNIQUE (user_id, provider_name);
+
+CREATE UNIQUE INDEX user_identities_provider_name_subject_idx
+    ON user_identities (provider_name, provider_user_id)
+    WHERE provider_user_id IS NOT NULL;
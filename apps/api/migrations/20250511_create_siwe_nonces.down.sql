@@ -0,0 +1,4 @@
+-- Add down migration script for Sign-In with Ethereum (EIP-4361)
+-- PostgreSQL cannot drop a value from an enum, 'ethereum' stays in auth_provider
+DROP TABLE IF EXISTS "siwe_nonces";
+DELETE FROM user_identities WHERE provider::text = 'ethereum';
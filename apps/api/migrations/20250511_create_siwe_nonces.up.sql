@@ -0,0 +1,14 @@
+-- Add up migration script for Sign-In with Ethereum (EIP-4361)
+ALTER TYPE auth_provider ADD VALUE IF NOT EXISTS 'ethereum';
+
+-- Nonces handed out for a wallet to sign, each one can be used once
+CREATE TABLE "siwe_nonces" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    nonce VARCHAR(64) NOT NULL UNIQUE,
+    -- Set when a signed-in user links a wallet, NULL for wallet login
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX siwe_nonces_expires_at_idx ON siwe_nonces (expires_at);
@@ -0,0 +1,3 @@
+-- Add down migration script for rate limiting and login lockout
+DROP TABLE IF EXISTS "login_lockouts";
+DROP TABLE IF EXISTS "rate_limit_hits";
@@ -0,0 +1,19 @@
+-- Add up migration script for rate limiting and login lockout
+-- Requests counted by the Postgres rate limit store, shared by all instances.
+-- Each row is one request inside the sliding window of its key.
+CREATE TABLE "rate_limit_hits" (
+    id BIGSERIAL PRIMARY KEY,
+    key VARCHAR(320) NOT NULL,
+    hit_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX rate_limit_hits_key_hit_at_idx ON rate_limit_hits (key, hit_at);
+CREATE INDEX rate_limit_hits_hit_at_idx ON rate_limit_hits (hit_at);
+
+-- Consecutive failed password logins of a user, the account is locked until locked_until
+CREATE TABLE "login_lockouts" (
+    user_id UUID NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMP WITH TIME ZONE
+);
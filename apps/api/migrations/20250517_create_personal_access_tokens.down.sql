@@ -0,0 +1,2 @@
+-- Add down migration script for personal access tokens
+DROP TABLE IF EXISTS "personal_access_tokens";
@@ -0,0 +1,2 @@
+-- Add down migration script for email changes
+DROP TABLE IF EXISTS "email_changes";
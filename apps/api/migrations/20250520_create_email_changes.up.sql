@@ -0,0 +1,19 @@
+-- Add up migration script for email changes
+-- Pending and completed email address changes. The confirmation token is sent to the new
+-- address and the revert token to the old one; only SHA-256 hashes are stored. Kept apart
+-- from users.verification_token so a pending password reset is not overwritten.
+CREATE TABLE "email_changes" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    old_email VARCHAR(255) NOT NULL,
+    new_email VARCHAR(255) NOT NULL,
+    confirm_token_hash VARCHAR(64) NOT NULL UNIQUE,
+    confirm_expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revert_token_hash VARCHAR(64) NOT NULL UNIQUE,
+    revert_expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    reverted_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX email_changes_user_id_idx ON email_changes (user_id);
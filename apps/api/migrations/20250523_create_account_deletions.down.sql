@@ -0,0 +1,8 @@
+-- Add down migration script for account deletions
+ALTER TABLE documents DROP CONSTRAINT documents_owner_id_fkey;
+ALTER TABLE documents
+    ADD CONSTRAINT documents_owner_id_fkey
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE;
+
+DROP TABLE IF EXISTS "account_deletions";
+DROP TYPE IF EXISTS document_disposition;
@@ -0,0 +1,23 @@
+-- Add up migration script for account deletions
+-- Self-service account deletion. A request waits for a grace period, logging in cancels it.
+-- Once due, owned documents are transferred or deleted as chosen and the user row is anonymized.
+CREATE TYPE document_disposition AS ENUM ('transfer', 'delete');
+
+CREATE TABLE "account_deletions" (
+    user_id UUID NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    scheduled_for TIMESTAMP WITH TIME ZONE NOT NULL,
+    document_disposition document_disposition NOT NULL,
+    -- New owner of the documents, required for 'transfer'
+    transfer_to UUID REFERENCES users(id) ON DELETE SET NULL,
+    completed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX account_deletions_scheduled_for_idx ON account_deletions (scheduled_for)
+    WHERE completed_at IS NULL;
+
+-- Deleting a user must not silently take shared documents with it
+ALTER TABLE documents DROP CONSTRAINT documents_owner_id_fkey;
+ALTER TABLE documents
+    ADD CONSTRAINT documents_owner_id_fkey
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE RESTRICT;
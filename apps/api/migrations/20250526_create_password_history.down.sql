@@ -0,0 +1,2 @@
+-- Add down migration script for password history
+DROP TABLE IF EXISTS "password_history";
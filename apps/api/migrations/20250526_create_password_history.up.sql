@@ -0,0 +1,11 @@
+-- Add up migration script for password history
+-- Hashes of the passwords a user had before, checked so old passwords are not reused.
+-- Only the most recent entries allowed by the policy are kept.
+CREATE TABLE "password_history" (
+    id UUID NOT NULL PRIMARY KEY DEFAULT (uuid_generate_v4()),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    password_hash VARCHAR(100) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX password_history_user_id_idx ON password_history (user_id, created_at DESC);
@@ -0,0 +1,10 @@
+-- Add down migration script for account deletion fallbacks
+CREATE OR REPLACE FUNCTION audit_logs_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE account_deletions DROP CONSTRAINT account_deletions_transfer_fallback_check;
+ALTER TABLE account_deletions DROP COLUMN transfer_fallback;
+DROP TYPE IF EXISTS transfer_fallback;
@@ -0,0 +1,28 @@
+-- Add up migration script for account deletion fallbacks
+-- A transfer chooses up front what happens to the documents when the recipient is
+-- deleted or scheduled for deletion by the time the account is deleted.
+CREATE TYPE transfer_fallback AS ENUM ('keep', 'delete');
+
+ALTER TABLE account_deletions ADD COLUMN transfer_fallback transfer_fallback;
+
+-- Requests scheduled before keep their documents, as they did until now
+UPDATE account_deletions SET transfer_fallback = 'keep'
+WHERE document_disposition = 'transfer';
+
+ALTER TABLE account_deletions
+    ADD CONSTRAINT account_deletions_transfer_fallback_check
+    CHECK ((document_disposition = 'transfer') = (transfer_fallback IS NOT NULL));
+
+-- A deleted account's address is erased from the audit log. Clearing actor_email
+-- is the only change allowed to an existing entry.
+CREATE OR REPLACE FUNCTION audit_logs_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+       AND NEW.actor_email IS NULL
+       AND to_jsonb(NEW) - 'actor_email' = to_jsonb(OLD) - 'actor_email' THEN
+        RETURN NEW;
+    END IF;
+
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
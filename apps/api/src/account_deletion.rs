@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::{
+    audit::{self, AuditEvent},
+    db::{AccountDeletionExt, DBClient},
+    models::AuditAction,
+    utils::{password, token},
+};
+
+/// 轮询到期注销请求的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// 每次轮询最多处理的注销请求数量
+const BATCH_SIZE: i64 = 20;
+
+/// 启动后台任务，注销宽限期已结束的账户
+///
+/// 每个账户在单独的事务中处理并锁定注销请求，多个实例同时运行时不会重复处理，
+/// 用户在处理前登录取消的请求会被跳过。
+pub fn spawn_deletion_worker(db_client: DBClient) {
+    tokio::spawn(async move {
+        loop {
+            match db_client.get_due_account_deletions(BATCH_SIZE).await {
+                Ok(user_ids) => {
+                    for user_id in user_ids {
+                        delete_account(&db_client, user_id).await;
+                    }
+                }
+                Err(e) => tracing::error!("获取到期的账户注销请求失败: {}", e),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+async fn delete_account(db_client: &DBClient, user_id: Uuid) {
+    // -- 用随机密码的哈希替换原密码，匿名化后的账户无法再用密码登录
+    let password_hash =
+        tokio::task::spawn_blocking(|| password::hash(token::generate_opaque_token()))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|hash| hash.map_err(|e| e.to_string()));
+    let password_hash = match password_hash {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::error!("注销账户 {} 时生成密码哈希失败: {}", user_id, e);
+            return;
+        }
+    };
+
+    let account = match db_client.delete_account(user_id, &password_hash).await {
+        Ok(Some(account)) => account,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("注销账户 {} 失败: {}", user_id, e);
+            return;
+        }
+    };
+
+    if account.transfer_failed {
+        tracing::warn!(
+            "账户 {} 指定的文档接收人已注销或申请注销，按用户的选择保留 {} 篇、删除 {} 篇文档",
+            account.email,
+            account.documents_kept,
+            account.documents_deleted
+        );
+    }
+
+    audit::record(
+        db_client,
+        AuditEvent::new(AuditAction::AccountDeleted)
+            .target("user", account.user_id)
+            .details(serde_json::json!({
+                "transferred_to": account.transferred_to,
+                "documents_transferred": account.documents_transferred,
+                "documents_deleted": account.documents_deleted,
+                "documents_kept": account.documents_kept,
+                "transfer_failed": account.transfer_failed,
+            })),
+    )
+    .await;
+
+    tracing::info!(
+        "账户 {} 已注销，转移文档 {} 篇，删除文档 {} 篇",
+        account.email,
+        account.documents_transferred,
+        account.documents_deleted
+    );
+}
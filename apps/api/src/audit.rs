@@ -0,0 +1,326 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use axum::{
+    Extension,
+    extract::{ConnectInfo, Request},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::TrustedProxies,
+    db::{AuditExt, DBClient, NewAuditLog},
+    models::{AuditAction, User},
+};
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// 发起请求的客户端信息，写入审计日志
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl RequestContext {
+    /// 客户端地址，限流等需要按地址计数的地方使用
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.ip_address.as_deref().and_then(|ip| ip.parse().ok())
+    }
+
+    fn from_request(req: &Request, proxies: &TrustedProxies) -> Self {
+        let headers = req.headers();
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        RequestContext {
+            ip_address: peer.map(|peer| client_ip(headers, peer, proxies).to_string()),
+            user_agent: headers
+                .get(axum::http::header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.chars().take(512).collect()),
+        }
+    }
+}
+
+/// 客户端地址
+///
+/// 只有连接来自可信代理时才读取转发头：从 `X-Forwarded-For` 右侧开始，跳过可信代理
+/// 追加的地址（或固定数量的代理层），第一个不可信的地址就是客户端。转发头中不是合法
+/// IP 的值到此为止，使用最后一个可信代理看到的地址。
+fn client_ip(headers: &HeaderMap, peer: IpAddr, proxies: &TrustedProxies) -> IpAddr {
+    let peer = peer.to_canonical();
+    if !proxies.trusts(peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+    for (hop, entry) in forwarded_for(headers).iter().rev().enumerate() {
+        let forwarded_by_proxy = match proxies.hops {
+            0 => proxies.contains(client),
+            hops => hop < hops,
+        };
+        if !forwarded_by_proxy {
+            break;
+        }
+
+        match entry.parse::<IpAddr>() {
+            Ok(ip) => client = ip.to_canonical(),
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+/// 转发头中的地址，`X-Forwarded-For` 可能出现多次，没有时使用 `X-Real-IP`
+fn forwarded_for(headers: &HeaderMap) -> Vec<&str> {
+    let values = if headers.contains_key("x-forwarded-for") {
+        headers.get_all("x-forwarded-for")
+    } else {
+        headers.get_all("x-real-ip")
+    };
+
+    values
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect()
+}
+
+/// 中间件 -- 记录当前请求的客户端信息，供后续写审计日志时使用
+///
+/// 信息保存在 task-local 中，仓储层等拿不到请求的代码也能记录 IP 和 User-Agent。
+pub async fn request_context(
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let context = RequestContext::from_request(&req, &app_state.env.trusted_proxies);
+    REQUEST_CONTEXT.scope(context, next.run(req)).await
+}
+
+/// 当前请求的客户端信息，不在请求中时返回空信息
+pub fn current_context() -> RequestContext {
+    REQUEST_CONTEXT
+        .try_with(|context| context.clone())
+        .unwrap_or_default()
+}
+
+/// 一条待写入的审计事件
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    action: AuditAction,
+    actor_id: Option<Uuid>,
+    actor_email: Option<String>,
+    target_type: Option<&'static str>,
+    target_id: Option<Uuid>,
+    details: serde_json::Value,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        AuditEvent {
+            action,
+            actor_id: None,
+            actor_email: None,
+            target_type: None,
+            target_id: None,
+            details: serde_json::json!({}),
+        }
+    }
+
+    /// 执行操作的用户
+    pub fn actor(mut self, user: &User) -> Self {
+        self.actor_id = Some(user.id);
+        self.actor_email = Some(user.email.clone());
+        self
+    }
+
+    /// 只知道用户 ID 时使用
+    pub fn actor_id(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    /// 未能识别用户时（例如登录失败）记录尝试的邮箱
+    pub fn actor_email(mut self, email: impl Into<String>) -> Self {
+        self.actor_email = Some(email.into());
+        self
+    }
+
+    /// 操作对象，`target_type` 为 `user`、`document` 等
+    pub fn target(mut self, target_type: &'static str, target_id: Uuid) -> Self {
+        self.target_type = Some(target_type);
+        self.target_id = Some(target_id);
+        self
+    }
+
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// 写入审计日志，并附带当前请求的 IP 和 User-Agent
+///
+/// 写入失败只记录错误日志，不影响业务操作。
+pub async fn record(db_client: &DBClient, event: AuditEvent) {
+    let context = current_context();
+    let action = event.action.to_str();
+
+    let entry = NewAuditLog {
+        actor_id: event.actor_id,
+        actor_email: event.actor_email,
+        action: action.to_string(),
+        target_type: event.target_type.map(str::to_string),
+        target_id: event.target_id,
+        details: event.details,
+        ip_address: context.ip_address,
+        user_agent: context.user_agent,
+    };
+
+    if let Err(e) = db_client.insert_audit_log(entry).await {
+        tracing::error!("写入审计日志失败 ({}): {}", action, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::config::IpNetwork;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn proxies(networks: &[&str], hops: usize) -> TrustedProxies {
+        TrustedProxies {
+            networks: networks
+                .iter()
+                .map(|network| IpNetwork::parse(network).unwrap())
+                .collect(),
+            hops,
+        }
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_headers_without_trusted_proxies() {
+        let headers = headers(&[("x-forwarded-for", "1.2.3.4"), ("x-real-ip", "5.6.7.8")]);
+        assert_eq!(
+            client_ip(&headers, ip("203.0.113.9"), &TrustedProxies::default()),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn ignores_forwarded_headers_from_untrusted_peers() {
+        let headers = headers(&[("x-forwarded-for", "1.2.3.4")]);
+        assert_eq!(
+            client_ip(&headers, ip("203.0.113.9"), &proxies(&["10.0.0.0/8"], 0)),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn skips_trusted_proxies_from_the_right() {
+        let headers = headers(&[("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.5")]);
+
+        assert_eq!(
+            client_ip(&headers, ip("127.0.0.1"), &proxies(&["127.0.0.1"], 0)),
+            ip("10.0.0.5")
+        );
+        // -- 最左边的地址由客户端填写，不可信
+        assert_eq!(
+            client_ip(
+                &headers,
+                ip("127.0.0.1"),
+                &proxies(&["127.0.0.1", "10.0.0.0/8"], 0)
+            ),
+            ip("1.2.3.4")
+        );
+    }
+
+    #[test]
+    fn counts_a_fixed_number_of_hops() {
+        let headers = headers(&[
+            ("x-forwarded-for", "6.6.6.6, 1.2.3.4"),
+            ("x-forwarded-for", "10.0.0.5"),
+        ]);
+
+        assert_eq!(
+            client_ip(&headers, ip("10.0.0.6"), &proxies(&[], 1)),
+            ip("10.0.0.5")
+        );
+        assert_eq!(
+            client_ip(&headers, ip("10.0.0.6"), &proxies(&[], 2)),
+            ip("1.2.3.4")
+        );
+        // -- 同时配置地址时，对端必须是其中之一
+        assert_eq!(
+            client_ip(&headers, ip("203.0.113.9"), &proxies(&["10.0.0.0/8"], 1)),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn stops_at_invalid_forwarded_values() {
+        let long = "x".repeat(200);
+        let headers = headers(&[("x-forwarded-for", &format!("1.2.3.4, {}", long))]);
+
+        let client = client_ip(&headers, ip("127.0.0.1"), &proxies(&["127.0.0.1"], 0));
+        assert_eq!(client, ip("127.0.0.1"));
+    }
+
+    #[test]
+    fn falls_back_to_x_real_ip() {
+        let headers = headers(&[("x-real-ip", "1.2.3.4")]);
+        assert_eq!(
+            client_ip(
+                &headers,
+                ip("::ffff:127.0.0.1"),
+                &proxies(&["127.0.0.1"], 0)
+            ),
+            ip("1.2.3.4")
+        );
+    }
+
+    #[test]
+    fn matches_networks() {
+        let network = IpNetwork::parse("10.0.0.0/8").unwrap();
+        assert!(network.contains(ip("10.255.0.1")));
+        assert!(network.contains(ip("::ffff:10.0.0.1")));
+        assert!(!network.contains(ip("11.0.0.1")));
+
+        let network = IpNetwork::parse("fd00::/8").unwrap();
+        assert!(network.contains(ip("fd12::1")));
+        assert!(!network.contains(ip("fe80::1")));
+
+        assert!(
+            IpNetwork::parse("0.0.0.0/0")
+                .unwrap()
+                .contains(ip("8.8.8.8"))
+        );
+        assert!(IpNetwork::parse("10.0.0.0/33").is_none());
+        assert!(IpNetwork::parse("localhost").is_none());
+    }
+}
@@ -1,18 +1,47 @@
 use std::env;
+use std::net::IpAddr;
+
+/// 内置登录方式的名称，OIDC 提供方不能使用
+const RESERVED_PROVIDER_NAMES: [&str; 5] = ["local", "google", "github", "oidc", "ethereum"];
 
 // -- 应用配置结构体
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
     pub server_port: u16,
+    /// HS256 访问令牌、登录验证码和 OAuth state 的签名密钥
     pub jwt_secret: String,
+    /// `jwt_secret` 来自 `JWT_SECRET_KEY`，而不是启动时随机生成的
+    pub jwt_secret_configured: bool,
+    /// RS256/EdDSA 签名密钥目录，未设置时访问令牌使用 HS256 和 `jwt_secret`
+    pub jwt_keys_dir: Option<String>,
+    /// 签发新令牌的密钥 ID (`<kid>.pem`)
+    pub jwt_active_key_id: Option<String>,
+    /// 令牌的 `iss`，校验时必须一致
+    pub jwt_issuer: String,
+    /// 令牌的 `aud`，校验时必须一致
+    pub jwt_audience: String,
     pub jwt_maxage: i64,
+    pub refresh_token_maxage_days: i64,
+    pub session_cache_seconds: u64,
+    pub magic_link_expires_minutes: i64,
+    pub magic_link_signup_enabled: bool,
+    pub login_code_expires_minutes: i64,
+    pub login_code_max_attempts: i32,
+    pub totp_issuer: String,
+    pub webauthn_rp_id: String,
+    pub webauthn_rp_name: String,
+    pub webauthn_origin: String,
+    pub siwe_domain: String,
+    pub siwe_chain_ids: Vec<u64>,
     pub frontend_url: String,
     pub log_dir: String,
     pub log_retention_days: u64,
     pub max_connections: u32,
     pub host: String,
     pub cors_allowed_origins: Vec<String>,
+    /// 可信的反向代理，只有经过它们的请求才读取转发头中的客户端地址
+    pub trusted_proxies: TrustedProxies,
     pub env: String,
     pub google_client_id: String,
     pub google_client_secret: String,
@@ -20,6 +49,17 @@ pub struct Config {
     pub github_client_id: String,
     pub github_client_secret: String,
     pub github_redirect_url: String,
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    pub rate_limit: RateLimitConfig,
+    pub password_policy: PasswordPolicyConfig,
+    pub event_buffer_size: usize,
+    pub webhook_max_attempts: i32,
+    pub webhook_retry_base_seconds: u64,
+    pub webhook_timeout_seconds: u64,
+    /// 允许 Webhook 地址指向回环、私有网络等内网地址，仅用于开发和测试
+    pub webhook_allow_private_networks: bool,
+    /// 注销账户的冷静期（天），期间重新登录即取消注销
+    pub account_deletion_grace_days: i64,
 }
 
 impl Config {
@@ -59,26 +99,122 @@ impl Config {
             });
 
         // JWT 配置
-        let jwt_secret = env::var("JWT_SECRET_KEY").unwrap_or_else(|_| {
+        let configured_secret = env::var("JWT_SECRET_KEY")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let jwt_secret_configured = configured_secret.is_some();
+        let jwt_secret = configured_secret.unwrap_or_else(|| {
             if env_mode == "production" {
                 eprintln!("警告: 生产环境中 JWT_SECRET_KEY 未设置，使用随机值");
             }
             uuid::Uuid::new_v4().to_string()
         });
 
+        // 非对称签名密钥，目录中每个 <kid>.pem 是一个私钥或已退役密钥的公钥
+        let jwt_keys_dir = env::var("JWT_KEYS_DIR").ok().filter(|dir| !dir.is_empty());
+        let jwt_active_key_id = env::var("JWT_ACTIVE_KEY_ID")
+            .ok()
+            .filter(|kid| !kid.is_empty());
+        if jwt_keys_dir.is_none() && env_mode == "production" {
+            eprintln!("警告: 生产环境中 JWT_KEYS_DIR 未设置，访问令牌使用 HS256 共享密钥签名");
+        }
+        let jwt_issuer = env::var("JWT_ISSUER").unwrap_or_else(|_| "doc-editor".to_string());
+        let jwt_audience =
+            env::var("JWT_AUDIENCE").unwrap_or_else(|_| "doc-editor-api".to_string());
+
+        // 访问令牌有效期（分钟），过期后通过刷新令牌换取新的访问令牌
         let jwt_maxage = env::var("JWT_MAXAGE")
-            .unwrap_or_else(|_| "60".to_string())
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: JWT_MAXAGE 解析失败，使用默认值 15");
+                15
+            });
+
+        let refresh_token_maxage_days = env::var("REFRESH_TOKEN_MAXAGE_DAYS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: REFRESH_TOKEN_MAXAGE_DAYS 解析失败，使用默认值 30");
+                30
+            });
+
+        // 会话验证结果的缓存时间（秒），其他实例吊销的会话最多延迟这么久失效
+        let session_cache_seconds = env::var("SESSION_CACHE_SECONDS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: SESSION_CACHE_SECONDS 解析失败，使用默认值 30");
+                30
+            });
+
+        // 免密登录链接的有效期（分钟），以及未注册的邮箱是否自动创建账户（同时适用于邮箱验证码登录）
+        let magic_link_expires_minutes = env::var("MAGIC_LINK_EXPIRES_MINUTES")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: MAGIC_LINK_EXPIRES_MINUTES 解析失败，使用默认值 15");
+                15
+            });
+
+        let magic_link_signup_enabled = env::var("MAGIC_LINK_SIGNUP_ENABLED")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        // 邮箱验证码登录：验证码有效期（分钟）和每个验证码允许的尝试次数
+        let login_code_expires_minutes = env::var("LOGIN_CODE_EXPIRES_MINUTES")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: LOGIN_CODE_EXPIRES_MINUTES 解析失败，使用默认值 5");
+                5
+            });
+
+        let login_code_max_attempts = env::var("LOGIN_CODE_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "5".to_string())
             .parse()
             .unwrap_or_else(|_| {
-                eprintln!("警告: JWT_MAXAGE 解析失败，使用默认值 60");
-                60
+                eprintln!("警告: LOGIN_CODE_MAX_ATTEMPTS 解析失败，使用默认值 5");
+                5
             });
 
+        // 两步验证：认证器应用中显示的服务名称
+        let totp_issuer = env::var("TOTP_ISSUER").unwrap_or_else(|_| "Doc Editor".to_string());
+
         // 前端 URL
         let frontend_url = env::var("FRONTEND_URL")
             .or_else(|_| env::var("VITE_PUBLIC_URL"))
             .unwrap_or_else(|_| "http://localhost:5173".to_string());
 
+        // WebAuthn 通行密钥：依赖方 ID 为前端域名，来源为前端地址
+        let webauthn_rp_id = env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| "localhost".to_string());
+        let webauthn_rp_name = env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| totp_issuer.clone());
+        let webauthn_origin = env::var("WEBAUTHN_ORIGIN").unwrap_or_else(|_| frontend_url.clone());
+
+        // 以太坊钱包登录 (EIP-4361)：签名消息中的域名必须是前端的主机名（含端口），
+        // 链 ID 必须在允许列表中
+        let siwe_domain = env::var("SIWE_DOMAIN").unwrap_or_else(|_| {
+            let without_scheme = frontend_url
+                .split_once("://")
+                .map_or(frontend_url.as_str(), |(_, rest)| rest);
+            without_scheme
+                .split('/')
+                .next()
+                .unwrap_or(without_scheme)
+                .to_string()
+        });
+        let siwe_chain_ids = env::var("SIWE_CHAIN_IDS")
+            .unwrap_or_else(|_| "1".to_string())
+            .split(',')
+            .filter_map(|id| {
+                let id = id.trim();
+                id.parse().ok().or_else(|| {
+                    eprintln!("警告: SIWE_CHAIN_IDS 中的链 ID {} 无效，已忽略", id);
+                    None
+                })
+            })
+            .collect();
+
         // CORS 配置
         let cors_allowed_origins = env::var("CORS_ALLOWED_ORIGINS")
             .unwrap_or_else(|_| frontend_url.clone())
@@ -86,6 +222,9 @@ impl Config {
             .map(|s| s.trim().to_string())
             .collect();
 
+        // 反向代理配置 -- 未配置时忽略 X-Forwarded-For，客户端地址一律取 TCP 连接的对端地址
+        let trusted_proxies = load_trusted_proxies();
+
         // 日志配置
         let log_dir = env::var("LOG_DIR").unwrap_or_else(|_| "./logs".to_string());
 
@@ -133,9 +272,81 @@ impl Config {
         let github_redirect_url = env::var("GITHUB_REDIRECT_URL")
             .unwrap_or_else(|_| format!("{}/api/auth/github/callback", frontend_url));
 
+        // 通用 OpenID Connect 登录提供方，可以配置任意多个
+        let oidc_providers = load_oidc_providers(&frontend_url);
+
+        // 登录、注册等接口的限流和密码错误锁定
+        let rate_limit = load_rate_limit();
+
+        // 注册、重置和修改密码时的密码策略
+        let password_policy = load_password_policy();
+
+        // 文档事件流配置 -- 服务端保留的历史事件数量，用于断线续传
+        let event_buffer_size = env::var("EVENT_BUFFER_SIZE")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: EVENT_BUFFER_SIZE 解析失败，使用默认值 1000");
+                1000
+            });
+
+        // Webhook 投递配置 -- 最大尝试次数、指数退避的基础间隔和请求超时
+        let webhook_max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: WEBHOOK_MAX_ATTEMPTS 解析失败，使用默认值 8");
+                8
+            });
+
+        let webhook_retry_base_seconds = env::var("WEBHOOK_RETRY_BASE_SECONDS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: WEBHOOK_RETRY_BASE_SECONDS 解析失败，使用默认值 30");
+                30
+            });
+
+        let webhook_timeout_seconds = env::var("WEBHOOK_TIMEOUT_SECONDS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: WEBHOOK_TIMEOUT_SECONDS 解析失败，使用默认值 10");
+                10
+            });
+
+        let webhook_allow_private_networks = env::var("WEBHOOK_ALLOW_PRIVATE_NETWORKS")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        let account_deletion_grace_days = env::var("ACCOUNT_DELETION_GRACE_DAYS")
+            .unwrap_or_else(|_| "14".to_string())
+            .parse()
+            .unwrap_or_else(|_| {
+                eprintln!("警告: ACCOUNT_DELETION_GRACE_DAYS 解析失败，使用默认值 14");
+                14
+            });
+
         Self {
             jwt_secret,
+            jwt_secret_configured,
+            jwt_keys_dir,
+            jwt_active_key_id,
+            jwt_issuer,
+            jwt_audience,
             jwt_maxage,
+            refresh_token_maxage_days,
+            session_cache_seconds,
+            magic_link_expires_minutes,
+            magic_link_signup_enabled,
+            login_code_expires_minutes,
+            login_code_max_attempts,
+            totp_issuer,
+            webauthn_rp_id,
+            webauthn_rp_name,
+            webauthn_origin,
+            siwe_domain,
+            siwe_chain_ids,
             database_url,
             server_port,
             frontend_url,
@@ -144,6 +355,7 @@ impl Config {
             max_connections,
             host,
             cors_allowed_origins,
+            trusted_proxies,
             env: env_mode,
             google_client_id,
             google_client_secret,
@@ -151,6 +363,340 @@ impl Config {
             github_client_id,
             github_client_secret,
             github_redirect_url,
+            oidc_providers,
+            rate_limit,
+            password_policy,
+            event_buffer_size,
+            webhook_max_attempts,
+            webhook_retry_base_seconds,
+            webhook_timeout_seconds,
+            webhook_allow_private_networks,
+            account_deletion_grace_days,
         }
     }
 }
+
+/// 通用 OpenID Connect 登录提供方配置
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    /// 提供方名称，用于登录地址和区分关联的身份，例如 `acme`
+    pub name: String,
+    /// 登录按钮上显示的名称
+    pub display_name: String,
+    /// 颁发者地址，从 `{issuer}/.well-known/openid-configuration` 发现其他端点
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+    pub scopes: Vec<String>,
+    /// ID 令牌中映射到用户名、邮箱和头像的声明
+    pub name_claim: String,
+    pub email_claim: String,
+    pub picture_claim: String,
+}
+
+/// 读取 `OIDC_PROVIDERS` 中列出的提供方
+///
+/// 每个提供方的配置使用 `OIDC_<名称>_` 前缀，名称转为大写、`-` 替换为 `_`，
+/// 例如 `acme` 的颁发者为 `OIDC_ACME_ISSUER`。缺少必要配置的提供方会被忽略。
+fn load_oidc_providers(frontend_url: &str) -> Vec<OidcProviderConfig> {
+    let Ok(names) = env::var("OIDC_PROVIDERS") else {
+        return Vec::new();
+    };
+
+    let mut providers: Vec<OidcProviderConfig> = Vec::new();
+    for name in names.split(',').map(|name| name.trim().to_lowercase()) {
+        if name.is_empty() {
+            continue;
+        }
+        let valid_name = name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name || RESERVED_PROVIDER_NAMES.contains(&name.as_str()) {
+            eprintln!("警告: OIDC 提供方名称 {} 无效，已忽略", name);
+            continue;
+        }
+        if providers.iter().any(|provider| provider.name == name) {
+            continue;
+        }
+
+        let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+        let var = |key: &str| {
+            env::var(format!("{}{}", prefix, key))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+
+        let (Some(issuer), Some(client_id)) = (var("ISSUER"), var("CLIENT_ID")) else {
+            eprintln!(
+                "警告: OIDC 提供方 {} 缺少 {}ISSUER 或 {}CLIENT_ID，已忽略",
+                name, prefix, prefix
+            );
+            continue;
+        };
+
+        let mut scopes: Vec<String> = var("SCOPES")
+            .unwrap_or_else(|| "openid email profile".to_string())
+            .split([' ', ','])
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect();
+        // -- 没有 openid 范围时提供方不会返回 ID 令牌
+        if !scopes.iter().any(|scope| scope == "openid") {
+            scopes.insert(0, "openid".to_string());
+        }
+
+        providers.push(OidcProviderConfig {
+            display_name: var("DISPLAY_NAME").unwrap_or_else(|| name.clone()),
+            issuer,
+            client_id,
+            client_secret: var("CLIENT_SECRET").unwrap_or_default(),
+            redirect_url: var("REDIRECT_URL")
+                .unwrap_or_else(|| format!("{}/api/auth/oidc/{}/callback", frontend_url, name)),
+            scopes,
+            name_claim: var("NAME_CLAIM").unwrap_or_else(|| "name".to_string()),
+            email_claim: var("EMAIL_CLAIM").unwrap_or_else(|| "email".to_string()),
+            picture_claim: var("PICTURE_CLAIM").unwrap_or_else(|| "picture".to_string()),
+            name,
+        });
+    }
+
+    providers
+}
+
+/// 限流计数的保存位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateLimitStoreKind {
+    /// 保存在本实例内存中，重启后清空
+    Memory,
+    /// 保存在 Postgres 中，多实例部署时共享
+    Postgres,
+}
+
+/// 滑动窗口：任意 `window_seconds` 秒内最多 `limit` 次请求
+#[derive(Debug, Clone, Copy)]
+pub struct SlidingWindow {
+    pub limit: u32,
+    pub window_seconds: u64,
+}
+
+/// 单个接口的限流规则，`None` 表示不按该维度限流
+#[derive(Debug, Clone, Copy)]
+pub struct RouteRateLimit {
+    pub by_ip: Option<SlidingWindow>,
+    /// 按请求体中的 `email` 字段限流
+    pub by_email: Option<SlidingWindow>,
+}
+
+/// 地址或网段，例如 `10.0.0.1`、`10.0.0.0/8`、`fd00::/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNetwork {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr.trim().parse().ok()?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse().ok().filter(|p| *p <= max_prefix)?,
+            None => max_prefix,
+        };
+
+        Some(IpNetwork { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                network.to_bits() & mask == ip.to_bits() & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                network.to_bits() & mask == ip.to_bits() & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 可信的反向代理
+///
+/// 两种配置方式：列出代理的地址或网段，从 `X-Forwarded-For` 右侧跳过这些地址；
+/// 或者给出固定经过的代理层数，取右起第 N 个地址。都未配置时不读取转发头。
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    pub networks: Vec<IpNetwork>,
+    pub hops: usize,
+}
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// 是否读取来自 `peer` 的请求中的转发头
+    pub fn trusts(&self, peer: IpAddr) -> bool {
+        if self.networks.is_empty() {
+            self.hops > 0
+        } else {
+            self.contains(peer)
+        }
+    }
+}
+
+/// 读取可信代理配置，`TRUSTED_PROXIES` 为逗号分隔的地址或网段
+fn load_trusted_proxies() -> TrustedProxies {
+    let networks = env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .filter_map(|value| {
+            IpNetwork::parse(value).or_else(|| {
+                eprintln!("警告: TRUSTED_PROXIES 中的地址 {} 无效，已忽略", value);
+                None
+            })
+        })
+        .collect();
+
+    let hops = env::var("TRUSTED_PROXY_HOPS")
+        .ok()
+        .map(|value| {
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("警告: TRUSTED_PROXY_HOPS 解析失败，使用默认值 0");
+                0
+            })
+        })
+        .unwrap_or(0);
+
+    TrustedProxies { networks, hops }
+}
+
+/// 登录相关接口的限流和账户锁定配置
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub store: RateLimitStoreKind,
+    pub login: RouteRateLimit,
+    pub register: RouteRateLimit,
+    pub forgot_password: RouteRateLimit,
+    pub resend_verification: RouteRateLimit,
+    /// 申请文档访问权限，邮箱维度按申请人的账户计数
+    pub access_request: RouteRateLimit,
+    /// 获取钱包登录的随机数，只按 IP 限流
+    pub ethereum_nonce: RouteRateLimit,
+    /// 连续密码错误达到该次数后锁定账户
+    pub lockout_threshold: i32,
+    /// 首次锁定的时长，之后每多错一次翻倍
+    pub lockout_base_seconds: u64,
+    pub lockout_max_seconds: u64,
+}
+
+/// 读取限流配置
+///
+/// 每个接口按 IP 和邮箱分别配置，格式为 `次数/秒数`，例如 `RATE_LIMIT_LOGIN_IP=20/300`，
+/// 设置为 `off` 时关闭该维度的限流。
+fn load_rate_limit() -> RateLimitConfig {
+    let store = match env::var("RATE_LIMIT_STORE").as_deref() {
+        Ok("postgres") => RateLimitStoreKind::Postgres,
+        Ok("memory") | Err(_) => RateLimitStoreKind::Memory,
+        Ok(other) => {
+            eprintln!("警告: RATE_LIMIT_STORE {} 无效，使用内存存储", other);
+            RateLimitStoreKind::Memory
+        }
+    };
+
+    let route = |name: &str, by_ip: &str, by_email: &str| RouteRateLimit {
+        by_ip: sliding_window(&format!("RATE_LIMIT_{}_IP", name), by_ip),
+        by_email: sliding_window(&format!("RATE_LIMIT_{}_EMAIL", name), by_email),
+    };
+
+    let number = |key: &str, default: u64| {
+        env::var(key)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+
+    RateLimitConfig {
+        store,
+        login: route("LOGIN", "20/300", "10/300"),
+        register: route("REGISTER", "5/3600", "3/3600"),
+        forgot_password: route("FORGOT_PASSWORD", "10/3600", "3/3600"),
+        resend_verification: route("RESEND_VERIFICATION", "10/3600", "3/3600"),
+        access_request: route("ACCESS_REQUEST", "30/3600", "10/3600"),
+        ethereum_nonce: route("ETHEREUM_NONCE", "30/300", "off"),
+        lockout_threshold: number("LOGIN_LOCKOUT_THRESHOLD", 5).max(1) as i32,
+        lockout_base_seconds: number("LOGIN_LOCKOUT_BASE_SECONDS", 60),
+        lockout_max_seconds: number("LOGIN_LOCKOUT_MAX_SECONDS", 3600),
+    }
+}
+
+fn sliding_window(key: &str, default: &str) -> Option<SlidingWindow> {
+    let value = env::var(key).unwrap_or_else(|_| default.to_string());
+    if value.trim() == "off" {
+        return None;
+    }
+
+    parse_sliding_window(&value).or_else(|| {
+        eprintln!("警告: {} 解析失败，使用默认值 {}", key, default);
+        parse_sliding_window(default)
+    })
+}
+
+fn parse_sliding_window(value: &str) -> Option<SlidingWindow> {
+    let (limit, seconds) = value.split_once('/')?;
+    Some(SlidingWindow {
+        limit: limit.trim().parse().ok()?,
+        window_seconds: seconds.trim().parse().ok().filter(|seconds| *seconds > 0)?,
+    })
+}
+
+/// 设置新密码时的密码策略
+#[derive(Debug, Clone)]
+pub struct PasswordPolicyConfig {
+    /// 最小长度（字符数）
+    pub min_length: usize,
+    /// 最低强度评分，0 到 4，0 表示不检查强度
+    pub min_score: u8,
+    /// 禁止密码包含用户名或邮箱
+    pub forbid_personal_info: bool,
+    /// 不能与最近 N 次使用过的密码（包括当前密码）相同，0 表示不检查
+    pub history_size: i64,
+    /// 泄露密码列表文件：按升序排列的 SHA-1 摘要，每条 20 字节
+    pub breached_list_path: Option<String>,
+}
+
+/// 读取密码策略配置
+fn load_password_policy() -> PasswordPolicyConfig {
+    let number = |key: &str, default: i64| {
+        env::var(key)
+            .ok()
+            .map(|value| {
+                value.parse().unwrap_or_else(|_| {
+                    eprintln!("警告: {} 解析失败，使用默认值 {}", key, default);
+                    default
+                })
+            })
+            .unwrap_or(default)
+            .max(0)
+    };
+
+    PasswordPolicyConfig {
+        min_length: number("PASSWORD_MIN_LENGTH", 8) as usize,
+        min_score: number("PASSWORD_MIN_SCORE", 2).min(4) as u8,
+        forbid_personal_info: env::var("PASSWORD_FORBID_PERSONAL_INFO")
+            .map(|value| value != "false")
+            .unwrap_or(true),
+        history_size: number("PASSWORD_HISTORY_SIZE", 5),
+        breached_list_path: env::var("PASSWORD_BREACHED_LIST")
+            .ok()
+            .filter(|path| !path.trim().is_empty()),
+    }
+}
@@ -4,12 +4,48 @@ use std::sync::Arc;
 use std::time::Duration;
 
 // Module declarations
+mod access_token;
+mod account_deletion;
+mod audit;
+mod comment;
 mod document;
+mod email_change;
+mod identity;
+mod login_code;
+mod magic_link;
+mod notification;
+mod passkey;
+mod password_history;
+mod rate_limit;
+mod refresh_token;
+mod session;
+mod siwe;
+mod suggestion;
+mod two_factor;
 mod user;
+mod webhook;
 
 // Public re-exports
+pub use access_token::AccessTokenExt;
+pub use account_deletion::{AccountDeletionExt, DeletedAccount};
+pub use audit::{AuditExt, AuditLogFilter, NewAuditLog};
+pub use comment::{COMMENT_PERMISSION, CommentExt};
 pub use document::DocumentExt;
+pub use email_change::EmailChangeExt;
+pub use identity::IdentityExt;
+pub use login_code::LoginCodeExt;
+pub use magic_link::MagicLinkExt;
+pub use notification::NotificationExt;
+pub use passkey::{NewPasskey, PasskeyExt};
+pub use password_history::PasswordHistoryExt;
+pub use rate_limit::RateLimitExt;
+pub use refresh_token::{RefreshTokenExt, RefreshTokenRotation};
+pub use session::SessionExt;
+pub use siwe::SiweExt;
+pub use suggestion::SuggestionExt;
+pub use two_factor::TwoFactorExt;
 pub use user::UserExt;
+pub use webhook::{WebhookAttemptResult, WebhookExt};
 
 /// Database client that provides access to all repositories
 ///
@@ -68,6 +104,9 @@ pub enum DbError {
     #[error("Permission denied for resource")]
     PermissionDenied,
 
+    #[error("Invalid permission change: {0}")]
+    InvalidPermissionChange(String),
+
     #[error("Email already exists")]
     EmailExists,
 
@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::{PersonalAccessToken, TokenScope};
+
+/// Personal access token row, scopes are stored as text
+struct AccessTokenRow {
+    id: Uuid,
+    user_id: Uuid,
+    name: String,
+    token_prefix: String,
+    scopes: Vec<String>,
+    expires_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    last_used_ip: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AccessTokenRow> for PersonalAccessToken {
+    fn from(row: AccessTokenRow) -> Self {
+        PersonalAccessToken {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            token_prefix: row.token_prefix,
+            // Unknown scopes grant nothing
+            scopes: row
+                .scopes
+                .iter()
+                .filter_map(|scope| TokenScope::parse(scope))
+                .collect(),
+            expires_at: row.expires_at,
+            last_used_at: row.last_used_at,
+            last_used_ip: row.last_used_ip,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Personal access token database operations extension trait
+///
+/// Only the SHA-256 hash of a token is stored, the token itself is shown once on creation.
+#[async_trait]
+pub trait AccessTokenExt {
+    /// Create a personal access token
+    ///
+    /// # Arguments
+    /// * `user_id` - Owner of the token
+    /// * `name` - Name chosen by the user
+    /// * `token_hash` - SHA-256 hash of the token
+    /// * `token_prefix` - Start of the token, shown in listings
+    /// * `scopes` - Permissions granted to the token
+    /// * `expires_at` - When the token stops working
+    async fn create_access_token(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        token_hash: &str,
+        token_prefix: &str,
+        scopes: &[TokenScope],
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<PersonalAccessToken>;
+
+    /// Get the active tokens of a user, newest first
+    async fn get_user_access_tokens(&self, user_id: Uuid) -> DbResult<Vec<PersonalAccessToken>>;
+
+    /// Look up an active token by hash and record its use
+    ///
+    /// # Returns
+    /// * `Ok(Some(token))` - The token is active, `last_used_at` was updated
+    /// * `Ok(None)` - Unknown, expired or revoked token
+    async fn use_access_token(
+        &self,
+        token_hash: &str,
+        ip_address: Option<String>,
+    ) -> DbResult<Option<PersonalAccessToken>>;
+
+    /// Revoke one token of a user
+    ///
+    /// # Returns
+    /// * `Err(DbError::NotFound)` - The user has no such active token
+    async fn revoke_access_token(&self, token_id: Uuid, user_id: Uuid) -> DbResult<()>;
+
+    /// Revoke every active token of a user
+    ///
+    /// # Returns
+    /// The number of revoked tokens
+    async fn revoke_user_access_tokens(&self, user_id: Uuid) -> DbResult<u64>;
+}
+
+#[async_trait]
+impl AccessTokenExt for DBClient {
+    async fn create_access_token(
+        &self,
+        user_id: Uuid,
+        name: &str,
+        token_hash: &str,
+        token_prefix: &str,
+        scopes: &[TokenScope],
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<PersonalAccessToken> {
+        let scopes: Vec<String> = scopes
+            .iter()
+            .map(|scope| scope.to_str().to_string())
+            .collect();
+
+        let token = sqlx::query_as!(
+            AccessTokenRow,
+            r#"
+            INSERT INTO personal_access_tokens
+                (user_id, name, token_hash, token_prefix, scopes, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, name, token_prefix, scopes, expires_at, last_used_at,
+                      last_used_ip, created_at
+            "#,
+            user_id,
+            name,
+            token_hash,
+            token_prefix,
+            &scopes,
+            expires_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(token.into())
+    }
+
+    async fn get_user_access_tokens(&self, user_id: Uuid) -> DbResult<Vec<PersonalAccessToken>> {
+        let tokens = sqlx::query_as!(
+            AccessTokenRow,
+            r#"
+            SELECT id, user_id, name, token_prefix, scopes, expires_at, last_used_at,
+                   last_used_ip, created_at
+            FROM personal_access_tokens
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(tokens.into_iter().map(Into::into).collect())
+    }
+
+    async fn use_access_token(
+        &self,
+        token_hash: &str,
+        ip_address: Option<String>,
+    ) -> DbResult<Option<PersonalAccessToken>> {
+        let token = sqlx::query_as!(
+            AccessTokenRow,
+            r#"
+            UPDATE personal_access_tokens
+            SET last_used_at = NOW(), last_used_ip = COALESCE($2, last_used_ip)
+            WHERE token_hash = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            RETURNING id, user_id, name, token_prefix, scopes, expires_at, last_used_at,
+                      last_used_ip, created_at
+            "#,
+            token_hash,
+            ip_address
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(token.map(Into::into))
+    }
+
+    async fn revoke_access_token(&self, token_id: Uuid, user_id: Uuid) -> DbResult<()> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            token_id,
+            user_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound("Access token not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn revoke_user_access_tokens(&self, user_id: Uuid) -> DbResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected())
+    }
+}
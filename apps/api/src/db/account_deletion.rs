@@ -0,0 +1,694 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::{AccountDeletion, DocumentDisposition, TransferFallback};
+
+/// Outcome of a completed account deletion
+#[derive(Debug, Clone)]
+pub struct DeletedAccount {
+    pub user_id: Uuid,
+    /// Address of the account before it was anonymized
+    pub email: String,
+    /// New owner of the documents, `None` when nothing was transferred
+    pub transferred_to: Option<Uuid>,
+    pub documents_transferred: u64,
+    pub documents_deleted: u64,
+    /// Documents left with the anonymized account because the transfer target is gone
+    pub documents_kept: i64,
+    /// The transfer target was gone and the chosen fallback was applied
+    pub transfer_failed: bool,
+}
+
+/// Account deletion database operations extension trait
+///
+/// A scheduled deletion stays pending until its grace period ends. The user row is
+/// anonymized rather than deleted, so comments and suggestions left on documents of
+/// other users keep their author.
+#[async_trait]
+pub trait AccountDeletionExt {
+    /// Schedule the deletion of an account, replacing a pending request of the user
+    ///
+    /// # Arguments
+    /// * `user_id` - Account to delete
+    /// * `scheduled_for` - End of the grace period
+    /// * `document_disposition` - What happens to the documents owned by the user
+    /// * `transfer_to` - New owner of the documents, required for `Transfer`
+    /// * `transfer_fallback` - What happens to the documents when the new owner is gone
+    ///   by the deletion time, required for `Transfer`
+    async fn schedule_account_deletion(
+        &self,
+        user_id: Uuid,
+        scheduled_for: DateTime<Utc>,
+        document_disposition: DocumentDisposition,
+        transfer_to: Option<Uuid>,
+        transfer_fallback: Option<TransferFallback>,
+    ) -> DbResult<AccountDeletion>;
+
+    /// Get the deletion request of a user, pending or completed
+    async fn get_account_deletion(&self, user_id: Uuid) -> DbResult<Option<AccountDeletion>>;
+
+    /// Cancel a pending deletion
+    ///
+    /// # Returns
+    /// * `Ok(true)` - A pending deletion was cancelled
+    /// * `Ok(false)` - The user had no pending deletion
+    async fn cancel_account_deletion(&self, user_id: Uuid) -> DbResult<bool>;
+
+    /// Get the users whose grace period has ended, oldest first
+    async fn get_due_account_deletions(&self, limit: i64) -> DbResult<Vec<Uuid>>;
+
+    /// Carry out a due deletion in one transaction
+    ///
+    /// Owned documents are transferred or deleted, sign-in methods, sessions, tokens,
+    /// personal settings and pending sign-in links or codes are removed, the address is
+    /// cleared from the audit log and the user row is anonymized. When the transfer
+    /// target has been deleted or is scheduled for deletion, the fallback chosen with
+    /// the request is applied.
+    ///
+    /// # Arguments
+    /// * `user_id` - Account to delete
+    /// * `password_hash` - Unusable password hash replacing the user's password
+    ///
+    /// # Returns
+    /// * `Ok(Some(account))` - The account was deleted
+    /// * `Ok(None)` - No due deletion, e.g. it was cancelled by a login meanwhile
+    async fn delete_account(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+    ) -> DbResult<Option<DeletedAccount>>;
+}
+
+#[async_trait]
+impl AccountDeletionExt for DBClient {
+    async fn schedule_account_deletion(
+        &self,
+        user_id: Uuid,
+        scheduled_for: DateTime<Utc>,
+        document_disposition: DocumentDisposition,
+        transfer_to: Option<Uuid>,
+        transfer_fallback: Option<TransferFallback>,
+    ) -> DbResult<AccountDeletion> {
+        let deletion = sqlx::query_as!(
+            AccountDeletion,
+            r#"
+            INSERT INTO account_deletions
+                (user_id, scheduled_for, document_disposition, transfer_to, transfer_fallback)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id) DO UPDATE
+            SET scheduled_for = EXCLUDED.scheduled_for,
+                document_disposition = EXCLUDED.document_disposition,
+                transfer_to = EXCLUDED.transfer_to,
+                transfer_fallback = EXCLUDED.transfer_fallback,
+                completed_at = NULL,
+                created_at = NOW()
+            RETURNING user_id, scheduled_for,
+                      document_disposition AS "document_disposition: DocumentDisposition",
+                      transfer_to,
+                      transfer_fallback AS "transfer_fallback: TransferFallback",
+                      completed_at, created_at
+            "#,
+            user_id,
+            scheduled_for,
+            document_disposition as DocumentDisposition,
+            transfer_to,
+            transfer_fallback as Option<TransferFallback>
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(deletion)
+    }
+
+    async fn get_account_deletion(&self, user_id: Uuid) -> DbResult<Option<AccountDeletion>> {
+        let deletion = sqlx::query_as!(
+            AccountDeletion,
+            r#"
+            SELECT user_id, scheduled_for,
+                   document_disposition AS "document_disposition: DocumentDisposition",
+                   transfer_to,
+                   transfer_fallback AS "transfer_fallback: TransferFallback",
+                   completed_at, created_at
+            FROM account_deletions
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(deletion)
+    }
+
+    async fn cancel_account_deletion(&self, user_id: Uuid) -> DbResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM account_deletions
+            WHERE user_id = $1 AND completed_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_due_account_deletions(&self, limit: i64) -> DbResult<Vec<Uuid>> {
+        let user_ids = sqlx::query_scalar!(
+            r#"
+            SELECT user_id
+            FROM account_deletions
+            WHERE completed_at IS NULL AND scheduled_for <= NOW()
+            ORDER BY scheduled_for
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(user_ids)
+    }
+
+    async fn delete_account(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+    ) -> DbResult<Option<DeletedAccount>> {
+        let mut tx = self.begin_transaction().await?;
+
+        // Lock the request, a concurrent login cancelling it or another instance
+        // processing it waits for this transaction
+        let deletion = sqlx::query_as!(
+            AccountDeletion,
+            r#"
+            SELECT user_id, scheduled_for,
+                   document_disposition AS "document_disposition: DocumentDisposition",
+                   transfer_to,
+                   transfer_fallback AS "transfer_fallback: TransferFallback",
+                   completed_at, created_at
+            FROM account_deletions
+            WHERE user_id = $1 AND completed_at IS NULL AND scheduled_for <= NOW()
+            FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let Some(deletion) = deletion else {
+            return Ok(None);
+        };
+
+        let email = sqlx::query_scalar!(
+            r#"
+            SELECT email FROM users WHERE id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let mut transferred_to = None;
+        let mut documents_transferred = 0;
+        let mut documents_deleted = 0;
+        let mut documents_kept = 0;
+        let mut transfer_failed = false;
+
+        match deletion.document_disposition {
+            DocumentDisposition::Transfer => {
+                // The target must still be an active account, neither deleted nor
+                // waiting for its own deletion
+                let target = sqlx::query_scalar!(
+                    r#"
+                    SELECT u.id
+                    FROM users u
+                    WHERE u.id = $1
+                      AND NOT EXISTS (
+                          SELECT 1 FROM account_deletions d WHERE d.user_id = u.id
+                      )
+                    "#,
+                    deletion.transfer_to
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(DbError::from)?;
+
+                if let Some(target) = target {
+                    // The new owner no longer needs a share on its own documents
+                    sqlx::query!(
+                        r#"
+                        DELETE FROM document_permissions p
+                        USING documents d
+                        WHERE p.document_id = d.id AND d.owner_id = $1 AND p.user_id = $2
+                        "#,
+                        user_id,
+                        target
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(DbError::from)?;
+
+                    documents_transferred = sqlx::query!(
+                        r#"
+                        UPDATE documents SET owner_id = $2, updated_at = NOW()
+                        WHERE owner_id = $1
+                        "#,
+                        user_id,
+                        target
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(DbError::from)?
+                    .rows_affected();
+
+                    transferred_to = Some(target);
+                } else {
+                    transfer_failed = true;
+
+                    match deletion.transfer_fallback {
+                        Some(TransferFallback::Delete) => {
+                            documents_deleted = delete_owned_documents(&mut tx, user_id).await?;
+                        }
+                        Some(TransferFallback::Keep) | None => {
+                            documents_kept = sqlx::query_scalar!(
+                                r#"
+                                SELECT COUNT(*) AS "count!" FROM documents WHERE owner_id = $1
+                                "#,
+                                user_id
+                            )
+                            .fetch_one(&mut *tx)
+                            .await
+                            .map_err(DbError::from)?;
+                        }
+                    }
+                }
+            }
+            DocumentDisposition::Delete => {
+                documents_deleted = delete_owned_documents(&mut tx, user_id).await?;
+            }
+        }
+
+        // Pending sign-in links and codes are keyed by the address, not the user
+        sqlx::query!(
+            r#"
+            WITH ml AS (DELETE FROM magic_links WHERE LOWER(email) = LOWER($1))
+            DELETE FROM login_codes WHERE LOWER(email) = LOWER($1)
+            "#,
+            email
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE audit_logs SET actor_email = NULL
+            WHERE actor_id = $1 AND actor_email IS NOT NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        // Sign-in methods, credentials and personal settings
+        sqlx::query!(
+            r#"
+            WITH
+                s AS (DELETE FROM sessions WHERE user_id = $1),
+                rt AS (DELETE FROM refresh_tokens WHERE user_id = $1),
+                pat AS (DELETE FROM personal_access_tokens WHERE user_id = $1),
+                ui AS (DELETE FROM user_identities WHERE user_id = $1),
+                pk AS (DELETE FROM passkeys WHERE user_id = $1),
+                ph AS (DELETE FROM password_history WHERE user_id = $1),
+                wc AS (DELETE FROM webauthn_challenges WHERE user_id = $1),
+                totp AS (DELETE FROM user_totp WHERE user_id = $1),
+                rc AS (DELETE FROM recovery_codes WHERE user_id = $1),
+                sn AS (DELETE FROM siwe_nonces WHERE user_id = $1),
+                ll AS (DELETE FROM login_lockouts WHERE user_id = $1),
+                ec AS (DELETE FROM email_changes WHERE user_id = $1),
+                wh AS (DELETE FROM webhooks WHERE user_id = $1),
+                n AS (DELETE FROM notifications WHERE user_id = $1),
+                np AS (DELETE FROM notification_preferences WHERE user_id = $1)
+            DELETE FROM document_permissions WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET name = 'Deleted user',
+                email = 'deleted-' || id || '@deleted.invalid',
+                password = $2,
+                verified = false,
+                role = 'user',
+                provider_user_id = NULL,
+                profile_picture = NULL,
+                verification_token = NULL,
+                token_expires_at = NULL,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE account_deletions SET completed_at = NOW() WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(Some(DeletedAccount {
+            user_id,
+            email,
+            transferred_to,
+            documents_transferred,
+            documents_deleted,
+            documents_kept,
+            transfer_failed,
+        }))
+    }
+}
+
+/// Delete the documents owned by a user with their comments and shares
+async fn delete_owned_documents(conn: &mut PgConnection, user_id: Uuid) -> DbResult<u64> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM documents WHERE owner_id = $1
+        "#,
+        user_id
+    )
+    .execute(conn)
+    .await
+    .map_err(DbError::from)?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::{AuditExt, DocumentExt, NewAuditLog, UserExt};
+    use crate::models::PermissionLevel;
+
+    async fn user(db_client: &DBClient, email: &str) -> Uuid {
+        db_client
+            .save_user("user", email, "hash", "token", Utc::now())
+            .await
+            .unwrap()
+            .id
+    }
+
+    /// 申请一个已经到期的注销
+    async fn schedule_due(
+        db_client: &DBClient,
+        user_id: Uuid,
+        document_disposition: DocumentDisposition,
+        transfer_to: Option<Uuid>,
+        transfer_fallback: Option<TransferFallback>,
+    ) {
+        db_client
+            .schedule_account_deletion(
+                user_id,
+                Utc::now() - Duration::seconds(1),
+                document_disposition,
+                transfer_to,
+                transfer_fallback,
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn owner_of(db_client: &DBClient, document_id: Uuid) -> Option<Uuid> {
+        sqlx::query_scalar("SELECT owner_id FROM documents WHERE id = $1")
+            .bind(document_id)
+            .fetch_optional(db_client.pool())
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn transfers_documents_and_erases_personal_data(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let alice = user(&db_client, "alice@example.com").await;
+        let bob = user(&db_client, "bob@example.com").await;
+
+        let document = db_client
+            .create_document("标题", "内容", alice, false)
+            .await
+            .unwrap();
+        db_client
+            .share_document(document.id, bob, PermissionLevel::ReadWrite, alice)
+            .await
+            .unwrap();
+
+        // -- 登录链接、验证码和审计日志只记录了邮箱
+        sqlx::query(
+            "INSERT INTO magic_links (email, token_hash, expires_at)
+             VALUES ('Alice@example.com', 'link', NOW() + INTERVAL '1 hour')",
+        )
+        .execute(db_client.pool())
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO login_codes (email, code_hash, expires_at)
+             VALUES ('alice@example.com', 'code', NOW() + INTERVAL '1 hour')",
+        )
+        .execute(db_client.pool())
+        .await
+        .unwrap();
+        db_client
+            .insert_audit_log(NewAuditLog {
+                actor_id: Some(alice),
+                actor_email: Some("alice@example.com".to_string()),
+                action: "user.login".to_string(),
+                target_type: None,
+                target_id: None,
+                details: serde_json::json!({}),
+                ip_address: None,
+                user_agent: None,
+            })
+            .await
+            .unwrap();
+
+        schedule_due(
+            &db_client,
+            alice,
+            DocumentDisposition::Transfer,
+            Some(bob),
+            Some(TransferFallback::Delete),
+        )
+        .await;
+
+        let account = db_client
+            .delete_account(alice, "unusable")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.email, "alice@example.com");
+        assert_eq!(account.transferred_to, Some(bob));
+        assert_eq!(account.documents_transferred, 1);
+        assert!(!account.transfer_failed);
+
+        assert_eq!(owner_of(&db_client, document.id).await, Some(bob));
+        // -- 新的所有者不再需要单独的共享记录
+        assert!(
+            db_client
+                .get_document_permissions(document.id, bob)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let remaining: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(*) FROM magic_links) + (SELECT COUNT(*) FROM login_codes)",
+        )
+        .fetch_one(db_client.pool())
+        .await
+        .unwrap();
+        assert_eq!(remaining, 0);
+
+        let actor_emails: Vec<Option<String>> =
+            sqlx::query_scalar("SELECT actor_email FROM audit_logs WHERE actor_id = $1")
+                .bind(alice)
+                .fetch_all(db_client.pool())
+                .await
+                .unwrap();
+        assert_eq!(actor_emails, vec![None]);
+
+        let anonymized = db_client
+            .get_user(Some(alice), None, None, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(anonymized.name, "Deleted user");
+        assert_ne!(anonymized.email, "alice@example.com");
+        assert_eq!(anonymized.password, "unusable");
+    }
+
+    #[sqlx::test]
+    async fn deletes_documents(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let alice = user(&db_client, "alice@example.com").await;
+
+        let document = db_client
+            .create_document("标题", "内容", alice, false)
+            .await
+            .unwrap();
+        schedule_due(&db_client, alice, DocumentDisposition::Delete, None, None).await;
+
+        let account = db_client
+            .delete_account(alice, "unusable")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.transferred_to, None);
+        assert_eq!(account.documents_deleted, 1);
+        assert_eq!(owner_of(&db_client, document.id).await, None);
+    }
+
+    #[sqlx::test]
+    async fn applies_fallback_when_target_is_deleted(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let alice = user(&db_client, "alice@example.com").await;
+        let bob = user(&db_client, "bob@example.com").await;
+
+        let document = db_client
+            .create_document("标题", "内容", alice, false)
+            .await
+            .unwrap();
+        schedule_due(
+            &db_client,
+            alice,
+            DocumentDisposition::Transfer,
+            Some(bob),
+            Some(TransferFallback::Keep),
+        )
+        .await;
+
+        // -- 接收人先被注销
+        schedule_due(&db_client, bob, DocumentDisposition::Delete, None, None).await;
+        db_client
+            .delete_account(bob, "unusable")
+            .await
+            .unwrap()
+            .unwrap();
+
+        let account = db_client
+            .delete_account(alice, "unusable")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(account.transfer_failed);
+        assert_eq!(account.transferred_to, None);
+        assert_eq!(account.documents_kept, 1);
+        assert_eq!(owner_of(&db_client, document.id).await, Some(alice));
+    }
+
+    #[sqlx::test]
+    async fn applies_fallback_when_target_is_scheduled_for_deletion(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let alice = user(&db_client, "alice@example.com").await;
+        let bob = user(&db_client, "bob@example.com").await;
+
+        let document = db_client
+            .create_document("标题", "内容", alice, false)
+            .await
+            .unwrap();
+        schedule_due(
+            &db_client,
+            alice,
+            DocumentDisposition::Transfer,
+            Some(bob),
+            Some(TransferFallback::Delete),
+        )
+        .await;
+
+        // -- 接收人的注销还在宽限期内
+        db_client
+            .schedule_account_deletion(
+                bob,
+                Utc::now() + Duration::days(7),
+                DocumentDisposition::Delete,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let account = db_client
+            .delete_account(alice, "unusable")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(account.transfer_failed);
+        assert_eq!(account.documents_transferred, 0);
+        assert_eq!(account.documents_deleted, 1);
+        assert_eq!(owner_of(&db_client, document.id).await, None);
+    }
+
+    #[sqlx::test]
+    async fn skips_cancelled_and_pending_deletions(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let alice = user(&db_client, "alice@example.com").await;
+
+        db_client
+            .schedule_account_deletion(
+                alice,
+                Utc::now() + Duration::days(7),
+                DocumentDisposition::Delete,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(
+            db_client
+                .delete_account(alice, "unusable")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        schedule_due(&db_client, alice, DocumentDisposition::Delete, None, None).await;
+        assert!(db_client.cancel_account_deletion(alice).await.unwrap());
+        assert!(
+            db_client
+                .delete_account(alice, "unusable")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
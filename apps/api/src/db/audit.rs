@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::AuditLog;
+
+/// A new audit log entry
+#[derive(Debug, Clone)]
+pub struct NewAuditLog {
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Filters of the audit log query, every field is optional
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogFilter {
+    pub actor_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Audit log database operations extension trait
+///
+/// The audit log is append-only: entries can be inserted and queried,
+/// the database rejects updates and deletes. The one exception is clearing
+/// `actor_email` when the account is deleted.
+#[async_trait]
+pub trait AuditExt {
+    /// Append an entry to the audit log
+    async fn insert_audit_log(&self, entry: NewAuditLog) -> DbResult<()>;
+
+    /// Query the audit log, newest first
+    ///
+    /// # Arguments
+    /// * `filter` - Filters to apply
+    /// * `page` - Page number (1-based)
+    /// * `limit` - Number of entries per page
+    ///
+    /// # Returns
+    /// * `Ok(Vec<AuditLog>)` - Matching entries
+    /// * `Err(DbError)` - Database error
+    async fn get_audit_logs(
+        &self,
+        filter: &AuditLogFilter,
+        page: u32,
+        limit: usize,
+    ) -> DbResult<Vec<AuditLog>>;
+
+    /// Count the entries matching the filters
+    async fn get_audit_log_count(&self, filter: &AuditLogFilter) -> DbResult<i64>;
+
+    /// Get the entries a user performed or that target the user, oldest first
+    ///
+    /// # Arguments
+    /// * `user_id` - User ID
+    /// * `limit` - Maximum number of entries
+    async fn get_user_audit_logs(&self, user_id: Uuid, limit: i64) -> DbResult<Vec<AuditLog>>;
+}
+
+#[async_trait]
+impl AuditExt for DBClient {
+    async fn insert_audit_log(&self, entry: NewAuditLog) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (
+                actor_id, actor_email, action, target_type, target_id, details,
+                ip_address, user_agent
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            entry.actor_id,
+            entry.actor_email,
+            entry.action,
+            entry.target_type,
+            entry.target_id,
+            entry.details,
+            entry.ip_address,
+            entry.user_agent
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn get_audit_logs(
+        &self,
+        filter: &AuditLogFilter,
+        page: u32,
+        limit: usize,
+    ) -> DbResult<Vec<AuditLog>> {
+        let offset = (page - 1) as i64 * limit as i64;
+
+        let logs = sqlx::query_as!(
+            AuditLog,
+            r#"
+            SELECT id, actor_id, actor_email, action, target_type, target_id, details,
+                   ip_address, user_agent, created_at
+            FROM audit_logs
+            WHERE ($1::uuid IS NULL OR actor_id = $1)
+              AND ($2::text IS NULL OR action = $2)
+              AND ($3::text IS NULL OR target_type = $3)
+              AND ($4::uuid IS NULL OR target_id = $4)
+              AND ($5::timestamptz IS NULL OR created_at >= $5)
+              AND ($6::timestamptz IS NULL OR created_at < $6)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $7 OFFSET $8
+            "#,
+            filter.actor_id,
+            filter.action,
+            filter.target_type,
+            filter.target_id,
+            filter.from,
+            filter.to,
+            limit as i64,
+            offset
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(logs)
+    }
+
+    async fn get_audit_log_count(&self, filter: &AuditLogFilter) -> DbResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*)
+            FROM audit_logs
+            WHERE ($1::uuid IS NULL OR actor_id = $1)
+              AND ($2::text IS NULL OR action = $2)
+              AND ($3::text IS NULL OR target_type = $3)
+              AND ($4::uuid IS NULL OR target_id = $4)
+              AND ($5::timestamptz IS NULL OR created_at >= $5)
+              AND ($6::timestamptz IS NULL OR created_at < $6)
+            "#,
+            filter.actor_id,
+            filter.action,
+            filter.target_type,
+            filter.target_id,
+            filter.from,
+            filter.to
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn get_user_audit_logs(&self, user_id: Uuid, limit: i64) -> DbResult<Vec<AuditLog>> {
+        let logs = sqlx::query_as!(
+            AuditLog,
+            r#"
+            SELECT id, actor_id, actor_email, action, target_type, target_id, details,
+                   ip_address, user_agent, created_at
+            FROM audit_logs
+            WHERE actor_id = $1 OR (target_type = 'user' AND target_id = $1)
+            ORDER BY created_at, id
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(logs)
+    }
+}
@@ -0,0 +1,686 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+use super::DocumentExt;
+
+use crate::models::{Comment, CommentAnchor, CommentThread, PermissionLevel};
+use crate::utils::anchor::{AnchorResolution, DocumentText};
+
+/// Minimum permission level required to create and reply to comments
+pub const COMMENT_PERMISSION: PermissionLevel = PermissionLevel::Comment;
+
+/// Comment database operations extension trait
+///
+/// Defines all operations related to comment threads in the database
+#[async_trait]
+pub trait CommentExt {
+    /// Get the comment threads of a document
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    /// * `user_id` - User requesting the threads (for permission check)
+    /// * `include_resolved` - Whether resolved threads are returned
+    ///
+    /// # Returns
+    /// * `Ok(Vec<CommentThread>)` - List of threads, oldest first
+    /// * `Err(DbError)` - Database error
+    async fn get_comment_threads(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        include_resolved: bool,
+    ) -> DbResult<Vec<CommentThread>>;
+
+    /// Get a comment thread by its ID
+    ///
+    /// # Arguments
+    /// * `thread_id` - Thread ID
+    ///
+    /// # Returns
+    /// * `Ok(Some(CommentThread))` - Thread found
+    /// * `Ok(None)` - Thread not found
+    /// * `Err(DbError)` - Database error
+    async fn get_comment_thread(&self, thread_id: Uuid) -> DbResult<Option<CommentThread>>;
+
+    /// Get all comments belonging to the given threads
+    ///
+    /// # Arguments
+    /// * `thread_ids` - Thread IDs
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Comment>)` - List of comments, oldest first
+    /// * `Err(DbError)` - Database error
+    async fn get_thread_comments(&self, thread_ids: &[Uuid]) -> DbResult<Vec<Comment>>;
+
+    /// Get a single comment by its ID
+    ///
+    /// # Arguments
+    /// * `comment_id` - Comment ID
+    ///
+    /// # Returns
+    /// * `Ok(Some(Comment))` - Comment found
+    /// * `Ok(None)` - Comment not found
+    /// * `Err(DbError)` - Database error
+    async fn get_comment(&self, comment_id: Uuid) -> DbResult<Option<Comment>>;
+
+    /// Start a new comment thread anchored to a document range
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    /// * `author_id` - Author of the thread (for permission check)
+    /// * `anchor` - Anchor of the thread
+    /// * `content` - Content of the root comment
+    ///
+    /// # Returns
+    /// * `Ok((CommentThread, Comment))` - Created thread and its root comment
+    /// * `Err(DbError)` - Database error
+    async fn create_comment_thread(
+        &self,
+        document_id: Uuid,
+        author_id: Uuid,
+        anchor: CommentAnchor,
+        content: String,
+    ) -> DbResult<(CommentThread, Comment)>;
+
+    /// Reply to a comment thread
+    ///
+    /// # Arguments
+    /// * `thread_id` - Thread ID
+    /// * `author_id` - Author of the reply (for permission check)
+    /// * `content` - Reply content
+    ///
+    /// # Returns
+    /// * `Ok(Comment)` - Created reply
+    /// * `Err(DbError)` - Database error
+    async fn add_comment_reply(
+        &self,
+        thread_id: Uuid,
+        author_id: Uuid,
+        content: String,
+    ) -> DbResult<Comment>;
+
+    /// Edit a comment, only allowed for its author while they can still comment on the document
+    ///
+    /// # Arguments
+    /// * `comment_id` - Comment ID
+    /// * `author_id` - User editing the comment
+    /// * `content` - New content
+    ///
+    /// # Returns
+    /// * `Ok(Comment)` - Updated comment
+    /// * `Err(DbError)` - Database error
+    async fn update_comment(
+        &self,
+        comment_id: Uuid,
+        author_id: Uuid,
+        content: String,
+    ) -> DbResult<Comment>;
+
+    /// Delete a comment, allowed for its author while they can still comment on the
+    /// document and for the document owner
+    ///
+    /// Deleting the root comment removes the whole thread.
+    ///
+    /// # Arguments
+    /// * `comment_id` - Comment ID
+    /// * `user_id` - User deleting the comment
+    ///
+    /// # Returns
+    /// * `Ok(())` - Comment deleted successfully
+    /// * `Err(DbError)` - Database error
+    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid) -> DbResult<()>;
+
+    /// Resolve or reopen a comment thread
+    ///
+    /// Allowed for the thread author and users who can edit the document.
+    ///
+    /// # Arguments
+    /// * `thread_id` - Thread ID
+    /// * `user_id` - User changing the state
+    /// * `resolved` - `true` to resolve, `false` to reopen
+    ///
+    /// # Returns
+    /// * `Ok(CommentThread)` - Updated thread
+    /// * `Err(DbError)` - Database error
+    async fn set_comment_thread_resolved(
+        &self,
+        thread_id: Uuid,
+        user_id: Uuid,
+        resolved: bool,
+    ) -> DbResult<CommentThread>;
+
+    /// Re-map comment anchors after the document content changed
+    ///
+    /// Anchors whose text moved get new offsets, anchors whose text was
+    /// deleted are marked as orphaned.
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    /// * `content` - New document content
+    ///
+    /// # Returns
+    /// * `Ok(())` - Anchors re-mapped successfully
+    /// * `Err(DbError)` - Database error
+    async fn remap_comment_anchors(&self, document_id: Uuid, content: &str) -> DbResult<()>;
+}
+
+#[async_trait]
+impl CommentExt for DBClient {
+    async fn get_comment_threads(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        include_resolved: bool,
+    ) -> DbResult<Vec<CommentThread>> {
+        let has_permission = self
+            .check_document_permission(document_id, user_id, PermissionLevel::Read)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let threads = sqlx::query_as!(
+            CommentThread,
+            r#"
+            SELECT id, document_id, author_id, block_id, start_offset, end_offset,
+                   crdt_position, quoted_text, orphaned, resolved, resolved_by, resolved_at,
+                   created_at, updated_at
+            FROM comment_threads
+            WHERE document_id = $1 AND ($2 OR resolved = false)
+            ORDER BY created_at ASC
+            "#,
+            document_id,
+            include_resolved
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(threads)
+    }
+
+    async fn get_comment_thread(&self, thread_id: Uuid) -> DbResult<Option<CommentThread>> {
+        let thread = sqlx::query_as!(
+            CommentThread,
+            r#"
+            SELECT id, document_id, author_id, block_id, start_offset, end_offset,
+                   crdt_position, quoted_text, orphaned, resolved, resolved_by, resolved_at,
+                   created_at, updated_at
+            FROM comment_threads
+            WHERE id = $1
+            "#,
+            thread_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(thread)
+    }
+
+    async fn get_thread_comments(&self, thread_ids: &[Uuid]) -> DbResult<Vec<Comment>> {
+        let comments = sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT id, thread_id, author_id, content, edited, created_at, updated_at
+            FROM comments
+            WHERE thread_id = ANY($1)
+            ORDER BY created_at ASC, id ASC
+            "#,
+            thread_ids
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(comments)
+    }
+
+    async fn get_comment(&self, comment_id: Uuid) -> DbResult<Option<Comment>> {
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            SELECT id, thread_id, author_id, content, edited, created_at, updated_at
+            FROM comments
+            WHERE id = $1
+            "#,
+            comment_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(comment)
+    }
+
+    async fn create_comment_thread(
+        &self,
+        document_id: Uuid,
+        author_id: Uuid,
+        anchor: CommentAnchor,
+        content: String,
+    ) -> DbResult<(CommentThread, Comment)> {
+        let has_permission = self
+            .check_document_permission(document_id, author_id, COMMENT_PERMISSION)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        // Start a transaction so a thread never exists without its root comment
+        let mut tx = self.begin_transaction().await?;
+
+        let thread = sqlx::query_as!(
+            CommentThread,
+            r#"
+            INSERT INTO comment_threads (
+                document_id, author_id, block_id, start_offset, end_offset,
+                crdt_position, quoted_text
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, document_id, author_id, block_id, start_offset, end_offset,
+                      crdt_position, quoted_text, orphaned, resolved, resolved_by, resolved_at,
+                      created_at, updated_at
+            "#,
+            document_id,
+            author_id,
+            anchor.block_id,
+            anchor.start_offset,
+            anchor.end_offset,
+            anchor.crdt_position,
+            anchor.quoted_text
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            INSERT INTO comments (thread_id, author_id, content)
+            VALUES ($1, $2, $3)
+            RETURNING id, thread_id, author_id, content, edited, created_at, updated_at
+            "#,
+            thread.id,
+            author_id,
+            content
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok((thread, comment))
+    }
+
+    async fn add_comment_reply(
+        &self,
+        thread_id: Uuid,
+        author_id: Uuid,
+        content: String,
+    ) -> DbResult<Comment> {
+        let thread = self
+            .get_comment_thread(thread_id)
+            .await?
+            .ok_or(DbError::NotFound("Comment thread not found".to_string()))?;
+
+        let has_permission = self
+            .check_document_permission(thread.document_id, author_id, COMMENT_PERMISSION)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let comment = sqlx::query_as!(
+            Comment,
+            r#"
+            INSERT INTO comments (thread_id, author_id, content)
+            VALUES ($1, $2, $3)
+            RETURNING id, thread_id, author_id, content, edited, created_at, updated_at
+            "#,
+            thread_id,
+            author_id,
+            content
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(comment)
+    }
+
+    async fn update_comment(
+        &self,
+        comment_id: Uuid,
+        author_id: Uuid,
+        content: String,
+    ) -> DbResult<Comment> {
+        let comment = self
+            .get_comment(comment_id)
+            .await?
+            .ok_or(DbError::NotFound("Comment not found".to_string()))?;
+
+        if comment.author_id != author_id {
+            return Err(DbError::PermissionDenied);
+        }
+
+        // Authorship alone is not enough once the author's share has been revoked
+        let thread = self
+            .get_comment_thread(comment.thread_id)
+            .await?
+            .ok_or(DbError::NotFound("Comment thread not found".to_string()))?;
+        let has_permission = self
+            .check_document_permission(thread.document_id, author_id, COMMENT_PERMISSION)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let updated_comment = sqlx::query_as!(
+            Comment,
+            r#"
+            UPDATE comments
+            SET content = $1, edited = true, updated_at = NOW()
+            WHERE id = $2
+            RETURNING id, thread_id, author_id, content, edited, created_at, updated_at
+            "#,
+            content,
+            comment_id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(updated_comment)
+    }
+
+    async fn delete_comment(&self, comment_id: Uuid, user_id: Uuid) -> DbResult<()> {
+        let comment = self
+            .get_comment(comment_id)
+            .await?
+            .ok_or(DbError::NotFound("Comment not found".to_string()))?;
+
+        let thread = self
+            .get_comment_thread(comment.thread_id)
+            .await?
+            .ok_or(DbError::NotFound("Comment thread not found".to_string()))?;
+
+        // The document owner may moderate any comment, authors need current access
+        let required_permission = if comment.author_id == user_id {
+            COMMENT_PERMISSION
+        } else {
+            PermissionLevel::Owner
+        };
+        let has_permission = self
+            .check_document_permission(thread.document_id, user_id, required_permission)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let root_comment_id = sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM comments
+            WHERE thread_id = $1
+            ORDER BY created_at ASC, id ASC
+            LIMIT 1
+            "#,
+            thread.id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        if root_comment_id == comment_id {
+            // Deleting the root comment removes the whole thread (replies cascade)
+            sqlx::query!(
+                r#"
+                DELETE FROM comment_threads
+                WHERE id = $1
+                "#,
+                thread.id
+            )
+            .execute(self.pool())
+            .await
+            .map_err(DbError::from)?;
+        } else {
+            sqlx::query!(
+                r#"
+                DELETE FROM comments
+                WHERE id = $1
+                "#,
+                comment_id
+            )
+            .execute(self.pool())
+            .await
+            .map_err(DbError::from)?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_comment_thread_resolved(
+        &self,
+        thread_id: Uuid,
+        user_id: Uuid,
+        resolved: bool,
+    ) -> DbResult<CommentThread> {
+        let thread = self
+            .get_comment_thread(thread_id)
+            .await?
+            .ok_or(DbError::NotFound("Comment thread not found".to_string()))?;
+
+        let permission_check = if thread.author_id == user_id {
+            self.check_document_permission(thread.document_id, user_id, COMMENT_PERMISSION)
+                .await?
+        } else {
+            self.check_document_permission(thread.document_id, user_id, PermissionLevel::ReadWrite)
+                .await?
+        };
+
+        if !permission_check {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let updated_thread = sqlx::query_as!(
+            CommentThread,
+            r#"
+            UPDATE comment_threads
+            SET resolved = $1,
+                resolved_by = CASE WHEN $1 THEN $2::uuid ELSE NULL END,
+                resolved_at = CASE WHEN $1 THEN NOW() ELSE NULL END,
+                updated_at = NOW()
+            WHERE id = $3
+            RETURNING id, document_id, author_id, block_id, start_offset, end_offset,
+                      crdt_position, quoted_text, orphaned, resolved, resolved_by, resolved_at,
+                      created_at, updated_at
+            "#,
+            resolved,
+            user_id,
+            thread_id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(updated_thread)
+    }
+
+    async fn remap_comment_anchors(&self, document_id: Uuid, content: &str) -> DbResult<()> {
+        // Content that is not Tiptap JSON cannot be inspected, keep anchors as they are
+        let Some(document_text) = DocumentText::parse(content) else {
+            return Ok(());
+        };
+
+        let threads = sqlx::query_as!(
+            CommentThread,
+            r#"
+            SELECT id, document_id, author_id, block_id, start_offset, end_offset,
+                   crdt_position, quoted_text, orphaned, resolved, resolved_by, resolved_at,
+                   created_at, updated_at
+            FROM comment_threads
+            WHERE document_id = $1 AND orphaned = false
+            "#,
+            document_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        let mut tx = self.begin_transaction().await?;
+
+        for thread in threads {
+            match document_text.resolve(&thread) {
+                AnchorResolution::Unchanged => {}
+                AnchorResolution::Moved {
+                    start_offset,
+                    end_offset,
+                } => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE comment_threads
+                        SET start_offset = $1, end_offset = $2, updated_at = NOW()
+                        WHERE id = $3
+                        "#,
+                        start_offset,
+                        end_offset,
+                        thread.id
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(DbError::from)?;
+                }
+                AnchorResolution::Orphaned => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE comment_threads
+                        SET orphaned = true, updated_at = NOW()
+                        WHERE id = $1
+                        "#,
+                        thread.id
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(DbError::from)?;
+                }
+            }
+        }
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::UserExt;
+
+    fn content(text: &str) -> String {
+        json!({
+            "type": "doc",
+            "content": [{
+                "type": "paragraph",
+                "attrs": { "id": "p1" },
+                "content": [{ "type": "text", "text": text }]
+            }]
+        })
+        .to_string()
+    }
+
+    async fn thread(
+        db_client: &DBClient,
+        document_id: Uuid,
+        author_id: Uuid,
+        block_id: &str,
+        start_offset: i32,
+        quoted_text: &str,
+    ) -> Uuid {
+        let anchor = CommentAnchor {
+            block_id: Some(block_id.to_string()),
+            start_offset: Some(start_offset),
+            end_offset: Some(start_offset + quoted_text.chars().count() as i32),
+            crdt_position: None,
+            quoted_text: quoted_text.to_string(),
+        };
+        db_client
+            .create_comment_thread(document_id, author_id, anchor, "评论".to_string())
+            .await
+            .unwrap()
+            .0
+            .id
+    }
+
+    async fn get(db_client: &DBClient, thread_id: Uuid) -> CommentThread {
+        db_client
+            .get_comment_thread(thread_id)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn remaps_anchors_after_update(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let owner = db_client
+            .save_user("owner", "owner@example.com", "hash", "token", Utc::now())
+            .await
+            .unwrap()
+            .id;
+        let document = db_client
+            .create_document(
+                "标题".to_string(),
+                content("Hello world, good world"),
+                owner,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let moved = thread(&db_client, document.id, owner, "p1", 6, "world").await;
+        let duplicate = thread(&db_client, document.id, owner, "p1", 18, "world").await;
+        let deleted = thread(&db_client, document.id, owner, "p1", 13, "good").await;
+        let missing_block = thread(&db_client, document.id, owner, "p2", 0, "Hello").await;
+
+        db_client
+            .remap_comment_anchors(document.id, &content("Oh, Hello world, bad world"))
+            .await
+            .unwrap();
+
+        let thread = get(&db_client, moved).await;
+        assert_eq!(
+            (thread.start_offset, thread.end_offset),
+            (Some(10), Some(15))
+        );
+        assert!(!thread.orphaned);
+
+        // -- 两处相同的文本各自跟随最近的一处
+        let thread = get(&db_client, duplicate).await;
+        assert_eq!(
+            (thread.start_offset, thread.end_offset),
+            (Some(21), Some(26))
+        );
+        assert!(!thread.orphaned);
+
+        let thread = get(&db_client, deleted).await;
+        assert!(thread.orphaned);
+        assert_eq!(thread.start_offset, Some(13));
+
+        assert!(get(&db_client, missing_block).await.orphaned);
+    }
+}
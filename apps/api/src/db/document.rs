@@ -187,6 +187,62 @@ pub trait DocumentExt {
         document_id: Uuid,
         owner_id: Uuid,
     ) -> DbResult<Vec<DocumentPermission>>;
+
+    /// Get a single sharing permission by its ID
+    ///
+    /// # Arguments
+    /// * `permission_id` - Permission ID
+    ///
+    /// # Returns
+    /// * `Ok(Some(DocumentPermission))` - Permission found
+    /// * `Ok(None)` - Permission not found
+    /// * `Err(DbError)` - Database error
+    async fn get_document_permission(
+        &self,
+        permission_id: Uuid,
+    ) -> DbResult<Option<DocumentPermission>>;
+
+    /// Get the IDs of every user with access to a document (owner and shared users)
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Uuid>)` - Owner and shared user IDs
+    /// * `Err(DbError)` - Database error
+    async fn get_document_member_ids(&self, document_id: Uuid) -> DbResult<Vec<Uuid>>;
+
+    /// Get every document owned by a user, oldest first
+    ///
+    /// # Arguments
+    /// * `owner_id` - Owner's user ID
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Document>)` - Owned documents
+    /// * `Err(DbError)` - Database error
+    async fn get_owned_documents(&self, owner_id: Uuid) -> DbResult<Vec<Document>>;
+
+    /// Get the shares involving a user: those on documents the user owns
+    /// and those granted to the user
+    ///
+    /// # Arguments
+    /// * `user_id` - User ID
+    ///
+    /// # Returns
+    /// * `Ok(Vec<DocumentPermission>)` - Matching permissions
+    /// * `Err(DbError)` - Database error
+    async fn get_user_shares(&self, user_id: Uuid) -> DbResult<Vec<DocumentPermission>>;
+}
+
+/// Sharing may grant Read, Comment or ReadWrite; ownership is never handed out by sharing
+fn validate_shared_permission_level(permission_level: &PermissionLevel) -> DbResult<()> {
+    if *permission_level == PermissionLevel::Owner {
+        return Err(DbError::InvalidPermissionChange(
+            "Owner permission cannot be granted by sharing".to_string(),
+        ));
+    }
+
+    Ok(())
 }
 
 #[async_trait]
@@ -199,7 +255,7 @@ impl DocumentExt for DBClient {
         let document = sqlx::query_as!(
             Document,
             r#"
-            SELECT id, title, content, owner_id, is_public, created_at, updated_at
+            SELECT id, title, content, owner_id, is_public, revision, created_at, updated_at
             FROM documents 
             WHERE id = $1
             "#,
@@ -245,13 +301,13 @@ impl DocumentExt for DBClient {
             r#"
             SELECT d.id as "id!", d.title as "title!", d.content as "content!", 
                    d.owner_id as "owner_id!", d.is_public as "is_public!", 
-                   d.created_at, d.updated_at
+                   d.revision as "revision!", d.created_at, d.updated_at
             FROM documents d
             WHERE d.owner_id = $1
             UNION
             SELECT d.id as "id!", d.title as "title!", d.content as "content!", 
                    d.owner_id as "owner_id!", d.is_public as "is_public!", 
-                   d.created_at, d.updated_at
+                   d.revision as "revision!", d.created_at, d.updated_at
             FROM documents d
             JOIN document_permissions dp ON d.id = dp.document_id
             WHERE dp.user_id = $1
@@ -274,6 +330,7 @@ impl DocumentExt for DBClient {
                 content: row.content,
                 owner_id: row.owner_id,
                 is_public: row.is_public,
+                revision: row.revision,
                 created_at: row.created_at,
                 updated_at: row.updated_at,
             })
@@ -294,7 +351,7 @@ impl DocumentExt for DBClient {
             r#"
             INSERT INTO documents (title, content, owner_id, is_public)
             VALUES ($1, $2, $3, $4)
-            RETURNING id, title, content, owner_id, is_public, created_at, updated_at
+            RETURNING id, title, content, owner_id, is_public, revision, created_at, updated_at
             "#,
             title.into(),
             content.into(),
@@ -339,9 +396,9 @@ impl DocumentExt for DBClient {
             Document,
             r#"
             UPDATE documents
-            SET title = $1, content = $2, is_public = $3, updated_at = NOW()
+            SET title = $1, content = $2, is_public = $3, revision = revision + 1, updated_at = NOW()
             WHERE id = $4
-            RETURNING id, title, content, owner_id, is_public, created_at, updated_at
+            RETURNING id, title, content, owner_id, is_public, revision, created_at, updated_at
             "#,
             new_title,
             new_content,
@@ -433,6 +490,14 @@ impl DocumentExt for DBClient {
             return Err(DbError::PermissionDenied);
         }
 
+        if user_id == document.owner_id {
+            return Err(DbError::InvalidPermissionChange(
+                "Cannot share a document with its owner".to_string(),
+            ));
+        }
+
+        validate_shared_permission_level(&permission_level)?;
+
         // Create the permission
         let permission = sqlx::query_as!(
             DocumentPermission,
@@ -483,6 +548,8 @@ impl DocumentExt for DBClient {
             return Err(DbError::PermissionDenied);
         }
 
+        validate_shared_permission_level(&permission_level)?;
+
         // Update the permission
         let updated_permission = sqlx::query_as!(
             DocumentPermission,
@@ -557,7 +624,7 @@ impl DocumentExt for DBClient {
         let document = sqlx::query_as!(
             Document,
             r#"
-            SELECT id, title, content, owner_id, is_public, created_at, updated_at
+            SELECT id, title, content, owner_id, is_public, revision, created_at, updated_at
             FROM documents 
             WHERE id = $1
             "#,
@@ -593,26 +660,8 @@ impl DocumentExt for DBClient {
         .await
         .map_err(DbError::from)?;
 
-        if let Some(perm) = permission {
-            match (required_permission, perm.permission_level) {
-                // Read permission is granted for ReadWrite or Owner levels too
-                (PermissionLevel::Read, PermissionLevel::Read) => Ok(true),
-                (PermissionLevel::Read, PermissionLevel::ReadWrite) => Ok(true),
-                (PermissionLevel::Read, PermissionLevel::Owner) => Ok(true),
-
-                // ReadWrite permission is granted for ReadWrite or Owner levels
-                (PermissionLevel::ReadWrite, PermissionLevel::ReadWrite) => Ok(true),
-                (PermissionLevel::ReadWrite, PermissionLevel::Owner) => Ok(true),
-
-                // Owner permission is granted only for Owner level
-                (PermissionLevel::Owner, PermissionLevel::Owner) => Ok(true),
-
-                // All other combinations are denied
-                _ => Ok(false),
-            }
-        } else {
-            Ok(false)
-        }
+        // Levels are hierarchical: Owner > ReadWrite > Comment > Read
+        Ok(permission.is_some_and(|perm| perm.permission_level.includes(&required_permission)))
     }
 
     async fn get_document_permissions(
@@ -646,4 +695,164 @@ impl DocumentExt for DBClient {
 
         Ok(permissions)
     }
+
+    async fn get_document_permission(
+        &self,
+        permission_id: Uuid,
+    ) -> DbResult<Option<DocumentPermission>> {
+        let permission = sqlx::query_as!(
+            DocumentPermission,
+            r#"
+            SELECT id, document_id, user_id, permission_level as "permission_level: PermissionLevel", created_at, updated_at
+            FROM document_permissions
+            WHERE id = $1
+            "#,
+            permission_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(permission)
+    }
+
+    async fn get_document_member_ids(&self, document_id: Uuid) -> DbResult<Vec<Uuid>> {
+        let member_ids = sqlx::query_scalar!(
+            r#"
+            SELECT owner_id as "user_id!"
+            FROM documents
+            WHERE id = $1
+            UNION
+            SELECT user_id as "user_id!"
+            FROM document_permissions
+            WHERE document_id = $1
+            "#,
+            document_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(member_ids)
+    }
+
+    async fn get_owned_documents(&self, owner_id: Uuid) -> DbResult<Vec<Document>> {
+        let documents = sqlx::query_as!(
+            Document,
+            r#"
+            SELECT id, title, content, owner_id, is_public, revision, created_at, updated_at
+            FROM documents
+            WHERE owner_id = $1
+            ORDER BY created_at, id
+            "#,
+            owner_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(documents)
+    }
+
+    async fn get_user_shares(&self, user_id: Uuid) -> DbResult<Vec<DocumentPermission>> {
+        let permissions = sqlx::query_as!(
+            DocumentPermission,
+            r#"
+            SELECT p.id, p.document_id, p.user_id, p.permission_level as "permission_level: PermissionLevel", p.created_at, p.updated_at
+            FROM document_permissions p
+            JOIN documents d ON d.id = p.document_id
+            WHERE d.owner_id = $1 OR p.user_id = $1
+            ORDER BY p.created_at, p.id
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(permissions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::UserExt;
+
+    async fn user(db_client: &DBClient, email: &str) -> Uuid {
+        db_client
+            .save_user("user", email, "hash", "token", Utc::now())
+            .await
+            .unwrap()
+            .id
+    }
+
+    #[sqlx::test]
+    async fn comment_permission_includes_read_only(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let owner = user(&db_client, "owner@example.com").await;
+        let commenter = user(&db_client, "commenter@example.com").await;
+
+        let document = db_client
+            .create_document("标题", "内容", owner, false)
+            .await
+            .unwrap();
+        db_client
+            .share_document(document.id, commenter, PermissionLevel::Comment, owner)
+            .await
+            .unwrap();
+
+        for (level, expected) in [
+            (PermissionLevel::Read, true),
+            (PermissionLevel::Comment, true),
+            (PermissionLevel::ReadWrite, false),
+            (PermissionLevel::Owner, false),
+        ] {
+            assert_eq!(
+                db_client
+                    .check_document_permission(document.id, commenter, level)
+                    .await
+                    .unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn owner_cannot_be_granted_by_sharing(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let owner = user(&db_client, "owner@example.com").await;
+        let other = user(&db_client, "other@example.com").await;
+
+        let document = db_client
+            .create_document("标题", "内容", owner, false)
+            .await
+            .unwrap();
+
+        let err = db_client
+            .share_document(document.id, other, PermissionLevel::Owner, owner)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::InvalidPermissionChange(_)));
+
+        // -- 已有的共享也不能升级为所有者
+        let permission = db_client
+            .share_document(document.id, other, PermissionLevel::ReadWrite, owner)
+            .await
+            .unwrap();
+        let err = db_client
+            .update_document_permission(permission.id, PermissionLevel::Owner, owner)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::InvalidPermissionChange(_)));
+
+        assert!(
+            !db_client
+                .check_document_permission(document.id, other, PermissionLevel::Owner)
+                .await
+                .unwrap()
+        );
+    }
 }
@@ -0,0 +1,581 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::EmailChange;
+
+/// Email change database operations extension trait
+///
+/// Changes are looked up by the SHA-256 hash of the confirmation or revert token.
+/// They are stored apart from `users.verification_token`, which is shared by email
+/// verification and password reset.
+#[async_trait]
+pub trait EmailChangeExt {
+    /// Store a requested email change, replacing any unconfirmed request of the user
+    ///
+    /// A confirmed change can be reverted from the old address for a while, no new change is
+    /// accepted until that window is over so the revert link always restores the right address.
+    ///
+    /// # Arguments
+    /// * `user_id` - User changing the address
+    /// * `old_email` - Current address, receives the revert link
+    /// * `new_email` - Requested address, receives the confirmation link
+    /// * `confirm_token_hash` - SHA-256 hash of the confirmation token
+    /// * `confirm_expires_at` - Expiration time of the confirmation link
+    /// * `revert_token_hash` - SHA-256 hash of the revert token
+    /// * `revert_expires_at` - Expiration time of the revert link
+    ///
+    /// # Returns
+    /// * `Err(DbError::ConstraintViolation)` - A confirmed change can still be reverted
+    #[allow(clippy::too_many_arguments)]
+    async fn create_email_change(
+        &self,
+        user_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        confirm_token_hash: &str,
+        confirm_expires_at: DateTime<Utc>,
+        revert_token_hash: &str,
+        revert_expires_at: DateTime<Utc>,
+    ) -> DbResult<EmailChange>;
+
+    /// Confirm a change and swap the user's address in one transaction
+    ///
+    /// The address is marked as verified, the local password identity follows the new address.
+    ///
+    /// # Returns
+    /// * `Ok(Some(change))` - The address was changed
+    /// * `Ok(None)` - Unknown, expired, used or reverted token, the address changed since, or
+    ///   another confirmed change can still be reverted
+    /// * `Err(DbError::EmailExists)` - The new address belongs to another user
+    async fn confirm_email_change(&self, confirm_token_hash: &str)
+    -> DbResult<Option<EmailChange>>;
+
+    /// Revert a change from the old address
+    ///
+    /// An unconfirmed change is cancelled, a confirmed one restores the old address whatever
+    /// the current one is. Whoever requested the change knew the password, so it is replaced
+    /// and the owner has to reset it.
+    ///
+    /// # Arguments
+    /// * `revert_token_hash` - SHA-256 hash of the revert token
+    /// * `invalidated_password` - Hash of a random password replacing the current one
+    ///
+    /// # Returns
+    /// * `Ok(Some(change))` - The change was reverted
+    /// * `Ok(None)` - Unknown, expired or used token
+    /// * `Err(DbError::EmailExists)` - The old address was taken by another user meanwhile
+    async fn revert_email_change(
+        &self,
+        revert_token_hash: &str,
+        invalidated_password: &str,
+    ) -> DbResult<Option<EmailChange>>;
+}
+
+#[async_trait]
+impl EmailChangeExt for DBClient {
+    async fn create_email_change(
+        &self,
+        user_id: Uuid,
+        old_email: &str,
+        new_email: &str,
+        confirm_token_hash: &str,
+        confirm_expires_at: DateTime<Utc>,
+        revert_token_hash: &str,
+        revert_expires_at: DateTime<Utc>,
+    ) -> DbResult<EmailChange> {
+        let mut tx = self.begin_transaction().await?;
+
+        if revert_pending(&mut tx, user_id, None).await? {
+            return Err(DbError::ConstraintViolation(
+                "The previous email change can still be reverted".to_string(),
+            ));
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM email_changes
+            WHERE user_id = $1 AND confirmed_at IS NULL AND reverted_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let change = sqlx::query_as!(
+            EmailChange,
+            r#"
+            INSERT INTO email_changes
+                (user_id, old_email, new_email, confirm_token_hash, confirm_expires_at,
+                 revert_token_hash, revert_expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, user_id, old_email, new_email, confirm_expires_at, revert_expires_at,
+                      confirmed_at, reverted_at, created_at
+            "#,
+            user_id,
+            old_email,
+            new_email,
+            confirm_token_hash,
+            confirm_expires_at,
+            revert_token_hash,
+            revert_expires_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(change)
+    }
+
+    async fn confirm_email_change(
+        &self,
+        confirm_token_hash: &str,
+    ) -> DbResult<Option<EmailChange>> {
+        let mut tx = self.begin_transaction().await?;
+
+        let change = sqlx::query_as!(
+            EmailChange,
+            r#"
+            UPDATE email_changes
+            SET confirmed_at = NOW()
+            WHERE confirm_token_hash = $1
+              AND confirmed_at IS NULL
+              AND reverted_at IS NULL
+              AND confirm_expires_at > NOW()
+            RETURNING id, user_id, old_email, new_email, confirm_expires_at, revert_expires_at,
+                      confirmed_at, reverted_at, created_at
+            "#,
+            confirm_token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let Some(change) = change else {
+            return Ok(None);
+        };
+
+        if !swap_email(
+            &mut tx,
+            change.user_id,
+            Some(&change.old_email),
+            &change.new_email,
+        )
+        .await?
+        {
+            return Ok(None);
+        }
+
+        // Checked after the user row is locked, so a change confirmed concurrently is visible
+        if revert_pending(&mut tx, change.user_id, Some(change.id)).await? {
+            return Ok(None);
+        }
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(Some(change))
+    }
+
+    async fn revert_email_change(
+        &self,
+        revert_token_hash: &str,
+        invalidated_password: &str,
+    ) -> DbResult<Option<EmailChange>> {
+        let mut tx = self.begin_transaction().await?;
+
+        let change = sqlx::query_as!(
+            EmailChange,
+            r#"
+            UPDATE email_changes
+            SET reverted_at = NOW()
+            WHERE revert_token_hash = $1 AND reverted_at IS NULL AND revert_expires_at > NOW()
+            RETURNING id, user_id, old_email, new_email, confirm_expires_at, revert_expires_at,
+                      confirmed_at, reverted_at, created_at
+            "#,
+            revert_token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let Some(change) = change else {
+            return Ok(None);
+        };
+
+        if change.confirmed_at.is_some() {
+            swap_email(&mut tx, change.user_id, None, &change.old_email).await?;
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM email_changes
+            WHERE user_id = $1 AND confirmed_at IS NULL AND reverted_at IS NULL
+            "#,
+            change.user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+            change.user_id,
+            invalidated_password
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(Some(change))
+    }
+}
+
+/// Whether a confirmed change of the user, other than `except`, can still be reverted
+async fn revert_pending(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    except: Option<Uuid>,
+) -> DbResult<bool> {
+    let pending = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM email_changes
+            WHERE user_id = $1
+              AND id IS DISTINCT FROM $2
+              AND confirmed_at IS NOT NULL
+              AND reverted_at IS NULL
+              AND revert_expires_at > NOW()
+        ) AS "pending!"
+        "#,
+        user_id,
+        except
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::from)?;
+
+    Ok(pending)
+}
+
+/// Move a user to another address, inside the caller's transaction
+///
+/// With `from`, returns `false` when the user's address is no longer `from`.
+async fn swap_email(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    from: Option<&str>,
+    to: &str,
+) -> DbResult<bool> {
+    // Addresses are unique regardless of case, the column constraint only covers exact matches
+    let taken = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM users WHERE LOWER(email) = LOWER($1) AND id <> $2
+        ) AS "taken!"
+        "#,
+        to,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::from)?;
+
+    if taken {
+        return Err(DbError::EmailExists);
+    }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $3, verified = true, updated_at = NOW()
+        WHERE id = $1 AND ($2::TEXT IS NULL OR email = $2)
+        "#,
+        user_id,
+        from,
+        to
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| match e.as_database_error() {
+        Some(db_err) if db_err.is_unique_violation() => DbError::EmailExists,
+        _ => DbError::from(e),
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE user_identities
+        SET email = $2
+        WHERE user_id = $1 AND provider_name = 'local'
+        "#,
+        user_id,
+        to
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::from)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::UserExt;
+
+    async fn user(db_client: &DBClient) -> Uuid {
+        db_client
+            .save_user("owner", "a@example.com", "hash", "token", Utc::now())
+            .await
+            .unwrap()
+            .id
+    }
+
+    async fn request(
+        db_client: &DBClient,
+        user_id: Uuid,
+        from: &str,
+        to: &str,
+        revert_days: i64,
+    ) -> DbResult<EmailChange> {
+        let now = Utc::now();
+        db_client
+            .create_email_change(
+                user_id,
+                from,
+                to,
+                &format!("confirm:{}", to),
+                now + Duration::hours(24),
+                &format!("revert:{}", to),
+                now + Duration::days(revert_days),
+            )
+            .await
+    }
+
+    async fn email_and_password(db_client: &DBClient, user_id: Uuid) -> (String, String) {
+        let user = db_client
+            .get_user(Some(user_id), None, None, None)
+            .await
+            .unwrap()
+            .unwrap();
+        (user.email, user.password)
+    }
+
+    #[sqlx::test]
+    async fn no_new_change_while_revert_is_possible(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let user_id = user(&db_client).await;
+
+        request(&db_client, user_id, "a@example.com", "b@example.com", 7)
+            .await
+            .unwrap();
+        // -- 未确认的申请可以被新的申请替换
+        request(&db_client, user_id, "a@example.com", "b@example.com", 7)
+            .await
+            .unwrap();
+        assert!(
+            db_client
+                .confirm_email_change("confirm:b@example.com")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let err = request(&db_client, user_id, "b@example.com", "c@example.com", 7)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::ConstraintViolation(_)));
+        assert_eq!(
+            email_and_password(&db_client, user_id).await.0,
+            "b@example.com"
+        );
+    }
+
+    #[sqlx::test]
+    async fn new_change_allowed_after_revert_window(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let user_id = user(&db_client).await;
+
+        request(&db_client, user_id, "a@example.com", "b@example.com", 0)
+            .await
+            .unwrap();
+        db_client
+            .confirm_email_change("confirm:b@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+
+        request(&db_client, user_id, "b@example.com", "c@example.com", 7)
+            .await
+            .unwrap();
+        db_client
+            .confirm_email_change("confirm:c@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            email_and_password(&db_client, user_id).await.0,
+            "c@example.com"
+        );
+    }
+
+    #[sqlx::test]
+    async fn confirm_refuses_chained_change(pool: PgPool) {
+        let db_client = DBClient::new(pool.clone());
+        let user_id = user(&db_client).await;
+
+        // -- 两个申请同时存在（例如并发提交），第一个确认后第二个不能再确认
+        request(&db_client, user_id, "a@example.com", "b@example.com", 7)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO email_changes
+                (user_id, old_email, new_email, confirm_token_hash, confirm_expires_at,
+                 revert_token_hash, revert_expires_at)
+             VALUES ($1, 'b@example.com', 'c@example.com', 'confirm:c@example.com',
+                     NOW() + INTERVAL '1 day', 'revert:c@example.com', NOW() + INTERVAL '7 days')",
+        )
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        db_client
+            .confirm_email_change("confirm:b@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            db_client
+                .confirm_email_change("confirm:c@example.com")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            email_and_password(&db_client, user_id).await.0,
+            "b@example.com"
+        );
+    }
+
+    #[sqlx::test]
+    async fn revert_restores_old_email_and_replaces_password(pool: PgPool) {
+        let db_client = DBClient::new(pool.clone());
+        let user_id = user(&db_client).await;
+
+        request(&db_client, user_id, "a@example.com", "b@example.com", 7)
+            .await
+            .unwrap();
+        db_client
+            .confirm_email_change("confirm:b@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+
+        // -- 邮箱在确认后又被改成其他地址，撤销仍然恢复旧邮箱
+        sqlx::query("UPDATE users SET email = 'x@example.com' WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let change = db_client
+            .revert_email_change("revert:b@example.com", "invalidated")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(change.old_email, "a@example.com");
+        assert_eq!(
+            email_and_password(&db_client, user_id).await,
+            ("a@example.com".to_string(), "invalidated".to_string())
+        );
+
+        // -- 撤销链接只能使用一次，撤销后可以重新申请修改
+        assert!(
+            db_client
+                .revert_email_change("revert:b@example.com", "other")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        request(&db_client, user_id, "a@example.com", "c@example.com", 7)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn revert_cancels_unconfirmed_change(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let user_id = user(&db_client).await;
+
+        request(&db_client, user_id, "a@example.com", "b@example.com", 7)
+            .await
+            .unwrap();
+        db_client
+            .revert_email_change("revert:b@example.com", "invalidated")
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(
+            db_client
+                .confirm_email_change("confirm:b@example.com")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            email_and_password(&db_client, user_id).await,
+            ("a@example.com".to_string(), "invalidated".to_string())
+        );
+    }
+
+    #[sqlx::test]
+    async fn revert_fails_when_old_email_was_taken(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let user_id = user(&db_client).await;
+
+        request(&db_client, user_id, "a@example.com", "b@example.com", 7)
+            .await
+            .unwrap();
+        db_client
+            .confirm_email_change("confirm:b@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        db_client
+            .save_user("other", "A@example.com", "hash", "token2", Utc::now())
+            .await
+            .unwrap();
+
+        let err = db_client
+            .revert_email_change("revert:b@example.com", "invalidated")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::EmailExists));
+        assert_eq!(
+            email_and_password(&db_client, user_id).await,
+            ("b@example.com".to_string(), "hash".to_string())
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use async_trait::async_trait;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::{AuthProvider, User, UserIdentity};
+
+/// Linked login identity database operations extension trait
+#[async_trait]
+pub trait IdentityExt {
+    /// Get the identities linked to a user, oldest first
+    async fn get_user_identities(&self, user_id: Uuid) -> DbResult<Vec<UserIdentity>>;
+
+    /// Check whether a user has linked an identity of the given provider
+    async fn has_identity(&self, user_id: Uuid, provider: AuthProvider) -> DbResult<bool>;
+
+    /// Link an external identity to a user
+    ///
+    /// # Arguments
+    /// * `user_id` - The user to link the identity to
+    /// * `provider` - Authentication provider
+    /// * `provider_name` - Provider name, the configured name for OIDC providers
+    /// * `provider_user_id` - Subject ID at the provider
+    /// * `email` - Email reported by the provider
+    ///
+    /// # Returns
+    /// * `Err(DbError::ConstraintViolation)` - The identity belongs to another user,
+    ///   or the user already linked an identity of this provider
+    async fn link_identity(
+        &self,
+        user_id: Uuid,
+        provider: AuthProvider,
+        provider_name: &str,
+        provider_user_id: &str,
+        email: &str,
+    ) -> DbResult<UserIdentity>;
+
+    /// Link the local password identity, does nothing if it is already linked
+    async fn link_local_identity(&self, user_id: Uuid, email: &str) -> DbResult<()>;
+
+    /// Record a successful login with an identity
+    async fn update_identity_usage(&self, user_id: Uuid, provider_name: &str) -> DbResult<()>;
+
+    /// Unlink an identity from a user
+    ///
+    /// Passkeys count as a login method, the last identity can be unlinked
+    /// while the user still has a passkey.
+    ///
+    /// # Returns
+    /// * `Err(DbError::NotFound)` - The identity does not exist or belongs to another user
+    /// * `Err(DbError::ConstraintViolation)` - It is the last remaining login method
+    async fn unlink_identity(&self, user_id: Uuid, identity_id: Uuid) -> DbResult<UserIdentity>;
+}
+
+/// Insert the identity a user signed up with, inside the transaction creating the user
+pub(super) async fn insert_identity(
+    conn: &mut PgConnection,
+    user: &User,
+    provider_name: &str,
+) -> DbResult<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_identities (user_id, provider, provider_name, provider_user_id, email)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user.id,
+        user.auth_provider as AuthProvider,
+        provider_name,
+        user.provider_user_id,
+        user.email
+    )
+    .execute(conn)
+    .await
+    .map_err(DbError::from)?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl IdentityExt for DBClient {
+    async fn get_user_identities(&self, user_id: Uuid) -> DbResult<Vec<UserIdentity>> {
+        let identities = sqlx::query_as!(
+            UserIdentity,
+            r#"
+            SELECT id, user_id, provider as "provider: AuthProvider", provider_name,
+                   provider_user_id, email, created_at, last_used_at
+            FROM user_identities
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(identities)
+    }
+
+    async fn has_identity(&self, user_id: Uuid, provider: AuthProvider) -> DbResult<bool> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM user_identities WHERE user_id = $1 AND provider = $2
+            ) AS "exists!"
+            "#,
+            user_id,
+            provider as AuthProvider
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(exists)
+    }
+
+    async fn link_identity(
+        &self,
+        user_id: Uuid,
+        provider: AuthProvider,
+        provider_name: &str,
+        provider_user_id: &str,
+        email: &str,
+    ) -> DbResult<UserIdentity> {
+        let identity = sqlx::query_as!(
+            UserIdentity,
+            r#"
+            INSERT INTO user_identities
+                (user_id, provider, provider_name, provider_user_id, email, last_used_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT DO NOTHING
+            RETURNING id, user_id, provider as "provider: AuthProvider", provider_name,
+                      provider_user_id, email, created_at, last_used_at
+            "#,
+            user_id,
+            provider as AuthProvider,
+            provider_name,
+            provider_user_id,
+            email
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        identity.ok_or_else(|| {
+            DbError::ConstraintViolation(format!("A {} account is already linked", provider_name))
+        })
+    }
+
+    async fn link_local_identity(&self, user_id: Uuid, email: &str) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_identities (user_id, provider, provider_name, email)
+            VALUES ($1, 'local', 'local', $2)
+            ON CONFLICT (user_id, provider_name) DO NOTHING
+            "#,
+            user_id,
+            email
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn update_identity_usage(&self, user_id: Uuid, provider_name: &str) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE user_identities
+            SET last_used_at = NOW()
+            WHERE user_id = $1 AND provider_name = $2
+            "#,
+            user_id,
+            provider_name
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn unlink_identity(&self, user_id: Uuid, identity_id: Uuid) -> DbResult<UserIdentity> {
+        let mut tx = self.begin_transaction().await?;
+
+        // Lock the user so that two concurrent unlinks cannot remove both remaining methods
+        sqlx::query!(
+            r#"
+            SELECT id FROM users WHERE id = $1 FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?
+        .ok_or_else(|| DbError::NotFound("User not found".to_string()))?;
+
+        let remaining = sqlx::query_scalar!(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM user_identities WHERE user_id = $1 AND id <> $2)
+                + (SELECT COUNT(*) FROM passkeys WHERE user_id = $1) AS "count!"
+            "#,
+            user_id,
+            identity_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        if remaining == 0 {
+            return Err(DbError::ConstraintViolation(
+                "Cannot unlink the last login method".to_string(),
+            ));
+        }
+
+        let identity = sqlx::query_as!(
+            UserIdentity,
+            r#"
+            DELETE FROM user_identities
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, user_id, provider as "provider: AuthProvider", provider_name,
+                      provider_user_id, email, created_at, last_used_at
+            "#,
+            identity_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?
+        .ok_or_else(|| DbError::NotFound("Identity not found".to_string()))?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(identity)
+    }
+}
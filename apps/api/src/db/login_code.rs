@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::LoginCode;
+
+/// Email login code database operations extension trait
+#[async_trait]
+pub trait LoginCodeExt {
+    /// Store a login code, invalidating the codes previously sent to the same address
+    ///
+    /// # Arguments
+    /// * `email` - Address the code was sent to
+    /// * `code_hash` - HMAC of the code
+    /// * `ip_address` - IP address that requested the code
+    /// * `expires_at` - Expiration time
+    async fn create_login_code(
+        &self,
+        email: &str,
+        code_hash: &str,
+        ip_address: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<()>;
+
+    /// Count the codes requested for an email and from an IP address since a point in time
+    ///
+    /// `email` is compared case-insensitively and must be lowercase.
+    ///
+    /// # Returns
+    /// * `Ok((by_email, by_ip))` - Number of codes requested
+    async fn count_recent_login_codes(
+        &self,
+        email: &str,
+        ip_address: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> DbResult<(i64, i64)>;
+
+    /// Record an attempt to use the latest active code of an email address
+    ///
+    /// # Arguments
+    /// * `email` - Lowercase email address
+    /// * `max_attempts` - Attempts allowed per code
+    ///
+    /// # Returns
+    /// * `Ok(Some(LoginCode))` - The code to compare against, its attempt is counted
+    /// * `Ok(None)` - No unused, unexpired code with attempts left
+    async fn take_login_code_attempt(
+        &self,
+        email: &str,
+        max_attempts: i32,
+    ) -> DbResult<Option<LoginCode>>;
+
+    /// Mark a code as used
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The code was consumed by this call
+    /// * `Ok(false)` - The code had already been used
+    async fn consume_login_code(&self, code_id: Uuid) -> DbResult<bool>;
+}
+
+#[async_trait]
+impl LoginCodeExt for DBClient {
+    async fn create_login_code(
+        &self,
+        email: &str,
+        code_hash: &str,
+        ip_address: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE login_codes
+            SET consumed_at = NOW()
+            WHERE LOWER(email) = LOWER($1) AND consumed_at IS NULL
+            "#,
+            email
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO login_codes (email, code_hash, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            email,
+            code_hash,
+            ip_address,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn count_recent_login_codes(
+        &self,
+        email: &str,
+        ip_address: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> DbResult<(i64, i64)> {
+        let counts = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE LOWER(email) = $1) AS "by_email!",
+                COUNT(*) FILTER (WHERE $2::text IS NOT NULL AND ip_address = $2) AS "by_ip!"
+            FROM login_codes
+            WHERE created_at >= $3 AND (LOWER(email) = $1 OR ip_address = $2)
+            "#,
+            email,
+            ip_address,
+            since
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok((counts.by_email, counts.by_ip))
+    }
+
+    async fn take_login_code_attempt(
+        &self,
+        email: &str,
+        max_attempts: i32,
+    ) -> DbResult<Option<LoginCode>> {
+        // Counting the attempt before comparing keeps concurrent guesses within the limit
+        let code = sqlx::query_as!(
+            LoginCode,
+            r#"
+            UPDATE login_codes
+            SET attempts = attempts + 1
+            WHERE id = (
+                SELECT id
+                FROM login_codes
+                WHERE LOWER(email) = $1
+                  AND consumed_at IS NULL
+                  AND expires_at > NOW()
+                ORDER BY created_at DESC
+                LIMIT 1
+            )
+            AND attempts < $2
+            RETURNING id, email, code_hash, attempts, ip_address, expires_at, consumed_at,
+                      created_at
+            "#,
+            email,
+            max_attempts
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(code)
+    }
+
+    async fn consume_login_code(&self, code_id: Uuid) -> DbResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE login_codes
+            SET consumed_at = NOW()
+            WHERE id = $1 AND consumed_at IS NULL
+            "#,
+            code_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
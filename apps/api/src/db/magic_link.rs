@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+/// Magic link database operations extension trait
+///
+/// Links are looked up by the SHA-256 hash of the token sent by email.
+#[async_trait]
+pub trait MagicLinkExt {
+    /// Store a sign-in link
+    ///
+    /// # Arguments
+    /// * `email` - Address the link was sent to
+    /// * `token_hash` - SHA-256 hash of the token
+    /// * `ip_address` - IP address that requested the link
+    /// * `expires_at` - Expiration time
+    async fn create_magic_link(
+        &self,
+        email: &str,
+        token_hash: &str,
+        ip_address: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<()>;
+
+    /// Count the links requested for an email and from an IP address since a point in time
+    ///
+    /// `email` is compared case-insensitively and must be lowercase.
+    ///
+    /// # Returns
+    /// * `Ok((by_email, by_ip))` - Number of links requested
+    async fn count_recent_magic_links(
+        &self,
+        email: &str,
+        ip_address: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> DbResult<(i64, i64)>;
+
+    /// Consume a sign-in link
+    ///
+    /// # Returns
+    /// * `Ok(Some(email))` - The link was valid, it can not be used again
+    /// * `Ok(None)` - Unknown, expired or already used link
+    async fn consume_magic_link(&self, token_hash: &str) -> DbResult<Option<String>>;
+}
+
+#[async_trait]
+impl MagicLinkExt for DBClient {
+    async fn create_magic_link(
+        &self,
+        email: &str,
+        token_hash: &str,
+        ip_address: Option<&str>,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO magic_links (email, token_hash, ip_address, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            email,
+            token_hash,
+            ip_address,
+            expires_at
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn count_recent_magic_links(
+        &self,
+        email: &str,
+        ip_address: Option<&str>,
+        since: DateTime<Utc>,
+    ) -> DbResult<(i64, i64)> {
+        let counts = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE LOWER(email) = $1) AS "by_email!",
+                COUNT(*) FILTER (WHERE $2::text IS NOT NULL AND ip_address = $2) AS "by_ip!"
+            FROM magic_links
+            WHERE created_at >= $3 AND (LOWER(email) = $1 OR ip_address = $2)
+            "#,
+            email,
+            ip_address,
+            since
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok((counts.by_email, counts.by_ip))
+    }
+
+    async fn consume_magic_link(&self, token_hash: &str) -> DbResult<Option<String>> {
+        let email = sqlx::query_scalar!(
+            r#"
+            UPDATE magic_links
+            SET consumed_at = NOW()
+            WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > NOW()
+            RETURNING email
+            "#,
+            token_hash
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(email)
+    }
+}
@@ -0,0 +1,360 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::{Notification, NotificationPreference, NotificationType};
+
+/// Notification database operations extension trait
+///
+/// Defines all operations related to in-app notifications in the database
+#[async_trait]
+pub trait NotificationExt {
+    /// Create a notification if the recipient has this type enabled
+    ///
+    /// Users are never notified about their own actions.
+    ///
+    /// # Arguments
+    /// * `user_id` - Recipient
+    /// * `actor_id` - User who triggered the notification
+    /// * `notification_type` - Type of the notification
+    /// * `document_id` - Related document
+    /// * `data` - Type specific details
+    ///
+    /// # Returns
+    /// * `Ok(Some(Notification))` - Notification created
+    /// * `Ok(None)` - Skipped because of the recipient's preferences
+    /// * `Err(DbError)` - Database error
+    async fn create_notification(
+        &self,
+        user_id: Uuid,
+        actor_id: Option<Uuid>,
+        notification_type: NotificationType,
+        document_id: Option<Uuid>,
+        data: serde_json::Value,
+    ) -> DbResult<Option<Notification>>;
+
+    /// Get the notifications of a user, unread first then newest first
+    ///
+    /// # Arguments
+    /// * `user_id` - User ID
+    /// * `page` - Page number (1-based)
+    /// * `limit` - Number of notifications per page
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Notification>)` - List of notifications
+    /// * `Err(DbError)` - Database error
+    async fn get_notifications(
+        &self,
+        user_id: Uuid,
+        page: u32,
+        limit: usize,
+    ) -> DbResult<Vec<Notification>>;
+
+    /// Get total count of notifications for a user
+    async fn get_notification_count(&self, user_id: Uuid) -> DbResult<i64>;
+
+    /// Get count of unread notifications for a user
+    async fn get_unread_notification_count(&self, user_id: Uuid) -> DbResult<i64>;
+
+    /// Mark a notification as read
+    ///
+    /// # Arguments
+    /// * `notification_id` - Notification ID
+    /// * `user_id` - Owner of the notification
+    ///
+    /// # Returns
+    /// * `Ok(Notification)` - Updated notification
+    /// * `Err(DbError)` - Database error
+    async fn mark_notification_read(
+        &self,
+        notification_id: Uuid,
+        user_id: Uuid,
+    ) -> DbResult<Notification>;
+
+    /// Mark all notifications of a user as read
+    ///
+    /// # Returns
+    /// * `Ok(u64)` - Number of notifications marked as read
+    /// * `Err(DbError)` - Database error
+    async fn mark_all_notifications_read(&self, user_id: Uuid) -> DbResult<u64>;
+
+    /// Get the notification preferences of a user, one entry per type
+    async fn get_notification_preferences(
+        &self,
+        user_id: Uuid,
+    ) -> DbResult<Vec<NotificationPreference>>;
+
+    /// Enable or disable a notification type for a user
+    async fn set_notification_preference(
+        &self,
+        user_id: Uuid,
+        notification_type: NotificationType,
+        enabled: bool,
+    ) -> DbResult<()>;
+
+    /// Ask the owner of a document for access
+    ///
+    /// A missing document is not reported, so requests cannot probe which documents
+    /// exist. While an earlier request of the same user for the same document is still
+    /// unread, no new notification is created.
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    /// * `requester_id` - User requesting access
+    /// * `data` - Request details
+    ///
+    /// # Returns
+    /// * `Ok(Some(Notification))` - Notification sent to the owner
+    /// * `Ok(None)` - No notification: unknown document, duplicate request or disabled type
+    /// * `Err(DbError)` - Database error
+    async fn request_document_access(
+        &self,
+        document_id: Uuid,
+        requester_id: Uuid,
+        data: serde_json::Value,
+    ) -> DbResult<Option<Notification>>;
+}
+
+#[async_trait]
+impl NotificationExt for DBClient {
+    async fn create_notification(
+        &self,
+        user_id: Uuid,
+        actor_id: Option<Uuid>,
+        notification_type: NotificationType,
+        document_id: Option<Uuid>,
+        data: serde_json::Value,
+    ) -> DbResult<Option<Notification>> {
+        if actor_id == Some(user_id) {
+            return Ok(None);
+        }
+
+        // The insert is skipped when the recipient disabled this type
+        let notification = sqlx::query_as!(
+            Notification,
+            r#"
+            INSERT INTO notifications (user_id, actor_id, notification_type, document_id, data)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT EXISTS (
+                SELECT 1 FROM notification_preferences
+                WHERE user_id = $1 AND notification_type = $3 AND enabled = false
+            )
+            RETURNING id as "id!", user_id as "user_id!", actor_id,
+                      notification_type as "notification_type!: NotificationType",
+                      document_id, data as "data!", read_at, created_at
+            "#,
+            user_id,
+            actor_id,
+            notification_type as NotificationType,
+            document_id,
+            data
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(notification)
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: Uuid,
+        page: u32,
+        limit: usize,
+    ) -> DbResult<Vec<Notification>> {
+        let offset = (page - 1) * limit as u32;
+
+        let notifications = sqlx::query_as!(
+            Notification,
+            r#"
+            SELECT id, user_id, actor_id,
+                   notification_type as "notification_type: NotificationType",
+                   document_id, data, read_at, created_at
+            FROM notifications
+            WHERE user_id = $1
+            ORDER BY (read_at IS NULL) DESC, created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit as i64,
+            offset as i64
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(notifications)
+    }
+
+    async fn get_notification_count(&self, user_id: Uuid) -> DbResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) FROM notifications WHERE user_id = $1"#,
+            user_id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn get_unread_notification_count(&self, user_id: Uuid) -> DbResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) FROM notifications WHERE user_id = $1 AND read_at IS NULL"#,
+            user_id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn mark_notification_read(
+        &self,
+        notification_id: Uuid,
+        user_id: Uuid,
+    ) -> DbResult<Notification> {
+        let notification = sqlx::query_as!(
+            Notification,
+            r#"
+            UPDATE notifications
+            SET read_at = COALESCE(read_at, NOW())
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, user_id, actor_id,
+                      notification_type as "notification_type: NotificationType",
+                      document_id, data, read_at, created_at
+            "#,
+            notification_id,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        notification.ok_or(DbError::NotFound("Notification not found".to_string()))
+    }
+
+    async fn mark_all_notifications_read(&self, user_id: Uuid) -> DbResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE notifications
+            SET read_at = NOW()
+            WHERE user_id = $1 AND read_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_notification_preferences(
+        &self,
+        user_id: Uuid,
+    ) -> DbResult<Vec<NotificationPreference>> {
+        let disabled = sqlx::query_scalar!(
+            r#"
+            SELECT notification_type as "notification_type: NotificationType"
+            FROM notification_preferences
+            WHERE user_id = $1 AND enabled = false
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(NotificationType::ALL
+            .into_iter()
+            .map(|notification_type| NotificationPreference {
+                notification_type,
+                enabled: !disabled.contains(&notification_type),
+            })
+            .collect())
+    }
+
+    async fn set_notification_preference(
+        &self,
+        user_id: Uuid,
+        notification_type: NotificationType,
+        enabled: bool,
+    ) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO notification_preferences (user_id, notification_type, enabled)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, notification_type)
+            DO UPDATE SET enabled = EXCLUDED.enabled, updated_at = NOW()
+            "#,
+            user_id,
+            notification_type as NotificationType,
+            enabled
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn request_document_access(
+        &self,
+        document_id: Uuid,
+        requester_id: Uuid,
+        data: serde_json::Value,
+    ) -> DbResult<Option<Notification>> {
+        let owner_id = sqlx::query_scalar!(
+            r#"SELECT owner_id FROM documents WHERE id = $1"#,
+            document_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        let Some(owner_id) = owner_id else {
+            return Ok(None);
+        };
+
+        if owner_id == requester_id {
+            return Err(DbError::ConstraintViolation(
+                "You already own this document".to_string(),
+            ));
+        }
+
+        let notification = sqlx::query_as!(
+            Notification,
+            r#"
+            INSERT INTO notifications (user_id, actor_id, notification_type, document_id, data)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT EXISTS (
+                SELECT 1 FROM notification_preferences
+                WHERE user_id = $1 AND notification_type = $3 AND enabled = false
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM notifications
+                WHERE user_id = $1 AND actor_id = $2 AND notification_type = $3
+                  AND document_id = $4 AND read_at IS NULL
+            )
+            RETURNING id as "id!", user_id as "user_id!", actor_id,
+                      notification_type as "notification_type!: NotificationType",
+                      document_id, data as "data!", read_at, created_at
+            "#,
+            owner_id,
+            requester_id,
+            NotificationType::AccessRequest as NotificationType,
+            document_id,
+            data
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(notification)
+    }
+}
@@ -0,0 +1,337 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::{Passkey, WebauthnChallenge};
+
+/// New passkey to store after a successful registration ceremony
+pub struct NewPasskey<'a> {
+    pub user_id: Uuid,
+    pub name: &'a str,
+    pub credential_id: &'a str,
+    pub public_key: &'a [u8],
+    pub algorithm: i32,
+    pub sign_count: i64,
+    pub transports: &'a [String],
+}
+
+/// WebAuthn passkey database operations extension trait
+#[async_trait]
+pub trait PasskeyExt {
+    /// Store the challenge of a registration or authentication ceremony
+    ///
+    /// # Arguments
+    /// * `user_id` - The user, `None` for passwordless login where the user is not yet known
+    /// * `purpose` - Ceremony the challenge may be used for
+    /// * `challenge` - Base64url encoded random challenge
+    /// * `expires_at` - Expiration time
+    async fn create_webauthn_challenge(
+        &self,
+        user_id: Option<Uuid>,
+        purpose: &str,
+        challenge: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<Uuid>;
+
+    /// Take a challenge so that it can only be used once
+    ///
+    /// # Returns
+    /// * `Ok(Some(challenge))` - The challenge exists, was not expired and matched the purpose
+    /// * `Ok(None)` - Unknown, expired or already used challenge
+    async fn take_webauthn_challenge(
+        &self,
+        challenge_id: Uuid,
+        purpose: &str,
+    ) -> DbResult<Option<WebauthnChallenge>>;
+
+    /// Store a registered passkey
+    ///
+    /// # Returns
+    /// * `Err(DbError::ConstraintViolation)` - The credential is already registered
+    async fn create_passkey(&self, passkey: NewPasskey<'_>) -> DbResult<Passkey>;
+
+    /// Get the passkeys of a user, oldest first
+    async fn get_user_passkeys(&self, user_id: Uuid) -> DbResult<Vec<Passkey>>;
+
+    /// Find a passkey by its base64url encoded credential ID
+    async fn get_passkey_by_credential_id(&self, credential_id: &str) -> DbResult<Option<Passkey>>;
+
+    /// Record a successful authentication with the new signature counter
+    ///
+    /// The counter must grow, unless the authenticator does not implement one and
+    /// always reports zero. Concurrent authentications with the same counter value
+    /// cannot both succeed.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The counter was updated
+    /// * `Ok(false)` - The counter did not grow, the credential may have been cloned
+    async fn update_passkey_usage(&self, passkey_id: Uuid, sign_count: i64) -> DbResult<bool>;
+
+    /// Delete a passkey of a user
+    ///
+    /// # Returns
+    /// * `Err(DbError::NotFound)` - The passkey does not exist or belongs to another user
+    async fn delete_passkey(&self, passkey_id: Uuid, user_id: Uuid) -> DbResult<Passkey>;
+
+    /// Count the passkeys of a user
+    async fn count_user_passkeys(&self, user_id: Uuid) -> DbResult<i64>;
+}
+
+#[async_trait]
+impl PasskeyExt for DBClient {
+    async fn create_webauthn_challenge(
+        &self,
+        user_id: Option<Uuid>,
+        purpose: &str,
+        challenge: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<Uuid> {
+        // Expired challenges are never used again, clean them up on the way
+        sqlx::query!(
+            r#"
+            DELETE FROM webauthn_challenges WHERE expires_at < NOW()
+            "#
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO webauthn_challenges (user_id, purpose, challenge, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+            user_id,
+            purpose,
+            challenge,
+            expires_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(id)
+    }
+
+    async fn take_webauthn_challenge(
+        &self,
+        challenge_id: Uuid,
+        purpose: &str,
+    ) -> DbResult<Option<WebauthnChallenge>> {
+        let challenge = sqlx::query_as!(
+            WebauthnChallenge,
+            r#"
+            DELETE FROM webauthn_challenges
+            WHERE id = $1 AND purpose = $2 AND expires_at > NOW()
+            RETURNING id, user_id, purpose, challenge, expires_at, created_at
+            "#,
+            challenge_id,
+            purpose
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(challenge)
+    }
+
+    async fn create_passkey(&self, passkey: NewPasskey<'_>) -> DbResult<Passkey> {
+        let created = sqlx::query_as!(
+            Passkey,
+            r#"
+            INSERT INTO passkeys (user_id, name, credential_id, public_key, algorithm, sign_count, transports)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (credential_id) DO NOTHING
+            RETURNING id, user_id, name, credential_id, public_key, algorithm, sign_count,
+                      transports, created_at, last_used_at
+            "#,
+            passkey.user_id,
+            passkey.name,
+            passkey.credential_id,
+            passkey.public_key,
+            passkey.algorithm,
+            passkey.sign_count,
+            passkey.transports
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        created.ok_or_else(|| {
+            DbError::ConstraintViolation("Passkey is already registered".to_string())
+        })
+    }
+
+    async fn get_user_passkeys(&self, user_id: Uuid) -> DbResult<Vec<Passkey>> {
+        let passkeys = sqlx::query_as!(
+            Passkey,
+            r#"
+            SELECT id, user_id, name, credential_id, public_key, algorithm, sign_count,
+                   transports, created_at, last_used_at
+            FROM passkeys
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(passkeys)
+    }
+
+    async fn get_passkey_by_credential_id(&self, credential_id: &str) -> DbResult<Option<Passkey>> {
+        let passkey = sqlx::query_as!(
+            Passkey,
+            r#"
+            SELECT id, user_id, name, credential_id, public_key, algorithm, sign_count,
+                   transports, created_at, last_used_at
+            FROM passkeys
+            WHERE credential_id = $1
+            "#,
+            credential_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(passkey)
+    }
+
+    async fn update_passkey_usage(&self, passkey_id: Uuid, sign_count: i64) -> DbResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE passkeys
+            SET sign_count = $2, last_used_at = NOW()
+            WHERE id = $1 AND (sign_count < $2 OR (sign_count = 0 AND $2 = 0))
+            "#,
+            passkey_id,
+            sign_count
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_passkey(&self, passkey_id: Uuid, user_id: Uuid) -> DbResult<Passkey> {
+        let passkey = sqlx::query_as!(
+            Passkey,
+            r#"
+            DELETE FROM passkeys
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, user_id, name, credential_id, public_key, algorithm, sign_count,
+                      transports, created_at, last_used_at
+            "#,
+            passkey_id,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        passkey.ok_or_else(|| DbError::NotFound("Passkey not found".to_string()))
+    }
+
+    async fn count_user_passkeys(&self, user_id: Uuid) -> DbResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM passkeys WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::UserExt;
+
+    async fn passkey(db_client: &DBClient, sign_count: i64) -> Passkey {
+        let user = db_client
+            .save_user("owner", "owner@example.com", "hash", "token", Utc::now())
+            .await
+            .unwrap();
+        db_client
+            .create_passkey(NewPasskey {
+                user_id: user.id,
+                name: "Laptop",
+                credential_id: "credential",
+                public_key: b"key",
+                algorithm: -7,
+                sign_count,
+                transports: &[],
+            })
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn sign_count_must_grow(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let passkey = passkey(&db_client, 5).await;
+
+        assert!(!db_client.update_passkey_usage(passkey.id, 5).await.unwrap());
+        assert!(!db_client.update_passkey_usage(passkey.id, 4).await.unwrap());
+        assert!(!db_client.update_passkey_usage(passkey.id, 0).await.unwrap());
+        assert!(db_client.update_passkey_usage(passkey.id, 6).await.unwrap());
+        assert!(!db_client.update_passkey_usage(passkey.id, 6).await.unwrap());
+
+        let stored = db_client
+            .get_passkey_by_credential_id("credential")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.sign_count, 6);
+        assert!(stored.last_used_at.is_some());
+    }
+
+    #[sqlx::test]
+    async fn authenticators_without_counter_stay_at_zero(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let passkey = passkey(&db_client, 0).await;
+
+        assert!(db_client.update_passkey_usage(passkey.id, 0).await.unwrap());
+        assert!(db_client.update_passkey_usage(passkey.id, 0).await.unwrap());
+        assert!(db_client.update_passkey_usage(passkey.id, 3).await.unwrap());
+        assert!(!db_client.update_passkey_usage(passkey.id, 0).await.unwrap());
+    }
+
+    #[sqlx::test]
+    async fn concurrent_uses_of_one_counter_value(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let passkey = passkey(&db_client, 1).await;
+
+        let updated = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut tasks = tokio::task::JoinSet::new();
+            for _ in 0..10 {
+                let db_client = db_client.clone();
+                tasks.spawn(async move { db_client.update_passkey_usage(passkey.id, 2).await });
+            }
+            tasks.join_all().await
+        });
+
+        assert_eq!(
+            updated
+                .into_iter()
+                .filter(|ok| *ok.as_ref().unwrap())
+                .count(),
+            1
+        );
+    }
+}
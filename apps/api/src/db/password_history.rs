@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+/// Password history database operations extension trait
+///
+/// Only password hashes are stored; the current password stays in `users.password`.
+#[async_trait]
+pub trait PasswordHistoryExt {
+    /// Get the hashes of the most recent previous passwords of a user, newest first
+    async fn get_password_history(&self, user_id: Uuid, limit: i64) -> DbResult<Vec<String>>;
+
+    /// Remember a password that was replaced, keeping only the most recent entries
+    ///
+    /// # Arguments
+    /// * `user_id` - User whose password changed
+    /// * `password_hash` - Hash of the replaced password
+    /// * `keep` - Number of entries to keep for the user
+    async fn record_password_history(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+        keep: i64,
+    ) -> DbResult<()>;
+}
+
+#[async_trait]
+impl PasswordHistoryExt for DBClient {
+    async fn get_password_history(&self, user_id: Uuid, limit: i64) -> DbResult<Vec<String>> {
+        let hashes = sqlx::query_scalar!(
+            r#"
+            SELECT password_hash
+            FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC, id
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(hashes)
+    }
+
+    async fn record_password_history(
+        &self,
+        user_id: Uuid,
+        password_hash: &str,
+        keep: i64,
+    ) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = $1
+              AND id NOT IN (
+                  SELECT id FROM password_history
+                  WHERE user_id = $1
+                  ORDER BY created_at DESC, id
+                  LIMIT $2
+              )
+            "#,
+            user_id,
+            keep
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::LoginLockout;
+
+/// Rate limiting and login lockout database operations extension trait
+#[async_trait]
+pub trait RateLimitExt {
+    /// Count a request against a sliding window, shared by all instances
+    ///
+    /// Requests of the same key are serialized with an advisory lock.
+    /// A rejected request is not counted.
+    ///
+    /// # Arguments
+    /// * `key` - Rate limit key, e.g. `login:ip:203.0.113.7`
+    /// * `limit` - Requests allowed within the window
+    /// * `window_seconds` - Length of the sliding window
+    ///
+    /// # Returns
+    /// * `Ok(None)` - The request is allowed and was counted
+    /// * `Ok(Some(retry_at))` - The limit is reached until `retry_at`
+    async fn record_rate_limit_hit(
+        &self,
+        key: &str,
+        limit: i64,
+        window_seconds: f64,
+    ) -> DbResult<Option<DateTime<Utc>>>;
+
+    /// Delete the counted requests older than a point in time
+    async fn purge_rate_limit_hits(&self, before: DateTime<Utc>) -> DbResult<u64>;
+
+    /// Get the failed password login state of a user
+    async fn get_login_lockout(&self, user_id: Uuid) -> DbResult<Option<LoginLockout>>;
+
+    /// Count a failed password login
+    ///
+    /// Failures before `reset_before` are forgotten and counting starts over.
+    ///
+    /// # Returns
+    /// * `Ok(i32)` - Number of consecutive failures including this one
+    async fn record_login_failure(
+        &self,
+        user_id: Uuid,
+        reset_before: DateTime<Utc>,
+    ) -> DbResult<i32>;
+
+    /// Lock password login of a user until a point in time
+    async fn lock_login(&self, user_id: Uuid, locked_until: DateTime<Utc>) -> DbResult<()>;
+
+    /// Clear the failed attempts and any lockout after a successful login or password reset
+    async fn clear_login_failures(&self, user_id: Uuid) -> DbResult<()>;
+}
+
+#[async_trait]
+impl RateLimitExt for DBClient {
+    async fn record_rate_limit_hit(
+        &self,
+        key: &str,
+        limit: i64,
+        window_seconds: f64,
+    ) -> DbResult<Option<DateTime<Utc>>> {
+        let mut tx = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            SELECT pg_advisory_xact_lock(hashtext($1))
+            "#,
+            key
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let window = sqlx::query!(
+            r#"
+            SELECT COUNT(*) AS "count!",
+                   MIN(hit_at) + make_interval(secs => $2) AS retry_at
+            FROM rate_limit_hits
+            WHERE key = $1 AND hit_at > NOW() - make_interval(secs => $2)
+            "#,
+            key,
+            window_seconds
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        if window.count >= limit {
+            tx.commit().await.map_err(DbError::from)?;
+            return Ok(window.retry_at);
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO rate_limit_hits (key) VALUES ($1)
+            "#,
+            key
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(None)
+    }
+
+    async fn purge_rate_limit_hits(&self, before: DateTime<Utc>) -> DbResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM rate_limit_hits WHERE hit_at < $1
+            "#,
+            before
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_login_lockout(&self, user_id: Uuid) -> DbResult<Option<LoginLockout>> {
+        let lockout = sqlx::query_as!(
+            LoginLockout,
+            r#"
+            SELECT user_id, failed_attempts, last_failed_at, locked_until
+            FROM login_lockouts
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(lockout)
+    }
+
+    async fn record_login_failure(
+        &self,
+        user_id: Uuid,
+        reset_before: DateTime<Utc>,
+    ) -> DbResult<i32> {
+        let failed_attempts = sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_lockouts (user_id, failed_attempts, last_failed_at)
+            VALUES ($1, 1, NOW())
+            ON CONFLICT (user_id) DO UPDATE
+            SET failed_attempts = CASE
+                    WHEN login_lockouts.last_failed_at < $2 THEN 1
+                    ELSE login_lockouts.failed_attempts + 1
+                END,
+                last_failed_at = NOW()
+            RETURNING failed_attempts
+            "#,
+            user_id,
+            reset_before
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(failed_attempts)
+    }
+
+    async fn lock_login(&self, user_id: Uuid, locked_until: DateTime<Utc>) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE login_lockouts SET locked_until = $2 WHERE user_id = $1
+            "#,
+            user_id,
+            locked_until
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn clear_login_failures(&self, user_id: Uuid) -> DbResult<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM login_lockouts WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::RefreshToken;
+
+/// Outcome of presenting a refresh token for rotation
+#[derive(Debug)]
+pub enum RefreshTokenRotation {
+    /// The token was valid and has been replaced by the returned token
+    Rotated(RefreshToken),
+    /// The token had already been rotated, its whole family is now revoked
+    Reused(RefreshToken),
+    /// Unknown, expired or revoked token
+    Invalid,
+}
+
+/// Refresh token database operations extension trait
+///
+/// Tokens are looked up by the SHA-256 hash of the opaque value handed to the client.
+#[async_trait]
+pub trait RefreshTokenExt {
+    /// Store a refresh token
+    ///
+    /// # Arguments
+    /// * `user_id` - Owner of the token
+    /// * `family_id` - Family the token belongs to, the id of the login session
+    /// * `token_hash` - SHA-256 hash of the token
+    /// * `expires_at` - Expiration time
+    async fn create_refresh_token(
+        &self,
+        user_id: Uuid,
+        family_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<RefreshToken>;
+
+    /// Exchange a refresh token for a new one in the same family
+    ///
+    /// The presented token is marked as used and the session is extended. Presenting
+    /// a token that was already used revokes every token of its family and the session.
+    ///
+    /// # Arguments
+    /// * `token_hash` - Hash of the presented token
+    /// * `new_token_hash` - Hash of the replacement token
+    /// * `expires_at` - Expiration time of the replacement token
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        new_token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<RefreshTokenRotation>;
+}
+
+#[async_trait]
+impl RefreshTokenExt for DBClient {
+    async fn create_refresh_token(
+        &self,
+        user_id: Uuid,
+        family_id: Uuid,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<RefreshToken> {
+        let token = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, family_id, token_hash, expires_at, used_at, revoked_at,
+                      replaced_by, created_at
+            "#,
+            user_id,
+            family_id,
+            token_hash,
+            expires_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(token)
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        new_token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<RefreshTokenRotation> {
+        let mut tx = self.begin_transaction().await?;
+
+        // Lock the row so two concurrent rotations of the same token cannot both succeed
+        let current = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            SELECT id, user_id, family_id, token_hash, expires_at, used_at, revoked_at,
+                   replaced_by, created_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        let Some(current) = current else {
+            return Ok(RefreshTokenRotation::Invalid);
+        };
+
+        if current.revoked_at.is_some() || current.expires_at <= Utc::now() {
+            return Ok(RefreshTokenRotation::Invalid);
+        }
+
+        if current.used_at.is_some() {
+            sqlx::query!(
+                r#"
+                UPDATE refresh_tokens
+                SET revoked_at = NOW()
+                WHERE family_id = $1 AND revoked_at IS NULL
+                "#,
+                current.family_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(DbError::from)?;
+
+            sqlx::query!(
+                r#"
+                UPDATE sessions
+                SET revoked_at = NOW()
+                WHERE id = $1 AND revoked_at IS NULL
+                "#,
+                current.family_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(DbError::from)?;
+
+            tx.commit().await.map_err(DbError::from)?;
+
+            return Ok(RefreshTokenRotation::Reused(current));
+        }
+
+        let replacement = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, family_id, token_hash, expires_at, used_at, revoked_at,
+                      replaced_by, created_at
+            "#,
+            current.user_id,
+            current.family_id,
+            new_token_hash,
+            expires_at
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET used_at = NOW(), replaced_by = $1
+            WHERE id = $2
+            "#,
+            replacement.id,
+            current.id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET expires_at = $1, last_seen_at = NOW()
+            WHERE id = $2
+            "#,
+            expires_at,
+            current.family_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(RefreshTokenRotation::Rotated(replacement))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::{SessionExt, UserExt};
+
+    /// 创建用户和登录会话，返回会话 ID（即令牌族 ID）
+    async fn session(db_client: &DBClient) -> (Uuid, Uuid) {
+        let user_id = db_client
+            .save_user("user", "user@example.com", "hash", "token", Utc::now())
+            .await
+            .unwrap()
+            .id;
+        let session = db_client
+            .create_session(user_id, None, None, Utc::now() + Duration::days(30))
+            .await
+            .unwrap();
+        (user_id, session.id)
+    }
+
+    async fn rotate(db_client: &DBClient, from: &str, to: &str) -> RefreshTokenRotation {
+        db_client
+            .rotate_refresh_token(from, to, Utc::now() + Duration::days(30))
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn reusing_a_rotated_token_revokes_the_family(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let (user_id, family_id) = session(&db_client).await;
+        db_client
+            .create_refresh_token(user_id, family_id, "a", Utc::now() + Duration::days(30))
+            .await
+            .unwrap();
+
+        let RefreshTokenRotation::Rotated(b) = rotate(&db_client, "a", "b").await else {
+            panic!("first rotation must succeed");
+        };
+        assert_eq!(b.family_id, family_id);
+
+        // -- 旧令牌被再次使用，说明令牌可能已泄露
+        assert!(matches!(
+            rotate(&db_client, "a", "c").await,
+            RefreshTokenRotation::Reused(_)
+        ));
+        assert!(matches!(
+            rotate(&db_client, "b", "d").await,
+            RefreshTokenRotation::Invalid
+        ));
+        assert!(
+            db_client
+                .get_session(family_id, user_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[sqlx::test]
+    async fn expired_token_is_invalid(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let (user_id, family_id) = session(&db_client).await;
+        db_client
+            .create_refresh_token(user_id, family_id, "a", Utc::now() - Duration::seconds(1))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            rotate(&db_client, "a", "b").await,
+            RefreshTokenRotation::Invalid
+        ));
+        assert!(matches!(
+            rotate(&db_client, "unknown", "c").await,
+            RefreshTokenRotation::Invalid
+        ));
+        // -- 过期令牌不算重用，会话不受影响
+        assert!(
+            db_client
+                .get_session(family_id, user_id)
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[sqlx::test]
+    async fn concurrent_rotation_has_one_winner(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let (user_id, family_id) = session(&db_client).await;
+        db_client
+            .create_refresh_token(user_id, family_id, "a", Utc::now() + Duration::days(30))
+            .await
+            .unwrap();
+
+        // -- 在多线程运行时上同时轮换同一个令牌
+        let rotations = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut tasks = tokio::task::JoinSet::new();
+            for i in 0..10 {
+                let db_client = db_client.clone();
+                tasks.spawn(async move {
+                    db_client
+                        .rotate_refresh_token(
+                            "a",
+                            &format!("b{}", i),
+                            Utc::now() + Duration::days(30),
+                        )
+                        .await
+                });
+            }
+            tasks.join_all().await
+        });
+
+        let rotated = rotations
+            .iter()
+            .filter(|rotation| matches!(rotation, Ok(RefreshTokenRotation::Rotated(_))))
+            .count();
+        assert_eq!(rotated, 1);
+        // -- 其余请求看到的是已使用的令牌，整个令牌族随之吊销
+        assert!(rotations.iter().all(|rotation| rotation.is_ok()));
+        assert!(
+            db_client
+                .get_session(family_id, user_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
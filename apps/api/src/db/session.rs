@@ -0,0 +1,236 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::Session;
+
+/// Session database operations extension trait
+///
+/// Revoking a session also revokes the refresh tokens issued for it.
+#[async_trait]
+pub trait SessionExt {
+    /// Create a session for a new login
+    ///
+    /// # Arguments
+    /// * `user_id` - The user who logged in
+    /// * `ip_address` - Client IP address, if known
+    /// * `user_agent` - Client User-Agent, if known
+    /// * `expires_at` - When the session ends unless its refresh token is rotated
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<Session>;
+
+    /// Check that a session is active and record the activity
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The session is active, `last_seen_at` was updated
+    /// * `Ok(false)` - Unknown, expired or revoked session
+    async fn touch_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid,
+        ip_address: Option<String>,
+    ) -> DbResult<bool>;
+
+    /// Get an active session of a user
+    async fn get_session(&self, session_id: Uuid, user_id: Uuid) -> DbResult<Option<Session>>;
+
+    /// Get the active sessions of a user, most recently seen first
+    async fn get_user_sessions(&self, user_id: Uuid) -> DbResult<Vec<Session>>;
+
+    /// Revoke one session of a user
+    ///
+    /// # Returns
+    /// * `Err(DbError::NotFound)` - The user has no such active session
+    async fn revoke_session(&self, session_id: Uuid, user_id: Uuid) -> DbResult<()>;
+
+    /// Revoke every active session of a user
+    ///
+    /// # Arguments
+    /// * `user_id` - The user
+    /// * `except` - Session to keep, usually the current one
+    ///
+    /// # Returns
+    /// The ids of the revoked sessions
+    async fn revoke_user_sessions(
+        &self,
+        user_id: Uuid,
+        except: Option<Uuid>,
+    ) -> DbResult<Vec<Uuid>>;
+}
+
+#[async_trait]
+impl SessionExt for DBClient {
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<Session> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (user_id, ip_address, user_agent, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id, user_agent, ip_address, created_at, last_seen_at,
+                      expires_at, revoked_at
+            "#,
+            user_id,
+            ip_address,
+            user_agent,
+            expires_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(session)
+    }
+
+    async fn touch_session(
+        &self,
+        session_id: Uuid,
+        user_id: Uuid,
+        ip_address: Option<String>,
+    ) -> DbResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = NOW(), ip_address = COALESCE($3, ip_address)
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+            session_id,
+            user_id,
+            ip_address
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_session(&self, session_id: Uuid, user_id: Uuid) -> DbResult<Option<Session>> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_seen_at,
+                   expires_at, revoked_at
+            FROM sessions
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+            session_id,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(session)
+    }
+
+    async fn get_user_sessions(&self, user_id: Uuid) -> DbResult<Vec<Session>> {
+        let sessions = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT id, user_id, user_agent, ip_address, created_at, last_seen_at,
+                   expires_at, revoked_at
+            FROM sessions
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY last_seen_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(sessions)
+    }
+
+    async fn revoke_session(&self, session_id: Uuid, user_id: Uuid) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            session_id,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound("Session not found".to_string()));
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE family_id = $1 AND revoked_at IS NULL
+            "#,
+            session_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn revoke_user_sessions(
+        &self,
+        user_id: Uuid,
+        except: Option<Uuid>,
+    ) -> DbResult<Vec<Uuid>> {
+        let mut tx = self.begin_transaction().await?;
+
+        let revoked = sqlx::query_scalar!(
+            r#"
+            UPDATE sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1
+              AND revoked_at IS NULL
+              AND ($2::uuid IS NULL OR id <> $2)
+            RETURNING id
+            "#,
+            user_id,
+            except
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = NOW()
+            WHERE family_id = ANY($1) AND revoked_at IS NULL
+            "#,
+            &revoked
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(revoked)
+    }
+}
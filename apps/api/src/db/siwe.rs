@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::SiweNonce;
+
+/// Sign-In with Ethereum nonce database operations extension trait
+#[async_trait]
+pub trait SiweExt {
+    /// Store a nonce for a wallet to sign
+    ///
+    /// # Arguments
+    /// * `user_id` - The user linking a wallet, `None` for wallet login
+    /// * `nonce` - Random alphanumeric nonce
+    /// * `expires_at` - Expiration time
+    async fn create_siwe_nonce(
+        &self,
+        user_id: Option<Uuid>,
+        nonce: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<SiweNonce>;
+
+    /// Take a nonce so that it can only be used once
+    ///
+    /// # Returns
+    /// * `Ok(Some(nonce))` - The nonce exists and was not expired
+    /// * `Ok(None)` - Unknown, expired or already used nonce
+    async fn take_siwe_nonce(&self, nonce: &str) -> DbResult<Option<SiweNonce>>;
+}
+
+#[async_trait]
+impl SiweExt for DBClient {
+    async fn create_siwe_nonce(
+        &self,
+        user_id: Option<Uuid>,
+        nonce: &str,
+        expires_at: DateTime<Utc>,
+    ) -> DbResult<SiweNonce> {
+        // Expired nonces are never used again, clean them up on the way
+        sqlx::query!(
+            r#"
+            DELETE FROM siwe_nonces WHERE expires_at < NOW()
+            "#
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        let nonce = sqlx::query_as!(
+            SiweNonce,
+            r#"
+            INSERT INTO siwe_nonces (user_id, nonce, expires_at)
+            VALUES ($1, $2, $3)
+            RETURNING id, nonce, user_id, expires_at, created_at
+            "#,
+            user_id,
+            nonce,
+            expires_at
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(nonce)
+    }
+
+    async fn take_siwe_nonce(&self, nonce: &str) -> DbResult<Option<SiweNonce>> {
+        let nonce = sqlx::query_as!(
+            SiweNonce,
+            r#"
+            DELETE FROM siwe_nonces
+            WHERE nonce = $1 AND expires_at > NOW()
+            RETURNING id, nonce, user_id, expires_at, created_at
+            "#,
+            nonce
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn nonce_can_only_be_taken_once(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let expires_at = Utc::now() + Duration::minutes(5);
+        db_client
+            .create_siwe_nonce(None, "0123456789abcdef", expires_at)
+            .await
+            .unwrap();
+
+        let nonce = db_client.take_siwe_nonce("0123456789abcdef").await.unwrap();
+        assert!(nonce.is_some_and(|nonce| nonce.user_id.is_none()));
+        assert!(
+            db_client
+                .take_siwe_nonce("0123456789abcdef")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            db_client
+                .take_siwe_nonce("fedcba9876543210")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[sqlx::test]
+    async fn expired_nonce_is_rejected(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        db_client
+            .create_siwe_nonce(None, "0123456789abcdef", Utc::now() - Duration::seconds(1))
+            .await
+            .unwrap();
+
+        assert!(
+            db_client
+                .take_siwe_nonce("0123456789abcdef")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
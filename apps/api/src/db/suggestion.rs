@@ -0,0 +1,352 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::COMMENT_PERMISSION;
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+use super::DocumentExt;
+
+use crate::models::{Document, PermissionLevel, Suggestion, SuggestionKind, SuggestionStatus};
+
+/// Suggestion database operations extension trait
+///
+/// Defines all operations related to tracked-change suggestions in the database
+#[async_trait]
+pub trait SuggestionExt {
+    /// Get the suggestions of a document
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    /// * `user_id` - User requesting the suggestions (for permission check)
+    /// * `status` - Only return suggestions with this status, all when `None`
+    ///
+    /// # Returns
+    /// * `Ok(Vec<Suggestion>)` - List of suggestions, oldest first
+    /// * `Err(DbError)` - Database error
+    async fn get_suggestions(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        status: Option<SuggestionStatus>,
+    ) -> DbResult<Vec<Suggestion>>;
+
+    /// Get a suggestion by its ID
+    ///
+    /// # Arguments
+    /// * `suggestion_id` - Suggestion ID
+    ///
+    /// # Returns
+    /// * `Ok(Some(Suggestion))` - Suggestion found
+    /// * `Ok(None)` - Suggestion not found
+    /// * `Err(DbError)` - Database error
+    async fn get_suggestion(&self, suggestion_id: Uuid) -> DbResult<Option<Suggestion>>;
+
+    /// Propose a change to a block range of a document revision
+    ///
+    /// # Arguments
+    /// * `document_id` - Document ID
+    /// * `author_id` - Author of the suggestion (for permission check)
+    /// * `kind` - Kind of change
+    /// * `block_id` - Block containing the range
+    /// * `start_offset` / `end_offset` - Character range within the block
+    /// * `original_text` - Text currently in the range
+    /// * `text` - Replacement text
+    /// * `base_revision` - Document revision the suggestion was made on
+    ///
+    /// # Returns
+    /// * `Ok(Suggestion)` - Created suggestion
+    /// * `Err(DbError)` - Database error
+    #[allow(clippy::too_many_arguments)]
+    async fn create_suggestion(
+        &self,
+        document_id: Uuid,
+        author_id: Uuid,
+        kind: SuggestionKind,
+        block_id: String,
+        start_offset: i32,
+        end_offset: i32,
+        original_text: String,
+        text: String,
+        base_revision: i64,
+    ) -> DbResult<Suggestion>;
+
+    /// Mark a pending suggestion as accepted or rejected
+    ///
+    /// Accepting requires edit rights on the document. Rejecting is also
+    /// allowed for the author, which withdraws the suggestion. The status
+    /// only changes while the suggestion is still pending, so concurrent
+    /// reviews cannot both succeed.
+    ///
+    /// # Arguments
+    /// * `suggestion_id` - Suggestion ID
+    /// * `reviewer_id` - User reviewing the suggestion
+    /// * `status` - `Accepted` or `Rejected`
+    ///
+    /// # Returns
+    /// * `Ok(Suggestion)` - Reviewed suggestion
+    /// * `Err(DbError)` - Database error
+    async fn review_suggestion(
+        &self,
+        suggestion_id: Uuid,
+        reviewer_id: Uuid,
+        status: SuggestionStatus,
+    ) -> DbResult<Suggestion>;
+
+    /// Accept a pending suggestion and write its result to the document in one transaction
+    ///
+    /// The document is only updated while it is still at `expected_revision`, so a
+    /// concurrent edit between reading the document and applying the suggestion is
+    /// never overwritten. Either both the document and the suggestion change, or neither.
+    ///
+    /// # Arguments
+    /// * `suggestion_id` - Suggestion ID
+    /// * `reviewer_id` - User accepting the suggestion (needs edit rights)
+    /// * `expected_revision` - Document revision the new content was computed from
+    /// * `content` - Document content with the suggestion applied
+    ///
+    /// # Returns
+    /// * `Ok((Suggestion, Document))` - Accepted suggestion and the updated document
+    /// * `Err(DbError::ConstraintViolation)` - Suggestion already reviewed or document changed
+    /// * `Err(DbError)` - Database error
+    async fn accept_suggestion(
+        &self,
+        suggestion_id: Uuid,
+        reviewer_id: Uuid,
+        expected_revision: i64,
+        content: String,
+    ) -> DbResult<(Suggestion, Document)>;
+}
+
+#[async_trait]
+impl SuggestionExt for DBClient {
+    async fn get_suggestions(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        status: Option<SuggestionStatus>,
+    ) -> DbResult<Vec<Suggestion>> {
+        let has_permission = self
+            .check_document_permission(document_id, user_id, PermissionLevel::Read)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let suggestions = sqlx::query_as!(
+            Suggestion,
+            r#"
+            SELECT id, document_id, author_id, kind as "kind: SuggestionKind", block_id,
+                   start_offset, end_offset, original_text, text, base_revision,
+                   status as "status: SuggestionStatus", reviewed_by, reviewed_at,
+                   applied_revision, created_at, updated_at
+            FROM suggestions
+            WHERE document_id = $1 AND ($2::suggestion_status IS NULL OR status = $2)
+            ORDER BY created_at ASC
+            "#,
+            document_id,
+            status as Option<SuggestionStatus>
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(suggestions)
+    }
+
+    async fn get_suggestion(&self, suggestion_id: Uuid) -> DbResult<Option<Suggestion>> {
+        let suggestion = sqlx::query_as!(
+            Suggestion,
+            r#"
+            SELECT id, document_id, author_id, kind as "kind: SuggestionKind", block_id,
+                   start_offset, end_offset, original_text, text, base_revision,
+                   status as "status: SuggestionStatus", reviewed_by, reviewed_at,
+                   applied_revision, created_at, updated_at
+            FROM suggestions
+            WHERE id = $1
+            "#,
+            suggestion_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(suggestion)
+    }
+
+    async fn create_suggestion(
+        &self,
+        document_id: Uuid,
+        author_id: Uuid,
+        kind: SuggestionKind,
+        block_id: String,
+        start_offset: i32,
+        end_offset: i32,
+        original_text: String,
+        text: String,
+        base_revision: i64,
+    ) -> DbResult<Suggestion> {
+        let has_permission = self
+            .check_document_permission(document_id, author_id, COMMENT_PERMISSION)
+            .await?;
+
+        if !has_permission {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let suggestion = sqlx::query_as!(
+            Suggestion,
+            r#"
+            INSERT INTO suggestions (
+                document_id, author_id, kind, block_id, start_offset, end_offset,
+                original_text, text, base_revision
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, document_id, author_id, kind as "kind: SuggestionKind", block_id,
+                      start_offset, end_offset, original_text, text, base_revision,
+                      status as "status: SuggestionStatus", reviewed_by, reviewed_at,
+                      applied_revision, created_at, updated_at
+            "#,
+            document_id,
+            author_id,
+            kind as SuggestionKind,
+            block_id,
+            start_offset,
+            end_offset,
+            original_text,
+            text,
+            base_revision
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(suggestion)
+    }
+
+    async fn review_suggestion(
+        &self,
+        suggestion_id: Uuid,
+        reviewer_id: Uuid,
+        status: SuggestionStatus,
+    ) -> DbResult<Suggestion> {
+        let suggestion = self
+            .get_suggestion(suggestion_id)
+            .await?
+            .ok_or(DbError::NotFound("Suggestion not found".to_string()))?;
+
+        // Authors may withdraw their own suggestions, everything else needs edit rights
+        let is_withdrawal =
+            status == SuggestionStatus::Rejected && suggestion.author_id == reviewer_id;
+        if !is_withdrawal {
+            let can_edit = self
+                .check_document_permission(
+                    suggestion.document_id,
+                    reviewer_id,
+                    PermissionLevel::ReadWrite,
+                )
+                .await?;
+
+            if !can_edit {
+                return Err(DbError::PermissionDenied);
+            }
+        }
+
+        let reviewed = sqlx::query_as!(
+            Suggestion,
+            r#"
+            UPDATE suggestions
+            SET status = $1, reviewed_by = $2, reviewed_at = NOW(), updated_at = NOW()
+            WHERE id = $3 AND status = 'pending'
+            RETURNING id, document_id, author_id, kind as "kind: SuggestionKind", block_id,
+                      start_offset, end_offset, original_text, text, base_revision,
+                      status as "status: SuggestionStatus", reviewed_by, reviewed_at,
+                      applied_revision, created_at, updated_at
+            "#,
+            status as SuggestionStatus,
+            reviewer_id,
+            suggestion_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        reviewed.ok_or(DbError::ConstraintViolation(
+            "Suggestion has already been reviewed".to_string(),
+        ))
+    }
+
+    async fn accept_suggestion(
+        &self,
+        suggestion_id: Uuid,
+        reviewer_id: Uuid,
+        expected_revision: i64,
+        content: String,
+    ) -> DbResult<(Suggestion, Document)> {
+        let suggestion = self
+            .get_suggestion(suggestion_id)
+            .await?
+            .ok_or(DbError::NotFound("Suggestion not found".to_string()))?;
+
+        let can_edit = self
+            .check_document_permission(
+                suggestion.document_id,
+                reviewer_id,
+                PermissionLevel::ReadWrite,
+            )
+            .await?;
+
+        if !can_edit {
+            return Err(DbError::PermissionDenied);
+        }
+
+        let mut tx = self.begin_transaction().await?;
+
+        let document = sqlx::query_as!(
+            Document,
+            r#"
+            UPDATE documents
+            SET content = $1, revision = revision + 1, updated_at = NOW()
+            WHERE id = $2 AND revision = $3
+            RETURNING id, title, content, owner_id, is_public, revision, created_at, updated_at
+            "#,
+            content,
+            suggestion.document_id,
+            expected_revision
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?
+        .ok_or(DbError::ConstraintViolation(
+            "Document was changed meanwhile, please try again".to_string(),
+        ))?;
+
+        let accepted = sqlx::query_as!(
+            Suggestion,
+            r#"
+            UPDATE suggestions
+            SET status = 'accepted', reviewed_by = $1, reviewed_at = NOW(),
+                applied_revision = $2, updated_at = NOW()
+            WHERE id = $3 AND status = 'pending'
+            RETURNING id, document_id, author_id, kind as "kind: SuggestionKind", block_id,
+                      start_offset, end_offset, original_text, text, base_revision,
+                      status as "status: SuggestionStatus", reviewed_by, reviewed_at,
+                      applied_revision, created_at, updated_at
+            "#,
+            reviewer_id,
+            document.revision,
+            suggestion_id
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(DbError::from)?
+        .ok_or(DbError::ConstraintViolation(
+            "Suggestion has already been reviewed".to_string(),
+        ))?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok((accepted, document))
+    }
+}
@@ -0,0 +1,530 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+
+use crate::models::{RolePolicy, UserRole, UserTotp};
+
+/// Two-factor authentication database operations extension trait
+#[async_trait]
+pub trait TwoFactorExt {
+    /// Get the TOTP factor of a user, confirmed or not
+    async fn get_user_totp(&self, user_id: Uuid) -> DbResult<Option<UserTotp>>;
+
+    /// Start enrollment with a new secret, replacing an unconfirmed one
+    ///
+    /// # Returns
+    /// * `Err(DbError::ConstraintViolation)` - Two-factor authentication is already enabled
+    async fn start_totp_enrollment(&self, user_id: Uuid, secret: &str) -> DbResult<UserTotp>;
+
+    /// Confirm enrollment and store the recovery codes
+    ///
+    /// # Arguments
+    /// * `user_id` - The user
+    /// * `step` - Time step of the code used for confirmation
+    /// * `recovery_code_hashes` - SHA-256 hashes of the new recovery codes
+    async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> DbResult<()>;
+
+    /// Accept a time step, rejecting steps that were already used
+    ///
+    /// Also clears the failed attempt counter.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The step is newer than the last accepted one
+    /// * `Ok(false)` - The code was already used
+    async fn accept_totp_step(&self, user_id: Uuid, step: i64) -> DbResult<bool>;
+
+    /// Record a verification attempt before the code is compared
+    ///
+    /// The attempt counts as a failure until a code is accepted, which clears the counter.
+    /// After `max_attempts` consecutive failures, further attempts are refused until
+    /// `lockout_seconds` have passed since the last one.
+    ///
+    /// # Returns
+    /// * `Ok(Some(attempts))` - The attempt may go ahead, with the number of consecutive attempts
+    /// * `Ok(None)` - Too many failed attempts, or two-factor authentication is not enabled
+    async fn take_totp_attempt(
+        &self,
+        user_id: Uuid,
+        max_attempts: i32,
+        lockout_seconds: i64,
+    ) -> DbResult<Option<i32>>;
+
+    /// Disable two-factor authentication, removing the secret and recovery codes
+    async fn disable_totp(&self, user_id: Uuid) -> DbResult<()>;
+
+    /// Replace all recovery codes of a user
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: &[String],
+    ) -> DbResult<()>;
+
+    /// Use a recovery code
+    ///
+    /// Also clears the failed attempt counter.
+    ///
+    /// # Returns
+    /// * `Ok(true)` - The code was valid and is now used
+    /// * `Ok(false)` - Unknown or already used code
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> DbResult<bool>;
+
+    /// Count the unused recovery codes of a user
+    async fn count_recovery_codes(&self, user_id: Uuid) -> DbResult<i64>;
+
+    /// Get the security policies of every role, roles without a stored policy get the defaults
+    async fn get_role_policies(&self) -> DbResult<Vec<RolePolicy>>;
+
+    /// Whether users with the role must use two-factor authentication
+    async fn role_requires_two_factor(&self, role: UserRole) -> DbResult<bool>;
+
+    /// Set whether users with the role must use two-factor authentication
+    async fn set_role_requires_two_factor(
+        &self,
+        role: UserRole,
+        required: bool,
+    ) -> DbResult<RolePolicy>;
+}
+
+#[async_trait]
+impl TwoFactorExt for DBClient {
+    async fn get_user_totp(&self, user_id: Uuid) -> DbResult<Option<UserTotp>> {
+        let totp = sqlx::query_as!(
+            UserTotp,
+            r#"
+            SELECT user_id, secret, confirmed_at, last_used_step, failed_attempts,
+                   last_failed_at, created_at
+            FROM user_totp
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(totp)
+    }
+
+    async fn start_totp_enrollment(&self, user_id: Uuid, secret: &str) -> DbResult<UserTotp> {
+        let totp = sqlx::query_as!(
+            UserTotp,
+            r#"
+            INSERT INTO user_totp (user_id, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret,
+                failed_attempts = 0,
+                last_failed_at = NULL,
+                created_at = NOW()
+            WHERE user_totp.confirmed_at IS NULL
+            RETURNING user_id, secret, confirmed_at, last_used_step, failed_attempts,
+                      last_failed_at, created_at
+            "#,
+            user_id,
+            secret
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        totp.ok_or_else(|| {
+            DbError::ConstraintViolation("Two-factor authentication is already enabled".to_string())
+        })
+    }
+
+    async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET confirmed_at = NOW(), last_used_step = $2, failed_attempts = 0
+            WHERE user_id = $1 AND confirmed_at IS NULL
+            "#,
+            user_id,
+            step
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::ConstraintViolation(
+                "No pending two-factor enrollment".to_string(),
+            ));
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, UNNEST($2::text[])
+            "#,
+            user_id,
+            recovery_code_hashes
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn accept_totp_step(&self, user_id: Uuid, step: i64) -> DbResult<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET last_used_step = $2, failed_attempts = 0, last_failed_at = NULL
+            WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn take_totp_attempt(
+        &self,
+        user_id: Uuid,
+        max_attempts: i32,
+        lockout_seconds: i64,
+    ) -> DbResult<Option<i32>> {
+        // Counting the attempt before comparing keeps concurrent guesses within the limit
+        let attempts = sqlx::query_scalar!(
+            r#"
+            UPDATE user_totp
+            SET failed_attempts = failed_attempts + 1, last_failed_at = NOW()
+            WHERE user_id = $1
+              AND confirmed_at IS NOT NULL
+              AND (failed_attempts < $2
+                   OR last_failed_at IS NULL
+                   OR last_failed_at <= NOW() - make_interval(secs => $3))
+            RETURNING failed_attempts
+            "#,
+            user_id,
+            max_attempts,
+            lockout_seconds as f64
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(attempts)
+    }
+
+    async fn disable_totp(&self, user_id: Uuid) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_totp WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        recovery_code_hashes: &[String],
+    ) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            SELECT $1, UNNEST($2::text[])
+            "#,
+            user_id,
+            recovery_code_hashes
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code_hash: &str) -> DbResult<bool> {
+        let mut tx = self.begin_transaction().await?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE recovery_codes
+            SET used_at = NOW()
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE user_totp
+            SET failed_attempts = 0, last_failed_at = NULL
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(true)
+    }
+
+    async fn count_recovery_codes(&self, user_id: Uuid) -> DbResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(count.unwrap_or(0))
+    }
+
+    async fn get_role_policies(&self) -> DbResult<Vec<RolePolicy>> {
+        let policies = sqlx::query_as!(
+            RolePolicy,
+            r#"
+            SELECT roles.role as "role!: UserRole",
+                   COALESCE(p.require_two_factor, FALSE) as "require_two_factor!",
+                   COALESCE(p.updated_at, NOW()) as "updated_at!"
+            FROM UNNEST(enum_range(NULL::user_role)) AS roles(role)
+            LEFT JOIN role_policies p ON p.role = roles.role
+            ORDER BY roles.role
+            "#
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(policies)
+    }
+
+    async fn role_requires_two_factor(&self, role: UserRole) -> DbResult<bool> {
+        let required = sqlx::query_scalar!(
+            r#"
+            SELECT require_two_factor FROM role_policies WHERE role = $1
+            "#,
+            role as UserRole
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(required.unwrap_or(false))
+    }
+
+    async fn set_role_requires_two_factor(
+        &self,
+        role: UserRole,
+        required: bool,
+    ) -> DbResult<RolePolicy> {
+        let policy = sqlx::query_as!(
+            RolePolicy,
+            r#"
+            INSERT INTO role_policies (role, require_two_factor)
+            VALUES ($1, $2)
+            ON CONFLICT (role) DO UPDATE
+            SET require_two_factor = EXCLUDED.require_two_factor, updated_at = NOW()
+            RETURNING role as "role: UserRole", require_two_factor, updated_at
+            "#,
+            role as UserRole,
+            required
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::UserExt;
+
+    async fn enrolled_user(db_client: &DBClient) -> Uuid {
+        let user = db_client
+            .save_user("owner", "owner@example.com", "hash", "token", Utc::now())
+            .await
+            .unwrap();
+        db_client
+            .start_totp_enrollment(user.id, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")
+            .await
+            .unwrap();
+        db_client
+            .confirm_totp(user.id, 1, &["recovery".to_string()])
+            .await
+            .unwrap();
+        user.id
+    }
+
+    #[sqlx::test]
+    async fn concurrent_attempts_stay_within_the_limit(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let user_id = enrolled_user(&db_client).await;
+
+        // -- 在多线程运行时上同时发起请求
+        let attempts = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut tasks = tokio::task::JoinSet::new();
+            for _ in 0..20 {
+                let db_client = db_client.clone();
+                tasks.spawn(async move { db_client.take_totp_attempt(user_id, 5, 900).await });
+            }
+            tasks.join_all().await
+        });
+
+        let mut allowed: Vec<i32> = attempts
+            .into_iter()
+            .filter_map(|attempt| attempt.unwrap())
+            .collect();
+        allowed.sort();
+        assert_eq!(allowed, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[sqlx::test]
+    async fn accepted_codes_clear_attempts(pool: PgPool) {
+        let db_client = DBClient::new(pool);
+        let user_id = enrolled_user(&db_client).await;
+
+        for _ in 0..4 {
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap();
+        }
+        assert!(db_client.accept_totp_step(user_id, 2).await.unwrap());
+        assert_eq!(
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap(),
+            Some(1)
+        );
+
+        for _ in 0..4 {
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap();
+        }
+        assert!(
+            db_client
+                .use_recovery_code(user_id, "recovery")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !db_client
+                .use_recovery_code(user_id, "recovery")
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap(),
+            Some(1)
+        );
+    }
+
+    #[sqlx::test]
+    async fn lockout_expires(pool: PgPool) {
+        let db_client = DBClient::new(pool.clone());
+        let user_id = enrolled_user(&db_client).await;
+
+        for _ in 0..5 {
+            assert!(
+                db_client
+                    .take_totp_attempt(user_id, 5, 900)
+                    .await
+                    .unwrap()
+                    .is_some()
+            );
+        }
+        assert_eq!(
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap(),
+            None
+        );
+
+        sqlx::query!("UPDATE user_totp SET last_failed_at = NOW() - INTERVAL '16 minutes'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // -- 锁定到期后允许一次尝试，失败后重新锁定
+        assert_eq!(
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap(),
+            Some(6)
+        );
+        assert_eq!(
+            db_client.take_totp_attempt(user_id, 5, 900).await.unwrap(),
+            None
+        );
+    }
+}
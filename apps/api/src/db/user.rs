@@ -6,6 +6,7 @@ use uuid::Uuid;
 use super::DBClient;
 use super::DbError;
 use super::DbResult;
+use super::identity::insert_identity;
 
 use crate::models::{AuthProvider, User, UserRole};
 
@@ -34,10 +35,10 @@ pub trait UserExt {
         token: Option<&str>,
     ) -> DbResult<Option<User>>;
 
-    /// Get a user by auth provider and provider user ID
+    /// Get a user by one of their linked identities
     ///
     /// # Arguments
-    /// * `provider` - Authentication provider (e.g., Google)
+    /// * `provider_name` - Provider name (e.g., `google`, or a configured OIDC provider)
     /// * `provider_user_id` - User ID from the provider
     ///
     /// # Returns
@@ -46,10 +47,25 @@ pub trait UserExt {
     /// * `Err(DbError)` - Database error
     async fn get_user_by_provider(
         &self,
-        provider: AuthProvider,
+        provider_name: &str,
         provider_user_id: &str,
     ) -> DbResult<Option<User>>;
 
+    /// Get a user by email, ignoring case
+    ///
+    /// Emails are stored as entered at registration, so a normalized (lowercase)
+    /// address may differ from the stored one. An exact match wins when several
+    /// accounts differ only by case.
+    ///
+    /// # Arguments
+    /// * `email` - Email address
+    ///
+    /// # Returns
+    /// * `Ok(Some(User))` - User found
+    /// * `Ok(None)` - User not found
+    /// * `Err(DbError)` - Database error
+    async fn get_user_by_email(&self, email: &str) -> DbResult<Option<User>>;
+
     /// Get paginated list of users
     ///
     /// # Arguments
@@ -82,6 +98,25 @@ pub trait UserExt {
         token_expires_at: DateTime<Utc>,
     ) -> DbResult<User>;
 
+    /// Create a user who signed in with an emailed link
+    ///
+    /// The email address is already verified by the sign-in link.
+    ///
+    /// # Arguments
+    /// * `name` - Username
+    /// * `email` - Email address
+    /// * `password` - Hashed password (randomly generated)
+    ///
+    /// # Returns
+    /// * `Ok(User)` - Created user
+    /// * `Err(DbError)` - Database error
+    async fn save_passwordless_user(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+    ) -> DbResult<User>;
+
     /// Create a new user with Google authentication
     ///
     /// # Arguments
@@ -124,6 +159,54 @@ pub trait UserExt {
         picture: Option<String>,
     ) -> DbResult<User>;
 
+    /// Save a new user who signed in with a configured OpenID Connect provider
+    ///
+    /// # Arguments
+    /// * `name` - Username
+    /// * `email` - Email address
+    /// * `verified` - Whether the provider asserted the email as verified
+    /// * `password` - Hashed password (randomly generated)
+    /// * `provider_name` - Configured provider name
+    /// * `provider_user_id` - Subject (`sub`) at the provider
+    /// * `picture` - Profile picture URL
+    ///
+    /// # Returns
+    /// * `Ok(User)` - User created successfully
+    /// * `Err(DbError)` - Database error
+    #[allow(clippy::too_many_arguments)]
+    async fn save_oidc_user(
+        &self,
+        name: &str,
+        email: &str,
+        verified: bool,
+        password: &str,
+        provider_name: &str,
+        provider_user_id: &str,
+        picture: Option<String>,
+    ) -> DbResult<User>;
+
+    /// Save a new user who signed in with an Ethereum wallet
+    ///
+    /// Wallets have no email address, `email` is a placeholder derived from the
+    /// address and the user stays unverified until a real address is set.
+    ///
+    /// # Arguments
+    /// * `name` - Username
+    /// * `email` - Placeholder email address
+    /// * `password` - Hashed password (randomly generated)
+    /// * `address` - EIP-55 checksummed wallet address
+    ///
+    /// # Returns
+    /// * `Ok(User)` - User created successfully
+    /// * `Err(DbError)` - Database error
+    async fn save_ethereum_user(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+        address: &str,
+    ) -> DbResult<User>;
+
     /// Get total count of users for pagination
     ///
     /// # Returns
@@ -131,6 +214,24 @@ pub trait UserExt {
     /// * `Err(DbError)` - Database error
     async fn get_user_count(&self) -> DbResult<i64>;
 
+    /// Resolve mentioned users by ID, email or display name
+    ///
+    /// Handles match an email or a display name case-insensitively.
+    /// Display names shared by several users are ambiguous and ignored.
+    ///
+    /// # Arguments
+    /// * `user_ids` - Mentioned user IDs
+    /// * `handles` - Mentioned emails or display names, lowercase
+    ///
+    /// # Returns
+    /// * `Ok(Vec<User>)` - Resolved users
+    /// * `Err(DbError)` - Database error
+    async fn get_mentioned_users(
+        &self,
+        user_ids: &[Uuid],
+        handles: &[String],
+    ) -> DbResult<Vec<User>>;
+
     /// Update a user's display name
     ///
     /// # Arguments
@@ -325,11 +426,7 @@ impl UserExt for DBClient {
         Ok(user)
     }
 
-    async fn get_user_by_provider(
-        &self,
-        provider: AuthProvider,
-        provider_user_id: &str,
-    ) -> DbResult<Option<User>> {
+    async fn get_user_by_email(&self, email: &str) -> DbResult<Option<User>> {
         let user = sqlx::query_as!(
             User,
             r#"
@@ -341,9 +438,38 @@ impl UserExt for DBClient {
                 auth_provider as "auth_provider: AuthProvider",
                 provider_user_id, profile_picture
             FROM users 
-            WHERE auth_provider = $1 AND provider_user_id = $2
+            WHERE LOWER(email) = LOWER($1)
+            ORDER BY email = $1 DESC, created_at
+            LIMIT 1
+            "#,
+            email
+        )
+        .fetch_optional(self.pool())
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn get_user_by_provider(
+        &self,
+        provider_name: &str,
+        provider_user_id: &str,
+    ) -> DbResult<Option<User>> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            SELECT
+                u.id, u.name, u.email, u.password,
+                u.role as "role: UserRole", u.verified,
+                u.verification_token, u.token_expires_at,
+                u.created_at, u.updated_at,
+                u.auth_provider as "auth_provider: AuthProvider",
+                u.provider_user_id, u.profile_picture
+            FROM user_identities i
+            JOIN users u ON u.id = i.user_id
+            WHERE i.provider_name = $1 AND i.provider_user_id = $2
             "#,
-            provider as AuthProvider,
+            provider_name,
             provider_user_id
         )
         .fetch_optional(self.pool())
@@ -385,6 +511,8 @@ impl UserExt for DBClient {
         verification_token: T,
         token_expires_at: DateTime<Utc>,
     ) -> DbResult<User> {
+        let mut tx = self.begin_transaction().await?;
+
         let user = sqlx::query_as!(
             User,
             r#"
@@ -408,6 +536,38 @@ impl UserExt for DBClient {
             verification_token.into(),
             token_expires_at
         )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        insert_identity(&mut tx, &user, user.auth_provider.to_str()).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn save_passwordless_user(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+    ) -> DbResult<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (name, email, password, verified, auth_provider)
+            VALUES ($1, $2, $3, true, 'local')
+            RETURNING
+                id, name, email, password,
+                role as "role: UserRole", verified,
+                verification_token, token_expires_at,
+                created_at, updated_at,
+                auth_provider as "auth_provider: AuthProvider",
+                provider_user_id, profile_picture
+            "#,
+            name,
+            email,
+            password
+        )
         .fetch_one(self.pool())
         .await?;
 
@@ -423,6 +583,7 @@ impl UserExt for DBClient {
         profile_picture: Option<String>,
     ) -> DbResult<User> {
         let profile_pic_str: Option<String> = profile_picture;
+        let mut tx = self.begin_transaction().await?;
 
         let user = sqlx::query_as!(
             User,
@@ -447,9 +608,12 @@ impl UserExt for DBClient {
             provider_user_id.into(),
             profile_pic_str
         )
-        .fetch_one(self.pool())
+        .fetch_one(&mut *tx)
         .await?;
 
+        insert_identity(&mut tx, &user, user.auth_provider.to_str()).await?;
+        tx.commit().await?;
+
         Ok(user)
     }
 
@@ -461,6 +625,8 @@ impl UserExt for DBClient {
         provider_user_id: &str,
         picture: Option<String>,
     ) -> DbResult<User> {
+        let mut tx = self.begin_transaction().await?;
+
         let user = sqlx::query_as::<_, User>(
             r#"
             INSERT INTO users (name, email, password, auth_provider, provider_user_id, profile_picture, verified)
@@ -473,13 +639,127 @@ impl UserExt for DBClient {
         .bind(password)
         .bind(provider_user_id)
         .bind(picture)
-        .fetch_one(self.pool())
+        .fetch_one(&mut *tx)
         .await
         .map_err(DbError::from)?;
 
+        insert_identity(&mut tx, &user, user.auth_provider.to_str()).await?;
+        tx.commit().await.map_err(DbError::from)?;
+
         Ok(user)
     }
 
+    async fn save_oidc_user(
+        &self,
+        name: &str,
+        email: &str,
+        verified: bool,
+        password: &str,
+        provider_name: &str,
+        provider_user_id: &str,
+        picture: Option<String>,
+    ) -> DbResult<User> {
+        let mut tx = self.begin_transaction().await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (
+                name, email, password,
+                verified, auth_provider, provider_user_id,
+                profile_picture
+            )
+            VALUES ($1, $2, $3, $4, 'oidc', $5, $6)
+            RETURNING
+                id, name, email, password,
+                role as "role: UserRole", verified,
+                verification_token, token_expires_at,
+                created_at, updated_at,
+                auth_provider as "auth_provider: AuthProvider",
+                provider_user_id, profile_picture
+            "#,
+            name,
+            email,
+            password,
+            verified,
+            provider_user_id,
+            picture
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        insert_identity(&mut tx, &user, provider_name).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn save_ethereum_user(
+        &self,
+        name: &str,
+        email: &str,
+        password: &str,
+        address: &str,
+    ) -> DbResult<User> {
+        let mut tx = self.begin_transaction().await?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (name, email, password, verified, auth_provider, provider_user_id)
+            VALUES ($1, $2, $3, false, 'ethereum', $4)
+            RETURNING
+                id, name, email, password,
+                role as "role: UserRole", verified,
+                verification_token, token_expires_at,
+                created_at, updated_at,
+                auth_provider as "auth_provider: AuthProvider",
+                provider_user_id, profile_picture
+            "#,
+            name,
+            email,
+            password,
+            address
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        insert_identity(&mut tx, &user, user.auth_provider.to_str()).await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn get_mentioned_users(
+        &self,
+        user_ids: &[Uuid],
+        handles: &[String],
+    ) -> DbResult<Vec<User>> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT 
+                id, name, email, password, 
+                role as "role: UserRole", verified, 
+                verification_token, token_expires_at,
+                created_at, updated_at,
+                auth_provider as "auth_provider: AuthProvider",
+                provider_user_id, profile_picture
+            FROM users
+            WHERE id = ANY($1)
+               OR LOWER(email) = ANY($2)
+               OR (LOWER(name) = ANY($2)
+                   AND (SELECT COUNT(*) FROM users u WHERE LOWER(u.name) = LOWER(users.name)) = 1)
+            "#,
+            user_ids,
+            handles
+        )
+        .fetch_all(self.pool())
+        .await?;
+
+        Ok(users)
+    }
+
     async fn get_user_count(&self) -> DbResult<i64> {
         let record = sqlx::query!("SELECT COUNT(*) as count FROM users")
             .fetch_one(self.pool())
@@ -0,0 +1,490 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::DBClient;
+use super::DbError;
+use super::DbResult;
+use super::DocumentExt;
+
+use crate::models::{
+    PermissionLevel, Webhook, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus,
+    WebhookEvent,
+};
+
+/// Result of a single delivery attempt, recorded in the response log
+#[derive(Debug, Clone)]
+pub struct WebhookAttemptResult {
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+}
+
+/// Webhook database operations extension trait
+///
+/// Defines all operations related to webhooks and their deliveries in the database
+#[async_trait]
+pub trait WebhookExt {
+    /// Register a webhook for a user, optionally limited to one document
+    ///
+    /// # Arguments
+    /// * `user_id` - Owner of the webhook
+    /// * `document_id` - Document to watch, all readable documents when `None`
+    /// * `url` - Endpoint receiving the deliveries
+    /// * `secret` - Secret used to sign the deliveries
+    /// * `events` - Subscribed events
+    ///
+    /// # Returns
+    /// * `Ok(Webhook)` - Created webhook
+    /// * `Err(DbError)` - Database error
+    async fn create_webhook(
+        &self,
+        user_id: Uuid,
+        document_id: Option<Uuid>,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+    ) -> DbResult<Webhook>;
+
+    /// Get a webhook by its ID
+    async fn get_webhook(&self, webhook_id: Uuid) -> DbResult<Option<Webhook>>;
+
+    /// Get all webhooks registered by a user
+    async fn get_user_webhooks(&self, user_id: Uuid) -> DbResult<Vec<Webhook>>;
+
+    /// Update a webhook, only allowed for its owner
+    ///
+    /// # Arguments
+    /// * `webhook_id` - Webhook ID
+    /// * `user_id` - User updating the webhook
+    /// * `url` - New endpoint
+    /// * `events` - New subscribed events
+    /// * `active` - Enable or pause the webhook
+    ///
+    /// # Returns
+    /// * `Ok(Webhook)` - Updated webhook
+    /// * `Err(DbError)` - Database error
+    async fn update_webhook(
+        &self,
+        webhook_id: Uuid,
+        user_id: Uuid,
+        url: Option<String>,
+        events: Option<Vec<String>>,
+        active: Option<bool>,
+    ) -> DbResult<Webhook>;
+
+    /// Delete a webhook and its deliveries, only allowed for its owner
+    async fn delete_webhook(&self, webhook_id: Uuid, user_id: Uuid) -> DbResult<()>;
+
+    /// Queue a delivery for every active webhook subscribed to the event
+    ///
+    /// Webhooks only receive events of documents their owner can still read.
+    ///
+    /// # Arguments
+    /// * `event` - Event that happened
+    /// * `document_id` - Document the event belongs to
+    /// * `payload` - Body sent to the endpoints
+    ///
+    /// # Returns
+    /// * `Ok(u64)` - Number of queued deliveries
+    /// * `Err(DbError)` - Database error
+    async fn enqueue_webhook_deliveries(
+        &self,
+        event: WebhookEvent,
+        document_id: Uuid,
+        payload: serde_json::Value,
+    ) -> DbResult<u64>;
+
+    /// Get the deliveries of a webhook, newest first
+    ///
+    /// # Arguments
+    /// * `webhook_id` - Webhook ID
+    /// * `page` - Page number (1-based)
+    /// * `limit` - Number of deliveries per page
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: Uuid,
+        page: u32,
+        limit: usize,
+    ) -> DbResult<Vec<WebhookDelivery>>;
+
+    /// Get a delivery by its ID
+    async fn get_webhook_delivery(&self, delivery_id: Uuid) -> DbResult<Option<WebhookDelivery>>;
+
+    /// Get the response log of a delivery, oldest attempt first
+    async fn get_webhook_delivery_attempts(
+        &self,
+        delivery_id: Uuid,
+    ) -> DbResult<Vec<WebhookDeliveryAttempt>>;
+
+    /// Queue a delivery again, resetting its retry schedule
+    async fn redeliver_webhook_delivery(&self, delivery_id: Uuid) -> DbResult<WebhookDelivery>;
+
+    /// Claim due deliveries for sending
+    ///
+    /// Claimed deliveries are pushed back by `lease_seconds` so that a crashed
+    /// worker does not lose them and concurrent workers do not send them twice.
+    ///
+    /// # Arguments
+    /// * `limit` - Maximum number of deliveries to claim
+    /// * `lease_seconds` - How long the claim lasts
+    async fn claim_due_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease_seconds: i64,
+    ) -> DbResult<Vec<WebhookDelivery>>;
+
+    /// Record an attempt and schedule the next one
+    ///
+    /// # Arguments
+    /// * `delivery_id` - Delivery ID
+    /// * `result` - Outcome of the attempt
+    /// * `status` - New delivery status
+    /// * `next_attempt_at` - When to retry, for pending deliveries
+    async fn record_webhook_attempt(
+        &self,
+        delivery_id: Uuid,
+        result: WebhookAttemptResult,
+        status: WebhookDeliveryStatus,
+        next_attempt_at: DateTime<Utc>,
+    ) -> DbResult<()>;
+}
+
+#[async_trait]
+impl WebhookExt for DBClient {
+    async fn create_webhook(
+        &self,
+        user_id: Uuid,
+        document_id: Option<Uuid>,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+    ) -> DbResult<Webhook> {
+        if let Some(document_id) = document_id {
+            let has_permission = self
+                .check_document_permission(document_id, user_id, PermissionLevel::Read)
+                .await?;
+
+            if !has_permission {
+                return Err(DbError::PermissionDenied);
+            }
+        }
+
+        let webhook = sqlx::query_as!(
+            Webhook,
+            r#"
+            INSERT INTO webhooks (user_id, document_id, url, secret, events)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, user_id, document_id, url, secret, events, active,
+                      created_at, updated_at
+            "#,
+            user_id,
+            document_id,
+            url,
+            secret,
+            &events
+        )
+        .fetch_one(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(webhook)
+    }
+
+    async fn get_webhook(&self, webhook_id: Uuid) -> DbResult<Option<Webhook>> {
+        let webhook = sqlx::query_as!(
+            Webhook,
+            r#"
+            SELECT id, user_id, document_id, url, secret, events, active,
+                   created_at, updated_at
+            FROM webhooks
+            WHERE id = $1
+            "#,
+            webhook_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(webhook)
+    }
+
+    async fn get_user_webhooks(&self, user_id: Uuid) -> DbResult<Vec<Webhook>> {
+        let webhooks = sqlx::query_as!(
+            Webhook,
+            r#"
+            SELECT id, user_id, document_id, url, secret, events, active,
+                   created_at, updated_at
+            FROM webhooks
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(webhooks)
+    }
+
+    async fn update_webhook(
+        &self,
+        webhook_id: Uuid,
+        user_id: Uuid,
+        url: Option<String>,
+        events: Option<Vec<String>>,
+        active: Option<bool>,
+    ) -> DbResult<Webhook> {
+        let webhook = sqlx::query_as!(
+            Webhook,
+            r#"
+            UPDATE webhooks
+            SET url = COALESCE($1, url),
+                events = COALESCE($2, events),
+                active = COALESCE($3, active),
+                updated_at = NOW()
+            WHERE id = $4 AND user_id = $5
+            RETURNING id, user_id, document_id, url, secret, events, active,
+                      created_at, updated_at
+            "#,
+            url,
+            events.as_deref(),
+            active,
+            webhook_id,
+            user_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        webhook.ok_or(DbError::NotFound("Webhook not found".to_string()))
+    }
+
+    async fn delete_webhook(&self, webhook_id: Uuid, user_id: Uuid) -> DbResult<()> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM webhooks
+            WHERE id = $1 AND user_id = $2
+            "#,
+            webhook_id,
+            user_id
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound("Webhook not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn enqueue_webhook_deliveries(
+        &self,
+        event: WebhookEvent,
+        document_id: Uuid,
+        payload: serde_json::Value,
+    ) -> DbResult<u64> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload)
+            SELECT w.id, $1::text, $3
+            FROM webhooks w
+            WHERE w.active
+              AND $1::text = ANY(w.events)
+              AND (w.document_id IS NULL OR w.document_id = $2)
+              AND (
+                  EXISTS (SELECT 1 FROM documents d WHERE d.id = $2 AND d.owner_id = w.user_id)
+                  OR EXISTS (
+                      SELECT 1 FROM document_permissions dp
+                      WHERE dp.document_id = $2 AND dp.user_id = w.user_id
+                  )
+              )
+            "#,
+            event.to_str(),
+            document_id,
+            payload
+        )
+        .execute(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_webhook_deliveries(
+        &self,
+        webhook_id: Uuid,
+        page: u32,
+        limit: usize,
+    ) -> DbResult<Vec<WebhookDelivery>> {
+        let offset = (page - 1) as i64 * limit as i64;
+
+        let deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT id, webhook_id, event, payload, status as "status: WebhookDeliveryStatus",
+                   attempts, next_attempt_at, last_attempt_at, response_status,
+                   created_at, updated_at
+            FROM webhook_deliveries
+            WHERE webhook_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            webhook_id,
+            limit as i64,
+            offset
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(deliveries)
+    }
+
+    async fn get_webhook_delivery(&self, delivery_id: Uuid) -> DbResult<Option<WebhookDelivery>> {
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT id, webhook_id, event, payload, status as "status: WebhookDeliveryStatus",
+                   attempts, next_attempt_at, last_attempt_at, response_status,
+                   created_at, updated_at
+            FROM webhook_deliveries
+            WHERE id = $1
+            "#,
+            delivery_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(delivery)
+    }
+
+    async fn get_webhook_delivery_attempts(
+        &self,
+        delivery_id: Uuid,
+    ) -> DbResult<Vec<WebhookDeliveryAttempt>> {
+        let attempts = sqlx::query_as!(
+            WebhookDeliveryAttempt,
+            r#"
+            SELECT id, delivery_id, response_status, response_body, error, duration_ms,
+                   created_at
+            FROM webhook_delivery_attempts
+            WHERE delivery_id = $1
+            ORDER BY created_at ASC
+            "#,
+            delivery_id
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(attempts)
+    }
+
+    async fn redeliver_webhook_delivery(&self, delivery_id: Uuid) -> DbResult<WebhookDelivery> {
+        let delivery = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'pending', attempts = 0, next_attempt_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, webhook_id, event, payload, status as "status: WebhookDeliveryStatus",
+                      attempts, next_attempt_at, last_attempt_at, response_status,
+                      created_at, updated_at
+            "#,
+            delivery_id
+        )
+        .fetch_optional(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        delivery.ok_or(DbError::NotFound("Webhook delivery not found".to_string()))
+    }
+
+    async fn claim_due_webhook_deliveries(
+        &self,
+        limit: i64,
+        lease_seconds: i64,
+    ) -> DbResult<Vec<WebhookDelivery>> {
+        let deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            UPDATE webhook_deliveries
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at ASC
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, webhook_id, event, payload, status as "status: WebhookDeliveryStatus",
+                      attempts, next_attempt_at, last_attempt_at, response_status,
+                      created_at, updated_at
+            "#,
+            limit,
+            lease_seconds as f64
+        )
+        .fetch_all(self.pool())
+        .await
+        .map_err(DbError::from)?;
+
+        Ok(deliveries)
+    }
+
+    async fn record_webhook_attempt(
+        &self,
+        delivery_id: Uuid,
+        result: WebhookAttemptResult,
+        status: WebhookDeliveryStatus,
+        next_attempt_at: DateTime<Utc>,
+    ) -> DbResult<()> {
+        let mut tx = self.begin_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_delivery_attempts (
+                delivery_id, response_status, response_body, error, duration_ms
+            )
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            delivery_id,
+            result.response_status,
+            result.response_body,
+            result.error,
+            result.duration_ms
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $1,
+                attempts = attempts + 1,
+                next_attempt_at = $2,
+                last_attempt_at = NOW(),
+                response_status = $3,
+                updated_at = NOW()
+            WHERE id = $4
+            "#,
+            status as WebhookDeliveryStatus,
+            next_attempt_at,
+            result.response_status,
+            delivery_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::from)?;
+
+        tx.commit().await.map_err(DbError::from)?;
+
+        Ok(())
+    }
+}
@@ -3,7 +3,13 @@ use core::str;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-use crate::models::{AuthProvider, User, UserRole};
+use crate::models::{
+    AccountDeletion, AuditLog, AuthProvider, Comment, CommentAnchor, CommentThread, Document,
+    DocumentDisposition, DocumentPermission, Notification, NotificationPreference, Passkey,
+    PersonalAccessToken, RolePolicy, Session, Suggestion, SuggestionKind, SuggestionStatus,
+    TokenScope, TransferFallback, User, UserIdentity, UserRole, Webhook, WebhookDelivery,
+    WebhookDeliveryAttempt, WebhookEvent,
+};
 
 #[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
 pub struct RegisterUserDto {
@@ -14,11 +20,16 @@ pub struct RegisterUserDto {
         email(message = "Email is invalid")
     )]
     pub email: String,
-    #[validate(length(min = 6, message = "Password must be at least 6 characters"))]
+    /// Length and strength are checked by the password policy
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "Password must be between 1 and 128 characters"
+    ))]
     pub password: String,
 
     #[validate(
-        length(min = 6, message = "Confirm Password must be at least 6 characters"),
+        length(min = 1, message = "Confirm Password is required"),
         must_match(other = "password", message = "passwords do not match")
     )]
     #[serde(rename = "passwordConfirm")]
@@ -32,7 +43,11 @@ pub struct LoginUserDto {
         email(message = "Email is invalid")
     )]
     pub email: String,
-    #[validate(length(min = 6, message = "Password must be at least 6 characters"))]
+    #[validate(length(
+        min = 6,
+        max = 128,
+        message = "Password must be between 6 and 128 characters"
+    ))]
     pub password: String,
 }
 
@@ -89,6 +104,12 @@ impl FilterUserDto {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserData {
     pub user: FilterUserDto,
+    #[serde(
+        rename = "unreadNotifications",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub unread_notifications: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,6 +129,14 @@ pub struct UserListResponseDto {
 pub struct UserLoginResponseDto {
     pub status: String,
     pub token: String,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RefreshTokenDto {
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -135,30 +164,120 @@ fn validate_user_role(role: &UserRole) -> Result<(), validator::ValidationError>
     }
 }
 
+#[derive(Debug, Validate, Clone, Serialize, Deserialize)]
+pub struct AccountDeletionDto {
+    /// Current password, accounts without a password login must have logged in recently
+    #[serde(default)]
+    pub password: String,
+
+    /// What happens to the documents owned by the user
+    pub documents: DocumentDisposition,
+
+    /// Email of the user receiving the documents, required for `transfer`
+    #[serde(rename = "transferTo")]
+    #[validate(email(message = "Transfer email is invalid"))]
+    pub transfer_to: Option<String>,
+
+    /// What happens to the documents if the recipient is no longer available when the
+    /// account is deleted, required for `transfer`
+    #[serde(rename = "transferFallback")]
+    pub transfer_fallback: Option<TransferFallback>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountDeletionResponseDto {
+    pub status: String,
+    pub data: Option<AccountDeletion>,
+}
+
+/// Shares involving the exporting user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountExportSharesDto {
+    /// Shares on documents the user owns
+    #[serde(rename = "grantedByMe")]
+    pub granted_by_me: Vec<DocumentPermission>,
+    /// Documents of other users shared with the user
+    #[serde(rename = "grantedToMe")]
+    pub granted_to_me: Vec<DocumentPermission>,
+}
+
+/// Machine-readable export of the personal data of a user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountExportDto {
+    #[serde(rename = "exportedAt")]
+    pub exported_at: DateTime<Utc>,
+    pub profile: FilterUserDto,
+    pub identities: Vec<UserIdentity>,
+    pub documents: Vec<Document>,
+    pub shares: AccountExportSharesDto,
+    /// Entries the user performed or that target the user, oldest first
+    #[serde(rename = "auditLogs")]
+    pub audit_logs: Vec<AuditLog>,
+}
+
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
 pub struct UserPasswordUpdateDto {
-    #[validate(length(min = 6, message = "new password must be at least 6 characters"))]
+    /// Length and strength are checked by the password policy
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "new password must be between 1 and 128 characters"
+    ))]
     pub new_password: String,
 
     #[validate(
-        length(
-            min = 6,
-            message = "new password confirm must be at least 6 characters"
-        ),
+        length(min = 1, message = "new password confirm is required"),
         must_match(other = "new_password", message = "new passwords do not match")
     )]
     pub new_password_confirm: String,
 
-    #[validate(length(min = 6, message = "Old password must be at least 6 characters"))]
+    #[validate(length(
+        min = 6,
+        max = 128,
+        message = "Old password must be between 6 and 128 characters"
+    ))]
     pub old_password: String,
 }
 
+#[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+pub struct EmailUpdateDto {
+    #[validate(
+        length(min = 1, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    pub new_email: String,
+
+    /// Current password, accounts without a password login must have logged in recently
+    #[serde(default)]
+    pub password: String,
+}
+
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
 pub struct VerifyEmailQueryDto {
     #[validate(length(min = 1, message = "Token is required."))]
     pub token: String,
 }
 
+#[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+pub struct PasswordlessLoginRequestDto {
+    #[validate(
+        length(min = 1, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    pub email: String,
+}
+
+#[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+pub struct LoginCodeVerifyDto {
+    #[validate(
+        length(min = 1, message = "Email is required"),
+        email(message = "Email is invalid")
+    )]
+    pub email: String,
+    #[validate(length(equal = 6, message = "Code must be 6 digits"))]
+    pub code: String,
+}
+
 #[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
 pub struct ForgotPasswordRequestDto {
     #[validate(
@@ -173,14 +292,16 @@ pub struct ResetPasswordRequestDto {
     #[validate(length(min = 1, message = "Token is required."))]
     pub token: String,
 
-    #[validate(length(min = 6, message = "new password must be at least 6 characters"))]
+    /// Length and strength are checked by the password policy
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "new password must be between 1 and 128 characters"
+    ))]
     pub new_password: String,
 
     #[validate(
-        length(
-            min = 6,
-            message = "new password confirm must be at least 6 characters"
-        ),
+        length(min = 1, message = "new password confirm is required"),
         must_match(other = "new_password", message = "new passwords do not match")
     )]
     pub new_password_confirm: String,
@@ -212,6 +333,36 @@ pub struct GoogleCallbackDto {
     pub error: Option<String>,
 }
 
+/// OIDC 授权回调的查询参数
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OidcCallbackDto {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcProviderDto {
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcProviderListResponseDto {
+    pub status: String,
+    pub providers: Vec<OidcProviderDto>,
+}
+
+/// 发起 OAuth 登录时的查询参数
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct OAuthLoginQueryDto {
+    /// 登录完成后返回的前端路径，必须以 `/` 开头
+    #[serde(rename = "returnTo")]
+    pub return_to: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct GithubUserInfo {
     pub id: i64,
@@ -228,3 +379,549 @@ pub struct GithubCallbackDto {
     pub state: Option<String>,
     pub error: Option<String>,
 }
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[validate(schema(function = "validate_comment_anchor"))]
+pub struct CreateCommentThreadDto {
+    pub document_id: uuid::Uuid,
+    #[validate(length(min = 1, max = 255, message = "Block id is invalid"))]
+    pub block_id: Option<String>,
+    #[validate(range(min = 0, message = "Start offset must not be negative"))]
+    pub start_offset: Option<i32>,
+    #[validate(range(min = 0, message = "End offset must not be negative"))]
+    pub end_offset: Option<i32>,
+    #[validate(length(min = 1, message = "CRDT position is invalid"))]
+    pub crdt_position: Option<String>,
+    #[validate(length(min = 1, message = "Quoted text is required"))]
+    pub quoted_text: String,
+    #[validate(length(min = 1, max = 10000, message = "Comment must be 1-10000 characters"))]
+    pub content: String,
+}
+
+impl CreateCommentThreadDto {
+    pub fn anchor(&self) -> CommentAnchor {
+        CommentAnchor {
+            block_id: self.block_id.clone(),
+            start_offset: self.start_offset,
+            end_offset: self.end_offset,
+            crdt_position: self.crdt_position.clone(),
+            quoted_text: self.quoted_text.clone(),
+        }
+    }
+}
+
+fn validate_comment_anchor(dto: &CreateCommentThreadDto) -> Result<(), validator::ValidationError> {
+    match (&dto.block_id, dto.start_offset, dto.end_offset) {
+        (Some(_), Some(start), Some(end)) if start <= end => Ok(()),
+        (None, None, None) if dto.crdt_position.is_some() => Ok(()),
+        _ => Err(
+            validator::ValidationError::new("invalid_anchor").with_message(
+                "Anchor requires block_id with start_offset <= end_offset, or crdt_position".into(),
+            ),
+        ),
+    }
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CommentContentDto {
+    #[validate(length(min = 1, max = 10000, message = "Comment must be 1-10000 characters"))]
+    pub content: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentThreadsQueryDto {
+    pub document_id: uuid::Uuid,
+    pub include_resolved: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentThreadDto {
+    #[serde(flatten)]
+    pub thread: CommentThread,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentThreadResponseDto {
+    pub status: String,
+    pub data: CommentThreadDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentThreadListResponseDto {
+    pub status: String,
+    pub threads: Vec<CommentThreadDto>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentResponseDto {
+    pub status: String,
+    pub data: Comment,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+#[validate(schema(function = "validate_suggestion_range"))]
+pub struct CreateSuggestionDto {
+    pub document_id: uuid::Uuid,
+    #[validate(length(min = 1, max = 255, message = "Block id is invalid"))]
+    pub block_id: String,
+    #[validate(range(min = 0, message = "Start offset must not be negative"))]
+    pub start_offset: i32,
+    #[validate(range(min = 0, message = "End offset must not be negative"))]
+    pub end_offset: i32,
+    /// Text currently in the range, empty for pure insertions
+    #[serde(default)]
+    pub original_text: String,
+    /// Replacement text, empty for pure deletions
+    #[serde(default)]
+    #[validate(length(
+        max = 10000,
+        message = "Suggested text must be at most 10000 characters"
+    ))]
+    pub text: String,
+    /// Revision of the document the suggestion was made on
+    pub base_revision: i64,
+}
+
+impl CreateSuggestionDto {
+    pub fn kind(&self) -> SuggestionKind {
+        match (self.original_text.is_empty(), self.text.is_empty()) {
+            (true, _) => SuggestionKind::Insert,
+            (false, true) => SuggestionKind::Delete,
+            (false, false) => SuggestionKind::Replace,
+        }
+    }
+}
+
+fn validate_suggestion_range(dto: &CreateSuggestionDto) -> Result<(), validator::ValidationError> {
+    if dto.start_offset > dto.end_offset {
+        return Err(validator::ValidationError::new("invalid_range")
+            .with_message("start_offset must not be greater than end_offset".into()));
+    }
+
+    let range_length = (dto.end_offset - dto.start_offset) as usize;
+    if dto.original_text.chars().count() != range_length {
+        return Err(validator::ValidationError::new("invalid_range")
+            .with_message("original_text must match the length of the range".into()));
+    }
+
+    if dto.original_text.is_empty() && dto.text.is_empty() {
+        return Err(validator::ValidationError::new("empty_suggestion")
+            .with_message("Suggestion must insert or delete text".into()));
+    }
+
+    if dto.original_text == dto.text {
+        return Err(validator::ValidationError::new("empty_suggestion")
+            .with_message("Suggestion does not change the text".into()));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestionsQueryDto {
+    pub document_id: uuid::Uuid,
+    pub status: Option<SuggestionStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestionResponseDto {
+    pub status: String,
+    pub data: Suggestion,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestionListResponseDto {
+    pub status: String,
+    pub suggestions: Vec<Suggestion>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationResponseDto {
+    pub status: String,
+    pub data: Notification,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationListResponseDto {
+    pub status: String,
+    pub notifications: Vec<Notification>,
+    pub results: i64,
+    pub unread: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationPreferencesDto {
+    pub preferences: Vec<NotificationPreference>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationPreferencesResponseDto {
+    pub status: String,
+    pub data: NotificationPreferencesDto,
+}
+
+#[derive(Validate, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AccessRequestDto {
+    pub document_id: uuid::Uuid,
+    #[validate(length(max = 500, message = "Message must be at most 500 characters"))]
+    pub message: Option<String>,
+}
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhookDto {
+    #[validate(url(message = "Webhook URL is invalid"))]
+    pub url: String,
+    #[validate(length(min = 1, message = "At least one event is required"))]
+    pub events: Vec<WebhookEvent>,
+    /// Only receive events of this document
+    pub document_id: Option<uuid::Uuid>,
+}
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateWebhookDto {
+    #[validate(url(message = "Webhook URL is invalid"))]
+    pub url: Option<String>,
+    #[validate(length(min = 1, message = "At least one event is required"))]
+    pub events: Option<Vec<WebhookEvent>>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookResponseDto {
+    pub status: String,
+    pub data: Webhook,
+}
+
+/// The signing secret is only returned when the webhook is created
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookCreatedDto {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookCreatedResponseDto {
+    pub status: String,
+    pub data: WebhookCreatedDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookListResponseDto {
+    pub status: String,
+    pub webhooks: Vec<Webhook>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveryListResponseDto {
+    pub status: String,
+    pub deliveries: Vec<WebhookDelivery>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveryDto {
+    #[serde(flatten)]
+    pub delivery: WebhookDelivery,
+    pub attempts_log: Vec<WebhookDeliveryAttempt>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveryResponseDto {
+    pub status: String,
+    pub data: WebhookDeliveryDto,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditLogExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AuditLogQueryDto {
+    #[validate(range(min = 1))]
+    pub page: Option<usize>,
+    #[validate(range(min = 1, max = 50))]
+    pub limit: Option<usize>,
+    pub actor_id: Option<uuid::Uuid>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<uuid::Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub format: Option<AuditLogExportFormat>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogListResponseDto {
+    pub status: String,
+    pub logs: Vec<AuditLog>,
+    pub results: usize,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionDto {
+    #[serde(flatten)]
+    pub session: Session,
+    /// 是否为发起请求的会话
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionListResponseDto {
+    pub status: String,
+    pub sessions: Vec<SessionDto>,
+    pub results: usize,
+}
+
+#[derive(Debug, Validate, Clone, Serialize, Deserialize)]
+pub struct CreateAccessTokenDto {
+    #[validate(length(min = 1, max = 100, message = "Name must be 1 to 100 characters"))]
+    pub name: String,
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<TokenScope>,
+    /// 有效期（天）
+    #[serde(rename = "expiresInDays")]
+    #[validate(range(min = 1, max = 365, message = "Expiry must be 1 to 365 days"))]
+    pub expires_in_days: i64,
+}
+
+/// The token itself is only returned when it is created
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenCreatedDto {
+    #[serde(flatten)]
+    pub access_token: PersonalAccessToken,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenCreatedResponseDto {
+    pub status: String,
+    pub data: AccessTokenCreatedDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenListResponseDto {
+    pub status: String,
+    pub tokens: Vec<PersonalAccessToken>,
+    pub results: usize,
+}
+
+#[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+pub struct TwoFactorCodeDto {
+    /// 认证器应用中的六位验证码，或一个恢复码
+    #[validate(length(min = 6, max = 32, message = "Code is invalid"))]
+    pub code: String,
+}
+
+#[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+pub struct ChallengeTokenDto {
+    #[serde(rename = "challengeToken")]
+    #[validate(length(min = 1, message = "Challenge token is required"))]
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Validate, Default, Clone, Serialize, Deserialize)]
+pub struct TwoFactorChallengeVerifyDto {
+    #[serde(rename = "challengeToken")]
+    #[validate(length(min = 1, message = "Challenge token is required"))]
+    pub challenge_token: String,
+    #[validate(length(min = 6, max = 32, message = "Code is invalid"))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeResponseDto {
+    /// `two_factor_required` 或 `two_factor_setup_required`
+    pub status: String,
+    #[serde(rename = "challengeToken")]
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpSetupDto {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpSetupResponseDto {
+    pub status: String,
+    pub data: TotpSetupDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryCodesResponseDto {
+    pub status: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorLoginResponseDto {
+    pub status: String,
+    pub token: String,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorStatusDto {
+    pub enabled: bool,
+    /// 已开始设置但尚未确认
+    pub pending: bool,
+    /// 用户的角色是否要求两步验证
+    pub required: bool,
+    #[serde(rename = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorStatusResponseDto {
+    pub status: String,
+    pub data: TwoFactorStatusDto,
+}
+
+#[derive(Debug, Validate, Clone, Serialize, Deserialize)]
+pub struct UpdateRolePolicyDto {
+    pub role: UserRole,
+    #[serde(rename = "requireTwoFactor")]
+    pub require_two_factor: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RolePolicyListResponseDto {
+    pub status: String,
+    pub policies: Vec<RolePolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RolePolicyResponseDto {
+    pub status: String,
+    pub data: RolePolicy,
+}
+
+/// `navigator.credentials.create()` 返回的凭证，二进制字段为 Base64url 编码
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyAttestationDto {
+    pub id: String,
+    pub response: PasskeyAttestationResponseDto,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyAttestationResponseDto {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+    #[serde(default)]
+    pub transports: Vec<String>,
+}
+
+/// `navigator.credentials.get()` 返回的断言，二进制字段为 Base64url 编码
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyAssertionDto {
+    pub id: String,
+    pub response: PasskeyAssertionResponseDto,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyAssertionResponseDto {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(rename = "userHandle")]
+    pub user_handle: Option<String>,
+}
+
+#[derive(Debug, Validate, Clone, Serialize, Deserialize)]
+pub struct PasskeyRegisterDto {
+    #[serde(rename = "challengeId")]
+    pub challenge_id: uuid::Uuid,
+    #[validate(length(min = 1, max = 100, message = "Name must be 1-100 characters"))]
+    pub name: String,
+    pub credential: PasskeyAttestationDto,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyLoginDto {
+    #[serde(rename = "challengeId")]
+    pub challenge_id: uuid::Uuid,
+    pub credential: PasskeyAssertionDto,
+}
+
+#[derive(Debug, Validate, Clone, Serialize, Deserialize)]
+pub struct PasskeyTwoFactorDto {
+    #[serde(rename = "challengeToken")]
+    #[validate(length(min = 1, message = "Challenge token is required"))]
+    pub challenge_token: String,
+    #[serde(rename = "challengeId")]
+    pub challenge_id: uuid::Uuid,
+    pub credential: PasskeyAssertionDto,
+}
+
+/// 传给浏览器 WebAuthn API 的参数
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyOptionsResponseDto {
+    pub status: String,
+    #[serde(rename = "challengeId")]
+    pub challenge_id: uuid::Uuid,
+    #[serde(rename = "publicKey")]
+    pub public_key: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyListResponseDto {
+    pub status: String,
+    pub passkeys: Vec<Passkey>,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasskeyResponseDto {
+    pub status: String,
+    pub data: Passkey,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdentityListResponseDto {
+    pub status: String,
+    pub identities: Vec<UserIdentity>,
+    pub results: usize,
+}
+
+/// 钱包登录用的随机数，前端据此构造 EIP-4361 消息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SiweNonceResponseDto {
+    pub status: String,
+    pub nonce: String,
+    /// 消息中必须使用的域名
+    pub domain: String,
+    /// 允许的链 ID
+    #[serde(rename = "chainIds")]
+    pub chain_ids: Vec<u64>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 钱包签名的 EIP-4361 消息
+#[derive(Debug, Validate, Clone, Serialize, Deserialize)]
+pub struct SiweVerifyDto {
+    #[validate(length(min = 1, max = 4096, message = "Message must be 1-4096 characters"))]
+    pub message: String,
+    #[validate(length(min = 1, max = 200, message = "Signature is required"))]
+    pub signature: String,
+}
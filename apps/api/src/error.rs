@@ -1,15 +1,20 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::db::DbError;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub status: String,
     pub message: String,
+    /// 逐条列出的错误，例如未满足的密码策略规则
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<serde_json::Value>,
 }
 
 impl fmt::Display for ErrorResponse {
@@ -32,6 +37,10 @@ pub enum ErrorMessage {
     TokenNotProvided,
     PermissionDenied,
     UserNotAuthenticated,
+    SessionRevoked,
+    AccessTokenNotAllowed,
+    MissingScope(&'static str),
+    RecentLoginRequired,
 }
 
 impl fmt::Display for ErrorMessage {
@@ -56,6 +65,18 @@ impl ErrorMessage {
                 format!("Password must not be more than {} characters", max_length)
             }
             ErrorMessage::InvalidToken => "Authentication token is invalid or expired".to_string(),
+            ErrorMessage::SessionRevoked => {
+                "This session has been logged out, please log in again".to_string()
+            }
+            ErrorMessage::AccessTokenNotAllowed => {
+                "Personal access tokens cannot be used for this action".to_string()
+            }
+            ErrorMessage::MissingScope(scope) => {
+                format!("This access token does not have the '{}' scope", scope)
+            }
+            ErrorMessage::RecentLoginRequired => {
+                "Please log in again to confirm this action".to_string()
+            }
             ErrorMessage::TokenNotProvided => {
                 "You are not logged in, please provide a token".to_string()
             }
@@ -73,6 +94,10 @@ impl ErrorMessage {
 pub struct HttpError {
     pub message: String,
     pub status: StatusCode,
+    /// 限流或锁定时客户端需要等待的秒数，写入 `Retry-After` 响应头
+    pub retry_after: Option<u64>,
+    /// 写入响应体的 `errors` 字段
+    pub errors: Option<serde_json::Value>,
 }
 
 impl HttpError {
@@ -80,6 +105,8 @@ impl HttpError {
         HttpError {
             message: message.into(),
             status,
+            retry_after: None,
+            errors: None,
         }
     }
 
@@ -87,6 +114,8 @@ impl HttpError {
         HttpError {
             message: message.into(),
             status: StatusCode::INTERNAL_SERVER_ERROR,
+            retry_after: None,
+            errors: None,
         }
     }
 
@@ -94,6 +123,8 @@ impl HttpError {
         HttpError {
             message: message.into(),
             status: StatusCode::BAD_REQUEST,
+            retry_after: None,
+            errors: None,
         }
     }
 
@@ -101,6 +132,8 @@ impl HttpError {
         HttpError {
             message: message.into(),
             status: StatusCode::CONFLICT,
+            retry_after: None,
+            errors: None,
         }
     }
 
@@ -108,16 +141,58 @@ impl HttpError {
         HttpError {
             message: message.into(),
             status: StatusCode::UNAUTHORIZED,
+            retry_after: None,
+            errors: None,
         }
     }
 
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        HttpError {
+            message: message.into(),
+            status: StatusCode::FORBIDDEN,
+            retry_after: None,
+            errors: None,
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        HttpError {
+            message: message.into(),
+            status: StatusCode::NOT_FOUND,
+            retry_after: None,
+            errors: None,
+        }
+    }
+
+    pub fn too_many_requests(message: impl Into<String>, retry_after: u64) -> Self {
+        HttpError {
+            message: message.into(),
+            status: StatusCode::TOO_MANY_REQUESTS,
+            retry_after: Some(retry_after),
+            errors: None,
+        }
+    }
+
+    /// 附带逐条列出的错误
+    pub fn with_errors(mut self, errors: serde_json::Value) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
     pub fn into_http_response(self) -> Response {
         let json_response = Json(ErrorResponse {
             status: "fail".to_string(),
             message: self.message.clone(),
+            errors: self.errors,
         });
 
-        (self.status, json_response).into_response()
+        let mut response = (self.status, json_response).into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
     }
 }
 
@@ -133,6 +208,33 @@ impl fmt::Display for HttpError {
 
 impl std::error::Error for HttpError {}
 
+/// 将数据库错误映射为对应的 HTTP 状态码
+impl From<DbError> for HttpError {
+    fn from(error: DbError) -> Self {
+        match error {
+            DbError::PermissionDenied => {
+                HttpError::forbidden(ErrorMessage::PermissionDenied.to_string())
+            }
+            DbError::NotFound(_) | DbError::UserNotFound | DbError::DocumentNotFound => {
+                HttpError::not_found(error.to_string())
+            }
+            DbError::EmailExists => {
+                HttpError::unique_constraint_violation(ErrorMessage::EmailExist.to_string())
+            }
+            DbError::InvalidCredentials | DbError::InvalidToken => {
+                HttpError::bad_request(error.to_string())
+            }
+            DbError::ConstraintViolation(_) | DbError::InvalidPermissionChange(_) => {
+                HttpError::bad_request(error.to_string())
+            }
+            DbError::Sqlx(_) | DbError::TransactionError(_) => {
+                tracing::error!("数据库操作失败: {}", error);
+                HttpError::server_error(ErrorMessage::ServerError.to_string())
+            }
+        }
+    }
+}
+
 impl IntoResponse for HttpError {
     fn into_response(self) -> Response {
         self.into_http_response()
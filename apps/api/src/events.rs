@@ -0,0 +1,303 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::PermissionLevel;
+
+/// 文档事件类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentEventKind {
+    Created,
+    Updated,
+    Deleted,
+    Shared,
+    Unshared,
+}
+
+impl DocumentEventKind {
+    /// SSE `event:` 字段使用的事件名称
+    pub fn to_str(self) -> &'static str {
+        match self {
+            DocumentEventKind::Created => "document.created",
+            DocumentEventKind::Updated => "document.updated",
+            DocumentEventKind::Deleted => "document.deleted",
+            DocumentEventKind::Shared => "document.shared",
+            DocumentEventKind::Unshared => "document.unshared",
+        }
+    }
+}
+
+/// 推送给客户端的文档变更事件
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentEvent {
+    pub id: u64,
+    pub kind: DocumentEventKind,
+    #[serde(rename = "documentId")]
+    pub document_id: Uuid,
+    #[serde(rename = "actorId")]
+    pub actor_id: Uuid,
+    pub title: Option<String>,
+    /// 共享 / 取消共享事件的目标用户
+    #[serde(rename = "userId")]
+    pub user_id: Option<Uuid>,
+    pub permission: Option<PermissionLevel>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    /// 能够收到该事件的用户，不对外输出
+    #[serde(skip)]
+    pub recipients: Vec<Uuid>,
+}
+
+impl DocumentEvent {
+    /// 判断事件是否对指定用户可见
+    pub fn is_visible_to(&self, user_id: Uuid) -> bool {
+        self.recipients.contains(&user_id)
+    }
+}
+
+/// 发布事件时的输入参数，`id` 与 `created_at` 由 [`EventHub`] 分配
+#[derive(Debug, Clone)]
+pub struct NewDocumentEvent {
+    pub kind: DocumentEventKind,
+    pub document_id: Uuid,
+    pub actor_id: Uuid,
+    pub title: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub permission: Option<PermissionLevel>,
+    pub recipients: Vec<Uuid>,
+}
+
+/// 订阅结果
+///
+/// - `replay` -- 根据 `Last-Event-ID` 需要补发的历史事件
+/// - `missed` -- 请求的事件已被挤出缓冲区，客户端需要重新拉取完整数据
+/// - `receiver` -- 实时事件接收端
+pub struct Subscription {
+    pub replay: Vec<DocumentEvent>,
+    pub missed: bool,
+    pub receiver: broadcast::Receiver<DocumentEvent>,
+}
+
+struct EventBuffer {
+    next_id: u64,
+    events: VecDeque<DocumentEvent>,
+}
+
+/// 文档事件中心 -- 负责分配事件 ID、保存有界的历史事件并广播给订阅者
+///
+/// 发布与订阅共用同一把锁，保证补发的历史事件与实时事件之间既不重复也不遗漏。
+pub struct EventHub {
+    sender: broadcast::Sender<DocumentEvent>,
+    buffer: Mutex<EventBuffer>,
+    capacity: usize,
+}
+
+impl EventHub {
+    /// 创建事件中心
+    ///
+    /// # 参数
+    /// * `capacity` - 服务端保留的历史事件数量，用于 `Last-Event-ID` 断线续传
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+
+        EventHub {
+            sender,
+            buffer: Mutex::new(EventBuffer {
+                next_id: 1,
+                events: VecDeque::with_capacity(capacity),
+            }),
+            capacity,
+        }
+    }
+
+    /// 发布事件，返回分配的事件 ID
+    pub fn publish(&self, event: NewDocumentEvent) -> u64 {
+        let mut buffer = self.buffer.lock().unwrap();
+
+        let event = DocumentEvent {
+            id: buffer.next_id,
+            kind: event.kind,
+            document_id: event.document_id,
+            actor_id: event.actor_id,
+            title: event.title,
+            user_id: event.user_id,
+            permission: event.permission,
+            created_at: Utc::now(),
+            recipients: event.recipients,
+        };
+        buffer.next_id += 1;
+
+        if buffer.events.len() == self.capacity {
+            buffer.events.pop_front();
+        }
+        buffer.events.push_back(event.clone());
+
+        // -- 没有订阅者时 send 会返回错误，可以忽略
+        let _ = self.sender.send(event.clone());
+
+        tracing::debug!(
+            "发布文档事件 {} #{}，文档: {}",
+            event.kind.to_str(),
+            event.id,
+            event.document_id
+        );
+
+        event.id
+    }
+
+    /// 订阅事件
+    ///
+    /// # 参数
+    /// * `user_id` - 订阅者，只补发对其可见的事件
+    /// * `last_event_id` - 客户端最后收到的事件 ID
+    pub fn subscribe(&self, user_id: Uuid, last_event_id: Option<u64>) -> Subscription {
+        let buffer = self.buffer.lock().unwrap();
+        let receiver = self.sender.subscribe();
+
+        let Some(last_event_id) = last_event_id else {
+            return Subscription {
+                replay: Vec::new(),
+                missed: false,
+                receiver,
+            };
+        };
+
+        // -- 服务重启后事件 ID 会重新计数，或者请求的事件已被挤出缓冲区
+        let oldest_id = buffer
+            .events
+            .front()
+            .map(|event| event.id)
+            .unwrap_or(buffer.next_id);
+        let missed = last_event_id >= buffer.next_id || last_event_id + 1 < oldest_id;
+
+        let replay = if missed {
+            Vec::new()
+        } else {
+            buffer
+                .events
+                .iter()
+                .filter(|event| event.id > last_event_id && event.is_visible_to(user_id))
+                .cloned()
+                .collect()
+        };
+
+        Subscription {
+            replay,
+            missed,
+            receiver,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(recipients: &[Uuid]) -> NewDocumentEvent {
+        NewDocumentEvent {
+            kind: DocumentEventKind::Updated,
+            document_id: Uuid::new_v4(),
+            actor_id: Uuid::new_v4(),
+            title: None,
+            user_id: None,
+            permission: None,
+            recipients: recipients.to_vec(),
+        }
+    }
+
+    fn replay_ids(subscription: &Subscription) -> Vec<u64> {
+        subscription.replay.iter().map(|event| event.id).collect()
+    }
+
+    #[test]
+    fn without_last_event_id_only_live_events() {
+        let hub = EventHub::new(10);
+        let user = Uuid::new_v4();
+        hub.publish(event(&[user]));
+
+        let mut subscription = hub.subscribe(user, None);
+        assert!(subscription.replay.is_empty());
+        assert!(!subscription.missed);
+
+        let id = hub.publish(event(&[user]));
+        assert_eq!(subscription.receiver.try_recv().unwrap().id, id);
+    }
+
+    #[test]
+    fn replays_visible_events_after_last_event_id() {
+        let hub = EventHub::new(10);
+        let (user, other) = (Uuid::new_v4(), Uuid::new_v4());
+
+        hub.publish(event(&[user]));
+        hub.publish(event(&[user, other]));
+        hub.publish(event(&[other]));
+        hub.publish(event(&[user]));
+
+        let subscription = hub.subscribe(user, Some(1));
+        assert!(!subscription.missed);
+        assert_eq!(replay_ids(&subscription), vec![2, 4]);
+
+        let subscription = hub.subscribe(other, Some(0));
+        assert!(!subscription.missed);
+        assert_eq!(replay_ids(&subscription), vec![2, 3]);
+
+        // -- 已经收到最新事件时没有需要补发的内容
+        let subscription = hub.subscribe(user, Some(4));
+        assert!(!subscription.missed);
+        assert!(subscription.replay.is_empty());
+    }
+
+    #[test]
+    fn last_event_id_from_before_restart_is_missed() {
+        let hub = EventHub::new(10);
+        let user = Uuid::new_v4();
+
+        // -- 重启后事件 ID 从 1 开始，客户端带来的是重启前的 ID
+        let subscription = hub.subscribe(user, Some(42));
+        assert!(subscription.missed);
+        assert!(subscription.replay.is_empty());
+
+        hub.publish(event(&[user]));
+        let subscription = hub.subscribe(user, Some(1));
+        assert!(!subscription.missed);
+        assert!(hub.subscribe(user, Some(2)).missed);
+    }
+
+    #[test]
+    fn evicted_events_are_missed() {
+        let hub = EventHub::new(3);
+        let user = Uuid::new_v4();
+        for _ in 0..5 {
+            hub.publish(event(&[user]));
+        }
+
+        // -- 缓冲区中只剩 3..=5
+        let subscription = hub.subscribe(user, Some(2));
+        assert!(!subscription.missed);
+        assert_eq!(replay_ids(&subscription), vec![3, 4, 5]);
+
+        let subscription = hub.subscribe(user, Some(1));
+        assert!(subscription.missed);
+        assert!(subscription.replay.is_empty());
+    }
+
+    #[test]
+    fn live_events_follow_the_replay() {
+        let hub = EventHub::new(10);
+        let user = Uuid::new_v4();
+        hub.publish(event(&[user]));
+
+        let mut subscription = hub.subscribe(user, Some(0));
+        assert_eq!(replay_ids(&subscription), vec![1]);
+
+        hub.publish(event(&[user]));
+        assert_eq!(subscription.receiver.try_recv().unwrap().id, 2);
+        assert!(subscription.receiver.try_recv().is_err());
+    }
+}
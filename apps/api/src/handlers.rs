@@ -1,2 +1,15 @@
+pub mod access_tokens;
+pub mod account;
+pub mod audit;
 pub mod auth;
+pub mod comments;
+pub mod events;
+pub mod identities;
+pub mod notifications;
+pub mod passkeys;
+pub mod sessions;
+pub mod suggestions;
+pub mod two_factor;
 pub mod users;
+pub mod webhooks;
+pub mod well_known;
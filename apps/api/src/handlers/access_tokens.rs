@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    handler::Handler,
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{delete, get},
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::AccessTokenExt,
+    dtos::{
+        AccessTokenCreatedDto, AccessTokenCreatedResponseDto, AccessTokenListResponseDto,
+        CreateAccessTokenDto, Response,
+    },
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, fresh_user},
+    models::{AuditAction, TokenScope, UserRole},
+    utils::token,
+};
+
+pub fn access_tokens_handler() -> Router {
+    Router::new()
+        .route(
+            "/",
+            get(get_access_tokens).post(create_access_token.layer(middleware::from_fn(fresh_user))),
+        )
+        .route("/{token_id}", delete(revoke_access_token))
+}
+
+/// 获取当前用户未过期、未吊销的个人访问令牌，不包含令牌本身
+pub async fn get_access_tokens(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let tokens = app_state
+        .db_client
+        .get_user_access_tokens(user.user.id)
+        .await?;
+
+    let response = AccessTokenListResponseDto {
+        status: "success".to_string(),
+        results: tokens.len(),
+        tokens,
+    };
+
+    Ok(Json(response))
+}
+
+/// 创建个人访问令牌，令牌只在响应中出现这一次，数据库只保存哈希
+///
+/// `admin` 权限只能由管理员授予，令牌的权限不会超过用户自身的角色。
+pub async fn create_access_token(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(mut body): Json<CreateAccessTokenDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate().map_err(|e| {
+        tracing::warn!("访问令牌请求验证失败: {}", e);
+        HttpError::bad_request(e.to_string())
+    })?;
+
+    if body.scopes.contains(&TokenScope::Admin) && user.user.role != UserRole::Admin {
+        return Err(HttpError::forbidden(
+            "只有管理员可以创建带 admin 权限的令牌".to_string(),
+        ));
+    }
+
+    body.scopes.sort_by_key(|scope| scope.to_str());
+    body.scopes.dedup();
+
+    let (access_token, token_prefix) = token::generate_access_token();
+    let expires_at = Utc::now() + Duration::days(body.expires_in_days);
+
+    let created = app_state
+        .db_client
+        .create_access_token(
+            user.user.id,
+            body.name.trim(),
+            &token::hash_opaque_token(&access_token),
+            &token_prefix,
+            &body.scopes,
+            expires_at,
+        )
+        .await?;
+
+    tracing::info!(
+        "用户 {} 创建访问令牌 {} ({})",
+        user.user.email,
+        created.id,
+        created.name
+    );
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::AccessTokenCreated)
+            .actor(&user.user)
+            .target("user", user.user.id)
+            .details(serde_json::json!({
+                "token_id": created.id,
+                "name": created.name,
+                "scopes": created.scopes,
+                "expires_at": created.expires_at,
+            })),
+    )
+    .await;
+
+    let response = AccessTokenCreatedResponseDto {
+        status: "success".to_string(),
+        data: AccessTokenCreatedDto {
+            access_token: created,
+            token: access_token,
+        },
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// 吊销个人访问令牌，使用该令牌的请求立即失效
+pub async fn revoke_access_token(
+    Path(token_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .revoke_access_token(token_id, user.user.id)
+        .await?;
+
+    tracing::info!("用户 {} 吊销访问令牌 {}", user.user.email, token_id);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::AccessTokenRevoked)
+            .actor(&user.user)
+            .target("user", user.user.id)
+            .details(serde_json::json!({ "token_id": token_id })),
+    )
+    .await;
+
+    let response = Response {
+        status: "success",
+        message: "Access token revoked successfully".to_string(),
+    };
+
+    Ok(Json(response))
+}
@@ -0,0 +1,244 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, Router, http::header, response::IntoResponse, routing::get};
+use chrono::{Duration, Utc};
+use validator::Validate;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{
+        AccessTokenExt, AccountDeletionExt, AuditExt, DocumentExt, IdentityExt, SessionExt, UserExt,
+    },
+    dtos::{
+        AccountDeletionDto, AccountDeletionResponseDto, AccountExportDto, AccountExportSharesDto,
+        FilterUserDto,
+    },
+    error::{ErrorMessage, HttpError},
+    mail::mails::send_account_deletion_email,
+    middleware::JWTAuthMiddleware,
+    models::{AuditAction, AuthProvider, DocumentDisposition},
+    utils::password,
+};
+
+/// 导出数据中最多包含的审计日志条数
+const EXPORT_AUDIT_LOG_LIMIT: i64 = 10_000;
+
+pub fn account_handler() -> Router {
+    Router::new()
+        .route("/export", get(export_account_data))
+        .route(
+            "/deletion",
+            get(get_account_deletion).post(schedule_account_deletion),
+        )
+}
+
+/// 导出个人数据 -- 个人资料、登录方式、拥有的文档、共享记录和相关审计日志
+///
+/// 以 JSON 附件返回，供用户在注销账户前下载保存。
+pub async fn export_account_data(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let user = &user.user;
+
+    let identities = app_state.db_client.get_user_identities(user.id).await?;
+    let documents = app_state.db_client.get_owned_documents(user.id).await?;
+    let (granted_to_me, granted_by_me) = app_state
+        .db_client
+        .get_user_shares(user.id)
+        .await?
+        .into_iter()
+        .partition(|permission| permission.user_id == user.id);
+    let audit_logs = app_state
+        .db_client
+        .get_user_audit_logs(user.id, EXPORT_AUDIT_LOG_LIMIT)
+        .await?;
+
+    let exported_at = Utc::now();
+    let export = AccountExportDto {
+        exported_at,
+        profile: FilterUserDto::filter_user(user),
+        identities,
+        documents,
+        shares: AccountExportSharesDto {
+            granted_by_me,
+            granted_to_me,
+        },
+        audit_logs,
+    };
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::DataExported)
+            .actor(user)
+            .target("user", user.id)
+            .details(serde_json::json!({
+                "documents": export.documents.len(),
+                "audit_logs": export.audit_logs.len(),
+            })),
+    )
+    .await;
+
+    tracing::info!("用户 {} 导出了个人数据", user.email);
+
+    let disposition = format!(
+        "attachment; filename=\"account-export-{}.json\"",
+        exported_at.format("%Y%m%d%H%M%S")
+    );
+
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+/// 查询当前账户的注销请求，没有请求时 `data` 为空
+pub async fn get_account_deletion(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let deletion = app_state
+        .db_client
+        .get_account_deletion(user.user.id)
+        .await?
+        .filter(|deletion| deletion.completed_at.is_none());
+
+    Ok(Json(AccountDeletionResponseDto {
+        status: "success".to_string(),
+        data: deletion,
+    }))
+}
+
+/// 申请注销账户
+///
+/// 宽限期结束后账户才会被注销，期间重新登录即可取消。申请后立即吊销所有会话和
+/// 个人访问令牌；拥有的文档按用户的选择转移给其他用户或一并删除。
+/// 设置了密码的账户需要提交当前密码，没有密码的账户需要在几分钟内重新登录过。
+pub async fn schedule_account_deletion(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    Json(body): Json<AccountDeletionDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate().map_err(|e| {
+        tracing::warn!("账户注销请求验证失败: {}", e);
+        HttpError::bad_request(e.to_string())
+    })?;
+
+    let user = &auth.user;
+
+    // -- 只通过第三方登录或钱包创建的账户没有可以确认的密码，改为要求刚刚重新登录
+    if app_state
+        .db_client
+        .has_identity(user.id, AuthProvider::Local)
+        .await?
+    {
+        let password_match = password::compare(&body.password, &user.password)
+            .map_err(|_| HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?;
+
+        if !password_match {
+            tracing::warn!("注销账户时密码不匹配，用户ID: {}", user.id);
+            return Err(HttpError::bad_request(
+                ErrorMessage::WrongCredentials.to_string(),
+            ));
+        }
+    } else {
+        auth.require_recent_login(&app_state.db_client).await?;
+    }
+
+    // -- 转移文档时接收人必须是其他没有注销请求的用户，并且要选择接收人届时不可用时如何处理
+    let (transfer_to, transfer_fallback) = match body.documents {
+        DocumentDisposition::Transfer => {
+            let email = body
+                .transfer_to
+                .as_deref()
+                .map(|email| email.trim().to_lowercase())
+                .ok_or_else(|| HttpError::bad_request("请填写文档接收人的邮箱".to_string()))?;
+
+            let target = app_state
+                .db_client
+                .get_user(None, None, Some(&email), None)
+                .await?
+                .ok_or_else(|| HttpError::bad_request("文档接收人不存在".to_string()))?;
+
+            if target.id == user.id {
+                return Err(HttpError::bad_request("不能把文档转移给自己".to_string()));
+            }
+
+            match app_state.db_client.get_account_deletion(target.id).await? {
+                Some(deletion) if deletion.completed_at.is_some() => {
+                    return Err(HttpError::bad_request("文档接收人不存在".to_string()));
+                }
+                Some(_) => {
+                    return Err(HttpError::bad_request(
+                        "文档接收人已申请注销账户，不能接收文档".to_string(),
+                    ));
+                }
+                None => {}
+            }
+
+            let fallback = body.transfer_fallback.ok_or_else(|| {
+                HttpError::bad_request("请选择文档接收人不可用时保留还是删除文档".to_string())
+            })?;
+
+            (Some(target.id), Some(fallback))
+        }
+        DocumentDisposition::Delete => (None, None),
+    };
+
+    let grace_days = app_state.env.account_deletion_grace_days;
+    let deletion = app_state
+        .db_client
+        .schedule_account_deletion(
+            user.id,
+            Utc::now() + Duration::days(grace_days),
+            body.documents,
+            transfer_to,
+            transfer_fallback,
+        )
+        .await?;
+
+    let revoked = app_state
+        .db_client
+        .revoke_user_sessions(user.id, None)
+        .await?;
+    app_state.session_cache.evict(&revoked);
+    app_state.session_cache.evict_user(user.id);
+    app_state
+        .db_client
+        .revoke_user_access_tokens(user.id)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::AccountDeletionScheduled)
+            .actor(user)
+            .target("user", user.id)
+            .details(serde_json::json!({
+                "scheduled_for": deletion.scheduled_for,
+                "documents": deletion.document_disposition.to_str(),
+                "transfer_to": deletion.transfer_to,
+                "transfer_fallback": deletion.transfer_fallback.map(|fallback| fallback.to_str()),
+            })),
+    )
+    .await;
+
+    tracing::info!(
+        "用户 {} 申请注销账户，将于 {} 注销",
+        user.email,
+        deletion.scheduled_for
+    );
+
+    let (email, name) = (user.email.clone(), user.name.clone());
+    let scheduled_for = deletion
+        .scheduled_for
+        .format("%Y-%m-%d %H:%M UTC")
+        .to_string();
+    tokio::spawn(async move {
+        if let Err(e) = send_account_deletion_email(&email, &name, &scheduled_for).await {
+            tracing::error!("发送账户注销通知失败: {}", e);
+        }
+    });
+
+    Ok(Json(AccountDeletionResponseDto {
+        status: "success".to_string(),
+        data: Some(deletion),
+    }))
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::Query,
+    http::header,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    db::{AuditExt, AuditLogFilter},
+    dtos::{AuditLogExportFormat, AuditLogListResponseDto, AuditLogQueryDto},
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, fresh_user, role_check, scope_check},
+    models::{AuditLog, TokenScope, UserRole},
+};
+
+/// 单次导出的最大条数
+const MAX_EXPORT_ROWS: usize = 10_000;
+
+pub fn audit_handler() -> Router {
+    Router::new()
+        .route("/", get(get_audit_logs))
+        .route("/export", get(export_audit_logs))
+        // -- 审计日志只有管理员可以查看
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+        .layer(middleware::from_fn(|req, next| {
+            scope_check(req, next, TokenScope::Admin)
+        }))
+        .layer(middleware::from_fn(fresh_user))
+}
+
+/// 分页查询审计日志，可按操作者、操作类型、对象和时间范围过滤
+pub async fn get_audit_logs(
+    Query(query_params): Query<AuditLogQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let page = query_params.page.unwrap_or(1) as u32;
+    let limit = query_params.limit.unwrap_or(10);
+    let filter = audit_filter(&query_params);
+
+    let logs = app_state
+        .db_client
+        .get_audit_logs(&filter, page, limit)
+        .await?;
+    let total = app_state.db_client.get_audit_log_count(&filter).await?;
+
+    let response = AuditLogListResponseDto {
+        status: "success".to_string(),
+        results: logs.len(),
+        logs,
+        total,
+    };
+
+    Ok(Json(response))
+}
+
+/// 导出审计日志，`format` 为 `csv`（默认）或 `json`
+///
+/// 使用与查询接口相同的过滤条件，最多导出 10000 条，超出时请缩小时间范围。
+pub async fn export_audit_logs(
+    Query(query_params): Query<AuditLogQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<Response, HttpError> {
+    let filter = audit_filter(&query_params);
+    let logs = app_state
+        .db_client
+        .get_audit_logs(&filter, 1, MAX_EXPORT_ROWS)
+        .await?;
+
+    tracing::info!("管理员 {} 导出 {} 条审计日志", user.user.email, logs.len());
+
+    let format = query_params.format.unwrap_or_default();
+    let timestamp = Utc::now().format("%Y%m%d%H%M%S");
+    let (content_type, extension, body) = match format {
+        AuditLogExportFormat::Csv => ("text/csv; charset=utf-8", "csv", logs_to_csv(&logs)),
+        AuditLogExportFormat::Json => (
+            "application/json",
+            "json",
+            serde_json::to_string(&logs).map_err(|e| HttpError::server_error(e.to_string()))?,
+        ),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"audit-logs-{}.{}\"",
+                    timestamp, extension
+                ),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn audit_filter(query_params: &AuditLogQueryDto) -> AuditLogFilter {
+    AuditLogFilter {
+        actor_id: query_params.actor_id,
+        action: query_params.action.clone(),
+        target_type: query_params.target_type.clone(),
+        target_id: query_params.target_id,
+        from: query_params.from,
+        to: query_params.to,
+    }
+}
+
+fn logs_to_csv(logs: &[AuditLog]) -> String {
+    let mut csv = String::from(
+        "id,created_at,actor_id,actor_email,action,target_type,target_id,ip_address,user_agent,details\r\n",
+    );
+
+    for log in logs {
+        let fields = [
+            log.id.to_string(),
+            log.created_at.to_rfc3339(),
+            log.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            log.actor_email.clone().unwrap_or_default(),
+            log.action.clone(),
+            log.target_type.clone().unwrap_or_default(),
+            log.target_id.map(|id| id.to_string()).unwrap_or_default(),
+            log.ip_address.clone().unwrap_or_default(),
+            log.user_agent.clone().unwrap_or_default(),
+            log.details.to_string(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv
+}
+
+/// 转义 CSV 字段
+///
+/// 以 `=`、`+`、`-`、`@` 开头的内容会被表格软件当作公式执行，加上 `'` 前缀。
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
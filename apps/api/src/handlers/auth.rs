@@ -1,12 +1,20 @@
+mod email_change;
+mod ethereum;
 mod login;
+mod magic_link;
 mod oauth;
+mod otp;
+mod passkey;
 mod passwords;
 mod register;
+mod tokens;
+mod two_factor;
 
 use axum::{
     Extension, Json, Router,
     extract::Query,
     http::{HeaderMap, header},
+    middleware,
     response::{IntoResponse, Redirect},
     routing::{get, post},
 };
@@ -21,24 +29,124 @@ use crate::{
     dtos::{ResendVerificationDto, Response, VerifyEmailQueryDto},
     error::{ErrorMessage, HttpError},
     mail::mails::{send_verification_email, send_welcome_email},
+    middleware::auth,
+    rate_limit::{RateLimitRoute, rate_limit},
     utils::token,
 };
 
 pub fn auth_handler() -> Router {
     Router::new()
-        .route("/register", post(register::register))
-        .route("/login", post(login::login))
+        // -- 注册、登录、找回密码和重发验证邮件按 IP 和邮箱限流
+        .route(
+            "/register",
+            post(register::register).layer(middleware::from_fn_with_state(
+                RateLimitRoute::Register,
+                rate_limit,
+            )),
+        )
+        .route(
+            "/login",
+            post(login::login).layer(middleware::from_fn_with_state(
+                RateLimitRoute::Login,
+                rate_limit,
+            )),
+        )
+        // -- 使用刷新令牌换取新的访问令牌，刷新令牌同时轮换
+        .route("/refresh", post(tokens::refresh))
+        // -- 注销当前会话，需要登录
+        .route(
+            "/logout",
+            post(tokens::logout).layer(middleware::from_fn(auth)),
+        )
+        // -- 两步验证：用第一步签发的挑战令牌完成登录
+        .route("/2fa/verify", post(two_factor::verify_two_factor))
+        .route("/2fa/setup", post(two_factor::setup_two_factor))
+        .route(
+            "/2fa/setup/confirm",
+            post(two_factor::confirm_two_factor_setup),
+        )
+        .route("/2fa/passkey/options", post(passkey::two_factor_options))
+        .route("/2fa/passkey/verify", post(passkey::verify_two_factor))
+        // -- 通行密钥 (WebAuthn) 免密登录
+        .route("/passkey/options", post(passkey::login_options))
+        .route("/passkey/verify", post(passkey::login))
         .route("/verify", get(verify_email))
+        // -- 修改邮箱：新邮箱确认，旧邮箱撤销
+        .route(
+            "/email-change/confirm",
+            get(email_change::confirm_email_change),
+        )
+        .route(
+            "/email-change/revert",
+            get(email_change::revert_email_change),
+        )
+        // -- 免密登录：发送一次性登录链接，点击后登录
+        .route("/magic-link", post(magic_link::request_magic_link))
+        .route(
+            "/magic-link/verify",
+            get(magic_link::confirm_magic_link).post(magic_link::verify_magic_link),
+        )
+        // -- 邮箱验证码登录
+        .route("/otp/request", post(otp::request_login_code))
+        .route("/otp/verify", post(otp::verify_login_code))
         // -- 重新发送验证邮件的端点
-        .route("/resend-verification", post(resend_verification_email))
-        .route("/forgot-password", post(passwords::forgot_password))
+        .route(
+            "/resend-verification",
+            post(resend_verification_email).layer(middleware::from_fn_with_state(
+                RateLimitRoute::ResendVerification,
+                rate_limit,
+            )),
+        )
+        .route(
+            "/forgot-password",
+            post(passwords::forgot_password).layer(middleware::from_fn_with_state(
+                RateLimitRoute::ForgotPassword,
+                rate_limit,
+            )),
+        )
         .route("/reset-password", post(passwords::reset_password))
         // -- Google OAuth 登录端点
         .route("/google/login", get(oauth::google_oauth_login))
         .route("/google/callback", get(oauth::google_oauth_callback))
+        // -- 已登录用户关联 Google 账户
+        .route(
+            "/google/link",
+            get(oauth::google_oauth_link).layer(middleware::from_fn(auth)),
+        )
         // -- GitHub OAuth 登录端点
         .route("/github/login", get(oauth::github_oauth_login))
         .route("/github/callback", get(oauth::github_oauth_callback))
+        // -- 已登录用户关联 GitHub 账户
+        .route(
+            "/github/link",
+            get(oauth::github_oauth_link).layer(middleware::from_fn(auth)),
+        )
+        // -- 配置的 OpenID Connect 提供方登录端点
+        .route("/oidc/providers", get(oauth::oidc_providers))
+        .route("/oidc/{provider}/login", get(oauth::oidc_login))
+        .route("/oidc/{provider}/callback", get(oauth::oidc_callback))
+        .route(
+            "/oidc/{provider}/link",
+            get(oauth::oidc_link).layer(middleware::from_fn(auth)),
+        )
+        // -- 以太坊钱包登录 (Sign-In with Ethereum)，随机数保存在数据库中，按 IP 限流
+        .route(
+            "/ethereum/nonce",
+            post(ethereum::login_nonce).layer(middleware::from_fn_with_state(
+                RateLimitRoute::EthereumNonce,
+                rate_limit,
+            )),
+        )
+        .route("/ethereum/verify", post(ethereum::login))
+        // -- 已登录用户关联以太坊钱包
+        .route(
+            "/ethereum/link/nonce",
+            post(ethereum::link_nonce).layer(middleware::from_fn(auth)),
+        )
+        .route(
+            "/ethereum/link",
+            post(ethereum::link).layer(middleware::from_fn(auth)),
+        )
 }
 
 /// 处理邮箱验证请求 -- 验证用户的邮箱验证 token
@@ -113,6 +221,7 @@ pub async fn verify_email(
             tracing::error!("更新用户验证状态失败: {}", e);
             HttpError::server_error(e.to_string())
         })?;
+    app_state.session_cache.evict_user(user.id);
 
     tracing::info!("用户 {} 邮箱验证成功", user.email);
 
@@ -122,35 +231,15 @@ pub async fn verify_email(
         Err(e) => tracing::error!("发送欢迎邮件失败: {}", e),
     }
 
-    // -- 创建 JWT token
-    let token = token::create_token(
-        &user.id.to_string(),
-        app_state.env.jwt_secret.as_bytes(),
-        app_state.env.jwt_maxage,
-    )
-    .map_err(|e| {
-        tracing::error!("创建 JWT token 失败: {}", e);
-        HttpError::server_error(e.to_string())
-    })?;
-
-    // -- 设置 cookie
-    let cookie_duration = time::Duration::minutes(app_state.env.jwt_maxage * 60);
-    let cookie = Cookie::build(("token", token.clone()))
-        .path("/")
-        .max_age(cookie_duration)
-        .http_only(true)
-        .build();
-
-    let mut headers = HeaderMap::new();
-    headers.append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-
-    // -- 重定向到前端
-    let redirect = Redirect::to(&app_state.env.frontend_url);
-    let mut response = redirect.into_response();
-    response.headers_mut().extend(headers);
-
     tracing::info!("用户 {} 验证完成，重定向到前端", user.email);
-    Ok(response)
+
+    // -- 签发访问令牌和刷新令牌，写入 cookie 并重定向到前端
+    tokens::login_redirect(&app_state, &user, "email_verification", false, None)
+        .await
+        .map_err(|e| {
+            tracing::error!("签发登录令牌失败: {}", e);
+            e
+        })
 }
 
 pub async fn resend_verification_email(
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension,
+    extract::Query,
+    response::{IntoResponse, Redirect},
+};
+use validator::Validate;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{AccessTokenExt, EmailChangeExt, SessionExt},
+    dtos::VerifyEmailQueryDto,
+    error::HttpError,
+    models::AuditAction,
+    utils::{password, token},
+};
+
+/// 确认修改邮箱 -- 新邮箱收到的链接
+///
+/// 在同一个事务中更新邮箱并标记为已验证，新邮箱已被其他账户使用时返回 409 错误。
+pub async fn confirm_email_change(
+    Query(query_params): Query<VerifyEmailQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let change = app_state
+        .db_client
+        .confirm_email_change(&token::hash_opaque_token(&query_params.token))
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("无效或已过期的邮箱确认链接");
+            HttpError::bad_request("确认链接无效或已过期，请重新申请修改邮箱".to_string())
+        })?;
+
+    app_state.session_cache.evict_user(change.user_id);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::EmailChanged)
+            .actor_id(change.user_id)
+            .target("user", change.user_id)
+            .details(serde_json::json!({ "from": change.old_email, "to": change.new_email })),
+    )
+    .await;
+
+    tracing::info!(
+        "用户 {} 的邮箱已修改为 {}",
+        change.old_email,
+        change.new_email
+    );
+
+    Ok(Redirect::to(&format!(
+        "{}/settings?emailChange=confirmed",
+        app_state.env.frontend_url
+    )))
+}
+
+/// 撤销修改邮箱 -- 旧邮箱收到的链接
+///
+/// 修改尚未确认时直接取消；已经确认时改回旧邮箱，无论当前邮箱是什么。撤销说明账户可能已被他人控制，
+/// 申请修改的人知道密码，因此把密码替换为随机值，用户需要通过忘记密码重新设置，
+/// 同时吊销所有会话和个人访问令牌。
+pub async fn revert_email_change(
+    Query(query_params): Query<VerifyEmailQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let random_password = uuid::Uuid::new_v4().to_string();
+    let invalidated_password =
+        password::hash(&random_password).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let change = app_state
+        .db_client
+        .revert_email_change(
+            &token::hash_opaque_token(&query_params.token),
+            &invalidated_password,
+        )
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("无效或已过期的邮箱撤销链接");
+            HttpError::bad_request("撤销链接无效或已过期".to_string())
+        })?;
+
+    let revoked = app_state
+        .db_client
+        .revoke_user_sessions(change.user_id, None)
+        .await?;
+    app_state.session_cache.evict(&revoked);
+    app_state.session_cache.evict_user(change.user_id);
+    app_state
+        .db_client
+        .revoke_user_access_tokens(change.user_id)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::EmailChangeReverted)
+            .actor_id(change.user_id)
+            .target("user", change.user_id)
+            .details(serde_json::json!({
+                "from": change.new_email,
+                "to": change.old_email,
+                "confirmed": change.confirmed_at.is_some(),
+            })),
+    )
+    .await;
+
+    tracing::warn!(
+        "用户 {} 撤销了修改邮箱为 {} 的请求，已重置密码并吊销全部会话",
+        change.old_email,
+        change.new_email
+    );
+
+    Ok(Redirect::to(&format!(
+        "{}/settings?emailChange=reverted",
+        app_state.env.frontend_url
+    )))
+}
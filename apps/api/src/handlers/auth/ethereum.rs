@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, response::IntoResponse};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{
+    oauth::{ProviderProfile, link_to_user},
+    tokens,
+};
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{IdentityExt, SiweExt, UserExt},
+    dtos::{Response, SiweNonceResponseDto, SiweVerifyDto},
+    error::HttpError,
+    middleware::JWTAuthMiddleware,
+    models::{AuditAction, AuthProvider, User},
+    utils::{password, siwe},
+};
+
+/// 随机数有效期（分钟），钱包需要在此时间内完成签名
+const NONCE_EXPIRES_MINUTES: i64 = 5;
+/// 钱包账户的占位邮箱域名，`.invalid` 是保留的顶级域名，不会投递到真实邮箱
+const PLACEHOLDER_EMAIL_DOMAIN: &str = "ethereum.invalid";
+
+/// 生成钱包登录用的随机数
+pub async fn login_nonce(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    create_nonce(&app_state, None).await
+}
+
+/// 生成关联钱包用的随机数，只能由当前用户使用
+pub async fn link_nonce(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    create_nonce(&app_state, Some(user.user.id)).await
+}
+
+/// 使用以太坊钱包登录 (EIP-4361)
+///
+/// 校验签名消息的域名、随机数、有效期和链 ID，并从签名中恢复钱包地址。
+/// 已关联该钱包的账户直接登录，否则创建新账户；新账户没有邮箱，使用占位邮箱。
+pub async fn login(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<SiweVerifyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let address = verify_message(&app_state, None, &body).await?;
+
+    let user = match app_state
+        .db_client
+        .get_user_by_provider(AuthProvider::Ethereum.to_str(), &address)
+        .await?
+    {
+        Some(user) => {
+            app_state
+                .db_client
+                .update_identity_usage(user.id, AuthProvider::Ethereum.to_str())
+                .await?;
+            user
+        }
+        None => create_user(&app_state, &address).await?,
+    };
+
+    tracing::info!("用户 {} 使用钱包 {} 登录", user.email, address);
+
+    tokens::login_response(&app_state, &user, AuthProvider::Ethereum.to_str()).await
+}
+
+/// 已登录用户关联以太坊钱包
+pub async fn link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<SiweVerifyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let address = verify_message(&app_state, Some(user.user.id), &body).await?;
+
+    let profile = ProviderProfile {
+        provider: AuthProvider::Ethereum,
+        provider_name: AuthProvider::Ethereum.to_str().to_string(),
+        subject: address.clone(),
+        email: user.user.email.clone(),
+        email_verified: false,
+        name: address,
+        picture: None,
+    };
+    link_to_user(&app_state, user.user.id, &profile).await?;
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Wallet linked".to_string(),
+    }))
+}
+
+async fn create_nonce(
+    app_state: &AppState,
+    user_id: Option<Uuid>,
+) -> Result<Json<SiweNonceResponseDto>, HttpError> {
+    let expires_at = Utc::now() + Duration::minutes(NONCE_EXPIRES_MINUTES);
+    let nonce = app_state
+        .db_client
+        .create_siwe_nonce(user_id, &siwe::generate_nonce(), expires_at)
+        .await?;
+
+    Ok(Json(SiweNonceResponseDto {
+        status: "success".to_string(),
+        nonce: nonce.nonce,
+        domain: app_state.env.siwe_domain.clone(),
+        chain_ids: app_state.env.siwe_chain_ids.clone(),
+        expires_at: nonce.expires_at,
+    }))
+}
+
+/// 校验签名消息，返回 EIP-55 格式的钱包地址
+///
+/// 随机数在校验签名前就被消耗，同一个随机数只能尝试一次。
+async fn verify_message(
+    app_state: &AppState,
+    user_id: Option<Uuid>,
+    body: &SiweVerifyDto,
+) -> Result<String, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let message = siwe::parse(&body.message).map_err(HttpError::bad_request)?;
+
+    message
+        .validate(
+            &app_state.env.siwe_domain,
+            &app_state.env.siwe_chain_ids,
+            Utc::now(),
+        )
+        .map_err(|e| {
+            tracing::warn!("钱包登录消息无效: {}", e);
+            HttpError::unauthorized(format!("钱包登录失败: {}", e))
+        })?;
+
+    app_state
+        .db_client
+        .take_siwe_nonce(&message.nonce)
+        .await?
+        .filter(|nonce| nonce.user_id == user_id)
+        .ok_or_else(|| HttpError::bad_request("登录请求无效或已过期，请重试".to_string()))?;
+
+    let signer = siwe::recover_address(&body.message, &body.signature).map_err(|e| {
+        tracing::warn!("钱包签名校验失败: {}", e);
+        HttpError::unauthorized(format!("钱包登录失败: {}", e))
+    })?;
+
+    // -- 智能合约钱包 (EIP-1271) 需要链上校验，这里只支持普通账户的签名
+    if signer != message.address {
+        return Err(HttpError::unauthorized(
+            "签名与消息中的钱包地址不匹配".to_string(),
+        ));
+    }
+
+    Ok(siwe::to_checksum_address(&message.address))
+}
+
+/// 为新钱包创建账户，密码随机生成，因此不会关联本地密码登录
+async fn create_user(app_state: &AppState, address: &str) -> Result<User, HttpError> {
+    let random_password = Uuid::new_v4().to_string();
+    let hashed_password =
+        password::hash(&random_password).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let name = format!("{}…{}", &address[..6], &address[address.len() - 4..]);
+    let email = format!(
+        "{}@{}",
+        address.to_ascii_lowercase(),
+        PLACEHOLDER_EMAIL_DOMAIN
+    );
+
+    let user = app_state
+        .db_client
+        .save_ethereum_user(&name, &email, &hashed_password, address)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::OAuthLinked)
+            .actor(&user)
+            .target("user", user.id)
+            .details(serde_json::json!({ "provider": AuthProvider::Ethereum.to_str() })),
+    )
+    .await;
+
+    Ok(user)
+}
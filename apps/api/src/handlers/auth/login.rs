@@ -1,22 +1,24 @@
 use std::sync::Arc;
 
-use axum::{
-    Extension, Json,
-    http::{HeaderMap, header},
-    response::IntoResponse,
-};
-use axum_extra::extract::cookie::Cookie;
-use chrono::Duration;
+use axum::{Extension, Json, response::IntoResponse};
+use chrono::{DateTime, Duration, Utc};
 use validator::Validate;
 
+use super::tokens;
+
 use crate::{
     AppState,
-    db::UserExt,
-    dtos::{LoginUserDto, UserLoginResponseDto},
+    audit::{self, AuditEvent},
+    db::{IdentityExt, RateLimitExt, UserExt},
+    dtos::LoginUserDto,
     error::{ErrorMessage, HttpError},
-    utils::{password, token},
+    models::{AuditAction, AuthProvider, User},
+    utils::password,
 };
 
+/// 距上次密码错误超过该时长后重新计数
+const FAILURE_RESET_HOURS: i64 = 24;
+
 /// 处理用户登录请求 -- 验证用户身份并生成访问令牌
 ///
 /// # 参数
@@ -31,6 +33,7 @@ use crate::{
 ///   - `ServerError` -- 服务器内部错误
 ///   - `NotFound` -- 用户未找到
 ///   - `Forbidden` -- 账户未验证
+///   - `TooManyRequests` -- 密码连续错误过多，账户被临时锁定
 pub async fn login(
     Extension(app_state): Extension<Arc<AppState>>,
     Json(body): Json<LoginUserDto>,
@@ -44,44 +47,127 @@ pub async fn login(
         .await
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
-    let user = result.ok_or(HttpError::bad_request(
-        ErrorMessage::WrongCredentials.to_string(),
-    ))?;
-
-    let password_matched = password::compare(&body.password, &user.password)
-        .map_err(|_| HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?;
-
-    if password_matched {
-        let token = token::create_token(
-            &user.id.to_string(),
-            app_state.env.jwt_secret.as_bytes(),
-            app_state.env.jwt_maxage,
+    let Some(user) = result else {
+        audit::record(
+            &app_state.db_client,
+            AuditEvent::new(AuditAction::LoginFailed)
+                .actor_email(&body.email)
+                .details(serde_json::json!({ "reason": "unknown_email" })),
         )
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
+        .await;
 
-        let cookie_duration = time::Duration::minutes(app_state.env.jwt_maxage * 60);
-        let cookie = Cookie::build(("token", token.clone()))
-            .path("/")
-            .max_age(cookie_duration)
-            .http_only(true)
-            .build();
-
-        let response = axum::response::Json(UserLoginResponseDto {
-            status: "success".to_string(),
-            token,
-        });
+        return Err(HttpError::bad_request(
+            ErrorMessage::WrongCredentials.to_string(),
+        ));
+    };
 
-        let mut headers = HeaderMap::new();
+    // -- 密码连续错误过多时临时锁定，锁定期间不再校验密码
+    if let Some(locked_until) = app_state
+        .db_client
+        .get_login_lockout(user.id)
+        .await?
+        .and_then(|lockout| lockout.locked_until)
+        .filter(|locked_until| *locked_until > Utc::now())
+    {
+        tracing::warn!("用户 {} 已被锁定，拒绝密码登录", user.email);
+        return Err(locked_error(locked_until));
+    }
 
-        headers.append(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    let password_matched = password::compare(&body.password, &user.password)
+        .map_err(|_| HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?;
 
-        let mut response = response.into_response();
-        response.headers_mut().extend(headers);
+    // -- 通过第三方注册或取消关联了本地密码的账户不能使用密码登录
+    let password_linked = password_matched
+        && app_state
+            .db_client
+            .has_identity(user.id, AuthProvider::Local)
+            .await?;
 
-        Ok(response)
+    if password_linked {
+        app_state
+            .db_client
+            .update_identity_usage(user.id, AuthProvider::Local.to_str())
+            .await?;
+        app_state.db_client.clear_login_failures(user.id).await?;
+        tokens::login_response(&app_state, &user, "password").await
     } else {
+        let reason = if password_matched {
+            "password_not_linked"
+        } else {
+            "wrong_password"
+        };
+        audit::record(
+            &app_state.db_client,
+            AuditEvent::new(AuditAction::LoginFailed)
+                .actor(&user)
+                .target("user", user.id)
+                .details(serde_json::json!({ "reason": reason })),
+        )
+        .await;
+
+        if !password_matched && let Some(locked_until) = record_failure(&app_state, &user).await? {
+            return Err(locked_error(locked_until));
+        }
+
         Err(HttpError::bad_request(
             ErrorMessage::WrongCredentials.to_string(),
         ))
     }
 }
+
+/// 记录一次密码错误，连续错误达到阈值后锁定账户
+///
+/// 锁定时长从 `lockout_base_seconds` 开始，每多错一次翻倍，不超过 `lockout_max_seconds`。
+async fn record_failure(
+    app_state: &AppState,
+    user: &User,
+) -> Result<Option<DateTime<Utc>>, HttpError> {
+    let config = &app_state.env.rate_limit;
+    let failures = app_state
+        .db_client
+        .record_login_failure(user.id, Utc::now() - Duration::hours(FAILURE_RESET_HOURS))
+        .await?;
+
+    if failures < config.lockout_threshold {
+        return Ok(None);
+    }
+
+    let exponent = (failures - config.lockout_threshold).min(20) as u32;
+    let seconds = config
+        .lockout_base_seconds
+        .saturating_mul(1 << exponent)
+        .min(config.lockout_max_seconds);
+    let locked_until = Utc::now() + Duration::seconds(seconds as i64);
+
+    app_state
+        .db_client
+        .lock_login(user.id, locked_until)
+        .await?;
+
+    tracing::warn!(
+        "用户 {} 连续 {} 次密码错误，锁定 {} 秒",
+        user.email,
+        failures,
+        seconds
+    );
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::AccountLocked)
+            .actor(user)
+            .target("user", user.id)
+            .details(serde_json::json!({
+                "failed_attempts": failures,
+                "locked_seconds": seconds,
+            })),
+    )
+    .await;
+
+    Ok(Some(locked_until))
+}
+
+fn locked_error(locked_until: DateTime<Utc>) -> HttpError {
+    let milliseconds = (locked_until - Utc::now()).num_milliseconds();
+    let seconds = ((milliseconds + 999) / 1000).max(1) as u64;
+    HttpError::too_many_requests("密码错误次数过多，账户已被临时锁定，请稍后再试", seconds)
+}
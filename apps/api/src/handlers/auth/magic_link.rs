@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Form, Json,
+    extract::Query,
+    http::{StatusCode, header},
+    response::{Html, IntoResponse},
+};
+use chrono::{Duration, Utc};
+use validator::Validate;
+
+use super::tokens;
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{MagicLinkExt, UserExt},
+    dtos::{PasswordlessLoginRequestDto, Response, VerifyEmailQueryDto},
+    error::HttpError,
+    mail::mails::{escape_html, send_magic_link_email},
+    models::{AuditAction, User},
+    utils::{password, token},
+};
+
+/// 每个邮箱每小时最多请求的登录链接数
+const MAX_LINKS_PER_EMAIL: i64 = 5;
+/// 每个 IP 每小时最多请求的登录链接数
+const MAX_LINKS_PER_IP: i64 = 20;
+
+/// 点击登录链接后的确认页面，提交后才消费链接
+const CONFIRM_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Sign in</title>
+</head>
+<body style="font-family: Arial, sans-serif; background-color: #f4f4f4; padding: 20px;">
+    <form method="post" action="verify" style="max-width: 600px; margin: 0 auto; background-color: #ffffff; padding: 20px; border-radius: 8px;">
+        <h2 style="color: #333333;">Sign in</h2>
+        <p style="color: #555555;">Click the button below to finish signing in.</p>
+        <input type="hidden" name="token" value="{{token}}">
+        <button type="submit" style="padding: 10px 20px; font-size: 16px; color: #ffffff; background-color: #007bff; border: none; border-radius: 5px; cursor: pointer;">Sign in</button>
+    </form>
+</body>
+</html>
+"#;
+
+/// 处理免密登录请求 -- 向邮箱发送一次性登录链接
+///
+/// 无论邮箱是否注册都返回相同的响应，避免泄露账户是否存在。
+/// 未注册的邮箱只有在开启 `MAGIC_LINK_SIGNUP_ENABLED` 时才会收到链接，
+/// 点击后自动创建账户。
+pub async fn request_magic_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<PasswordlessLoginRequestDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let email = body.email.trim().to_lowercase();
+    let ip_address = audit::current_context().ip_address;
+
+    // -- 步骤 1: 按邮箱和 IP 限制请求频率
+    let (by_email, by_ip) = app_state
+        .db_client
+        .count_recent_magic_links(
+            &email,
+            ip_address.as_deref(),
+            Utc::now() - Duration::hours(1),
+        )
+        .await?;
+
+    if by_email >= MAX_LINKS_PER_EMAIL || by_ip >= MAX_LINKS_PER_IP {
+        tracing::warn!(
+            "登录链接请求过于频繁，邮箱: {}, IP: {:?}",
+            email,
+            ip_address
+        );
+        return Err(HttpError::new(
+            "Too many sign-in link requests, please try again later",
+            StatusCode::TOO_MANY_REQUESTS,
+        ));
+    }
+
+    // -- 步骤 2: 查找用户，未注册且不允许自动注册时不发送邮件
+    let user = app_state.db_client.get_user_by_email(&email).await?;
+
+    if user.is_none() && !app_state.env.magic_link_signup_enabled {
+        tracing::info!("邮箱 {} 未注册，不发送登录链接", email);
+        return Ok(Json(magic_link_sent()));
+    }
+
+    // -- 步骤 3: 生成一次性登录链接，只保存哈希
+    let magic_token = token::generate_opaque_token();
+    let expires_in = app_state.env.magic_link_expires_minutes;
+    let recipient = user
+        .as_ref()
+        .map(|user| user.email.clone())
+        .unwrap_or(email);
+
+    app_state
+        .db_client
+        .create_magic_link(
+            &recipient,
+            &token::hash_opaque_token(&magic_token),
+            ip_address.as_deref(),
+            Utc::now() + Duration::minutes(expires_in),
+        )
+        .await?;
+
+    let username = user
+        .as_ref()
+        .map(|user| user.name.as_str())
+        .unwrap_or(&recipient);
+
+    send_magic_link_email(&recipient, username, &magic_token, expires_in)
+        .await
+        .map_err(|e| {
+            tracing::error!("发送登录链接失败: {}", e);
+            HttpError::server_error("发送邮件失败".to_string())
+        })?;
+
+    tracing::info!("已向 {} 发送登录链接", recipient);
+
+    Ok(Json(magic_link_sent()))
+}
+
+/// 处理登录链接点击 -- 返回确认页面，不消费链接
+///
+/// 邮件安全网关和链接预览会自动请求邮件中的链接，GET 请求直接登录会让链接被它们用掉。
+/// 用户在确认页面上提交后才真正登录。
+pub async fn confirm_magic_link(
+    Query(query_params): Query<VerifyEmailQueryDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let page = CONFIRM_PAGE.replace("{{token}}", &escape_html(&query_params.token));
+
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::REFERRER_POLICY, "no-referrer"),
+        ],
+        Html(page),
+    ))
+}
+
+/// 处理登录链接确认 -- 消费链接、签发令牌并重定向到前端
+///
+/// 链接只能使用一次，过期或已使用的链接返回错误。
+pub async fn verify_magic_link(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Form(body): Form<VerifyEmailQueryDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let email = app_state
+        .db_client
+        .consume_magic_link(&token::hash_opaque_token(&body.token))
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("无效或已过期的登录链接");
+            HttpError::bad_request("登录链接无效或已过期，请重新获取".to_string())
+        })?;
+
+    let user = match app_state.db_client.get_user_by_email(&email).await? {
+        Some(user) => user,
+        None if app_state.env.magic_link_signup_enabled => create_user(&app_state, &email).await?,
+        None => {
+            return Err(HttpError::bad_request(
+                "登录链接无效或已过期，请重新获取".to_string(),
+            ));
+        }
+    };
+
+    tracing::info!("用户 {} 通过登录链接登录", user.email);
+
+    tokens::login_redirect(&app_state, &user, "magic_link", false, None).await
+}
+
+/// 首次免密登录时创建账户，用户名取邮箱 `@` 之前的部分
+pub(super) async fn create_user(app_state: &AppState, email: &str) -> Result<User, HttpError> {
+    let name = email.split('@').next().unwrap_or(email);
+    let random_password = uuid::Uuid::new_v4().to_string();
+    let hashed_password =
+        password::hash(&random_password).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let user = app_state
+        .db_client
+        .save_passwordless_user(name, email, &hashed_password)
+        .await?;
+
+    tracing::info!("通过登录链接创建用户: {}", user.email);
+
+    Ok(user)
+}
+
+fn magic_link_sent() -> Response {
+    Response {
+        status: "success",
+        message: "If the email can be used to sign in, a sign-in link has been sent".to_string(),
+    }
+}
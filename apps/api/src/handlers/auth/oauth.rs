@@ -1,5 +1,403 @@
 mod github;
 mod google;
+mod oidc;
 
 pub use github::*;
 pub use google::*;
+pub use oidc::*;
+
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
+};
+use chrono::{Duration, Utc};
+use oauth2::{CsrfToken, PkceCodeChallenge, PkceCodeVerifier};
+use uuid::Uuid;
+
+use super::tokens::login_redirect;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{IdentityExt, UserExt},
+    error::HttpError,
+    models::{AuditAction, AuthProvider, User},
+    utils::{
+        password,
+        token::{self, OAuthStateClaims},
+    },
+};
+
+/// OAuth 状态 cookie 的名称，只在 `/api/auth` 下发送
+const OAUTH_STATE_COOKIE: &str = "oauth_state";
+const OAUTH_STATE_COOKIE_PATH: &str = "/api/auth";
+/// 授权流程的有效期（分钟），超时后需要重新发起登录
+const OAUTH_STATE_EXPIRES_MINUTES: i64 = 10;
+/// 登录后返回路径的最大长度
+const MAX_RETURN_TO_LENGTH: usize = 512;
+
+/// 发起授权时生成的参数
+pub(super) struct AuthorizationRequest {
+    pub state: CsrfToken,
+    pub pkce_challenge: PkceCodeChallenge,
+    /// 只有 OIDC 提供方使用，防止 ID 令牌被重放
+    pub nonce: String,
+}
+
+/// 回调校验通过后得到的授权流程状态
+pub(super) struct AuthorizationState {
+    pub pkce_verifier: PkceCodeVerifier,
+    pub nonce: String,
+    pub return_to: Option<String>,
+    /// 关联流程中发起关联的用户
+    pub link_user_id: Option<Uuid>,
+}
+
+/// 从提供方获取到的用户资料
+pub(super) struct ProviderProfile {
+    pub provider: AuthProvider,
+    /// 提供方名称，内置提供方与 `provider` 相同，OIDC 提供方为配置的名称
+    pub provider_name: String,
+    /// 用户在提供方的唯一 ID
+    pub subject: String,
+    pub email: String,
+    /// 提供方是否确认该邮箱已验证，只有已验证的邮箱才会自动合并到同邮箱的账户
+    pub email_verified: bool,
+    pub name: String,
+    pub picture: Option<String>,
+}
+
+/// 生成 `state` 和 PKCE 校验码，签名后写入短期 cookie
+///
+/// `return_to` 必须是前端站内的相对路径，防止开放重定向。
+/// `link_user_id` 不为空时是已登录用户关联新的登录方式，回调时不会签发新的令牌。
+pub(super) fn begin_authorization(
+    app_state: &AppState,
+    jar: CookieJar,
+    provider: &str,
+    return_to: Option<String>,
+    link_user_id: Option<Uuid>,
+) -> Result<(CookieJar, AuthorizationRequest), HttpError> {
+    let return_to = match return_to.filter(|path| !path.is_empty()) {
+        Some(path) if is_safe_return_to(&path) => Some(path),
+        Some(_) => return Err(HttpError::bad_request("无效的返回地址".to_string())),
+        None => None,
+    };
+
+    let state = CsrfToken::new_random();
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let nonce = CsrfToken::new_random().secret().clone();
+    let now = Utc::now();
+
+    let claims = OAuthStateClaims {
+        provider: provider.to_string(),
+        state: state.secret().clone(),
+        pkce_verifier: pkce_verifier.secret().clone(),
+        nonce: nonce.clone(),
+        return_to,
+        link_user_id: link_user_id.map(|id| id.to_string()),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(OAUTH_STATE_EXPIRES_MINUTES)).timestamp() as usize,
+    };
+    let value = token::create_oauth_state_token(claims, app_state.env.jwt_secret.as_bytes())
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    // -- 提供方回调是跨站的顶级导航，SameSite=Lax 的 cookie 仍会发送
+    let cookie = Cookie::build((OAUTH_STATE_COOKIE, value))
+        .path(OAUTH_STATE_COOKIE_PATH)
+        .max_age(time::Duration::minutes(OAUTH_STATE_EXPIRES_MINUTES))
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .build();
+
+    Ok((
+        jar.add(cookie),
+        AuthorizationRequest {
+            state,
+            pkce_challenge,
+            nonce,
+        },
+    ))
+}
+
+/// 校验回调中的 `state` 与 cookie 中保存的是否一致，并取出 PKCE 校验码
+///
+/// 校验通过后清除状态 cookie，同一个授权流程只能完成一次。
+pub(super) fn finish_authorization(
+    app_state: &AppState,
+    jar: CookieJar,
+    provider: &str,
+    state: Option<&str>,
+) -> Result<(CookieJar, AuthorizationState), HttpError> {
+    let claims = jar
+        .get(OAUTH_STATE_COOKIE)
+        .and_then(|cookie| {
+            token::decode_oauth_state_token(cookie.value(), app_state.env.jwt_secret.as_bytes())
+        })
+        .ok_or_else(|| {
+            tracing::warn!("{} OAuth 回调缺少有效的状态 cookie", provider);
+            HttpError::bad_request("登录请求已过期，请重新登录".to_string())
+        })?;
+
+    let state_matched = claims.provider == provider
+        && state.is_some_and(|state| constant_time_eq(&claims.state, state));
+    if !state_matched {
+        tracing::warn!("{} OAuth 回调的 state 不匹配", provider);
+        return Err(HttpError::bad_request(
+            "登录请求校验失败，请重新登录".to_string(),
+        ));
+    }
+
+    let jar = jar.remove(Cookie::build(OAUTH_STATE_COOKIE).path(OAUTH_STATE_COOKIE_PATH));
+
+    Ok((
+        jar,
+        AuthorizationState {
+            pkce_verifier: PkceCodeVerifier::new(claims.pkce_verifier),
+            nonce: claims.nonce,
+            return_to: claims.return_to,
+            link_user_id: claims.link_user_id.and_then(|id| Uuid::parse_str(&id).ok()),
+        },
+    ))
+}
+
+/// 完成授权流程：关联流程中把身份关联到当前用户，否则登录对应的账户
+///
+/// 登录时按以下顺序查找账户：
+/// 1. 已关联该身份的账户
+/// 2. 提供方确认邮箱已验证、且本地邮箱也已验证的同邮箱账户，自动关联
+/// 3. 都没有时创建新账户
+pub(super) async fn complete_authorization(
+    app_state: &AppState,
+    jar: CookieJar,
+    return_to: Option<&str>,
+    link_user_id: Option<Uuid>,
+    profile: ProviderProfile,
+) -> Result<(CookieJar, Response), HttpError> {
+    if let Some(user_id) = link_user_id {
+        link_to_user(app_state, user_id, &profile).await?;
+
+        let mut redirect_url =
+            format!("{}{}", app_state.env.frontend_url, return_to.unwrap_or("/"));
+        let separator = if redirect_url.contains('?') { '&' } else { '?' };
+        redirect_url = format!(
+            "{}{}linked={}",
+            redirect_url, separator, profile.provider_name
+        );
+        return Ok((jar, Redirect::to(&redirect_url).into_response()));
+    }
+
+    let user = find_or_create_user(app_state, &profile).await?;
+
+    // 签发访问令牌和刷新令牌，重定向到前端并带上访问令牌参数
+    let response =
+        login_redirect(app_state, &user, &profile.provider_name, true, return_to).await?;
+
+    Ok((jar, response))
+}
+
+/// 把身份关联到已登录的用户，已经关联到其他账户的身份不能再关联
+pub(super) async fn link_to_user(
+    app_state: &AppState,
+    user_id: Uuid,
+    profile: &ProviderProfile,
+) -> Result<(), HttpError> {
+    let user = app_state
+        .db_client
+        .get_user(Some(user_id), None, None, None)
+        .await?
+        .ok_or_else(|| HttpError::unauthorized("用户不存在".to_string()))?;
+
+    if let Some(owner) = app_state
+        .db_client
+        .get_user_by_provider(&profile.provider_name, &profile.subject)
+        .await?
+    {
+        if owner.id == user.id {
+            return Ok(());
+        }
+        return Err(HttpError::bad_request(format!(
+            "该 {} 账户已关联到其他用户",
+            profile.provider_name
+        )));
+    }
+
+    let identity = app_state
+        .db_client
+        .link_identity(
+            user.id,
+            profile.provider,
+            &profile.provider_name,
+            &profile.subject,
+            &profile.email,
+        )
+        .await?;
+
+    tracing::info!("用户 {} 关联了 {} 账户", user.email, profile.provider_name);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::OAuthLinked)
+            .actor(&user)
+            .target("user", user.id)
+            .details(serde_json::json!({
+                "provider": profile.provider_name,
+                "identity_id": identity.id,
+                "via": "account_settings",
+            })),
+    )
+    .await;
+
+    Ok(())
+}
+
+async fn find_or_create_user(
+    app_state: &AppState,
+    profile: &ProviderProfile,
+) -> Result<User, HttpError> {
+    if let Some(user) = app_state
+        .db_client
+        .get_user_by_provider(&profile.provider_name, &profile.subject)
+        .await?
+    {
+        app_state
+            .db_client
+            .update_identity_usage(user.id, &profile.provider_name)
+            .await?;
+
+        // -- 只有通过该提供方注册的账户才同步提供方的资料
+        if user.auth_provider != profile.provider {
+            return Ok(user);
+        }
+        let user = match profile.provider {
+            AuthProvider::Github => {
+                app_state
+                    .db_client
+                    .update_github_user(&user.id, profile.name.clone(), profile.picture.clone())
+                    .await?
+            }
+            _ => {
+                app_state
+                    .db_client
+                    .update_google_user(&user.id, profile.name.clone(), profile.picture.clone())
+                    .await?
+            }
+        };
+        return Ok(user);
+    }
+
+    if let Some(user) = app_state
+        .db_client
+        .get_user(None, None, Some(&profile.email), None)
+        .await?
+    {
+        // -- 任何一方未验证邮箱时都不能自动合并，否则可以用别人的邮箱抢占账户
+        if !profile.email_verified || !user.verified {
+            return Err(HttpError::bad_request(format!(
+                "邮箱 {} 已被其他账户使用，请登录该账户后在账户设置中关联 {}",
+                profile.email, profile.provider_name
+            )));
+        }
+
+        let identity = app_state
+            .db_client
+            .link_identity(
+                user.id,
+                profile.provider,
+                &profile.provider_name,
+                &profile.subject,
+                &profile.email,
+            )
+            .await?;
+
+        audit::record(
+            &app_state.db_client,
+            AuditEvent::new(AuditAction::OAuthLinked)
+                .actor(&user)
+                .target("user", user.id)
+                .details(serde_json::json!({
+                    "provider": profile.provider_name,
+                    "identity_id": identity.id,
+                    "via": "verified_email",
+                })),
+        )
+        .await;
+
+        return Ok(user);
+    }
+
+    // 创建新用户，密码随机生成，用户不知道密码，因此不会关联本地密码登录
+    let random_password = uuid::Uuid::new_v4().to_string();
+    let hashed_password =
+        password::hash(&random_password).map_err(|e| HttpError::server_error(e.to_string()))?;
+
+    let user = match profile.provider {
+        AuthProvider::Github => {
+            app_state
+                .db_client
+                .save_github_user(
+                    &profile.name,
+                    &profile.email,
+                    &hashed_password,
+                    &profile.subject,
+                    profile.picture.clone(),
+                )
+                .await?
+        }
+        AuthProvider::Oidc => {
+            app_state
+                .db_client
+                .save_oidc_user(
+                    &profile.name,
+                    &profile.email,
+                    profile.email_verified,
+                    &hashed_password,
+                    &profile.provider_name,
+                    &profile.subject,
+                    profile.picture.clone(),
+                )
+                .await?
+        }
+        _ => {
+            app_state
+                .db_client
+                .save_google_user(
+                    profile.name.as_str(),
+                    profile.email.as_str(),
+                    hashed_password.as_str(),
+                    profile.subject.as_str(),
+                    profile.picture.clone(),
+                )
+                .await?
+        }
+    };
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::OAuthLinked)
+            .actor(&user)
+            .target("user", user.id)
+            .details(serde_json::json!({ "provider": profile.provider_name })),
+    )
+    .await;
+
+    Ok(user)
+}
+
+/// 只允许以单个 `/` 开头的站内路径
+fn is_safe_return_to(path: &str) -> bool {
+    path.len() <= MAX_RETURN_TO_LENGTH
+        && path.starts_with('/')
+        && !path.starts_with("//")
+        && !path.contains('\\')
+        && !path.chars().any(char::is_control)
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
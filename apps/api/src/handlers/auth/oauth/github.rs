@@ -4,23 +4,27 @@ use axum::{
     http::header,
     response::{IntoResponse, Redirect},
 };
-use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::{CookieJar, cookie::Cookie};
 use chrono::{Duration, Utc};
 use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
-    TokenResponse, TokenUrl, basic::BasicClient,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, RedirectUrl, Scope, TokenResponse,
+    TokenUrl, basic::BasicClient,
 };
 use reqwest::Client as HttpClient;
 use serde::Deserialize;
 use std::sync::Arc;
 
+use uuid::Uuid;
+
 use crate::{
     AppState,
-    db::UserExt,
-    dtos::{GithubCallbackDto, GithubUserInfo},
+    dtos::{GithubCallbackDto, GithubUserInfo, OAuthLoginQueryDto},
     error::HttpError,
+    handlers::auth::oauth::{
+        ProviderProfile, begin_authorization, complete_authorization, finish_authorization,
+    },
+    middleware::JWTAuthMiddleware,
     models::AuthProvider,
-    utils::{password, token},
 };
 
 /// 处理 GitHub OAuth 登录请求 -- 重定向到 GitHub 登录页面
@@ -29,12 +33,36 @@ use crate::{
 ///
 /// # 参数
 /// - `app_state` -- 应用程序状态，包含 GitHub OAuth 配置等共享资源
+/// - `query` -- 可选的 `returnTo`，登录完成后返回的前端路径
 ///
 /// # 返回
 /// - 重定向响应，将用户重定向到 GitHub 登录页面
 pub async fn github_oauth_login(
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Query(query): Query<OAuthLoginQueryDto>,
+) -> impl IntoResponse {
+    authorize(&app_state, cookie_jar, query.return_to, None)
+}
+
+/// 已登录用户关联 GitHub 账户 -- 重定向到 GitHub 登录页面
+///
+/// 回调时把 GitHub 账户关联到当前用户，然后返回 `returnTo`
+pub async fn github_oauth_link(
+    cookie_jar: CookieJar,
     Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Query(query): Query<OAuthLoginQueryDto>,
 ) -> impl IntoResponse {
+    authorize(&app_state, cookie_jar, query.return_to, Some(user.user.id))
+}
+
+fn authorize(
+    app_state: &AppState,
+    cookie_jar: CookieJar,
+    return_to: Option<String>,
+    link_user_id: Option<Uuid>,
+) -> Result<(CookieJar, Redirect), HttpError> {
     // 检查是否配置了 GitHub OAuth
     if app_state.env.github_client_id.is_empty() || app_state.env.github_client_secret.is_empty() {
         return Err(HttpError::server_error("GitHub OAuth 未配置".to_string()));
@@ -53,31 +81,37 @@ pub async fn github_oauth_login(
         .set_auth_uri(auth_url)
         .set_token_uri(token_url)
         .set_redirect_uri(
-            RedirectUrl::new(app_state.env.google_redirect_url.clone()).expect("无效的重定向 URL"),
+            RedirectUrl::new(app_state.env.github_redirect_url.clone()).expect("无效的重定向 URL"),
         );
 
+    // 生成 state 和 PKCE 校验码，保存在签名的短期 cookie 中，回调时校验
+    let (cookie_jar, request) =
+        begin_authorization(app_state, cookie_jar, "github", return_to, link_user_id)?;
+
     // 设置授权范围
-    let (auth_url, _csrf_token) = client
-        .authorize_url(CsrfToken::new_random)
+    let (auth_url, _) = client
+        .authorize_url(|| request.state)
+        .set_pkce_challenge(request.pkce_challenge)
         .add_scope(Scope::new("user:email".to_string())) // 获取用户邮箱
         .add_scope(Scope::new("read:user".to_string())) // 获取用户信息
         .url();
 
     // 重定向到 GitHub 授权页面
-    Ok(Redirect::to(auth_url.as_ref()))
+    Ok((cookie_jar, Redirect::to(auth_url.as_ref())))
 }
 
 /// 处理 GitHub OAuth 回调请求
 ///
-/// 验证 GitHub 返回的授权码，获取用户信息，并创建或更新用户
+/// 验证 GitHub 返回的授权码，获取用户信息，登录、创建用户或关联到当前用户
 ///
 /// # 参数
 /// - `app_state` -- 应用程序状态，包含数据库连接和 GitHub OAuth 配置
-/// - `query` -- URL 查询参数，包含授权码
+/// - `query` -- URL 查询参数，包含授权码和 state
 ///
 /// # 返回
 /// - 重定向响应，将用户重定向到前端应用
 pub async fn github_oauth_callback(
+    cookie_jar: CookieJar,
     Extension(app_state): Extension<Arc<AppState>>,
     Query(query): Query<GithubCallbackDto>,
 ) -> impl IntoResponse {
@@ -90,6 +124,10 @@ pub async fn github_oauth_callback(
         )));
     }
 
+    // 校验 state，防止 CSRF 和授权码注入
+    let (cookie_jar, authorization) =
+        finish_authorization(&app_state, cookie_jar, "github", query.state.as_deref())?;
+
     let auth_url = AuthUrl::new("https://github.com/login/oauth/authorize".to_string())
         .expect("Invalid authorization endpoint URL");
     let token_url = TokenUrl::new("https://github.com/login/oauth/access_token".to_string())
@@ -109,12 +147,13 @@ pub async fn github_oauth_callback(
         .set_auth_uri(auth_url)
         .set_token_uri(token_url)
         .set_redirect_uri(
-            RedirectUrl::new(app_state.env.google_redirect_url.clone()).expect("无效的重定向 URL"),
+            RedirectUrl::new(app_state.env.github_redirect_url.clone()).expect("无效的重定向 URL"),
         );
 
     // 交换授权码获取访问令牌
     let token_result = client
         .exchange_code(AuthorizationCode::new(query.code.clone()))
+        .set_pkce_verifier(authorization.pkce_verifier)
         .request_async(&http_client)
         .await
         .map_err(|e| {
@@ -130,93 +169,37 @@ pub async fn github_oauth_callback(
         HttpError::server_error("无法获取用户信息".to_string())
     })?;
 
-    // 获取用户邮箱（如果不在基本信息中）
-    let email = if let Some(email) = user_info.email.clone() {
-        email
-    } else {
-        get_github_user_emails(access_token).await.map_err(|e| {
-            tracing::error!("获取 GitHub 用户邮箱失败: {}", e);
-            HttpError::server_error("无法获取用户邮箱".to_string())
-        })?
-    };
-
-    // 检查用户是否已存在 (通过 provider_user_id 或 email)
-    let existing_user = app_state
-        .db_client
-        .get_user_by_provider(AuthProvider::Github, &user_info.id.to_string())
-        .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-    let user = if let Some(user) = existing_user {
-        // 用户已存在，更新信息
-        let name = user_info.name.unwrap_or(user_info.login.clone());
-        app_state
-            .db_client
-            .update_github_user(&user.id, name, user_info.avatar_url.clone())
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?
-    } else {
-        // 检查邮箱是否已注册但不是通过 GitHub
-        let email_user = app_state
-            .db_client
-            .get_user(None, None, Some(&email), None)
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-        if let Some(user) = email_user {
-            // 邮箱已经被其他非 GitHub 账户使用
-            if user.auth_provider != AuthProvider::Github {
-                return Err(HttpError::bad_request(format!(
-                    "邮箱 {} 已被其他账户使用",
-                    email
-                )));
-            }
+    // 公开邮箱不一定经过验证，优先使用邮箱 API 返回的已验证邮箱
+    let (email, email_verified) = match get_github_user_emails(access_token).await {
+        Ok(email) => (email, true),
+        Err(e) => {
+            tracing::warn!("获取 GitHub 已验证邮箱失败: {}", e);
+            let email = user_info
+                .email
+                .clone()
+                .ok_or_else(|| HttpError::server_error("无法获取用户邮箱".to_string()))?;
+            (email, false)
         }
+    };
 
-        // 创建新用户
-        let name = user_info.name.unwrap_or(user_info.login.clone());
-        let random_password = uuid::Uuid::new_v4().to_string();
-        let hashed_password =
-            password::hash(&random_password).map_err(|e| HttpError::server_error(e.to_string()))?;
-
-        app_state
-            .db_client
-            .save_github_user(
-                &name,
-                &email,
-                &hashed_password,
-                &user_info.id.to_string(),
-                user_info.avatar_url.clone(),
-            )
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?
+    let profile = ProviderProfile {
+        provider: AuthProvider::Github,
+        provider_name: AuthProvider::Github.to_str().to_string(),
+        subject: user_info.id.to_string(),
+        email,
+        email_verified,
+        name: user_info.name.unwrap_or(user_info.login),
+        picture: user_info.avatar_url,
     };
 
-    // 创建 JWT token
-    let token = token::create_token(
-        &user.id.to_string(),
-        app_state.env.jwt_secret.as_bytes(),
-        app_state.env.jwt_maxage,
+    complete_authorization(
+        &app_state,
+        cookie_jar,
+        authorization.return_to.as_deref(),
+        authorization.link_user_id,
+        profile,
     )
-    .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-    // 设置 cookie
-    let cookie_duration = time::Duration::minutes(app_state.env.jwt_maxage * 60);
-    let cookie = Cookie::build(("token", token.clone()))
-        .path("/")
-        .max_age(cookie_duration)
-        .http_only(true)
-        .build();
-
-    // 重定向到前端，并带上 token 参数
-    let redirect_url = format!("{}?token={}", app_state.env.frontend_url, token);
-
-    let mut response = Redirect::to(&redirect_url).into_response();
-    response
-        .headers_mut()
-        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-
-    Ok(response)
+    .await
 }
 
 /// 从 GitHub API 获取用户信息
@@ -255,7 +238,7 @@ async fn get_github_user_info(access_token: &str) -> Result<GithubUserInfo, Stri
 
 /// 从 GitHub API 获取用户邮箱
 ///
-/// 当用户未设置公开邮箱时，需要单独请求邮箱信息
+/// 只返回 GitHub 确认已验证的邮箱
 ///
 /// # 参数
 /// - `access_token` - GitHub 提供的访问令牌
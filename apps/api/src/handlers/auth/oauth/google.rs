@@ -4,23 +4,27 @@ use axum::{
     http::header,
     response::{IntoResponse, Redirect},
 };
-use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::{CookieJar, cookie::Cookie};
 use chrono::Duration;
 use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope,
-    TokenResponse, TokenUrl, basic::BasicClient,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, RedirectUrl, Scope, TokenResponse,
+    TokenUrl, basic::BasicClient,
 };
 use reqwest::Client as HttpClient;
 use std::sync::Arc;
 use validator::Validate;
 
+use uuid::Uuid;
+
 use crate::{
     AppState,
-    db::UserExt,
-    dtos::{GoogleCallbackDto, GoogleUserInfo},
+    dtos::{GoogleCallbackDto, GoogleUserInfo, OAuthLoginQueryDto},
     error::HttpError,
+    handlers::auth::oauth::{
+        ProviderProfile, begin_authorization, complete_authorization, finish_authorization,
+    },
+    middleware::JWTAuthMiddleware,
     models::AuthProvider,
-    utils::{password, token},
 };
 
 /// 处理 Google OAuth 登录请求 -- 重定向到 Google 登录页面
@@ -29,12 +33,36 @@ use crate::{
 ///
 /// # 参数
 /// - `app_state` -- 应用程序状态，包含 Google OAuth 配置等共享资源
+/// - `query` -- 可选的 `returnTo`，登录完成后返回的前端路径
 ///
 /// # 返回
 /// - 重定向响应，将用户重定向到 Google 登录页面
 pub async fn google_oauth_login(
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Query(query): Query<OAuthLoginQueryDto>,
+) -> impl IntoResponse {
+    authorize(&app_state, cookie_jar, query.return_to, None)
+}
+
+/// 已登录用户关联 Google 账户 -- 重定向到 Google 登录页面
+///
+/// 回调时把 Google 账户关联到当前用户，然后返回 `returnTo`
+pub async fn google_oauth_link(
+    cookie_jar: CookieJar,
     Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Query(query): Query<OAuthLoginQueryDto>,
 ) -> impl IntoResponse {
+    authorize(&app_state, cookie_jar, query.return_to, Some(user.user.id))
+}
+
+fn authorize(
+    app_state: &AppState,
+    cookie_jar: CookieJar,
+    return_to: Option<String>,
+    link_user_id: Option<Uuid>,
+) -> Result<(CookieJar, Redirect), HttpError> {
     // 检查是否配置了 Google OAuth
     if app_state.env.google_client_id.is_empty() || app_state.env.google_client_secret.is_empty() {
         return Err(HttpError::server_error("Google OAuth 未配置".to_string()));
@@ -56,28 +84,34 @@ pub async fn google_oauth_login(
             RedirectUrl::new(app_state.env.google_redirect_url.clone()).expect("无效的重定向 URL"),
         );
 
+    // 生成 state 和 PKCE 校验码，保存在签名的短期 cookie 中，回调时校验
+    let (cookie_jar, request) =
+        begin_authorization(app_state, cookie_jar, "google", return_to, link_user_id)?;
+
     // 设置授权范围
-    let (authorize_url, csrf_state) = client
-        .authorize_url(CsrfToken::new_random)
+    let (authorize_url, _) = client
+        .authorize_url(|| request.state)
+        .set_pkce_challenge(request.pkce_challenge)
         .add_scope(Scope::new("profile".to_string()))
         .add_scope(Scope::new("email".to_string()))
         .url();
 
     // 重定向到 Google 授权页面
-    Ok(Redirect::to(authorize_url.as_ref()))
+    Ok((cookie_jar, Redirect::to(authorize_url.as_ref())))
 }
 
 /// 处理 Google OAuth 回调请求
 ///
-/// 验证 Google 返回的授权码，获取用户信息，并创建或更新用户
+/// 验证 Google 返回的授权码，获取用户信息，登录、创建用户或关联到当前用户
 ///
 /// # 参数
 /// - `app_state` -- 应用程序状态，包含数据库连接和 Google OAuth 配置
-/// - `query` -- URL 查询参数，包含授权码
+/// - `query` -- URL 查询参数，包含授权码和 state
 ///
 /// # 返回
 /// - 重定向响应，将用户重定向到前端应用
 pub async fn google_oauth_callback(
+    cookie_jar: CookieJar,
     Extension(app_state): Extension<Arc<AppState>>,
     Query(query): Query<GoogleCallbackDto>,
 ) -> impl IntoResponse {
@@ -90,6 +124,10 @@ pub async fn google_oauth_callback(
         )));
     }
 
+    // 校验 state，防止 CSRF 和授权码注入
+    let (cookie_jar, authorization) =
+        finish_authorization(&app_state, cookie_jar, "google", query.state.as_deref())?;
+
     let auth_url = AuthUrl::new("https://accounts.google.com/o/oauth2/v2/auth".to_string())
         .expect("Invalid authorization endpoint URL");
     let token_url = TokenUrl::new("https://www.googleapis.com/oauth2/v3/token".to_string())
@@ -115,6 +153,7 @@ pub async fn google_oauth_callback(
     // 交换授权码获取访问令牌
     let token_result = client
         .exchange_code(AuthorizationCode::new(query.code.clone()))
+        .set_pkce_verifier(authorization.pkce_verifier)
         .request_async(&http_client)
         .await
         .map_err(|e| {
@@ -130,81 +169,24 @@ pub async fn google_oauth_callback(
         HttpError::server_error("无法获取用户信息".to_string())
     })?;
 
-    // 检查用户是否已存在 (通过 provider_user_id 或 email)
-    let existing_user = app_state
-        .db_client
-        .get_user_by_provider(AuthProvider::Google, &user_info.id)
-        .await
-        .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-    let user = if let Some(user) = existing_user {
-        // 用户已存在，更新信息
-        app_state
-            .db_client
-            .update_google_user(&user.id, user_info.name.clone(), user_info.picture.clone())
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?
-    } else {
-        // 检查邮箱是否已注册但不是通过 Google
-        let email_user = app_state
-            .db_client
-            .get_user(None, None, Some(&user_info.email), None)
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-        if let Some(user) = email_user {
-            // 邮箱已经被其他非 Google 账户使用
-            if user.auth_provider != AuthProvider::Google {
-                return Err(HttpError::bad_request(format!(
-                    "邮箱 {} 已被其他账户使用",
-                    user_info.email
-                )));
-            }
-        }
-
-        // 创建新用户
-        let random_password = uuid::Uuid::new_v4().to_string();
-        let hashed_password =
-            password::hash(&random_password).map_err(|e| HttpError::server_error(e.to_string()))?;
-
-        app_state
-            .db_client
-            .save_google_user(
-                &user_info.name,
-                &user_info.email,
-                &hashed_password,
-                &user_info.id,
-                user_info.picture.clone(),
-            )
-            .await
-            .map_err(|e| HttpError::server_error(e.to_string()))?
+    let profile = ProviderProfile {
+        provider: AuthProvider::Google,
+        provider_name: AuthProvider::Google.to_str().to_string(),
+        subject: user_info.id,
+        email: user_info.email,
+        email_verified: user_info.verified_email,
+        name: user_info.name,
+        picture: user_info.picture,
     };
 
-    // 创建 JWT token
-    let token = token::create_token(
-        &user.id.to_string(),
-        app_state.env.jwt_secret.as_bytes(),
-        app_state.env.jwt_maxage,
+    complete_authorization(
+        &app_state,
+        cookie_jar,
+        authorization.return_to.as_deref(),
+        authorization.link_user_id,
+        profile,
     )
-    .map_err(|e| HttpError::server_error(e.to_string()))?;
-
-    // 设置 cookie
-    let cookie_duration = time::Duration::minutes(app_state.env.jwt_maxage * 60);
-    let cookie = Cookie::build(("token", token.clone()))
-        .path("/")
-        .max_age(cookie_duration)
-        .http_only(true)
-        .build();
-
-    // 重定向到前端，并带上 token 参数
-    let redirect_url = format!("{}?token={}", app_state.env.frontend_url, token);
-
-    let mut response = Redirect::to(&redirect_url).into_response();
-    response
-        .headers_mut()
-        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
-
-    Ok(response)
+    .await
 }
 
 /// 从 Google API 获取用户信息
@@ -0,0 +1,238 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    response::{IntoResponse, Redirect},
+};
+use axum_extra::extract::CookieJar;
+use oauth2::{AuthUrl, ClientId, RedirectUrl, Scope, basic::BasicClient};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    config::OidcProviderConfig,
+    dtos::{OAuthLoginQueryDto, OidcCallbackDto, OidcProviderDto, OidcProviderListResponseDto},
+    error::HttpError,
+    handlers::auth::oauth::{
+        ProviderProfile, begin_authorization, complete_authorization, finish_authorization,
+    },
+    middleware::JWTAuthMiddleware,
+    models::AuthProvider,
+    oidc,
+};
+
+/// 获取已配置的 OIDC 登录提供方，前端据此显示登录按钮
+pub async fn oidc_providers(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    let providers = app_state
+        .env
+        .oidc_providers
+        .iter()
+        .map(|provider| OidcProviderDto {
+            name: provider.name.clone(),
+            display_name: provider.display_name.clone(),
+        })
+        .collect();
+
+    Json(OidcProviderListResponseDto {
+        status: "success".to_string(),
+        providers,
+    })
+}
+
+/// 处理 OIDC 登录请求 -- 重定向到提供方的授权页面
+///
+/// # 参数
+/// - `provider` -- 配置的提供方名称
+/// - `query` -- 可选的 `returnTo`，登录完成后返回的前端路径
+pub async fn oidc_login(
+    Path(provider): Path<String>,
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Query(query): Query<OAuthLoginQueryDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    authorize(&app_state, &provider, cookie_jar, query.return_to, None).await
+}
+
+/// 已登录用户关联 OIDC 账户 -- 重定向到提供方的授权页面
+pub async fn oidc_link(
+    Path(provider): Path<String>,
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Query(query): Query<OAuthLoginQueryDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    authorize(
+        &app_state,
+        &provider,
+        cookie_jar,
+        query.return_to,
+        Some(user.user.id),
+    )
+    .await
+}
+
+async fn authorize(
+    app_state: &AppState,
+    provider: &str,
+    cookie_jar: CookieJar,
+    return_to: Option<String>,
+    link_user_id: Option<Uuid>,
+) -> Result<(CookieJar, Redirect), HttpError> {
+    let provider = find_provider(app_state, provider)?;
+    let metadata = app_state
+        .oidc_client
+        .metadata(provider)
+        .await
+        .map_err(|e| provider_error(provider, e))?;
+
+    let auth_url = AuthUrl::new(metadata.authorization_endpoint)
+        .map_err(|_| HttpError::server_error("OIDC 授权端点无效".to_string()))?;
+    let redirect_url = RedirectUrl::new(provider.redirect_url.clone())
+        .map_err(|_| HttpError::server_error("OIDC 回调地址无效".to_string()))?;
+
+    let client = BasicClient::new(ClientId::new(provider.client_id.clone()))
+        .set_auth_uri(auth_url)
+        .set_redirect_uri(redirect_url);
+
+    // 生成 state、nonce 和 PKCE 校验码，保存在签名的短期 cookie 中，回调时校验
+    let (cookie_jar, request) = begin_authorization(
+        app_state,
+        cookie_jar,
+        &state_provider(provider),
+        return_to,
+        link_user_id,
+    )?;
+
+    let (authorize_url, _) = client
+        .authorize_url(|| request.state)
+        .set_pkce_challenge(request.pkce_challenge)
+        .add_scopes(provider.scopes.iter().cloned().map(Scope::new))
+        .add_extra_param("nonce", request.nonce)
+        .url();
+
+    Ok((cookie_jar, Redirect::to(authorize_url.as_ref())))
+}
+
+/// 处理 OIDC 回调请求
+///
+/// 用授权码换取令牌，校验 ID 令牌后按配置映射用户资料，登录、创建用户或关联到当前用户。
+/// ID 令牌中没有邮箱时从 userinfo 端点补充。
+pub async fn oidc_callback(
+    Path(provider): Path<String>,
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Query(query): Query<OidcCallbackDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let provider = find_provider(&app_state, &provider)?;
+
+    if let Some(error) = &query.error {
+        tracing::error!(
+            "OIDC 提供方 {} 返回错误: {} {}",
+            provider.name,
+            error,
+            query.error_description.as_deref().unwrap_or("")
+        );
+        return Err(HttpError::bad_request(format!(
+            "{} 登录失败: {}",
+            provider.display_name, error
+        )));
+    }
+
+    // 校验 state，防止 CSRF 和授权码注入
+    let (cookie_jar, authorization) = finish_authorization(
+        &app_state,
+        cookie_jar,
+        &state_provider(provider),
+        query.state.as_deref(),
+    )?;
+
+    let code = query
+        .code
+        .as_deref()
+        .ok_or_else(|| HttpError::bad_request("缺少授权码".to_string()))?;
+
+    let client = &app_state.oidc_client;
+    let metadata = client
+        .metadata(provider)
+        .await
+        .map_err(|e| provider_error(provider, e))?;
+
+    let tokens = client
+        .exchange_code(
+            provider,
+            &metadata,
+            code,
+            authorization.pkce_verifier.secret(),
+        )
+        .await
+        .map_err(|e| provider_error(provider, e))?;
+
+    let id_token = tokens
+        .id_token
+        .as_deref()
+        .ok_or_else(|| provider_error(provider, "令牌响应中没有 ID 令牌".to_string()))?;
+
+    let claims = client
+        .verify_id_token(provider, id_token, &authorization.nonce)
+        .await
+        .map_err(|e| {
+            tracing::warn!("OIDC 提供方 {} 的 ID 令牌无效: {}", provider.name, e);
+            HttpError::unauthorized(format!("{} 登录失败: {}", provider.display_name, e))
+        })?;
+
+    let mut user = oidc::map_claims(provider, &claims, None).map_err(HttpError::unauthorized)?;
+
+    if user.email.is_none() && metadata.userinfo_endpoint.is_some() {
+        let userinfo = client
+            .userinfo(&metadata, &tokens.access_token, &user.subject)
+            .await
+            .map_err(|e| provider_error(provider, e))?;
+        user = oidc::map_claims(provider, &claims, Some(&userinfo))
+            .map_err(HttpError::unauthorized)?;
+    }
+
+    let email = user.email.ok_or_else(|| {
+        HttpError::bad_request(format!("{} 未提供邮箱地址", provider.display_name))
+    })?;
+
+    let profile = ProviderProfile {
+        provider: AuthProvider::Oidc,
+        provider_name: provider.name.clone(),
+        subject: user.subject,
+        name: user.name.unwrap_or_else(|| email.clone()),
+        email,
+        email_verified: user.email_verified,
+        picture: user.picture,
+    };
+
+    complete_authorization(
+        &app_state,
+        cookie_jar,
+        authorization.return_to.as_deref(),
+        authorization.link_user_id,
+        profile,
+    )
+    .await
+}
+
+fn find_provider<'a>(
+    app_state: &'a AppState,
+    name: &str,
+) -> Result<&'a OidcProviderConfig, HttpError> {
+    app_state
+        .env
+        .oidc_providers
+        .iter()
+        .find(|provider| provider.name == name)
+        .ok_or_else(|| HttpError::not_found(format!("未配置登录提供方 {}", name)))
+}
+
+/// 写入状态 cookie 的提供方标识，防止一个提供方的回调使用另一个提供方的授权流程
+fn state_provider(provider: &OidcProviderConfig) -> String {
+    format!("oidc:{}", provider.name)
+}
+
+fn provider_error(provider: &OidcProviderConfig, error: String) -> HttpError {
+    tracing::error!("OIDC 提供方 {} 请求失败: {}", provider.name, error);
+    HttpError::server_error(format!("无法连接 {}", provider.display_name))
+}
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+use chrono::{Duration, Utc};
+use validator::Validate;
+
+use super::{magic_link, tokens};
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{LoginCodeExt, UserExt},
+    dtos::{LoginCodeVerifyDto, PasswordlessLoginRequestDto, Response},
+    error::HttpError,
+    mail::mails::send_login_code_email,
+    models::AuditAction,
+    utils::token,
+};
+
+/// 每个邮箱每小时最多请求的验证码数
+const MAX_CODES_PER_EMAIL: i64 = 5;
+/// 每个 IP 每小时最多请求的验证码数
+const MAX_CODES_PER_IP: i64 = 20;
+
+/// 处理验证码登录请求 -- 向邮箱发送六位数字验证码
+///
+/// 与登录链接相同，无论邮箱是否注册都返回相同的响应。
+/// 新验证码发出后，之前发送给该邮箱的验证码全部失效。
+pub async fn request_login_code(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<PasswordlessLoginRequestDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let email = body.email.trim().to_lowercase();
+    let ip_address = audit::current_context().ip_address;
+
+    // -- 步骤 1: 按邮箱和 IP 限制请求频率
+    let (by_email, by_ip) = app_state
+        .db_client
+        .count_recent_login_codes(
+            &email,
+            ip_address.as_deref(),
+            Utc::now() - Duration::hours(1),
+        )
+        .await?;
+
+    if by_email >= MAX_CODES_PER_EMAIL || by_ip >= MAX_CODES_PER_IP {
+        tracing::warn!("验证码请求过于频繁，邮箱: {}, IP: {:?}", email, ip_address);
+        return Err(HttpError::new(
+            "Too many sign-in code requests, please try again later",
+            StatusCode::TOO_MANY_REQUESTS,
+        ));
+    }
+
+    // -- 步骤 2: 查找用户，未注册且不允许自动注册时不发送邮件
+    let user = app_state.db_client.get_user_by_email(&email).await?;
+
+    if user.is_none() && !app_state.env.magic_link_signup_enabled {
+        tracing::info!("邮箱 {} 未注册，不发送登录验证码", email);
+        return Ok(Json(login_code_sent()));
+    }
+
+    // -- 步骤 3: 生成验证码，只保存 HMAC
+    let code = token::generate_login_code();
+    let expires_in = app_state.env.login_code_expires_minutes;
+
+    app_state
+        .db_client
+        .create_login_code(
+            &email,
+            &token::hash_login_code(app_state.env.jwt_secret.as_bytes(), &email, &code),
+            ip_address.as_deref(),
+            Utc::now() + Duration::minutes(expires_in),
+        )
+        .await?;
+
+    let username = user
+        .as_ref()
+        .map(|user| user.name.as_str())
+        .unwrap_or(&email);
+
+    let recipient = user
+        .as_ref()
+        .map_or(email.as_str(), |user| user.email.as_str());
+    send_login_code_email(recipient, username, &code, expires_in)
+        .await
+        .map_err(|e| {
+            tracing::error!("发送登录验证码失败: {}", e);
+            HttpError::server_error("发送邮件失败".to_string())
+        })?;
+
+    tracing::info!("已向 {} 发送登录验证码", email);
+
+    Ok(Json(login_code_sent()))
+}
+
+/// 处理验证码校验 -- 邮箱和验证码匹配时登录
+///
+/// 每个验证码最多尝试 `LOGIN_CODE_MAX_ATTEMPTS` 次，超过后需要重新获取。
+/// 登录成功后与密码登录相同，返回令牌并设置 cookie。
+pub async fn verify_login_code(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<LoginCodeVerifyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let email = body.email.trim().to_lowercase();
+    let invalid_code = || HttpError::bad_request("验证码错误或已过期".to_string());
+
+    let login_code = app_state
+        .db_client
+        .take_login_code_attempt(&email, app_state.env.login_code_max_attempts)
+        .await?
+        .ok_or_else(invalid_code)?;
+
+    let matched = token::verify_login_code(
+        app_state.env.jwt_secret.as_bytes(),
+        &email,
+        body.code.trim(),
+        &login_code.code_hash,
+    );
+
+    if !matched {
+        tracing::warn!(
+            "登录验证码错误，邮箱: {}，已尝试 {} 次",
+            email,
+            login_code.attempts
+        );
+
+        audit::record(
+            &app_state.db_client,
+            AuditEvent::new(AuditAction::LoginFailed)
+                .actor_email(&email)
+                .details(serde_json::json!({
+                    "reason": "wrong_code",
+                    "attempts": login_code.attempts,
+                })),
+        )
+        .await;
+
+        return Err(invalid_code());
+    }
+
+    // -- 并发请求中只有一个能消费验证码
+    if !app_state
+        .db_client
+        .consume_login_code(login_code.id)
+        .await?
+    {
+        return Err(invalid_code());
+    }
+
+    let user = match app_state
+        .db_client
+        .get_user_by_email(&login_code.email)
+        .await?
+    {
+        Some(user) => user,
+        None if app_state.env.magic_link_signup_enabled => {
+            magic_link::create_user(&app_state, &login_code.email).await?
+        }
+        None => return Err(invalid_code()),
+    };
+
+    tokens::login_response(&app_state, &user, "email_code").await
+}
+
+fn login_code_sent() -> Response {
+    Response {
+        status: "success",
+        message: "If the email can be used to sign in, a sign-in code has been sent".to_string(),
+    }
+}
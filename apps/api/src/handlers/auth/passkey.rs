@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, response::IntoResponse};
+use validator::Validate;
+
+use super::{tokens, two_factor::challenge_user};
+
+use crate::{
+    AppState,
+    db::UserExt,
+    dtos::{ChallengeTokenDto, PasskeyLoginDto, PasskeyTwoFactorDto},
+    error::HttpError,
+    handlers::passkeys,
+    utils::token::ChallengePurpose,
+};
+
+/// 生成免密登录的参数，由认证器选择该站点的通行密钥
+pub async fn login_options(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    let options = passkeys::authentication_options(&app_state, None).await?;
+    Ok(Json(options))
+}
+
+/// 处理通行密钥免密登录
+///
+/// 通行密钥本身同时验证了设备和用户身份，登录时不再要求两步验证。
+pub async fn login(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<PasskeyLoginDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let passkey =
+        passkeys::verify_authentication(&app_state, None, body.challenge_id, &body.credential)
+            .await?;
+
+    let user = app_state
+        .db_client
+        .get_user(Some(passkey.user_id), None, None, None)
+        .await?
+        .ok_or_else(|| HttpError::unauthorized("通行密钥未注册".to_string()))?;
+
+    tracing::info!("用户 {} 使用通行密钥 {} 登录", user.email, passkey.name);
+
+    let tokens = tokens::complete_login(
+        &app_state,
+        &user,
+        serde_json::json!({ "method": "passkey", "passkey_id": passkey.id }),
+    )
+    .await?;
+
+    Ok(tokens.into_response(&app_state.env))
+}
+
+/// 生成两步验证用的通行密钥参数，只允许该用户已注册的凭证
+pub async fn two_factor_options(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<ChallengeTokenDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let (_, user) = challenge_user(
+        &app_state,
+        &body.challenge_token,
+        ChallengePurpose::TwoFactor,
+    )
+    .await?;
+
+    let options = passkeys::authentication_options(&app_state, Some(&user)).await?;
+    Ok(Json(options))
+}
+
+/// 使用通行密钥完成登录的第二步
+pub async fn verify_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<PasskeyTwoFactorDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let (claims, user) = challenge_user(
+        &app_state,
+        &body.challenge_token,
+        ChallengePurpose::TwoFactor,
+    )
+    .await?;
+
+    let passkey = passkeys::verify_authentication(
+        &app_state,
+        Some(&user),
+        body.challenge_id,
+        &body.credential,
+    )
+    .await?;
+
+    let tokens = tokens::complete_login(
+        &app_state,
+        &user,
+        serde_json::json!({
+            "method": claims.method,
+            "second_factor": "passkey",
+            "passkey_id": passkey.id,
+        }),
+    )
+    .await?;
+
+    Ok(tokens.into_response(&app_state.env))
+}
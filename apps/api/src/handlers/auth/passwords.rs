@@ -6,10 +6,13 @@ use validator::Validate;
 
 use crate::{
     AppState,
-    db::UserExt,
+    audit::{self, AuditEvent},
+    db::{AccessTokenExt, IdentityExt, RateLimitExt, SessionExt, UserExt},
     dtos::{ForgotPasswordRequestDto, ResetPasswordRequestDto, Response},
     error::HttpError,
     mail::mails::send_forgot_password_email,
+    models::AuditAction,
+    password_policy::PasswordOwner,
     utils::password,
 };
 
@@ -113,6 +116,20 @@ pub async fn reset_password(
 
     let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
 
+    app_state
+        .password_policy
+        .check(
+            &app_state.db_client,
+            &body.new_password,
+            PasswordOwner {
+                id: Some(user_id),
+                name: &user.name,
+                email: &user.email,
+                current_hash: Some(&user.password),
+            },
+        )
+        .await?;
+
     let hash_password =
         password::hash(&body.new_password).map_err(|e| HttpError::server_error(e.to_string()))?;
 
@@ -121,6 +138,19 @@ pub async fn reset_password(
         .update_user_password(user_id, hash_password)
         .await
         .map_err(|e| HttpError::server_error(e.to_string()))?;
+    app_state
+        .password_policy
+        .remember(&app_state.db_client, user_id, &user.password)
+        .await?;
+
+    // -- 设置过密码后即可使用密码登录
+    app_state
+        .db_client
+        .link_local_identity(user_id, &user.email)
+        .await?;
+
+    // -- 通过邮箱重置了密码，解除密码错误导致的锁定
+    app_state.db_client.clear_login_failures(user_id).await?;
 
     app_state
         .db_client
@@ -128,6 +158,25 @@ pub async fn reset_password(
         .await
         .map_err(|e| HttpError::server_error(e.to_string()))?;
 
+    // -- 重置密码后所有已登录的会话和个人访问令牌全部失效
+    let revoked = app_state
+        .db_client
+        .revoke_user_sessions(user_id, None)
+        .await?;
+    app_state.session_cache.evict(&revoked);
+    app_state
+        .db_client
+        .revoke_user_access_tokens(user_id)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::PasswordReset)
+            .actor(&user)
+            .target("user", user.id),
+    )
+    .await;
+
     let response = Response {
         message: "Password has been successfully reset.".to_string(),
         status: "success",
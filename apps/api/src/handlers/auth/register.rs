@@ -10,6 +10,7 @@ use crate::{
     dtos::{RegisterUserDto, Response},
     error::{ErrorMessage, HttpError},
     mail::mails::send_verification_email,
+    password_policy::PasswordOwner,
     utils::password,
 };
 
@@ -22,7 +23,7 @@ use crate::{
 /// # 返回
 /// - `Ok(Response)` -- 注册成功，返回成功消息
 /// - `Err(HttpError)` -- 注册失败，返回错误信息
-///   - `BadRequest` -- 请求参数验证失败
+///   - `BadRequest` -- 请求参数验证失败或密码不符合密码策略
 ///   - `UniqueViolation` -- 邮箱已存在
 ///   - `ServerError` -- 服务器内部错误
 pub async fn register(
@@ -44,6 +45,21 @@ pub async fn register(
         return Err(HttpError::bad_request("邮箱已被注册".to_string()));
     }
 
+    // -- 检查密码策略
+    app_state
+        .password_policy
+        .check(
+            &app_state.db_client,
+            &body.password,
+            PasswordOwner {
+                id: None,
+                name: &body.name,
+                email: &body.email,
+                current_hash: None,
+            },
+        )
+        .await?;
+
     // -- 生成验证 token，有效期设置为 30 分钟
     let verification_token = uuid::Uuid::new_v4().to_string();
     let token_expires_at = Utc::now() + Duration::minutes(30);
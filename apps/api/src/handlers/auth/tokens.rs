@@ -0,0 +1,402 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json,
+    http::{HeaderMap, header},
+    response::{IntoResponse, Redirect},
+};
+use axum_extra::extract::{CookieJar, cookie::Cookie};
+use chrono::{Duration, Utc};
+use oauth2::url::form_urlencoded;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    config::Config,
+    db::{
+        AccountDeletionExt, PasskeyExt, RefreshTokenExt, RefreshTokenRotation, SessionExt,
+        TwoFactorExt, UserExt,
+    },
+    dtos::{RefreshTokenDto, Response, TwoFactorChallengeResponseDto, UserLoginResponseDto},
+    error::{ErrorMessage, HttpError},
+    middleware::JWTAuthMiddleware,
+    models::{AuditAction, User},
+    utils::token::{self, ChallengePurpose},
+};
+
+/// 刷新令牌的 cookie 名称，只在 `/api/auth` 下发送
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+const REFRESH_TOKEN_COOKIE_PATH: &str = "/api/auth";
+/// 两步验证挑战令牌的有效期（分钟）
+const CHALLENGE_EXPIRES_MINUTES: i64 = 5;
+
+/// 登录成功后签发的访问令牌和刷新令牌
+pub struct AuthTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+impl AuthTokens {
+    /// 设置 `token` 和 `refresh_token` 两个 cookie 的响应头
+    pub fn cookie_headers(&self, config: &Config) -> HeaderMap {
+        let access_cookie = Cookie::build(("token", self.access_token.clone()))
+            .path("/")
+            .max_age(time::Duration::minutes(config.jwt_maxage))
+            .http_only(true)
+            .build();
+        let refresh_cookie = Cookie::build((REFRESH_TOKEN_COOKIE, self.refresh_token.clone()))
+            .path(REFRESH_TOKEN_COOKIE_PATH)
+            .max_age(time::Duration::days(config.refresh_token_maxage_days))
+            .http_only(true)
+            .build();
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            access_cookie.to_string().parse().unwrap(),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            refresh_cookie.to_string().parse().unwrap(),
+        );
+        headers
+    }
+
+    /// 清除 `token` 和 `refresh_token` 两个 cookie 的响应头
+    pub fn clear_cookie_headers() -> HeaderMap {
+        let access_cookie = Cookie::build(("token", ""))
+            .path("/")
+            .max_age(time::Duration::ZERO)
+            .http_only(true)
+            .build();
+        let refresh_cookie = Cookie::build((REFRESH_TOKEN_COOKIE, ""))
+            .path(REFRESH_TOKEN_COOKIE_PATH)
+            .max_age(time::Duration::ZERO)
+            .http_only(true)
+            .build();
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::SET_COOKIE,
+            access_cookie.to_string().parse().unwrap(),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            refresh_cookie.to_string().parse().unwrap(),
+        );
+        headers
+    }
+
+    pub(super) fn into_response(self, config: &Config) -> axum::response::Response {
+        let headers = self.cookie_headers(config);
+
+        let mut response = Json(UserLoginResponseDto {
+            status: "success".to_string(),
+            token: self.access_token,
+            refresh_token: self.refresh_token,
+        })
+        .into_response();
+        response.headers_mut().extend(headers);
+        response
+    }
+}
+
+/// 为刚登录的用户创建会话，并签发访问令牌和该会话的第一个刷新令牌
+///
+/// 会话记录当前请求的 IP 和 User-Agent，用于设备管理。
+pub async fn issue_tokens(app_state: &AppState, user: &User) -> Result<AuthTokens, HttpError> {
+    let refresh_token = token::generate_opaque_token();
+    let expires_at = Utc::now() + Duration::days(app_state.env.refresh_token_maxage_days);
+    let context = audit::current_context();
+
+    let session = app_state
+        .db_client
+        .create_session(user.id, context.ip_address, context.user_agent, expires_at)
+        .await?;
+
+    app_state
+        .db_client
+        .create_refresh_token(
+            user.id,
+            session.id,
+            &token::hash_opaque_token(&refresh_token),
+            expires_at,
+        )
+        .await?;
+
+    Ok(AuthTokens {
+        access_token: access_token(app_state, user, session.id)?,
+        refresh_token,
+    })
+}
+
+/// 第一步验证（密码、OAuth、登录链接等）通过后的结果
+pub enum LoginStep {
+    /// 登录完成，已签发令牌
+    Complete(AuthTokens),
+    /// 还需要两步验证，或需要先设置两步验证
+    Challenge {
+        purpose: ChallengePurpose,
+        token: String,
+    },
+}
+
+/// 第一步验证通过后调用
+///
+/// 已开启两步验证（TOTP 或通行密钥）的用户，以及角色要求两步验证但尚未设置的用户，
+/// 只拿到短期的挑战令牌；其余用户直接完成登录。
+pub async fn begin_login(
+    app_state: &AppState,
+    user: &User,
+    method: &str,
+) -> Result<LoginStep, HttpError> {
+    // -- TOTP 和通行密钥都可以作为第二因素
+    let enabled = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await?
+        .is_some_and(|totp| totp.confirmed_at.is_some())
+        || app_state.db_client.count_user_passkeys(user.id).await? > 0;
+
+    let purpose = if enabled {
+        Some(ChallengePurpose::TwoFactor)
+    } else if app_state
+        .db_client
+        .role_requires_two_factor(user.role)
+        .await?
+    {
+        Some(ChallengePurpose::TwoFactorSetup)
+    } else {
+        None
+    };
+
+    if let Some(purpose) = purpose {
+        let token = token::create_challenge_token(
+            &user.id.to_string(),
+            purpose,
+            method,
+            &app_state.jwt_keys,
+            CHALLENGE_EXPIRES_MINUTES,
+        )
+        .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+        tracing::info!("用户 {} 需要完成两步验证", user.email);
+        return Ok(LoginStep::Challenge { purpose, token });
+    }
+
+    let tokens = complete_login(app_state, user, serde_json::json!({ "method": method })).await?;
+    Ok(LoginStep::Complete(tokens))
+}
+
+/// 记录登录成功的审计日志并签发令牌
+///
+/// 宽限期内的账户注销请求在登录成功时取消。
+pub async fn complete_login(
+    app_state: &AppState,
+    user: &User,
+    details: serde_json::Value,
+) -> Result<AuthTokens, HttpError> {
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::LoginSucceeded)
+            .actor(user)
+            .target("user", user.id)
+            .details(details),
+    )
+    .await;
+
+    if app_state.db_client.cancel_account_deletion(user.id).await? {
+        audit::record(
+            &app_state.db_client,
+            AuditEvent::new(AuditAction::AccountDeletionCancelled)
+                .actor(user)
+                .target("user", user.id),
+        )
+        .await;
+
+        tracing::info!("用户 {} 重新登录，已取消账户注销", user.email);
+    }
+
+    issue_tokens(app_state, user).await
+}
+
+/// 第一步验证通过后的 JSON 响应
+///
+/// 登录完成时令牌写入响应体和 cookie；需要两步验证时返回挑战令牌。
+pub async fn login_response(
+    app_state: &AppState,
+    user: &User,
+    method: &str,
+) -> Result<axum::response::Response, HttpError> {
+    match begin_login(app_state, user, method).await? {
+        LoginStep::Complete(tokens) => Ok(tokens.into_response(&app_state.env)),
+        LoginStep::Challenge { purpose, token } => Ok(Json(TwoFactorChallengeResponseDto {
+            status: challenge_status(purpose).to_string(),
+            challenge_token: token,
+        })
+        .into_response()),
+    }
+}
+
+/// 第一步验证通过后的重定向响应，用于 OAuth 回调、登录链接等浏览器跳转
+///
+/// 登录完成后重定向到前端的 `return_to` 路径（默认首页）；`with_access_token` 为 true 时
+/// 在地址上带上访问令牌参数，刷新令牌只通过 cookie 下发。需要两步验证时重定向到
+/// 前端的两步验证页面，并带上 `returnTo` 供验证完成后跳转。
+pub async fn login_redirect(
+    app_state: &AppState,
+    user: &User,
+    method: &str,
+    with_access_token: bool,
+    return_to: Option<&str>,
+) -> Result<axum::response::Response, HttpError> {
+    match begin_login(app_state, user, method).await? {
+        LoginStep::Complete(tokens) => {
+            let mut redirect_url =
+                format!("{}{}", app_state.env.frontend_url, return_to.unwrap_or(""));
+            if with_access_token {
+                let separator = if redirect_url.contains('?') { '&' } else { '?' };
+                redirect_url =
+                    format!("{}{}token={}", redirect_url, separator, tokens.access_token);
+            }
+
+            let mut response = Redirect::to(&redirect_url).into_response();
+            response
+                .headers_mut()
+                .extend(tokens.cookie_headers(&app_state.env));
+            Ok(response)
+        }
+        LoginStep::Challenge { purpose, token } => {
+            let mut redirect_url = format!(
+                "{}/login/two-factor?status={}&challenge={}",
+                app_state.env.frontend_url,
+                challenge_status(purpose),
+                token
+            );
+            if let Some(return_to) = return_to {
+                redirect_url.push_str("&returnTo=");
+                redirect_url.extend(form_urlencoded::byte_serialize(return_to.as_bytes()));
+            }
+            Ok(Redirect::to(&redirect_url).into_response())
+        }
+    }
+}
+
+fn challenge_status(purpose: ChallengePurpose) -> &'static str {
+    match purpose {
+        ChallengePurpose::TwoFactor => "two_factor_required",
+        ChallengePurpose::TwoFactorSetup => "two_factor_setup_required",
+    }
+}
+
+fn access_token(app_state: &AppState, user: &User, session_id: Uuid) -> Result<String, HttpError> {
+    token::create_token(
+        &user.id.to_string(),
+        &session_id.to_string(),
+        user.role,
+        &app_state.jwt_keys,
+        app_state.env.jwt_maxage,
+    )
+    .map_err(|e| HttpError::server_error(e.to_string()))
+}
+
+/// 处理刷新令牌请求 -- 用刷新令牌换取新的访问令牌
+///
+/// 刷新令牌从 `refresh_token` cookie 或请求体中读取。每次使用后都会轮换，
+/// 旧令牌立即失效；已经轮换过的旧令牌再次出现说明令牌可能被盗用，
+/// 此时吊销整个令牌家族，用户需要重新登录。
+pub async fn refresh(
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    body: Option<Json<RefreshTokenDto>>,
+) -> Result<impl IntoResponse, HttpError> {
+    let presented = body
+        .and_then(|Json(body)| body.refresh_token)
+        .or_else(|| {
+            cookie_jar
+                .get(REFRESH_TOKEN_COOKIE)
+                .map(|cookie| cookie.value().to_string())
+        })
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string()))?;
+
+    let refresh_token = token::generate_opaque_token();
+    let expires_at = Utc::now() + Duration::days(app_state.env.refresh_token_maxage_days);
+
+    let rotation = app_state
+        .db_client
+        .rotate_refresh_token(
+            &token::hash_opaque_token(&presented),
+            &token::hash_opaque_token(&refresh_token),
+            expires_at,
+        )
+        .await?;
+
+    match rotation {
+        RefreshTokenRotation::Rotated(replacement) => {
+            // -- 每次刷新都重新读取用户，新的访问令牌带上最新的角色
+            let user = app_state
+                .db_client
+                .get_user(Some(replacement.user_id), None, None, None)
+                .await?
+                .ok_or_else(|| {
+                    HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string())
+                })?;
+
+            let tokens = AuthTokens {
+                access_token: access_token(&app_state, &user, replacement.family_id)?,
+                refresh_token,
+            };
+            Ok(tokens.into_response(&app_state.env))
+        }
+        RefreshTokenRotation::Reused(reused) => {
+            tracing::warn!(
+                "检测到刷新令牌重复使用，吊销令牌家族 {}，用户ID: {}",
+                reused.family_id,
+                reused.user_id
+            );
+            app_state.session_cache.evict(&[reused.family_id]);
+
+            audit::record(
+                &app_state.db_client,
+                AuditEvent::new(AuditAction::RefreshTokenReused)
+                    .actor_id(reused.user_id)
+                    .target("user", reused.user_id)
+                    .details(serde_json::json!({ "family_id": reused.family_id })),
+            )
+            .await;
+
+            Err(HttpError::unauthorized(
+                ErrorMessage::InvalidToken.to_string(),
+            ))
+        }
+        RefreshTokenRotation::Invalid => Err(HttpError::unauthorized(
+            ErrorMessage::InvalidToken.to_string(),
+        )),
+    }
+}
+
+/// 处理注销请求 -- 吊销当前会话并清除登录 cookie
+///
+/// 会话吊销后，该会话签发的访问令牌和刷新令牌立即失效。
+pub async fn logout(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let session_id = user.require_session()?;
+
+    app_state
+        .db_client
+        .revoke_session(session_id, user.user.id)
+        .await?;
+    app_state.session_cache.evict(&[session_id]);
+
+    tracing::info!("用户 {} 注销会话 {}", user.user.email, session_id);
+
+    let response = Json(Response {
+        status: "success",
+        message: "Logged out successfully".to_string(),
+    });
+
+    Ok((AuthTokens::clear_cookie_headers(), response))
+}
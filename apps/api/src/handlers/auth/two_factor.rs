@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, response::IntoResponse};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::tokens;
+
+use crate::{
+    AppState,
+    db::UserExt,
+    dtos::{
+        ChallengeTokenDto, TotpSetupResponseDto, TwoFactorChallengeVerifyDto,
+        TwoFactorLoginResponseDto,
+    },
+    error::{ErrorMessage, HttpError},
+    handlers::two_factor,
+    models::User,
+    utils::token::{self, ChallengeClaims, ChallengePurpose},
+};
+
+/// 处理登录的第二步 -- 提交认证器应用中的验证码或一个恢复码
+///
+/// 挑战令牌由第一步验证签发，有效期很短。验证通过后签发访问令牌和刷新令牌。
+pub async fn verify_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<TwoFactorChallengeVerifyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let (claims, user) = challenge_user(
+        &app_state,
+        &body.challenge_token,
+        ChallengePurpose::TwoFactor,
+    )
+    .await?;
+
+    let factor = two_factor::verify_second_factor(&app_state, &user, &body.code).await?;
+
+    let tokens = tokens::complete_login(
+        &app_state,
+        &user,
+        serde_json::json!({
+            "method": claims.method,
+            "second_factor": factor.to_str(),
+        }),
+    )
+    .await?;
+
+    Ok(tokens.into_response(&app_state.env))
+}
+
+/// 角色要求两步验证但尚未设置时，登录过程中开始设置
+pub async fn setup_two_factor(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<ChallengeTokenDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let (_, user) = challenge_user(
+        &app_state,
+        &body.challenge_token,
+        ChallengePurpose::TwoFactorSetup,
+    )
+    .await?;
+
+    let data = two_factor::start_enrollment(&app_state, &user).await?;
+
+    Ok(Json(TotpSetupResponseDto {
+        status: "success".to_string(),
+        data,
+    }))
+}
+
+/// 登录过程中确认设置两步验证 -- 完成登录，并返回只展示一次的恢复码
+pub async fn confirm_two_factor_setup(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Json(body): Json<TwoFactorChallengeVerifyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let (claims, user) = challenge_user(
+        &app_state,
+        &body.challenge_token,
+        ChallengePurpose::TwoFactorSetup,
+    )
+    .await?;
+
+    let recovery_codes = two_factor::confirm_enrollment(&app_state, &user, &body.code).await?;
+
+    let tokens = tokens::complete_login(
+        &app_state,
+        &user,
+        serde_json::json!({
+            "method": claims.method,
+            "second_factor": "totp_setup",
+        }),
+    )
+    .await?;
+
+    let headers = tokens.cookie_headers(&app_state.env);
+    let response = Json(TwoFactorLoginResponseDto {
+        status: "success".to_string(),
+        token: tokens.access_token,
+        refresh_token: tokens.refresh_token,
+        recovery_codes,
+    });
+
+    Ok((headers, response))
+}
+
+/// 解析挑战令牌并查找对应的用户
+pub(super) async fn challenge_user(
+    app_state: &AppState,
+    challenge_token: &str,
+    purpose: ChallengePurpose,
+) -> Result<(ChallengeClaims, User), HttpError> {
+    let claims = token::decode_challenge_token(challenge_token, purpose, &app_state.jwt_keys)?;
+
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    let user = app_state
+        .db_client
+        .get_user(Some(user_id), None, None, None)
+        .await?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
+
+    Ok((claims, user))
+}
@@ -0,0 +1,390 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post, put},
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    db::{CommentExt, DocumentExt, NotificationExt},
+    dtos::{
+        CommentContentDto, CommentResponseDto, CommentThreadDto, CommentThreadListResponseDto,
+        CommentThreadResponseDto, CommentThreadsQueryDto, CreateCommentThreadDto, Response,
+    },
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, document_scope},
+    models::{Comment, CommentThread, NotificationType, PermissionLevel, WebhookEvent},
+    repositories::DocumentRepository,
+    utils::mention,
+};
+
+pub fn comments_handler() -> Router {
+    Router::new()
+        .route("/", get(get_comment_threads).post(create_comment_thread))
+        .route("/threads/{thread_id}/replies", post(reply_to_thread))
+        .route("/threads/{thread_id}/resolve", put(resolve_thread))
+        .route("/threads/{thread_id}/reopen", put(reopen_thread))
+        .route("/{comment_id}", put(update_comment).delete(delete_comment))
+        .layer(middleware::from_fn(document_scope))
+}
+
+/// 获取文档的评论线程及其回复
+///
+/// 默认只返回未解决的线程，`include_resolved=true` 时返回全部线程。
+pub async fn get_comment_threads(
+    Query(query_params): Query<CommentThreadsQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let threads = app_state
+        .db_client
+        .get_comment_threads(
+            query_params.document_id,
+            user.user.id,
+            query_params.include_resolved.unwrap_or(false),
+        )
+        .await?;
+
+    let thread_ids: Vec<Uuid> = threads.iter().map(|thread| thread.id).collect();
+    let mut comments = app_state.db_client.get_thread_comments(&thread_ids).await?;
+
+    // -- 按线程分组回复
+    let threads: Vec<CommentThreadDto> = threads
+        .into_iter()
+        .map(|thread| {
+            let (thread_comments, rest) = comments
+                .drain(..)
+                .partition(|comment| comment.thread_id == thread.id);
+            comments = rest;
+
+            CommentThreadDto {
+                thread,
+                comments: thread_comments,
+            }
+        })
+        .collect();
+
+    let response = CommentThreadListResponseDto {
+        status: "success".to_string(),
+        results: threads.len(),
+        threads,
+    };
+
+    Ok(Json(response))
+}
+
+/// 在文档的指定范围上创建评论线程
+pub async fn create_comment_thread(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<CreateCommentThreadDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate().map_err(|e| {
+        tracing::warn!("评论请求验证失败: {}", e);
+        HttpError::bad_request(e.to_string())
+    })?;
+
+    let (thread, comment) = app_state
+        .db_client
+        .create_comment_thread(
+            body.document_id,
+            user.user.id,
+            body.anchor(),
+            body.content.clone(),
+        )
+        .await?;
+
+    notify_comment_mentions(&app_state, &comment, &[]).await;
+    dispatch_comment_webhooks(&app_state, &thread, &comment).await;
+
+    tracing::info!(
+        "用户 {} 在文档 {} 上创建评论线程 {}",
+        user.user.email,
+        thread.document_id,
+        thread.id
+    );
+
+    let response = CommentThreadResponseDto {
+        status: "success".to_string(),
+        data: CommentThreadDto {
+            thread,
+            comments: vec![comment],
+        },
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// 回复评论线程
+pub async fn reply_to_thread(
+    Path(thread_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<CommentContentDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let comment = app_state
+        .db_client
+        .add_comment_reply(thread_id, user.user.id, body.content)
+        .await?;
+
+    notify_thread_participants(&app_state, &comment).await;
+    notify_comment_mentions(&app_state, &comment, &[]).await;
+    if let Ok(Some(thread)) = app_state.db_client.get_comment_thread(thread_id).await {
+        dispatch_comment_webhooks(&app_state, &thread, &comment).await;
+    }
+
+    let response = CommentResponseDto {
+        status: "success".to_string(),
+        data: comment,
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// 编辑评论，仅仍可评论该文档的作者本人可操作
+pub async fn update_comment(
+    Path(comment_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<CommentContentDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let previous_mentions = app_state
+        .db_client
+        .get_comment(comment_id)
+        .await?
+        .map(|comment| mention::text_mentions(&comment.content))
+        .unwrap_or_default();
+
+    let comment = app_state
+        .db_client
+        .update_comment(comment_id, user.user.id, body.content)
+        .await?;
+
+    // -- 只通知编辑后新增的提及
+    notify_comment_mentions(&app_state, &comment, &previous_mentions).await;
+
+    let response = CommentResponseDto {
+        status: "success".to_string(),
+        data: comment,
+    };
+
+    Ok(Json(response))
+}
+
+/// 删除评论，仍可评论该文档的作者本人或文档所有者可操作；删除首条评论会删除整个线程
+pub async fn delete_comment(
+    Path(comment_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .delete_comment(comment_id, user.user.id)
+        .await?;
+
+    tracing::info!("用户 {} 删除评论 {}", user.user.email, comment_id);
+
+    let response = Response {
+        status: "success",
+        message: "Comment deleted successfully".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+/// 将评论线程标记为已解决
+pub async fn resolve_thread(
+    Path(thread_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let thread = app_state
+        .db_client
+        .set_comment_thread_resolved(thread_id, user.user.id, true)
+        .await?;
+
+    thread_response(&app_state, thread).await
+}
+
+/// 重新打开已解决的评论线程
+pub async fn reopen_thread(
+    Path(thread_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let thread = app_state
+        .db_client
+        .set_comment_thread_resolved(thread_id, user.user.id, false)
+        .await?;
+
+    thread_response(&app_state, thread).await
+}
+
+async fn thread_response(
+    app_state: &AppState,
+    thread: CommentThread,
+) -> Result<Json<CommentThreadResponseDto>, HttpError> {
+    let comments = app_state
+        .db_client
+        .get_thread_comments(&[thread.id])
+        .await?;
+
+    Ok(Json(CommentThreadResponseDto {
+        status: "success".to_string(),
+        data: CommentThreadDto { thread, comments },
+    }))
+}
+
+/// 通知线程中的其他参与者有新回复，已失去文档访问权限的用户除外
+///
+/// 回复已经保存，通知失败只记录错误日志。
+async fn notify_thread_participants(app_state: &AppState, reply: &Comment) {
+    if let Err(e) = try_notify_thread_participants(app_state, reply).await {
+        tracing::error!("通知评论线程 {} 的参与者失败: {}", reply.thread_id, e);
+    }
+}
+
+async fn try_notify_thread_participants(
+    app_state: &AppState,
+    reply: &Comment,
+) -> Result<(), HttpError> {
+    let Some(thread) = app_state
+        .db_client
+        .get_comment_thread(reply.thread_id)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let comments = app_state
+        .db_client
+        .get_thread_comments(&[thread.id])
+        .await?;
+
+    let mut participants: Vec<Uuid> = comments.iter().map(|comment| comment.author_id).collect();
+    participants.sort();
+    participants.dedup();
+
+    let excerpt: String = reply.content.chars().take(200).collect();
+
+    for participant in participants {
+        if participant == reply.author_id {
+            continue;
+        }
+
+        let has_access = app_state
+            .db_client
+            .check_document_permission(thread.document_id, participant, PermissionLevel::Read)
+            .await?;
+        if !has_access {
+            continue;
+        }
+
+        app_state
+            .db_client
+            .create_notification(
+                participant,
+                Some(reply.author_id),
+                NotificationType::CommentReply,
+                Some(thread.document_id),
+                serde_json::json!({
+                    "thread_id": thread.id,
+                    "comment_id": reply.id,
+                    "excerpt": excerpt,
+                }),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// 通知评论中新增提及的用户，失败只记录错误日志
+async fn notify_comment_mentions(
+    app_state: &AppState,
+    comment: &Comment,
+    previous_mentions: &[mention::Mention],
+) {
+    let mentions =
+        mention::new_mentions(previous_mentions, mention::text_mentions(&comment.content));
+    if mentions.is_empty() {
+        return;
+    }
+
+    let thread = match app_state
+        .db_client
+        .get_comment_thread(comment.thread_id)
+        .await
+    {
+        Ok(Some(thread)) => thread,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("获取评论线程 {} 失败: {}", comment.thread_id, e);
+            return;
+        }
+    };
+
+    app_state
+        .document_repository
+        .notify_mentions(
+            thread.document_id,
+            comment.author_id,
+            mentions,
+            Some(thread.id),
+            Some(comment.content.clone()),
+        )
+        .await;
+}
+
+/// 触发 comment.created Webhook，失败只记录错误日志
+async fn dispatch_comment_webhooks(
+    app_state: &AppState,
+    thread: &CommentThread,
+    comment: &Comment,
+) {
+    let document = match app_state
+        .document_repository
+        .get_document(thread.document_id, Some(comment.author_id))
+        .await
+    {
+        Ok(Some(document)) => document,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("获取文档 {} 失败: {}", thread.document_id, e);
+            return;
+        }
+    };
+
+    let comment_data = serde_json::json!({
+        "comment": {
+            "id": comment.id,
+            "thread_id": thread.id,
+            "author_id": comment.author_id,
+            "content": comment.content,
+            "quoted_text": thread.quoted_text,
+        },
+    });
+
+    app_state
+        .document_repository
+        .dispatch_webhooks(
+            WebhookEvent::CommentCreated,
+            &document,
+            comment.author_id,
+            Some(comment_data),
+        )
+        .await;
+}
@@ -0,0 +1,90 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Extension, Router,
+    http::HeaderMap,
+    middleware,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+};
+use tokio_stream::{
+    Stream, StreamExt,
+    wrappers::{BroadcastStream, errors::BroadcastStreamRecvError},
+};
+
+use crate::{
+    AppState,
+    events::DocumentEvent,
+    middleware::{JWTAuthMiddleware, document_scope},
+};
+
+pub fn events_handler() -> Router {
+    Router::new()
+        .route("/stream", get(stream_events))
+        .layer(middleware::from_fn(document_scope))
+}
+
+/// 推送当前用户可见的文档变更事件 (Server-Sent Events)
+///
+/// # 事件类型
+/// - `document.created` / `document.updated` / `document.deleted`
+/// - `document.shared` / `document.unshared`
+/// - `reset` -- 无法补发断线期间的事件，客户端需要重新拉取文档列表
+///
+/// # 断线续传
+/// 浏览器重连时会携带 `Last-Event-ID` 请求头，服务端从有界的历史缓冲区中
+/// 补发该 ID 之后的事件；若事件已被挤出缓冲区，则发送 `reset` 事件。
+pub async fn stream_events(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let user_id = user.user.id;
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+
+    let subscription = app_state.event_hub.subscribe(user_id, last_event_id);
+
+    tracing::info!(
+        "用户 {} 订阅文档事件，Last-Event-ID: {:?}，补发 {} 条事件",
+        user.user.email,
+        last_event_id,
+        subscription.replay.len()
+    );
+
+    let reset = subscription.missed.then(reset_event);
+    let replay = subscription
+        .replay
+        .into_iter()
+        .map(|event| to_sse_event(&event));
+
+    let live = BroadcastStream::new(subscription.receiver).filter_map(move |result| {
+        match result {
+            Ok(event) if event.is_visible_to(user_id) => Some(to_sse_event(&event)),
+            Ok(_) => None,
+            // -- 客户端消费过慢导致事件被覆盖，通知客户端重新同步
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                tracing::warn!("用户 {} 的事件流落后，丢弃 {} 条事件", user_id, skipped);
+                Some(reset_event())
+            }
+        }
+    });
+
+    let stream = tokio_stream::iter(reset.into_iter().chain(replay)).chain(live);
+
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+fn to_sse_event(event: &DocumentEvent) -> Result<Event, axum::Error> {
+    Event::default()
+        .id(event.id.to_string())
+        .event(event.kind.to_str())
+        .json_data(event)
+}
+
+fn reset_event() -> Result<Event, axum::Error> {
+    Ok(Event::default().event("reset").data("{}"))
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    response::IntoResponse,
+    routing::{delete, get},
+};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::IdentityExt,
+    dtos::{IdentityListResponseDto, Response},
+    error::HttpError,
+    middleware::JWTAuthMiddleware,
+    models::AuditAction,
+};
+
+/// 关联新的第三方账户走 OAuth 流程，见 `/auth/{provider}/link`
+pub fn identities_handler() -> Router {
+    Router::new()
+        .route("/", get(get_identities))
+        .route("/{identity_id}", delete(unlink_identity))
+}
+
+/// 获取当前用户关联的所有登录方式
+pub async fn get_identities(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let identities = app_state
+        .db_client
+        .get_user_identities(user.user.id)
+        .await?;
+
+    Ok(Json(IdentityListResponseDto {
+        status: "success".to_string(),
+        results: identities.len(),
+        identities,
+    }))
+}
+
+/// 取消关联登录方式
+///
+/// 至少要保留一种登录方式（其他关联账户、本地密码或通行密钥）。
+pub async fn unlink_identity(
+    Path(identity_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let identity = app_state
+        .db_client
+        .unlink_identity(user.user.id, identity_id)
+        .await?;
+
+    tracing::info!(
+        "用户 {} 取消关联了 {} 登录方式",
+        user.user.email,
+        identity.provider.to_str()
+    );
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::OAuthUnlinked)
+            .actor(&user.user)
+            .target("user", user.user.id)
+            .details(serde_json::json!({
+                "provider": identity.provider.to_str(),
+                "identity_id": identity.id,
+            })),
+    )
+    .await;
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Login method unlinked".to_string(),
+    }))
+}
@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post, put},
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    AppState, audit,
+    db::NotificationExt,
+    dtos::{
+        AccessRequestDto, NotificationListResponseDto, NotificationPreferencesDto,
+        NotificationPreferencesResponseDto, NotificationResponseDto, RequestQueryDto, Response,
+    },
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, document_scope, session_only},
+    rate_limit::RateLimitRoute,
+};
+
+pub fn notifications_handler() -> Router {
+    // -- 通知中心没有对应的令牌权限范围，只允许登录会话访问
+    let notification_center = Router::new()
+        .route("/", get(get_notifications))
+        .route("/read-all", put(mark_all_notifications_read))
+        .route("/{notification_id}/read", put(mark_notification_read))
+        .route(
+            "/preferences",
+            get(get_notification_preferences).put(update_notification_preferences),
+        )
+        .layer(middleware::from_fn(session_only));
+
+    Router::new()
+        .route(
+            "/access-requests",
+            post(request_document_access).layer(middleware::from_fn(document_scope)),
+        )
+        .merge(notification_center)
+}
+
+/// 分页获取当前用户的通知，未读通知排在前面
+pub async fn get_notifications(
+    Query(query_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let page = query_params.page.unwrap_or(1) as u32;
+    let limit = query_params.limit.unwrap_or(10);
+
+    let notifications = app_state
+        .db_client
+        .get_notifications(user.user.id, page, limit)
+        .await?;
+    let results = app_state
+        .db_client
+        .get_notification_count(user.user.id)
+        .await?;
+    let unread = app_state
+        .db_client
+        .get_unread_notification_count(user.user.id)
+        .await?;
+
+    let response = NotificationListResponseDto {
+        status: "success".to_string(),
+        notifications,
+        results,
+        unread,
+    };
+
+    Ok(Json(response))
+}
+
+/// 将单条通知标记为已读
+pub async fn mark_notification_read(
+    Path(notification_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let notification = app_state
+        .db_client
+        .mark_notification_read(notification_id, user.user.id)
+        .await?;
+
+    let response = NotificationResponseDto {
+        status: "success".to_string(),
+        data: notification,
+    };
+
+    Ok(Json(response))
+}
+
+/// 将当前用户的全部通知标记为已读
+pub async fn mark_all_notifications_read(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let updated = app_state
+        .db_client
+        .mark_all_notifications_read(user.user.id)
+        .await?;
+
+    tracing::info!("用户 {} 将 {} 条通知标记为已读", user.user.email, updated);
+
+    let response = Response {
+        status: "success",
+        message: format!("{} notifications marked as read", updated),
+    };
+
+    Ok(Json(response))
+}
+
+/// 获取各类通知的开关设置，未设置的类型默认开启
+pub async fn get_notification_preferences(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let preferences = app_state
+        .db_client
+        .get_notification_preferences(user.user.id)
+        .await?;
+
+    let response = NotificationPreferencesResponseDto {
+        status: "success".to_string(),
+        data: NotificationPreferencesDto { preferences },
+    };
+
+    Ok(Json(response))
+}
+
+/// 更新通知开关，关闭的类型不再生成新的通知
+pub async fn update_notification_preferences(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<NotificationPreferencesDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    for preference in &body.preferences {
+        app_state
+            .db_client
+            .set_notification_preference(
+                user.user.id,
+                preference.notification_type,
+                preference.enabled,
+            )
+            .await?;
+    }
+
+    let preferences = app_state
+        .db_client
+        .get_notification_preferences(user.user.id)
+        .await?;
+
+    let response = NotificationPreferencesResponseDto {
+        status: "success".to_string(),
+        data: NotificationPreferencesDto { preferences },
+    };
+
+    Ok(Json(response))
+}
+
+/// 向文档所有者申请访问权限
+///
+/// 按 IP 和申请人限流；同一文档的上一次申请未读时不会重复通知所有者。
+/// 文档不存在时同样返回成功，不暴露文档是否存在。
+pub async fn request_document_access(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<AccessRequestDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    app_state
+        .rate_limiter
+        .check(
+            RateLimitRoute::AccessRequest,
+            audit::current_context().ip(),
+            Some(&user.user.email),
+        )
+        .await?;
+
+    let notification = app_state
+        .db_client
+        .request_document_access(
+            body.document_id,
+            user.user.id,
+            serde_json::json!({
+                "name": user.user.name,
+                "email": user.user.email,
+                "message": body.message,
+            }),
+        )
+        .await?;
+
+    if notification.is_some() {
+        tracing::info!("用户 {} 申请访问文档 {}", user.user.email, body.document_id);
+    }
+
+    let response = Response {
+        status: "success",
+        message: "Access request sent".to_string(),
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
@@ -0,0 +1,371 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    response::IntoResponse,
+    routing::{delete, get, post},
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    config::Config,
+    db::{NewPasskey, PasskeyExt, TwoFactorExt},
+    dtos::{
+        PasskeyAssertionDto, PasskeyListResponseDto, PasskeyOptionsResponseDto, PasskeyRegisterDto,
+        PasskeyResponseDto, Response,
+    },
+    error::HttpError,
+    middleware::JWTAuthMiddleware,
+    models::{AuditAction, Passkey, User},
+    utils::webauthn::{self, RelyingParty},
+};
+
+/// 注册新的通行密钥
+pub const PURPOSE_REGISTRATION: &str = "registration";
+/// 免密登录，此时还不知道用户是谁
+pub const PURPOSE_LOGIN: &str = "login";
+/// 作为两步验证的第二因素
+pub const PURPOSE_SECOND_FACTOR: &str = "second_factor";
+
+/// 挑战有效期（分钟）
+const CHALLENGE_EXPIRES_MINUTES: i64 = 5;
+
+pub fn passkeys_handler() -> Router {
+    Router::new()
+        .route("/", get(get_passkeys))
+        .route("/register/options", post(registration_options))
+        .route("/register", post(register))
+        .route("/{passkey_id}", delete(delete_passkey))
+}
+
+/// 获取当前用户注册的所有通行密钥
+pub async fn get_passkeys(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let passkeys = app_state.db_client.get_user_passkeys(user.user.id).await?;
+
+    Ok(Json(PasskeyListResponseDto {
+        status: "success".to_string(),
+        results: passkeys.len(),
+        passkeys,
+    }))
+}
+
+/// 生成注册通行密钥的参数，前端原样传给 `navigator.credentials.create()`
+///
+/// 要求可发现凭证（resident key），这样登录时不需要先输入邮箱。
+pub async fn registration_options(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let challenge = webauthn::generate_challenge();
+    let challenge_id = create_challenge(
+        &app_state,
+        Some(user.user.id),
+        PURPOSE_REGISTRATION,
+        &challenge,
+    )
+    .await?;
+
+    // -- 已注册的凭证不能在同一个认证器上重复注册
+    let exclude_credentials: Vec<serde_json::Value> = app_state
+        .db_client
+        .get_user_passkeys(user.user.id)
+        .await?
+        .iter()
+        .map(credential_descriptor)
+        .collect();
+
+    let pub_key_cred_params: Vec<serde_json::Value> = webauthn::SUPPORTED_ALGORITHMS
+        .iter()
+        .map(|alg| serde_json::json!({ "type": "public-key", "alg": alg }))
+        .collect();
+
+    let public_key = serde_json::json!({
+        "rp": {
+            "id": app_state.env.webauthn_rp_id,
+            "name": app_state.env.webauthn_rp_name,
+        },
+        "user": {
+            "id": webauthn::encode(user.user.id.as_bytes()),
+            "name": user.user.email,
+            "displayName": user.user.name,
+        },
+        "challenge": challenge,
+        "pubKeyCredParams": pub_key_cred_params,
+        "timeout": CHALLENGE_EXPIRES_MINUTES * 60 * 1000,
+        "excludeCredentials": exclude_credentials,
+        "authenticatorSelection": {
+            "residentKey": "required",
+            "requireResidentKey": true,
+            "userVerification": "preferred",
+        },
+        "attestation": "none",
+    });
+
+    Ok(Json(PasskeyOptionsResponseDto {
+        status: "success".to_string(),
+        challenge_id,
+        public_key,
+    }))
+}
+
+/// 校验 `navigator.credentials.create()` 的结果并保存通行密钥
+pub async fn register(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<PasskeyRegisterDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let challenge = app_state
+        .db_client
+        .take_webauthn_challenge(body.challenge_id, PURPOSE_REGISTRATION)
+        .await?
+        .filter(|challenge| challenge.user_id == Some(user.user.id))
+        .ok_or_else(invalid_challenge)?;
+
+    let credential = webauthn::verify_registration(
+        &relying_party(&app_state.env),
+        &challenge.challenge,
+        &body.credential.response.client_data_json,
+        &body.credential.response.attestation_object,
+    )
+    .map_err(|e| {
+        tracing::warn!("用户 {} 注册通行密钥失败: {}", user.user.email, e);
+        HttpError::bad_request(format!("通行密钥注册失败: {}", e))
+    })?;
+
+    let passkey = app_state
+        .db_client
+        .create_passkey(NewPasskey {
+            user_id: user.user.id,
+            name: body.name.trim(),
+            credential_id: &credential.credential_id,
+            public_key: &credential.public_key,
+            algorithm: credential.algorithm as i32,
+            sign_count: credential.sign_count as i64,
+            transports: &body.credential.response.transports,
+        })
+        .await?;
+
+    tracing::info!("用户 {} 注册通行密钥 {}", user.user.email, passkey.name);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::PasskeyAdded)
+            .actor(&user.user)
+            .target("user", user.user.id)
+            .details(serde_json::json!({ "passkey_id": passkey.id, "name": passkey.name })),
+    )
+    .await;
+
+    Ok(Json(PasskeyResponseDto {
+        status: "success".to_string(),
+        data: passkey,
+    }))
+}
+
+/// 删除通行密钥
+///
+/// 角色要求两步验证时，不能删除最后一个第二因素。
+pub async fn delete_passkey(
+    Path(passkey_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let totp_enabled = app_state
+        .db_client
+        .get_user_totp(user.user.id)
+        .await?
+        .is_some_and(|totp| totp.confirmed_at.is_some());
+
+    let passkey_count = app_state
+        .db_client
+        .count_user_passkeys(user.user.id)
+        .await?;
+
+    if !totp_enabled
+        && passkey_count <= 1
+        && app_state
+            .db_client
+            .role_requires_two_factor(user.user.role)
+            .await?
+    {
+        return Err(HttpError::forbidden(
+            "当前角色要求开启两步验证，不能删除最后一个通行密钥".to_string(),
+        ));
+    }
+
+    let passkey = app_state
+        .db_client
+        .delete_passkey(passkey_id, user.user.id)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::PasskeyRemoved)
+            .actor(&user.user)
+            .target("user", user.user.id)
+            .details(serde_json::json!({ "passkey_id": passkey.id, "name": passkey.name })),
+    )
+    .await;
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Passkey deleted".to_string(),
+    }))
+}
+
+/// 生成认证参数，前端原样传给 `navigator.credentials.get()`
+///
+/// 指定用户时只允许该用户的凭证（第二因素）；不指定时由认证器选择可发现凭证（免密登录），
+/// 此时要求认证器验证用户身份。
+pub async fn authentication_options(
+    app_state: &AppState,
+    user: Option<&User>,
+) -> Result<PasskeyOptionsResponseDto, HttpError> {
+    let challenge = webauthn::generate_challenge();
+
+    let (purpose, allow_credentials, user_verification) = match user {
+        Some(user) => {
+            let passkeys = app_state.db_client.get_user_passkeys(user.id).await?;
+            if passkeys.is_empty() {
+                return Err(HttpError::bad_request("尚未注册通行密钥".to_string()));
+            }
+            let allow: Vec<serde_json::Value> =
+                passkeys.iter().map(credential_descriptor).collect();
+            (PURPOSE_SECOND_FACTOR, allow, "preferred")
+        }
+        None => (PURPOSE_LOGIN, Vec::new(), "required"),
+    };
+
+    let challenge_id =
+        create_challenge(app_state, user.map(|user| user.id), purpose, &challenge).await?;
+
+    Ok(PasskeyOptionsResponseDto {
+        status: "success".to_string(),
+        challenge_id,
+        public_key: serde_json::json!({
+            "challenge": challenge,
+            "rpId": app_state.env.webauthn_rp_id,
+            "timeout": CHALLENGE_EXPIRES_MINUTES * 60 * 1000,
+            "allowCredentials": allow_credentials,
+            "userVerification": user_verification,
+        }),
+    })
+}
+
+/// 校验 `navigator.credentials.get()` 返回的断言，返回使用的通行密钥
+///
+/// `user` 为 `None` 时是免密登录，要求认证器验证过用户身份；否则凭证必须属于该用户。
+/// 签名计数器没有增长说明认证器可能被克隆，拒绝本次认证。
+pub async fn verify_authentication(
+    app_state: &AppState,
+    user: Option<&User>,
+    challenge_id: Uuid,
+    credential: &PasskeyAssertionDto,
+) -> Result<Passkey, HttpError> {
+    let purpose = if user.is_some() {
+        PURPOSE_SECOND_FACTOR
+    } else {
+        PURPOSE_LOGIN
+    };
+    let challenge = app_state
+        .db_client
+        .take_webauthn_challenge(challenge_id, purpose)
+        .await?
+        .filter(|challenge| challenge.user_id == user.map(|user| user.id))
+        .ok_or_else(invalid_challenge)?;
+
+    let passkey = app_state
+        .db_client
+        .get_passkey_by_credential_id(credential.id.trim_end_matches('='))
+        .await?
+        .filter(|passkey| user.is_none_or(|user| user.id == passkey.user_id))
+        .ok_or_else(|| HttpError::unauthorized("通行密钥未注册".to_string()))?;
+
+    // -- 可发现凭证会返回注册时的用户 ID，必须与凭证所属用户一致
+    if let Some(user_handle) = &credential.response.user_handle
+        && webauthn::decode(user_handle).ok().as_deref() != Some(passkey.user_id.as_bytes())
+    {
+        return Err(HttpError::unauthorized("通行密钥与用户不匹配".to_string()));
+    }
+
+    let assertion = webauthn::verify_assertion(
+        &relying_party(&app_state.env),
+        &challenge.challenge,
+        &passkey.public_key,
+        &credential.response.client_data_json,
+        &credential.response.authenticator_data,
+        &credential.response.signature,
+    )
+    .map_err(|e| {
+        tracing::warn!("通行密钥 {} 认证失败: {}", passkey.id, e);
+        HttpError::unauthorized(format!("通行密钥认证失败: {}", e))
+    })?;
+
+    if user.is_none() && !assertion.user_verified {
+        return Err(HttpError::unauthorized(
+            "免密登录需要在认证器上验证身份".to_string(),
+        ));
+    }
+
+    // -- 计数器在数据库中比较并更新，并发的认证不会使用同一个计数值
+    let sign_count = assertion.sign_count as i64;
+    if !app_state
+        .db_client
+        .update_passkey_usage(passkey.id, sign_count)
+        .await?
+    {
+        tracing::warn!(
+            "通行密钥 {} 签名计数器未增长 ({} <= {})，可能被克隆",
+            passkey.id,
+            sign_count,
+            passkey.sign_count
+        );
+        return Err(HttpError::unauthorized("通行密钥认证失败".to_string()));
+    }
+
+    Ok(passkey)
+}
+
+async fn create_challenge(
+    app_state: &AppState,
+    user_id: Option<Uuid>,
+    purpose: &str,
+    challenge: &str,
+) -> Result<Uuid, HttpError> {
+    let expires_at = Utc::now() + Duration::minutes(CHALLENGE_EXPIRES_MINUTES);
+
+    Ok(app_state
+        .db_client
+        .create_webauthn_challenge(user_id, purpose, challenge, expires_at)
+        .await?)
+}
+
+fn relying_party(config: &Config) -> RelyingParty<'_> {
+    RelyingParty {
+        id: &config.webauthn_rp_id,
+        origin: &config.webauthn_origin,
+    }
+}
+
+fn credential_descriptor(passkey: &Passkey) -> serde_json::Value {
+    serde_json::json!({
+        "type": "public-key",
+        "id": passkey.credential_id,
+        "transports": passkey.transports,
+    })
+}
+
+fn invalid_challenge() -> HttpError {
+    HttpError::bad_request("认证请求无效或已过期，请重试".to_string())
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::Path,
+    response::IntoResponse,
+    routing::{delete, get},
+};
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    db::SessionExt,
+    dtos::{Response, SessionDto, SessionListResponseDto},
+    error::HttpError,
+    middleware::JWTAuthMiddleware,
+};
+
+pub fn sessions_handler() -> Router {
+    Router::new()
+        .route("/", get(get_sessions).delete(revoke_other_sessions))
+        .route("/{session_id}", delete(revoke_session))
+}
+
+/// 获取当前用户所有未过期的登录会话，包含设备、IP 和最近活动时间
+pub async fn get_sessions(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let sessions: Vec<SessionDto> = app_state
+        .db_client
+        .get_user_sessions(user.user.id)
+        .await?
+        .into_iter()
+        .map(|session| SessionDto {
+            current: Some(session.id) == user.session_id,
+            session,
+        })
+        .collect();
+
+    let response = SessionListResponseDto {
+        status: "success".to_string(),
+        results: sessions.len(),
+        sessions,
+    };
+
+    Ok(Json(response))
+}
+
+/// 吊销指定会话，该设备需要重新登录
+pub async fn revoke_session(
+    Path(session_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .revoke_session(session_id, user.user.id)
+        .await?;
+    app_state.session_cache.evict(&[session_id]);
+
+    tracing::info!("用户 {} 吊销会话 {}", user.user.email, session_id);
+
+    let response = Response {
+        status: "success",
+        message: "Session revoked successfully".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+/// 吊销除当前会话以外的所有会话，注销当前会话请使用 `/auth/logout`
+pub async fn revoke_other_sessions(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let revoked = app_state
+        .db_client
+        .revoke_user_sessions(user.user.id, Some(user.require_session()?))
+        .await?;
+    app_state.session_cache.evict(&revoked);
+
+    tracing::info!(
+        "用户 {} 吊销了 {} 个其他会话",
+        user.user.email,
+        revoked.len()
+    );
+
+    let response = Response {
+        status: "success",
+        message: format!("{} sessions revoked", revoked.len()),
+    };
+
+    Ok(Json(response))
+}
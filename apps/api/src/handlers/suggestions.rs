@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, put},
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    db::{DbError, SuggestionExt},
+    dtos::{
+        CreateSuggestionDto, SuggestionListResponseDto, SuggestionResponseDto, SuggestionsQueryDto,
+    },
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, document_scope},
+    models::SuggestionStatus,
+    repositories::document::DocumentRepository,
+    utils::suggestion::apply_suggestion,
+};
+
+pub fn suggestions_handler() -> Router {
+    Router::new()
+        .route("/", get(get_suggestions).post(create_suggestion))
+        .route("/{suggestion_id}/accept", put(accept_suggestion))
+        .route("/{suggestion_id}/reject", put(reject_suggestion))
+        .layer(middleware::from_fn(document_scope))
+}
+
+/// 获取文档的修改建议，可按状态过滤
+pub async fn get_suggestions(
+    Query(query_params): Query<SuggestionsQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let suggestions = app_state
+        .db_client
+        .get_suggestions(query_params.document_id, user.user.id, query_params.status)
+        .await?;
+
+    let response = SuggestionListResponseDto {
+        status: "success".to_string(),
+        results: suggestions.len(),
+        suggestions,
+    };
+
+    Ok(Json(response))
+}
+
+/// 针对文档某个版本中的文本范围提出插入、删除或替换建议
+///
+/// 拥有评论权限即可提出建议，建议在被接受前不会修改文档。
+pub async fn create_suggestion(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<CreateSuggestionDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate().map_err(|e| {
+        tracing::warn!("修改建议请求验证失败: {}", e);
+        HttpError::bad_request(e.to_string())
+    })?;
+
+    let kind = body.kind();
+    let suggestion = app_state
+        .db_client
+        .create_suggestion(
+            body.document_id,
+            user.user.id,
+            kind,
+            body.block_id,
+            body.start_offset,
+            body.end_offset,
+            body.original_text,
+            body.text,
+            body.base_revision,
+        )
+        .await?;
+
+    tracing::info!(
+        "用户 {} 在文档 {} 上提出修改建议 {} ({})",
+        user.user.email,
+        suggestion.document_id,
+        suggestion.id,
+        suggestion.kind.to_str()
+    );
+
+    let response = SuggestionResponseDto {
+        status: "success".to_string(),
+        data: suggestion,
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// 接受修改建议，需要文档编辑权限
+///
+/// 文档内容和建议状态在同一个事务中更新，且只在文档仍是读取时的版本时写入，
+/// 并发的编辑不会被覆盖，建议也不会被重复应用。写入后的评论锚点重映射、
+/// 事件推送等与正常的文档更新相同，失败只记录错误日志。
+/// 记录提出者 (`author_id`)、批准者 (`reviewed_by`) 以及生成的文档版本。
+/// 文档在建议之后发生了无法自动合并的修改，或在接受过程中被修改时返回 409，建议保持待处理状态。
+pub async fn accept_suggestion(
+    Path(suggestion_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let reviewer_id = user.user.id;
+
+    let suggestion = app_state
+        .db_client
+        .get_suggestion(suggestion_id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Suggestion not found"))?;
+
+    if suggestion.status != SuggestionStatus::Pending {
+        return Err(HttpError::new(
+            "Suggestion has already been reviewed",
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    let previous = app_state
+        .document_repository
+        .get_document(suggestion.document_id, Some(reviewer_id))
+        .await?
+        .ok_or_else(|| HttpError::not_found("Document not found"))?;
+
+    let content = apply_suggestion(&previous.content, &suggestion, previous.revision).map_err(
+        |conflict| {
+            tracing::warn!(
+                "修改建议 {} 无法应用到文档 {}: {}",
+                suggestion_id,
+                previous.id,
+                conflict
+            );
+            HttpError::new(conflict.to_string(), StatusCode::CONFLICT)
+        },
+    )?;
+
+    let (suggestion, document) = app_state
+        .db_client
+        .accept_suggestion(suggestion_id, reviewer_id, previous.revision, content)
+        .await
+        .map_err(review_error)?;
+
+    app_state
+        .document_repository
+        .document_updated(Some(&previous), &document, true, reviewer_id)
+        .await;
+
+    tracing::info!(
+        "用户 {} 接受了用户 {} 的修改建议 {}，文档 {} 更新至版本 {}",
+        user.user.email,
+        suggestion.author_id,
+        suggestion_id,
+        document.id,
+        document.revision
+    );
+
+    let response = SuggestionResponseDto {
+        status: "success".to_string(),
+        data: suggestion,
+    };
+
+    Ok(Json(response))
+}
+
+/// 拒绝修改建议，文档编辑者或建议提出者本人（撤回）可操作
+pub async fn reject_suggestion(
+    Path(suggestion_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let suggestion = app_state
+        .db_client
+        .review_suggestion(suggestion_id, user.user.id, SuggestionStatus::Rejected)
+        .await
+        .map_err(review_error)?;
+
+    tracing::info!("用户 {} 拒绝了修改建议 {}", user.user.email, suggestion_id);
+
+    let response = SuggestionResponseDto {
+        status: "success".to_string(),
+        data: suggestion,
+    };
+
+    Ok(Json(response))
+}
+
+/// 建议已被他人处理或文档已被修改时返回 409
+fn review_error(error: DbError) -> HttpError {
+    match error {
+        DbError::ConstraintViolation(message) => HttpError::new(message, StatusCode::CONFLICT),
+        error => error.into(),
+    }
+}
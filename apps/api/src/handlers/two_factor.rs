@@ -0,0 +1,380 @@
+use std::sync::Arc;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    Extension, Json, Router,
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    audit::{self, AuditEvent},
+    db::{PasskeyExt, TwoFactorExt},
+    dtos::{
+        RecoveryCodesResponseDto, Response, RolePolicyListResponseDto, RolePolicyResponseDto,
+        TotpSetupDto, TotpSetupResponseDto, TwoFactorCodeDto, TwoFactorStatusDto,
+        TwoFactorStatusResponseDto, UpdateRolePolicyDto,
+    },
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, fresh_user, role_check},
+    models::{AuditAction, User, UserRole},
+    utils::{token, totp},
+};
+
+/// 连续验证失败达到此次数后暂时锁定
+const MAX_FAILED_ATTEMPTS: i32 = 5;
+/// 锁定时长（分钟）
+const LOCKOUT_MINUTES: i64 = 15;
+/// 每次生成的恢复码数量
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+pub fn two_factor_handler() -> Router {
+    let policy_routes = Router::new()
+        .route("/", get(get_role_policies).put(update_role_policy))
+        // -- 角色策略只有管理员可以查看和修改
+        .layer(middleware::from_fn(|state, req, next| {
+            role_check(state, req, next, vec![UserRole::Admin])
+        }))
+        .layer(middleware::from_fn(fresh_user));
+
+    Router::new()
+        .route("/", get(get_status).delete(disable))
+        .route("/setup", post(setup))
+        .route("/confirm", post(confirm))
+        .route("/recovery-codes", post(regenerate_recovery_codes))
+        .nest("/policies", policy_routes)
+}
+
+/// 两步验证使用的第二因素
+pub enum SecondFactor {
+    Totp,
+    RecoveryCode,
+}
+
+impl SecondFactor {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            SecondFactor::Totp => "totp",
+            SecondFactor::RecoveryCode => "recovery_code",
+        }
+    }
+}
+
+/// 生成新的 TOTP 密钥，开始设置两步验证
+pub async fn start_enrollment(
+    app_state: &AppState,
+    user: &User,
+) -> Result<TotpSetupDto, HttpError> {
+    let secret = totp::generate_secret();
+
+    app_state
+        .db_client
+        .start_totp_enrollment(user.id, &secret)
+        .await?;
+
+    Ok(TotpSetupDto {
+        otpauth_uri: totp::otpauth_uri(&app_state.env.totp_issuer, &user.email, &secret),
+        secret,
+    })
+}
+
+/// 用认证器应用中的验证码确认设置，返回一次性展示的恢复码
+pub async fn confirm_enrollment(
+    app_state: &AppState,
+    user: &User,
+    code: &str,
+) -> Result<Vec<String>, HttpError> {
+    let pending = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await?
+        .filter(|totp| totp.confirmed_at.is_none())
+        .ok_or_else(|| HttpError::bad_request("请先开始设置两步验证".to_string()))?;
+
+    let step = totp::verify(&pending.secret, code.trim(), Utc::now().timestamp())
+        .ok_or_else(invalid_code)?;
+
+    let (codes, hashes) = generate_recovery_codes();
+    app_state
+        .db_client
+        .confirm_totp(user.id, step, &hashes)
+        .await?;
+
+    tracing::info!("用户 {} 开启两步验证", user.email);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::TwoFactorEnabled)
+            .actor(user)
+            .target("user", user.id),
+    )
+    .await;
+
+    Ok(codes)
+}
+
+/// 校验 TOTP 验证码或恢复码
+///
+/// 同一个验证码只能使用一次；连续失败 `MAX_FAILED_ATTEMPTS` 次后，
+/// `LOCKOUT_MINUTES` 分钟内拒绝所有尝试。
+pub async fn verify_second_factor(
+    app_state: &AppState,
+    user: &User,
+    code: &str,
+) -> Result<SecondFactor, HttpError> {
+    let factor = app_state
+        .db_client
+        .get_user_totp(user.id)
+        .await?
+        .filter(|totp| totp.confirmed_at.is_some())
+        .ok_or_else(|| HttpError::bad_request("未开启两步验证".to_string()))?;
+
+    // -- 先计入尝试次数再比较，并发请求也无法超过次数限制
+    let attempts = app_state
+        .db_client
+        .take_totp_attempt(user.id, MAX_FAILED_ATTEMPTS, LOCKOUT_MINUTES * 60)
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("用户 {} 两步验证已锁定", user.email);
+            HttpError::new(
+                "验证失败次数过多，请稍后再试".to_string(),
+                StatusCode::TOO_MANY_REQUESTS,
+            )
+        })?;
+
+    let code = code.trim();
+    if code.len() == 6 && code.bytes().all(|b| b.is_ascii_digit()) {
+        if let Some(step) = totp::verify(&factor.secret, code, Utc::now().timestamp())
+            && app_state.db_client.accept_totp_step(user.id, step).await?
+        {
+            return Ok(SecondFactor::Totp);
+        }
+    } else if app_state
+        .db_client
+        .use_recovery_code(user.id, &hash_recovery_code(code))
+        .await?
+    {
+        let remaining = app_state.db_client.count_recovery_codes(user.id).await?;
+
+        audit::record(
+            &app_state.db_client,
+            AuditEvent::new(AuditAction::RecoveryCodeUsed)
+                .actor(user)
+                .target("user", user.id)
+                .details(serde_json::json!({ "remaining": remaining })),
+        )
+        .await;
+
+        return Ok(SecondFactor::RecoveryCode);
+    }
+
+    tracing::warn!("用户 {} 两步验证失败，连续失败 {} 次", user.email, attempts);
+
+    Err(invalid_code())
+}
+
+/// 查询当前用户的两步验证状态
+pub async fn get_status(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let factor = app_state.db_client.get_user_totp(user.user.id).await?;
+    let required = app_state
+        .db_client
+        .role_requires_two_factor(user.user.role)
+        .await?;
+    let recovery_codes_remaining = app_state
+        .db_client
+        .count_recovery_codes(user.user.id)
+        .await?;
+
+    Ok(Json(TwoFactorStatusResponseDto {
+        status: "success".to_string(),
+        data: TwoFactorStatusDto {
+            enabled: factor.as_ref().is_some_and(|f| f.confirmed_at.is_some()),
+            pending: factor.as_ref().is_some_and(|f| f.confirmed_at.is_none()),
+            required,
+            recovery_codes_remaining,
+        },
+    }))
+}
+
+/// 开始设置两步验证 -- 返回密钥和供认证器应用扫码的 otpauth URI
+pub async fn setup(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let data = start_enrollment(&app_state, &user.user).await?;
+
+    Ok(Json(TotpSetupResponseDto {
+        status: "success".to_string(),
+        data,
+    }))
+}
+
+/// 确认设置两步验证 -- 返回恢复码，恢复码只展示这一次
+pub async fn confirm(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<TwoFactorCodeDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let recovery_codes = confirm_enrollment(&app_state, &user.user, &body.code).await?;
+
+    Ok(Json(RecoveryCodesResponseDto {
+        status: "success".to_string(),
+        recovery_codes,
+    }))
+}
+
+/// 重新生成恢复码，旧的恢复码全部失效，需要提交当前的验证码
+pub async fn regenerate_recovery_codes(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<TwoFactorCodeDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    verify_second_factor(&app_state, &user.user, &body.code).await?;
+
+    let (recovery_codes, hashes) = generate_recovery_codes();
+    app_state
+        .db_client
+        .replace_recovery_codes(user.user.id, &hashes)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::RecoveryCodesRegenerated)
+            .actor(&user.user)
+            .target("user", user.user.id),
+    )
+    .await;
+
+    Ok(Json(RecoveryCodesResponseDto {
+        status: "success".to_string(),
+        recovery_codes,
+    }))
+}
+
+/// 关闭两步验证，需要提交当前的验证码；角色要求两步验证且没有通行密钥时不允许关闭
+pub async fn disable(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<TwoFactorCodeDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    // -- 注册了通行密钥时仍然满足角色要求
+    if app_state
+        .db_client
+        .count_user_passkeys(user.user.id)
+        .await?
+        == 0
+        && app_state
+            .db_client
+            .role_requires_two_factor(user.user.role)
+            .await?
+    {
+        return Err(HttpError::forbidden(
+            "当前角色要求开启两步验证，不能关闭".to_string(),
+        ));
+    }
+
+    verify_second_factor(&app_state, &user.user, &body.code).await?;
+
+    app_state.db_client.disable_totp(user.user.id).await?;
+
+    tracing::info!("用户 {} 关闭两步验证", user.user.email);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::TwoFactorDisabled)
+            .actor(&user.user)
+            .target("user", user.user.id),
+    )
+    .await;
+
+    Ok(Json(Response {
+        status: "success",
+        message: "Two-factor authentication disabled".to_string(),
+    }))
+}
+
+/// 查询所有角色的两步验证策略
+pub async fn get_role_policies(
+    Extension(app_state): Extension<Arc<AppState>>,
+) -> Result<impl IntoResponse, HttpError> {
+    let policies = app_state.db_client.get_role_policies().await?;
+
+    Ok(Json(RolePolicyListResponseDto {
+        status: "success".to_string(),
+        policies,
+    }))
+}
+
+/// 设置某个角色是否必须开启两步验证
+///
+/// 开启后，该角色下尚未设置两步验证的用户在下次登录时必须先完成设置。
+pub async fn update_role_policy(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<UpdateRolePolicyDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    let policy = app_state
+        .db_client
+        .set_role_requires_two_factor(body.role, body.require_two_factor)
+        .await?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::RolePolicyChanged)
+            .actor(&user.user)
+            .details(serde_json::json!({
+                "role": body.role.to_str(),
+                "require_two_factor": body.require_two_factor,
+            })),
+    )
+    .await;
+
+    Ok(Json(RolePolicyResponseDto {
+        status: "success".to_string(),
+        data: policy,
+    }))
+}
+
+fn invalid_code() -> HttpError {
+    HttpError::bad_request("验证码错误或已使用".to_string())
+}
+
+/// 生成一组恢复码，格式为 `xxxxx-xxxxx`，返回明文和哈希
+fn generate_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 10];
+            OsRng.fill_bytes(&mut bytes);
+            let chars: String = bytes
+                .iter()
+                .map(|b| RECOVERY_CODE_ALPHABET[*b as usize % RECOVERY_CODE_ALPHABET.len()] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect();
+    let hashes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+
+    (codes, hashes)
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    token::hash_opaque_token(&code.trim().to_lowercase())
+}
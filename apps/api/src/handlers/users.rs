@@ -3,26 +3,36 @@ use std::sync::Arc;
 use axum::{
     Extension, Json, Router,
     extract::Query,
+    http::StatusCode,
     middleware,
     response::IntoResponse,
     routing::{get, put},
 };
+use chrono::{Duration, Utc};
 use validator::Validate;
 
 use crate::{
     AppState,
-    db::UserExt,
+    audit::{self, AuditEvent},
+    db::{DbError, EmailChangeExt, IdentityExt, NotificationExt, SessionExt, UserExt},
     dtos::{
-        FilterUserDto, NameUpdateDto, RequestQueryDto, Response, RoleUpdateDto, UserData,
-        UserListResponseDto, UserPasswordUpdateDto, UserResponseDto,
+        EmailUpdateDto, FilterUserDto, NameUpdateDto, RequestQueryDto, Response, RoleUpdateDto,
+        UserData, UserListResponseDto, UserPasswordUpdateDto, UserResponseDto,
     },
     error::{ErrorMessage, HttpError},
-    middleware::{JWTAuthMiddleware, role_check},
-    models::UserRole,
+    mail::mails::{send_email_change_email, send_email_change_notice_email},
+    middleware::{JWTAuthMiddleware, fresh_user, role_check, scope_check, session_only},
+    models::{AuditAction, AuthProvider, TokenScope, UserRole},
+    password_policy::PasswordOwner,
     repositories::UserRepository,
-    utils::password,
+    utils::{password, token},
 };
 
+/// 新邮箱确认链接的有效期（小时）
+const EMAIL_CHANGE_CONFIRM_HOURS: i64 = 24;
+/// 旧邮箱撤销链接的有效期（天）
+const EMAIL_CHANGE_REVERT_DAYS: i64 = 7;
+
 pub fn users_handler() -> Router {
     Router::new()
         .route(
@@ -33,25 +43,53 @@ pub fn users_handler() -> Router {
         )
         .route(
             "/users",
-            get(get_users).layer(middleware::from_fn(|state, req, next| {
-                role_check(state, req, next, vec![UserRole::Admin])
-            })),
+            get(get_users)
+                .layer(middleware::from_fn(|state, req, next| {
+                    role_check(state, req, next, vec![UserRole::Admin])
+                }))
+                .layer(middleware::from_fn(|req, next| {
+                    scope_check(req, next, TokenScope::Admin)
+                }))
+                .layer(middleware::from_fn(fresh_user)),
+        )
+        // -- 修改账户信息只允许登录会话，个人访问令牌只能读取 `/me`
+        .route(
+            "/name",
+            put(update_user_name).layer(middleware::from_fn(session_only)),
+        )
+        .route(
+            "/role",
+            put(update_user_role)
+                .layer(middleware::from_fn(session_only))
+                .layer(middleware::from_fn(fresh_user)),
+        )
+        .route(
+            "/password",
+            put(update_user_password).layer(middleware::from_fn(session_only)),
+        )
+        .route(
+            "/email",
+            put(request_email_change).layer(middleware::from_fn(session_only)),
         )
-        .route("/name", put(update_user_name))
-        .route("/role", put(update_user_role))
-        .route("/password", put(update_user_password))
 }
 
 pub async fn get_me(
-    Extension(_app_state): Extension<Arc<AppState>>,
+    Extension(app_state): Extension<Arc<AppState>>,
     Extension(user): Extension<JWTAuthMiddleware>,
 ) -> Result<impl IntoResponse, HttpError> {
     let filtered_user = FilterUserDto::filter_user(&user.user);
 
+    // -- 附带未读通知数量，供前端显示角标
+    let unread_notifications = app_state
+        .db_client
+        .get_unread_notification_count(user.user.id)
+        .await?;
+
     let response_data = UserResponseDto {
         status: "success".to_string(),
         data: UserData {
             user: filtered_user,
+            unread_notifications: Some(unread_notifications),
         },
     };
 
@@ -120,12 +158,14 @@ pub async fn update_user_name(
             tracing::error!("更新用户名失败: {}", e);
             HttpError::server_error(e.to_string())
         })?;
+    app_state.session_cache.evict_user(user_id);
 
     let filtered_user = FilterUserDto::filter_user(&result);
 
     let response = UserResponseDto {
         data: UserData {
             user: filtered_user,
+            unread_notifications: None,
         },
         status: "success".to_string(),
     };
@@ -163,12 +203,26 @@ pub async fn update_user_role(
             tracing::error!("更新用户角色失败: {}", e);
             HttpError::server_error(e.to_string())
         })?;
+    app_state.session_cache.evict_user(result.id);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::RoleChanged)
+            .actor(&user.user)
+            .target("user", result.id)
+            .details(serde_json::json!({
+                "from": user.user.role.to_str(),
+                "to": result.role.to_str(),
+            })),
+    )
+    .await;
 
     let filtered_user = FilterUserDto::filter_user(&result);
 
     let response = UserResponseDto {
         data: UserData {
             user: filtered_user,
+            unread_notifications: None,
         },
         status: "success".to_string(),
     };
@@ -188,6 +242,7 @@ pub async fn update_user_password(
         HttpError::bad_request(e.to_string())
     })?;
 
+    let session_id = user.require_session()?;
     let user = &user.user;
     let user_id = uuid::Uuid::parse_str(&user.id.to_string()).unwrap();
 
@@ -211,6 +266,20 @@ pub async fn update_user_password(
         ));
     }
 
+    app_state
+        .password_policy
+        .check(
+            &app_state.db_client,
+            &body.new_password,
+            PasswordOwner {
+                id: Some(user_id),
+                name: &user.name,
+                email: &user.email,
+                current_hash: Some(&user.password),
+            },
+        )
+        .await?;
+
     let hash_password = password::hash(&body.new_password).map_err(|e| {
         tracing::error!("密码加密失败: {}", e);
         HttpError::server_error(e.to_string())
@@ -224,9 +293,29 @@ pub async fn update_user_password(
             tracing::error!("更新密码失败: {}", e);
             HttpError::server_error(e.to_string())
         })?;
+    app_state
+        .password_policy
+        .remember(&app_state.db_client, user_id, &user.password)
+        .await?;
+    app_state.session_cache.evict_user(user_id);
 
     tracing::info!("密码更新成功，用户ID: {}", user.id);
 
+    // -- 修改密码后只保留当前会话，其他设备需要重新登录
+    let revoked = app_state
+        .db_client
+        .revoke_user_sessions(user_id, Some(session_id))
+        .await?;
+    app_state.session_cache.evict(&revoked);
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::PasswordChanged)
+            .actor(&user)
+            .target("user", user.id),
+    )
+    .await;
+
     let response = Response {
         message: "Password updated Successfully".to_string(),
         status: "success",
@@ -234,3 +323,126 @@ pub async fn update_user_password(
 
     Ok(Json(response))
 }
+
+/// 申请修改邮箱 -- 向新邮箱发送确认链接，同时通知旧邮箱并附带撤销链接
+///
+/// 设置了密码的账户需要提交当前密码，没有密码的账户需要在几分钟内重新登录过。
+/// 新邮箱确认后才会生效；撤销链接在有效期内可以取消尚未确认的修改，或把已确认的修改改回旧邮箱。
+pub async fn request_email_change(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(auth): Extension<JWTAuthMiddleware>,
+    Json(body): Json<EmailUpdateDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate().map_err(|e| {
+        tracing::warn!("邮箱修改请求验证失败: {}", e);
+        HttpError::bad_request(e.to_string())
+    })?;
+
+    let user = &auth.user;
+    let new_email = body.new_email.trim().to_lowercase();
+
+    if new_email == user.email.to_lowercase() {
+        return Err(HttpError::bad_request("新邮箱与当前邮箱相同".to_string()));
+    }
+
+    // -- 只通过第三方登录或钱包创建的账户没有可以确认的密码，改为要求刚刚重新登录
+    if app_state
+        .db_client
+        .has_identity(user.id, AuthProvider::Local)
+        .await?
+    {
+        let password_match = password::compare(&body.password, &user.password)
+            .map_err(|_| HttpError::bad_request(ErrorMessage::WrongCredentials.to_string()))?;
+
+        if !password_match {
+            tracing::warn!("修改邮箱时密码不匹配，用户ID: {}", user.id);
+            return Err(HttpError::bad_request(
+                ErrorMessage::WrongCredentials.to_string(),
+            ));
+        }
+    } else {
+        auth.require_recent_login(&app_state.db_client).await?;
+    }
+
+    if app_state
+        .db_client
+        .get_user(None, None, Some(&new_email), None)
+        .await?
+        .is_some()
+    {
+        return Err(HttpError::unique_constraint_violation(
+            ErrorMessage::EmailExist.to_string(),
+        ));
+    }
+
+    let confirm_token = token::generate_opaque_token();
+    let revert_token = token::generate_opaque_token();
+    let now = Utc::now();
+
+    app_state
+        .db_client
+        .create_email_change(
+            user.id,
+            &user.email,
+            &new_email,
+            &token::hash_opaque_token(&confirm_token),
+            now + Duration::hours(EMAIL_CHANGE_CONFIRM_HOURS),
+            &token::hash_opaque_token(&revert_token),
+            now + Duration::days(EMAIL_CHANGE_REVERT_DAYS),
+        )
+        .await
+        .map_err(|e| match e {
+            DbError::ConstraintViolation(_) => HttpError::new(
+                format!(
+                    "上一次修改的邮箱在 {} 天内仍可撤销，期间不能再次修改邮箱",
+                    EMAIL_CHANGE_REVERT_DAYS
+                ),
+                StatusCode::CONFLICT,
+            ),
+            e => e.into(),
+        })?;
+
+    audit::record(
+        &app_state.db_client,
+        AuditEvent::new(AuditAction::EmailChangeRequested)
+            .actor(user)
+            .target("user", user.id)
+            .details(serde_json::json!({ "from": user.email, "to": new_email })),
+    )
+    .await;
+
+    tracing::info!("用户 {} 申请把邮箱修改为 {}", user.email, new_email);
+
+    let (old_email, name) = (user.email.clone(), user.name.clone());
+    tokio::spawn(async move {
+        if let Err(e) = send_email_change_email(
+            &new_email,
+            &name,
+            &confirm_token,
+            EMAIL_CHANGE_CONFIRM_HOURS,
+        )
+        .await
+        {
+            tracing::error!("发送邮箱确认邮件失败: {}", e);
+        }
+        if let Err(e) = send_email_change_notice_email(
+            &old_email,
+            &name,
+            &new_email,
+            &revert_token,
+            EMAIL_CHANGE_REVERT_DAYS,
+        )
+        .await
+        {
+            tracing::error!("发送邮箱修改通知失败: {}", e);
+        }
+    });
+
+    Ok(Json(Response {
+        status: "success",
+        message: format!(
+            "确认邮件已发送到新邮箱，请在 {} 小时内完成确认",
+            EMAIL_CHANGE_CONFIRM_HOURS
+        ),
+    }))
+}
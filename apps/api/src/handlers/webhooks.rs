@@ -0,0 +1,300 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, Query},
+    http::StatusCode,
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    AppState,
+    db::WebhookExt,
+    dtos::{
+        CreateWebhookDto, RequestQueryDto, Response, UpdateWebhookDto, WebhookCreatedDto,
+        WebhookCreatedResponseDto, WebhookDeliveryDto, WebhookDeliveryListResponseDto,
+        WebhookDeliveryResponseDto, WebhookListResponseDto, WebhookResponseDto,
+    },
+    error::HttpError,
+    middleware::{JWTAuthMiddleware, session_only},
+    models::{Webhook, WebhookDelivery, WebhookEvent},
+    webhooks,
+};
+
+pub fn webhooks_handler() -> Router {
+    Router::new()
+        .route("/", get(get_webhooks).post(create_webhook))
+        .route(
+            "/{webhook_id}",
+            get(get_webhook).put(update_webhook).delete(delete_webhook),
+        )
+        .route("/{webhook_id}/deliveries", get(get_deliveries))
+        .route("/{webhook_id}/deliveries/{delivery_id}", get(get_delivery))
+        .route(
+            "/{webhook_id}/deliveries/{delivery_id}/redeliver",
+            post(redeliver),
+        )
+        // -- Webhook 会把文档事件推送到外部地址，只允许登录会话管理
+        .layer(middleware::from_fn(session_only))
+}
+
+/// 获取当前用户注册的 Webhook
+pub async fn get_webhooks(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let webhooks = app_state.db_client.get_user_webhooks(user.user.id).await?;
+
+    let response = WebhookListResponseDto {
+        status: "success".to_string(),
+        results: webhooks.len(),
+        webhooks,
+    };
+
+    Ok(Json(response))
+}
+
+/// 注册 Webhook
+///
+/// 不指定 `document_id` 时接收当前用户可访问的所有文档的事件。
+/// 签名密钥只在创建时返回一次，用于校验 `X-Webhook-Signature` 请求头。
+pub async fn create_webhook(
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<CreateWebhookDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate().map_err(|e| {
+        tracing::warn!("Webhook 请求验证失败: {}", e);
+        HttpError::bad_request(e.to_string())
+    })?;
+    validate_webhook_url(&app_state, &body.url).await?;
+
+    let secret = webhooks::generate_secret();
+    let webhook = app_state
+        .db_client
+        .create_webhook(
+            user.user.id,
+            body.document_id,
+            body.url,
+            secret.clone(),
+            event_names(&body.events),
+        )
+        .await?;
+
+    tracing::info!(
+        "用户 {} 注册 Webhook {} -> {}",
+        user.user.email,
+        webhook.id,
+        webhook.url
+    );
+
+    let response = WebhookCreatedResponseDto {
+        status: "success".to_string(),
+        data: WebhookCreatedDto { webhook, secret },
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+pub async fn get_webhook(
+    Path(webhook_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let webhook = owned_webhook(&app_state, webhook_id, &user).await?;
+
+    let response = WebhookResponseDto {
+        status: "success".to_string(),
+        data: webhook,
+    };
+
+    Ok(Json(response))
+}
+
+/// 修改 Webhook 的地址、订阅事件或启用状态
+pub async fn update_webhook(
+    Path(webhook_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+    Json(body): Json<UpdateWebhookDto>,
+) -> Result<impl IntoResponse, HttpError> {
+    body.validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+    if let Some(url) = &body.url {
+        validate_webhook_url(&app_state, url).await?;
+    }
+
+    let webhook = app_state
+        .db_client
+        .update_webhook(
+            webhook_id,
+            user.user.id,
+            body.url,
+            body.events.as_deref().map(event_names),
+            body.active,
+        )
+        .await?;
+
+    let response = WebhookResponseDto {
+        status: "success".to_string(),
+        data: webhook,
+    };
+
+    Ok(Json(response))
+}
+
+pub async fn delete_webhook(
+    Path(webhook_id): Path<Uuid>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    app_state
+        .db_client
+        .delete_webhook(webhook_id, user.user.id)
+        .await?;
+
+    tracing::info!("用户 {} 删除 Webhook {}", user.user.email, webhook_id);
+
+    let response = Response {
+        status: "success",
+        message: "Webhook deleted successfully".to_string(),
+    };
+
+    Ok(Json(response))
+}
+
+/// 分页获取 Webhook 的投递记录，包含最近一次的响应状态码
+pub async fn get_deliveries(
+    Path(webhook_id): Path<Uuid>,
+    Query(query_params): Query<RequestQueryDto>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    query_params
+        .validate()
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let webhook = owned_webhook(&app_state, webhook_id, &user).await?;
+
+    let page = query_params.page.unwrap_or(1) as u32;
+    let limit = query_params.limit.unwrap_or(10);
+
+    let deliveries = app_state
+        .db_client
+        .get_webhook_deliveries(webhook.id, page, limit)
+        .await?;
+
+    let response = WebhookDeliveryListResponseDto {
+        status: "success".to_string(),
+        results: deliveries.len(),
+        deliveries,
+    };
+
+    Ok(Json(response))
+}
+
+/// 获取单次投递及每次尝试的响应日志
+pub async fn get_delivery(
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let delivery = owned_delivery(&app_state, webhook_id, delivery_id, &user).await?;
+
+    delivery_response(&app_state, delivery).await
+}
+
+/// 重新投递，重置重试计划并立即进入发送队列
+pub async fn redeliver(
+    Path((webhook_id, delivery_id)): Path<(Uuid, Uuid)>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    Extension(user): Extension<JWTAuthMiddleware>,
+) -> Result<impl IntoResponse, HttpError> {
+    let delivery = owned_delivery(&app_state, webhook_id, delivery_id, &user).await?;
+
+    let delivery = app_state
+        .db_client
+        .redeliver_webhook_delivery(delivery.id)
+        .await?;
+
+    tracing::info!(
+        "用户 {} 重新投递 Webhook {} 的投递 {}",
+        user.user.email,
+        webhook_id,
+        delivery_id
+    );
+
+    delivery_response(&app_state, delivery).await
+}
+
+/// 获取当前用户的 Webhook，其他用户的 Webhook 视为不存在
+async fn owned_webhook(
+    app_state: &AppState,
+    webhook_id: Uuid,
+    user: &JWTAuthMiddleware,
+) -> Result<Webhook, HttpError> {
+    app_state
+        .db_client
+        .get_webhook(webhook_id)
+        .await?
+        .filter(|webhook| webhook.user_id == user.user.id)
+        .ok_or_else(|| HttpError::not_found("Webhook not found"))
+}
+
+async fn owned_delivery(
+    app_state: &AppState,
+    webhook_id: Uuid,
+    delivery_id: Uuid,
+    user: &JWTAuthMiddleware,
+) -> Result<WebhookDelivery, HttpError> {
+    let webhook = owned_webhook(app_state, webhook_id, user).await?;
+
+    app_state
+        .db_client
+        .get_webhook_delivery(delivery_id)
+        .await?
+        .filter(|delivery| delivery.webhook_id == webhook.id)
+        .ok_or_else(|| HttpError::not_found("Webhook delivery not found"))
+}
+
+async fn delivery_response(
+    app_state: &AppState,
+    delivery: WebhookDelivery,
+) -> Result<Json<WebhookDeliveryResponseDto>, HttpError> {
+    let attempts_log = app_state
+        .db_client
+        .get_webhook_delivery_attempts(delivery.id)
+        .await?;
+
+    Ok(Json(WebhookDeliveryResponseDto {
+        status: "success".to_string(),
+        data: WebhookDeliveryDto {
+            delivery,
+            attempts_log,
+        },
+    }))
+}
+
+/// 只允许 http/https 地址，且不能指向内网地址
+async fn validate_webhook_url(app_state: &AppState, url: &str) -> Result<(), HttpError> {
+    webhooks::check_url(url, app_state.env.webhook_allow_private_networks)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Webhook 地址 {} 不可用: {}", url, e);
+            HttpError::bad_request(e)
+        })
+}
+
+fn event_names(events: &[WebhookEvent]) -> Vec<String> {
+    let mut names: Vec<String> = events
+        .iter()
+        .map(|event| event.to_str().to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use axum::{Extension, Json, Router, http::header, response::IntoResponse, routing::get};
+
+use crate::AppState;
+
+/// JWKS 的缓存时间（秒），轮换密钥时新公钥需要先发布这么久
+const JWKS_MAX_AGE_SECONDS: u64 = 300;
+
+pub fn well_known_handler() -> Router {
+    Router::new().route("/jwks.json", get(get_jwks))
+}
+
+/// 公开访问令牌的校验公钥 (JWKS)，其他服务据此按 `kid` 校验令牌
+///
+/// 使用 HS256 共享密钥时返回空列表。
+pub async fn get_jwks(Extension(app_state): Extension<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", JWKS_MAX_AGE_SECONDS),
+        )],
+        Json(app_state.jwt_keys.jwks().clone()),
+    )
+}
@@ -49,6 +49,157 @@ pub async fn send_forgot_password_email(
     send_email(to_email, subject, &template_path, &placeholders).await
 }
 
+pub async fn send_mention_email(
+    to_email: &str,
+    username: &str,
+    actor: &str,
+    document_id: &str,
+    document_title: &str,
+    excerpt: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subject = format!("{} mentioned you in {}", actor, document_title);
+    let template_path = get_template_path("Mention-email.html")?;
+    let config = Config::from_env();
+    let document_link = format!("{}/documents/{}", config.frontend_url, document_id);
+    // -- 评论中的提及附带评论摘录
+    let excerpt = excerpt
+        .map(|text| {
+            format!(
+                "<blockquote style=\"color: #555555; border-left: 4px solid #dddddd; margin: 0 0 16px 0; padding: 8px 16px;\">{}</blockquote>",
+                escape_html(text)
+            )
+        })
+        .unwrap_or_default();
+    let placeholders = vec![
+        ("{{username}}".to_string(), escape_html(username)),
+        ("{{actor}}".to_string(), escape_html(actor)),
+        (
+            "{{document_title}}".to_string(),
+            escape_html(document_title),
+        ),
+        ("{{document_link}}".to_string(), document_link),
+        ("{{excerpt}}".to_string(), excerpt),
+    ];
+
+    send_email(to_email, &subject, &template_path, &placeholders).await
+}
+
+/// 发送免密登录链接，`username` 为空时使用邮箱地址称呼
+pub async fn send_magic_link_email(
+    to_email: &str,
+    username: &str,
+    token: &str,
+    expires_in_minutes: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subject = "Your sign-in link";
+    let template_path = get_template_path("MagicLink-email.html")?;
+    let config = Config::from_env();
+    let base_url = format!(
+        "http://localhost:{}/api/auth/magic-link/verify",
+        config.server_port
+    );
+    let magic_link = create_verification_link(&base_url, token);
+    let placeholders = vec![
+        ("{{username}}".to_string(), escape_html(username)),
+        ("{{magic_link}}".to_string(), magic_link),
+        ("{{expires_in}}".to_string(), expires_in_minutes.to_string()),
+    ];
+
+    send_email(to_email, subject, &template_path, &placeholders).await
+}
+
+/// 发送六位数字登录验证码
+pub async fn send_login_code_email(
+    to_email: &str,
+    username: &str,
+    code: &str,
+    expires_in_minutes: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subject = format!("Your sign-in code is {}", code);
+    let template_path = get_template_path("LoginCode-email.html")?;
+    let placeholders = vec![
+        ("{{username}}".to_string(), escape_html(username)),
+        ("{{code}}".to_string(), code.to_string()),
+        ("{{expires_in}}".to_string(), expires_in_minutes.to_string()),
+    ];
+
+    send_email(to_email, &subject, &template_path, &placeholders).await
+}
+
+/// 发送新邮箱的确认链接
+pub async fn send_email_change_email(
+    to_email: &str,
+    username: &str,
+    token: &str,
+    expires_in_hours: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subject = "Confirm your new email address";
+    let template_path = get_template_path("EmailChange-email.html")?;
+    let config = Config::from_env();
+    let base_url = format!(
+        "http://localhost:{}/api/auth/email-change/confirm",
+        config.server_port
+    );
+    let confirm_link = create_verification_link(&base_url, token);
+    let placeholders = vec![
+        ("{{username}}".to_string(), escape_html(username)),
+        ("{{confirm_link}}".to_string(), confirm_link),
+        ("{{expires_in}}".to_string(), expires_in_hours.to_string()),
+    ];
+
+    send_email(to_email, subject, &template_path, &placeholders).await
+}
+
+/// 通知旧邮箱地址正在被修改，附带撤销链接
+pub async fn send_email_change_notice_email(
+    to_email: &str,
+    username: &str,
+    new_email: &str,
+    token: &str,
+    expires_in_days: i64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subject = "Your email address is being changed";
+    let template_path = get_template_path("EmailChangeNotice-email.html")?;
+    let config = Config::from_env();
+    let base_url = format!(
+        "http://localhost:{}/api/auth/email-change/revert",
+        config.server_port
+    );
+    let revert_link = create_verification_link(&base_url, token);
+    let placeholders = vec![
+        ("{{username}}".to_string(), escape_html(username)),
+        ("{{new_email}}".to_string(), escape_html(new_email)),
+        ("{{revert_link}}".to_string(), revert_link),
+        ("{{expires_in}}".to_string(), expires_in_days.to_string()),
+    ];
+
+    send_email(to_email, subject, &template_path, &placeholders).await
+}
+
+pub async fn send_account_deletion_email(
+    to_email: &str,
+    username: &str,
+    scheduled_for: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let subject = "Your account is scheduled for deletion";
+    let template_path = get_template_path("AccountDeletion-email.html")?;
+    let placeholders = vec![
+        ("{{username}}".to_string(), escape_html(username)),
+        ("{{scheduled_for}}".to_string(), scheduled_for.to_string()),
+    ];
+
+    send_email(to_email, subject, &template_path, &placeholders).await
+}
+
+/// 转义用户输入的内容，避免注入邮件 HTML
+pub(crate) fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// 获取邮件模板的绝对路径
 fn get_template_path(template_name: &str) -> Result<String, Box<dyn std::error::Error>> {
     let possible_paths = vec![
@@ -1,17 +1,26 @@
 #![allow(unused)]
 
+mod account_deletion;
+mod audit;
 mod config;
 mod db;
 mod dtos;
 mod error;
+mod events;
 mod handlers;
 mod mail;
 mod middleware;
 mod models;
+mod oidc;
+mod password_policy;
+mod rate_limit;
 mod repositories;
 mod routes;
+mod sessions;
 mod utils;
+mod webhooks;
 
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -36,6 +45,12 @@ pub struct AppState {
     pub db_client: DBClient,
     pub user_repository: repositories::user::DbUserRepository,
     pub document_repository: repositories::document::DbDocumentRepository,
+    pub event_hub: Arc<events::EventHub>,
+    pub session_cache: sessions::SessionCache,
+    pub oidc_client: oidc::OidcClient,
+    pub rate_limiter: rate_limit::RateLimiter,
+    pub password_policy: password_policy::PasswordPolicy,
+    pub jwt_keys: utils::jwt_keys::JwtKeys,
 }
 
 /// Bootstrap the application
@@ -130,14 +145,63 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let db_client = DBClient::new(pool);
     let db_client_arc = Arc::new(db_client.clone());
 
+    let event_hub = Arc::new(events::EventHub::new(config.event_buffer_size));
+
     let user_repository = repositories::user::DbUserRepository::new(db_client_arc.clone());
-    let document_repository = repositories::document::DbDocumentRepository::new(db_client_arc);
+    let document_repository =
+        repositories::document::DbDocumentRepository::new(db_client_arc, event_hub.clone());
+
+    // -- 启动 Webhook 后台投递任务
+    webhooks::spawn_delivery_worker(db_client.clone(), &config);
+
+    // -- 启动账户注销后台任务，处理宽限期已结束的注销请求
+    account_deletion::spawn_deletion_worker(db_client.clone());
+
+    // -- 登录相关接口的限流，计数保存在内存或 Postgres 中
+    let rate_limiter = rate_limit::RateLimiter::new(&config.rate_limit, db_client.clone());
+
+    // -- 密码策略，配置了泄露密码列表时在启动时打开
+    let password_policy =
+        password_policy::PasswordPolicy::new(&config.password_policy).map_err(|err| {
+            tracing::error!("🐞 Failed to load password policy: {}", err);
+            err
+        })?;
+
+    // -- 访问令牌的签名密钥，配置了密钥目录时使用 RS256/EdDSA，否则使用 HS256
+    let jwt_keys = match &config.jwt_keys_dir {
+        Some(_) if !config.jwt_secret_configured => {
+            // -- 登录验证码和 OAuth state 仍使用共享密钥签名，随机密钥在重启后或其他实例上无法校验
+            let err = "JWT_KEYS_DIR is set but JWT_SECRET_KEY is not, login codes and OAuth state need a persistent secret";
+            tracing::error!("🐞 Failed to load JWT keys: {}", err);
+            return Err(err.into());
+        }
+        Some(dir) => {
+            let keys =
+                utils::jwt_keys::JwtKeys::load(Path::new(dir), config.jwt_active_key_id.as_deref())
+                    .map_err(|err| {
+                        tracing::error!("🐞 Failed to load JWT keys: {}", err);
+                        err
+                    })?;
+            tracing::info!("JWT keys loaded from {}", dir);
+            keys
+        }
+        None => utils::jwt_keys::JwtKeys::shared_secret(config.jwt_secret.as_bytes()),
+    }
+    .with_issuer(&config.jwt_issuer, &config.jwt_audience);
 
     let app_state = Arc::new(AppState {
         env: config.clone(),
         db_client,
         user_repository,
         document_repository,
+        event_hub,
+        session_cache: sessions::SessionCache::new(std::time::Duration::from_secs(
+            config.session_cache_seconds,
+        )),
+        oidc_client: oidc::OidcClient::new(),
+        rate_limiter,
+        password_policy,
+        jwt_keys,
     });
 
     // -- 创建路由
@@ -158,7 +222,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // -- 开始处理请求
-    axum::serve(listener, app).await?;
+    // -- 保留客户端地址，供审计日志使用
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -5,6 +5,7 @@ use axum::{
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use chrono::{Duration, Utc};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -12,25 +13,98 @@ use axum_extra::extract::cookie::CookieJar;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    AppState,
-    db::UserExt,
+    AppState, audit,
+    db::{AccessTokenExt, DBClient, SessionExt, UserExt},
     error::{ErrorMessage, HttpError},
-    models::{User, UserRole},
+    models::{TokenScope, User, UserRole},
     utils::token,
 };
 
+/// 登录后多长时间内可以执行需要重新认证的操作
+pub const RECENT_LOGIN_MINUTES: i64 = 10;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct JWTAuthMiddleware {
     pub user: User,
+    /// 用于授权的角色：登录会话取自令牌声明，个人访问令牌和 `fresh_user` 取自数据库
+    pub role: UserRole,
+    /// 当前请求所属的登录会话，使用个人访问令牌时为空
+    pub session_id: Option<uuid::Uuid>,
+    /// 个人访问令牌的权限范围，使用登录会话时为空，不受限制
+    pub scopes: Option<Vec<TokenScope>>,
+}
+
+impl JWTAuthMiddleware {
+    /// 请求是否拥有指定权限，登录会话拥有用户的全部权限
+    pub fn has_scope(&self, scope: TokenScope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|granted| granted.grants(scope)))
+    }
+
+    /// 当前登录会话的 ID，使用个人访问令牌时返回 403 错误
+    pub fn require_session(&self) -> Result<uuid::Uuid, HttpError> {
+        self.session_id
+            .ok_or_else(|| HttpError::forbidden(ErrorMessage::AccessTokenNotAllowed.to_string()))
+    }
+
+    /// 要求当前登录会话在 `RECENT_LOGIN_MINUTES` 分钟内创建，否则返回 403 错误
+    ///
+    /// 没有密码的账户（第三方登录、OIDC、钱包）执行敏感操作前用重新登录代替密码确认，
+    /// 刷新令牌轮换不会延长这段时间。
+    pub async fn require_recent_login(&self, db_client: &DBClient) -> Result<(), HttpError> {
+        let session_id = self.require_session()?;
+        let session = db_client
+            .get_session(session_id, self.user.id)
+            .await?
+            .ok_or_else(|| HttpError::unauthorized(ErrorMessage::SessionRevoked.to_string()))?;
+
+        if Utc::now() - session.created_at > Duration::minutes(RECENT_LOGIN_MINUTES) {
+            return Err(HttpError::forbidden(
+                ErrorMessage::RecentLoginRequired.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
+/// 认证中间件 -- 只接受登录会话，拒绝个人访问令牌
+///
+/// 账户、会话和令牌管理等接口使用此中间件，令牌泄露时无法借此扩大权限。
 pub async fn auth(
     cookie_jar: CookieJar,
     Extension(app_state): Extension<Arc<AppState>>,
     mut req: Request,
     next: Next,
 ) -> Result<impl IntoResponse, HttpError> {
-    let cookies = cookie_jar
+    let auth = authenticate(&app_state, request_token(&cookie_jar, &req)).await?;
+    auth.require_session()?;
+
+    req.extensions_mut().insert(auth);
+
+    // -- 通过 Ok 包装异步执行下一个处理器的结果，将请求传递给路由处理函数继续处理
+    Ok(next.run(req).await)
+}
+
+/// 认证中间件 -- 同时接受登录会话和个人访问令牌
+///
+/// 路由需要通过 `scope_check`、`document_scope` 或 `session_only` 声明令牌需要的权限。
+pub async fn api_auth(
+    cookie_jar: CookieJar,
+    Extension(app_state): Extension<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HttpError> {
+    let auth = authenticate(&app_state, request_token(&cookie_jar, &req)).await?;
+    req.extensions_mut().insert(auth);
+
+    Ok(next.run(req).await)
+}
+
+/// 从 `token` Cookie 或 `Authorization: Bearer` 请求头中取出令牌
+fn request_token(cookie_jar: &CookieJar, req: &Request) -> Option<String> {
+    cookie_jar
         .get("token")
         .map(|cookie| cookie.value().to_string())
         .or_else(|| {
@@ -42,12 +116,24 @@ pub async fn auth(
                         .strip_prefix("Bearer ")
                         .map(|token| token.to_owned())
                 })
-        });
+        })
+}
 
-    let token = cookies
-        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string()))?;
+/// 识别令牌所属的用户
+///
+/// 以 `pat_` 开头的令牌是个人访问令牌，其他令牌按登录会话的 JWT 校验。
+async fn authenticate(
+    app_state: &AppState,
+    token: Option<String>,
+) -> Result<JWTAuthMiddleware, HttpError> {
+    let token =
+        token.ok_or_else(|| HttpError::unauthorized(ErrorMessage::TokenNotProvided.to_string()))?;
 
-    let token_details = match token::decode_token(token, app_state.env.jwt_secret.as_bytes()) {
+    if token.starts_with(token::ACCESS_TOKEN_PREFIX) {
+        return authenticate_access_token(app_state, &token).await;
+    }
+
+    let token_details = match token::decode_token(token, &app_state.jwt_keys) {
         Ok(token_details) => token_details,
         Err(_) => {
             return Err(HttpError::unauthorized(
@@ -56,25 +142,81 @@ pub async fn auth(
         }
     };
 
-    let user_id = uuid::Uuid::parse_str(&token_details.to_string())
+    let user_id = uuid::Uuid::parse_str(&token_details.sub)
+        .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+    let session_id = uuid::Uuid::parse_str(&token_details.sid)
         .map_err(|_| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
 
+    // -- 确认会话没有被注销或吊销，缓存时间内验证过的会话不再查询数据库
+    let user = match app_state.session_cache.get_user(session_id) {
+        Some(user) => user,
+        None => {
+            let active = app_state
+                .db_client
+                .touch_session(session_id, user_id, audit::current_context().ip_address)
+                .await
+                .map_err(|e| HttpError::server_error(e.to_string()))?;
+
+            if !active {
+                return Err(HttpError::unauthorized(
+                    ErrorMessage::SessionRevoked.to_string(),
+                ));
+            }
+
+            let user = load_user(app_state, user_id).await?;
+            app_state
+                .session_cache
+                .mark_verified(session_id, user.clone());
+            user
+        }
+    };
+
+    Ok(JWTAuthMiddleware {
+        user,
+        role: token_details.role,
+        session_id: Some(session_id),
+        scopes: None,
+    })
+}
+
+/// 校验个人访问令牌，并记录最近使用时间和 IP
+async fn authenticate_access_token(
+    app_state: &AppState,
+    token: &str,
+) -> Result<JWTAuthMiddleware, HttpError> {
+    let access_token = app_state
+        .db_client
+        .use_access_token(
+            &token::hash_opaque_token(token),
+            audit::current_context().ip_address,
+        )
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))?
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::InvalidToken.to_string()))?;
+
+    let user = load_user(app_state, access_token.user_id).await?;
+
+    Ok(JWTAuthMiddleware {
+        role: user.role,
+        user,
+        session_id: None,
+        scopes: Some(access_token.scopes),
+    })
+}
+
+async fn load_user(app_state: &AppState, user_id: uuid::Uuid) -> Result<User, HttpError> {
     let user = app_state
         .db_client
         .get_user(Some(user_id), None, None, None)
         .await
         .map_err(|_| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
 
-    let user =
-        user.ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))?;
-
-    req.extensions_mut()
-        .insert(JWTAuthMiddleware { user: user.clone() });
-
-    // -- 通过 Ok 包装异步执行下一个处理器的结果，将请求传递给路由处理函数继续处理
-    Ok(next.run(req).await)
+    user.ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNoLongerExist.to_string()))
 }
 
+/// 角色检查中间件 -- 按令牌声明中的角色授权，不查询数据库
+///
+/// 角色变更要等旧的访问令牌过期后才会体现，敏感接口需要在外层加上 `fresh_user`。
 pub async fn role_check(
     Extension(_app_state): Extension<Arc<AppState>>,
     req: Request,
@@ -86,7 +228,7 @@ pub async fn role_check(
         .get::<JWTAuthMiddleware>()
         .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNotAuthenticated.to_string()))?;
 
-    if !required_roles.contains(&user.user.role) {
+    if !required_roles.contains(&user.role) {
         return Err(HttpError::new(
             ErrorMessage::PermissionDenied.to_string(),
             StatusCode::FORBIDDEN,
@@ -96,3 +238,70 @@ pub async fn role_check(
     // -- 通过 Ok 包装异步执行下一个处理器的结果，将请求传递给路由处理函数继续处理
     Ok(next.run(req).await)
 }
+
+/// 从数据库重新读取用户和角色，用于敏感接口
+///
+/// 必须放在 `role_check` 外层，角色检查才会使用数据库中的最新角色。
+pub async fn fresh_user(
+    Extension(app_state): Extension<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<impl IntoResponse, HttpError> {
+    let user_id = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .map(|auth| auth.user.id)
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNotAuthenticated.to_string()))?;
+
+    let user = load_user(&app_state, user_id).await?;
+    if let Some(auth) = req.extensions_mut().get_mut::<JWTAuthMiddleware>() {
+        auth.role = user.role;
+        auth.user = user;
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// 要求个人访问令牌拥有指定权限，登录会话直接放行
+///
+/// 用法：`.layer(middleware::from_fn(|req, next| scope_check(req, next, TokenScope::Admin)))`
+pub async fn scope_check(
+    req: Request,
+    next: Next,
+    required_scope: TokenScope,
+) -> Result<impl IntoResponse, HttpError> {
+    let user = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNotAuthenticated.to_string()))?;
+
+    if !user.has_scope(required_scope) {
+        return Err(HttpError::forbidden(
+            ErrorMessage::MissingScope(required_scope.to_str()).to_string(),
+        ));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// 文档相关接口的权限：读取需要 `documents:read`，其他请求需要 `documents:write`
+pub async fn document_scope(req: Request, next: Next) -> Result<impl IntoResponse, HttpError> {
+    let required_scope = match *req.method() {
+        Method::GET | Method::HEAD => TokenScope::DocumentsRead,
+        _ => TokenScope::DocumentsWrite,
+    };
+
+    scope_check(req, next, required_scope).await
+}
+
+/// 只允许登录会话访问，拒绝个人访问令牌
+pub async fn session_only(req: Request, next: Next) -> Result<impl IntoResponse, HttpError> {
+    let user = req
+        .extensions()
+        .get::<JWTAuthMiddleware>()
+        .ok_or_else(|| HttpError::unauthorized(ErrorMessage::UserNotAuthenticated.to_string()))?;
+
+    user.require_session()?;
+
+    Ok(next.run(req).await)
+}
@@ -19,10 +19,13 @@ impl UserRole {
 
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq)]
 #[sqlx(type_name = "auth_provider", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum AuthProvider {
     Local,
     Google,
     Github,
+    Oidc,
+    Ethereum,
 }
 
 impl AuthProvider {
@@ -31,10 +34,48 @@ impl AuthProvider {
             AuthProvider::Local => "local",
             AuthProvider::Google => "google",
             AuthProvider::Github => "github",
+            AuthProvider::Oidc => "oidc",
+            AuthProvider::Ethereum => "ethereum",
         }
     }
 }
 
+/// Permission granted to a personal access token
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum TokenScope {
+    #[serde(rename = "documents:read")]
+    DocumentsRead,
+    #[serde(rename = "documents:write")]
+    DocumentsWrite,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl TokenScope {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            TokenScope::DocumentsRead => "documents:read",
+            TokenScope::DocumentsWrite => "documents:write",
+            TokenScope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "documents:read" => Some(TokenScope::DocumentsRead),
+            "documents:write" => Some(TokenScope::DocumentsWrite),
+            "admin" => Some(TokenScope::Admin),
+            _ => None,
+        }
+    }
+
+    /// Whether this scope allows what `required` allows, write access includes read access
+    pub fn grants(self, required: TokenScope) -> bool {
+        self == required
+            || (self == TokenScope::DocumentsWrite && required == TokenScope::DocumentsRead)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, sqlx::Type, Clone)]
 pub struct User {
     pub id: uuid::Uuid,
@@ -62,6 +103,8 @@ pub struct Document {
     pub content: String,
     pub owner_id: uuid::Uuid,
     pub is_public: bool,
+    /// Incremented on every update, suggestions record the revision they were made on
+    pub revision: i64,
     #[serde(rename = "createdAt")]
     pub created_at: Option<DateTime<Utc>>,
     #[serde(rename = "updatedAt")]
@@ -72,6 +115,7 @@ pub struct Document {
 #[sqlx(type_name = "permission_level", rename_all = "lowercase")]
 pub enum PermissionLevel {
     Read,
+    Comment,
     ReadWrite,
     Owner,
 }
@@ -80,10 +124,67 @@ impl PermissionLevel {
     pub fn to_str(&self) -> &'static str {
         match self {
             PermissionLevel::Read => "read",
+            PermissionLevel::Comment => "comment",
             PermissionLevel::ReadWrite => "readwrite",
             PermissionLevel::Owner => "owner",
         }
     }
+
+    /// Position in the permission hierarchy, higher levels include all lower ones
+    fn rank(&self) -> u8 {
+        match self {
+            PermissionLevel::Read => 0,
+            PermissionLevel::Comment => 1,
+            PermissionLevel::ReadWrite => 2,
+            PermissionLevel::Owner => 3,
+        }
+    }
+
+    /// Whether this level grants everything the `required` level grants
+    pub fn includes(&self, required: &PermissionLevel) -> bool {
+        self.rank() >= required.rank()
+    }
+}
+
+/// What happens to the documents a user owns when the account is deleted
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "document_disposition", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentDisposition {
+    /// Hand the documents over to another user
+    Transfer,
+    /// Delete the documents with their comments and shares
+    Delete,
+}
+
+impl DocumentDisposition {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            DocumentDisposition::Transfer => "transfer",
+            DocumentDisposition::Delete => "delete",
+        }
+    }
+}
+
+/// What happens to the documents when the transfer recipient is no longer available
+/// at the time the account is deleted
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "transfer_fallback", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFallback {
+    /// Leave the documents with the anonymized account
+    Keep,
+    /// Delete the documents with their comments and shares
+    Delete,
+}
+
+impl TransferFallback {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            TransferFallback::Keep => "keep",
+            TransferFallback::Delete => "delete",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
@@ -97,3 +198,569 @@ pub struct DocumentPermission {
     #[serde(rename = "updatedAt")]
     pub updated_at: Option<DateTime<Utc>>,
 }
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct CommentThread {
+    pub id: uuid::Uuid,
+    pub document_id: uuid::Uuid,
+    pub author_id: uuid::Uuid,
+    pub block_id: Option<String>,
+    pub start_offset: Option<i32>,
+    pub end_offset: Option<i32>,
+    pub crdt_position: Option<String>,
+    pub quoted_text: String,
+    pub orphaned: bool,
+    pub resolved: bool,
+    pub resolved_by: Option<uuid::Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Comment {
+    pub id: uuid::Uuid,
+    pub thread_id: uuid::Uuid,
+    pub author_id: uuid::Uuid,
+    pub content: String,
+    pub edited: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Anchor of a comment thread within a document
+///
+/// Either `block_id` with character offsets or an opaque `crdt_position`
+/// must be provided; `quoted_text` is kept to re-map the anchor after edits.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct CommentAnchor {
+    pub block_id: Option<String>,
+    pub start_offset: Option<i32>,
+    pub end_offset: Option<i32>,
+    pub crdt_position: Option<String>,
+    pub quoted_text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "suggestion_kind", rename_all = "lowercase")]
+pub enum SuggestionKind {
+    Insert,
+    Delete,
+    Replace,
+}
+
+impl SuggestionKind {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            SuggestionKind::Insert => "insert",
+            SuggestionKind::Delete => "delete",
+            SuggestionKind::Replace => "replace",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "suggestion_status", rename_all = "lowercase")]
+pub enum SuggestionStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+impl SuggestionStatus {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            SuggestionStatus::Pending => "pending",
+            SuggestionStatus::Accepted => "accepted",
+            SuggestionStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// A tracked change proposed against a block range of a given document revision
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Suggestion {
+    pub id: uuid::Uuid,
+    pub document_id: uuid::Uuid,
+    pub author_id: uuid::Uuid,
+    pub kind: SuggestionKind,
+    pub block_id: String,
+    pub start_offset: i32,
+    pub end_offset: i32,
+    pub original_text: String,
+    pub text: String,
+    pub base_revision: i64,
+    pub status: SuggestionStatus,
+    pub reviewed_by: Option<uuid::Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub applied_revision: Option<i64>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "notification_type", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationType {
+    DocumentShared,
+    PermissionChanged,
+    CommentReply,
+    Mention,
+    AccessRequest,
+}
+
+impl NotificationType {
+    pub const ALL: [NotificationType; 5] = [
+        NotificationType::DocumentShared,
+        NotificationType::PermissionChanged,
+        NotificationType::CommentReply,
+        NotificationType::Mention,
+        NotificationType::AccessRequest,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            NotificationType::DocumentShared => "document_shared",
+            NotificationType::PermissionChanged => "permission_changed",
+            NotificationType::CommentReply => "comment_reply",
+            NotificationType::Mention => "mention",
+            NotificationType::AccessRequest => "access_request",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Notification {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub actor_id: Option<uuid::Uuid>,
+    #[serde(rename = "type")]
+    pub notification_type: NotificationType,
+    pub document_id: Option<uuid::Uuid>,
+    pub data: serde_json::Value,
+    #[serde(rename = "readAt")]
+    pub read_at: Option<DateTime<Utc>>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NotificationPreference {
+    #[serde(rename = "type")]
+    pub notification_type: NotificationType,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Webhook {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub document_id: Option<uuid::Uuid>,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+#[sqlx(type_name = "webhook_delivery_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct WebhookDelivery {
+    pub id: uuid::Uuid,
+    pub webhook_id: uuid::Uuid,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: i32,
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(rename = "lastAttemptAt")]
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub response_status: Option<i32>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// One HTTP attempt of a webhook delivery
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct WebhookDeliveryAttempt {
+    pub id: uuid::Uuid,
+    pub delivery_id: uuid::Uuid,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Events a webhook can subscribe to
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    #[serde(rename = "document.created")]
+    DocumentCreated,
+    #[serde(rename = "document.updated")]
+    DocumentUpdated,
+    #[serde(rename = "document.published")]
+    DocumentPublished,
+    #[serde(rename = "document.shared")]
+    DocumentShared,
+    #[serde(rename = "comment.created")]
+    CommentCreated,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 5] = [
+        WebhookEvent::DocumentCreated,
+        WebhookEvent::DocumentUpdated,
+        WebhookEvent::DocumentPublished,
+        WebhookEvent::DocumentShared,
+        WebhookEvent::CommentCreated,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            WebhookEvent::DocumentCreated => "document.created",
+            WebhookEvent::DocumentUpdated => "document.updated",
+            WebhookEvent::DocumentPublished => "document.published",
+            WebhookEvent::DocumentShared => "document.shared",
+            WebhookEvent::CommentCreated => "comment.created",
+        }
+    }
+}
+
+/// Security and sharing actions recorded in the audit log
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    #[serde(rename = "auth.login.succeeded")]
+    LoginSucceeded,
+    #[serde(rename = "auth.login.failed")]
+    LoginFailed,
+    #[serde(rename = "auth.account.locked")]
+    AccountLocked,
+    #[serde(rename = "auth.oauth.linked")]
+    OAuthLinked,
+    #[serde(rename = "auth.oauth.unlinked")]
+    OAuthUnlinked,
+    #[serde(rename = "auth.refresh_token.reused")]
+    RefreshTokenReused,
+    #[serde(rename = "user.two_factor.enabled")]
+    TwoFactorEnabled,
+    #[serde(rename = "user.two_factor.disabled")]
+    TwoFactorDisabled,
+    #[serde(rename = "user.recovery_codes.regenerated")]
+    RecoveryCodesRegenerated,
+    #[serde(rename = "auth.recovery_code.used")]
+    RecoveryCodeUsed,
+    #[serde(rename = "policy.role.changed")]
+    RolePolicyChanged,
+    #[serde(rename = "user.passkey.added")]
+    PasskeyAdded,
+    #[serde(rename = "user.passkey.removed")]
+    PasskeyRemoved,
+    #[serde(rename = "user.access_token.created")]
+    AccessTokenCreated,
+    #[serde(rename = "user.access_token.revoked")]
+    AccessTokenRevoked,
+    #[serde(rename = "user.password.changed")]
+    PasswordChanged,
+    #[serde(rename = "user.password.reset")]
+    PasswordReset,
+    #[serde(rename = "user.email.change_requested")]
+    EmailChangeRequested,
+    #[serde(rename = "user.email.changed")]
+    EmailChanged,
+    #[serde(rename = "user.email.change_reverted")]
+    EmailChangeReverted,
+    #[serde(rename = "user.deletion.scheduled")]
+    AccountDeletionScheduled,
+    #[serde(rename = "user.deletion.cancelled")]
+    AccountDeletionCancelled,
+    #[serde(rename = "user.deleted")]
+    AccountDeleted,
+    #[serde(rename = "user.data.exported")]
+    DataExported,
+    #[serde(rename = "user.role.changed")]
+    RoleChanged,
+    #[serde(rename = "document.shared")]
+    DocumentShared,
+    #[serde(rename = "document.unshared")]
+    DocumentUnshared,
+    #[serde(rename = "document.permission.changed")]
+    PermissionChanged,
+    #[serde(rename = "document.deleted")]
+    DocumentDeleted,
+    #[serde(rename = "document.published")]
+    DocumentPublished,
+}
+
+impl AuditAction {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            AuditAction::LoginSucceeded => "auth.login.succeeded",
+            AuditAction::LoginFailed => "auth.login.failed",
+            AuditAction::AccountLocked => "auth.account.locked",
+            AuditAction::OAuthLinked => "auth.oauth.linked",
+            AuditAction::OAuthUnlinked => "auth.oauth.unlinked",
+            AuditAction::RefreshTokenReused => "auth.refresh_token.reused",
+            AuditAction::TwoFactorEnabled => "user.two_factor.enabled",
+            AuditAction::TwoFactorDisabled => "user.two_factor.disabled",
+            AuditAction::RecoveryCodesRegenerated => "user.recovery_codes.regenerated",
+            AuditAction::RecoveryCodeUsed => "auth.recovery_code.used",
+            AuditAction::RolePolicyChanged => "policy.role.changed",
+            AuditAction::PasskeyAdded => "user.passkey.added",
+            AuditAction::PasskeyRemoved => "user.passkey.removed",
+            AuditAction::AccessTokenCreated => "user.access_token.created",
+            AuditAction::AccessTokenRevoked => "user.access_token.revoked",
+            AuditAction::PasswordChanged => "user.password.changed",
+            AuditAction::PasswordReset => "user.password.reset",
+            AuditAction::EmailChangeRequested => "user.email.change_requested",
+            AuditAction::EmailChanged => "user.email.changed",
+            AuditAction::EmailChangeReverted => "user.email.change_reverted",
+            AuditAction::AccountDeletionScheduled => "user.deletion.scheduled",
+            AuditAction::AccountDeletionCancelled => "user.deletion.cancelled",
+            AuditAction::AccountDeleted => "user.deleted",
+            AuditAction::DataExported => "user.data.exported",
+            AuditAction::RoleChanged => "user.role.changed",
+            AuditAction::DocumentShared => "document.shared",
+            AuditAction::DocumentUnshared => "document.unshared",
+            AuditAction::PermissionChanged => "document.permission.changed",
+            AuditAction::DocumentDeleted => "document.deleted",
+            AuditAction::DocumentPublished => "document.published",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct AuditLog {
+    pub id: uuid::Uuid,
+    pub actor_id: Option<uuid::Uuid>,
+    pub actor_email: Option<String>,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<uuid::Uuid>,
+    pub details: serde_json::Value,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct RefreshToken {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub family_id: uuid::Uuid,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub replaced_by: Option<uuid::Uuid>,
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Session {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: DateTime<Utc>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PersonalAccessToken {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    /// Start of the token, enough to recognize it without revealing it
+    #[serde(rename = "tokenPrefix")]
+    pub token_prefix: String,
+    pub scopes: Vec<TokenScope>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(rename = "lastUsedIp")]
+    pub last_used_ip: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct LoginCode {
+    pub id: uuid::Uuid,
+    pub email: String,
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+    pub attempts: i32,
+    pub ip_address: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct UserTotp {
+    pub user_id: uuid::Uuid,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+    pub failed_attempts: i32,
+    pub last_failed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct RolePolicy {
+    pub role: UserRole,
+    #[serde(rename = "requireTwoFactor")]
+    pub require_two_factor: bool,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct Passkey {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub name: String,
+    #[serde(rename = "credentialId")]
+    pub credential_id: String,
+    #[serde(skip_serializing)]
+    pub public_key: Vec<u8>,
+    pub algorithm: i32,
+    #[serde(rename = "signCount")]
+    pub sign_count: i64,
+    pub transports: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct WebauthnChallenge {
+    pub id: uuid::Uuid,
+    pub user_id: Option<uuid::Uuid>,
+    pub purpose: String,
+    pub challenge: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct LoginLockout {
+    pub user_id: uuid::Uuid,
+    pub failed_attempts: i32,
+    pub last_failed_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct SiweNonce {
+    pub id: uuid::Uuid,
+    pub nonce: String,
+    pub user_id: Option<uuid::Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct UserIdentity {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub provider: AuthProvider,
+    /// Configured provider name, the same as `provider` for built-in providers
+    #[serde(rename = "providerName")]
+    pub provider_name: String,
+    #[serde(rename = "providerUserId")]
+    pub provider_user_id: Option<String>,
+    pub email: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "lastUsedAt")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct EmailChange {
+    pub id: uuid::Uuid,
+    pub user_id: uuid::Uuid,
+    pub old_email: String,
+    pub new_email: String,
+    pub confirm_expires_at: DateTime<Utc>,
+    /// The old address can undo the change until this time
+    pub revert_expires_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub reverted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, sqlx::FromRow, Clone)]
+pub struct AccountDeletion {
+    #[serde(rename = "userId")]
+    pub user_id: uuid::Uuid,
+    /// The account is deleted after this time unless the user logs in again
+    #[serde(rename = "scheduledFor")]
+    pub scheduled_for: DateTime<Utc>,
+    #[serde(rename = "documentDisposition")]
+    pub document_disposition: DocumentDisposition,
+    #[serde(rename = "transferTo")]
+    pub transfer_to: Option<uuid::Uuid>,
+    /// Set for transfers, applied when the recipient is gone by the deletion time
+    #[serde(rename = "transferFallback")]
+    pub transfer_fallback: Option<TransferFallback>,
+    #[serde(rename = "completedAt")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permission_levels_include_lower_ones() {
+        use PermissionLevel::*;
+
+        assert!(Comment.includes(&Read));
+        assert!(Comment.includes(&Comment));
+        assert!(!Comment.includes(&ReadWrite));
+        assert!(!Read.includes(&Comment));
+        assert!(ReadWrite.includes(&Comment));
+        assert!(Owner.includes(&ReadWrite));
+        assert!(!ReadWrite.includes(&Owner));
+    }
+}
@@ -0,0 +1,798 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header,
+    jwk::{Jwk, JwkSet},
+};
+use oauth2::url::form_urlencoded;
+use reqwest::header::ACCEPT;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::config::OidcProviderConfig;
+
+/// 发现文档和签名密钥的缓存时间
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+/// 遇到未知的签名密钥时，距上次获取超过该时间才重新获取，防止被用来放大请求
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 允许的 ID 令牌签名算法，不接受 `none` 和对称算法
+const ALLOWED_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// `.well-known/openid-configuration` 中用到的字段
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+}
+
+/// 令牌端点的响应
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub id_token: Option<String>,
+}
+
+/// 按提供方配置从声明中映射出的用户资料
+pub struct OidcUser {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+struct CachedProvider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+/// OpenID Connect 客户端，按提供方缓存发现文档和签名密钥
+pub struct OidcClient {
+    http: reqwest::Client,
+    cache: RwLock<HashMap<String, CachedProvider>>,
+}
+
+impl OidcClient {
+    pub fn new() -> Self {
+        let http = reqwest::ClientBuilder::new()
+            // Following redirects opens the client up to SSRF vulnerabilities.
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .expect("Client should build");
+
+        OidcClient {
+            http,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 获取提供方的发现文档
+    pub async fn metadata(
+        &self,
+        provider: &OidcProviderConfig,
+    ) -> Result<ProviderMetadata, String> {
+        let (metadata, _) = self.load(provider, false).await?;
+        Ok(metadata)
+    }
+
+    /// 用授权码换取令牌，同时提交 PKCE 校验码
+    ///
+    /// 提供方声明支持 `client_secret_basic`（或未声明）时使用 HTTP Basic 认证，否则在表单中提交客户端密钥。
+    pub async fn exchange_code(
+        &self,
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        code: &str,
+        pkce_verifier: &str,
+    ) -> Result<TokenResponse, String> {
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.redirect_url.as_str()),
+            ("code_verifier", pkce_verifier),
+        ];
+
+        let mut request = self
+            .http
+            .post(&metadata.token_endpoint)
+            .header(ACCEPT, "application/json");
+
+        let methods = &metadata.token_endpoint_auth_methods_supported;
+        if provider.client_secret.is_empty() {
+            params.push(("client_id", provider.client_id.as_str()));
+        } else if methods.is_empty() || methods.iter().any(|m| m == "client_secret_basic") {
+            // -- RFC 6749 要求先对客户端 ID 和密钥做表单编码
+            let encode = |value: &str| form_urlencoded::byte_serialize(value.as_bytes()).collect();
+            let username: String = encode(&provider.client_id);
+            let password: String = encode(&provider.client_secret);
+            request = request.basic_auth(username, Some(password));
+        } else {
+            params.push(("client_id", provider.client_id.as_str()));
+            params.push(("client_secret", provider.client_secret.as_str()));
+        }
+
+        let response = request
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| format!("请求令牌端点失败: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("令牌端点返回错误: {} - {}", status, text));
+        }
+
+        response
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| format!("解析令牌响应失败: {}", e))
+    }
+
+    /// 校验 ID 令牌的签名、颁发者、受众、有效期和 `nonce`，返回其中的声明
+    ///
+    /// 令牌使用了缓存中没有的密钥时重新获取 JWKS，以支持提供方轮换密钥。
+    pub async fn verify_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<Map<String, Value>, String> {
+        let header = decode_header(id_token).map_err(|_| "ID 令牌格式无效".to_string())?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(format!("不支持的 ID 令牌签名算法: {:?}", header.alg));
+        }
+
+        let (metadata, jwks) = self.load(provider, false).await?;
+        let jwk = match find_key(&jwks, header.kid.as_deref()) {
+            Some(jwk) => jwk.clone(),
+            None => {
+                let (_, jwks) = self.load(provider, true).await?;
+                find_key(&jwks, header.kid.as_deref())
+                    .cloned()
+                    .ok_or_else(|| "未找到 ID 令牌的签名密钥".to_string())?
+            }
+        };
+        let key = DecodingKey::from_jwk(&jwk).map_err(|e| format!("签名密钥无效: {}", e))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_required_spec_claims(&["exp", "iat", "iss", "aud", "sub"]);
+
+        let claims = decode::<Map<String, Value>>(id_token, &key, &validation)
+            .map_err(|e| format!("ID 令牌校验失败: {}", e))?
+            .claims;
+
+        // -- 有多个受众时，授权方必须是本服务
+        if let Some(Value::Array(audiences)) = claims.get("aud")
+            && audiences.len() > 1
+            && claims.get("azp").and_then(Value::as_str) != Some(provider.client_id.as_str())
+        {
+            return Err("ID 令牌的授权方不匹配".to_string());
+        }
+
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err("ID 令牌的 nonce 不匹配".to_string());
+        }
+
+        Ok(claims)
+    }
+
+    /// 从 userinfo 端点获取声明，`sub` 必须与 ID 令牌一致
+    pub async fn userinfo(
+        &self,
+        metadata: &ProviderMetadata,
+        access_token: &str,
+        subject: &str,
+    ) -> Result<Map<String, Value>, String> {
+        let endpoint = metadata
+            .userinfo_endpoint
+            .as_deref()
+            .ok_or_else(|| "提供方未提供 userinfo 端点".to_string())?;
+
+        let response = self
+            .http
+            .get(endpoint)
+            .bearer_auth(access_token)
+            .header(ACCEPT, "application/json")
+            .send()
+            .await
+            .map_err(|e| format!("请求 userinfo 端点失败: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("userinfo 端点返回错误: {} - {}", status, text));
+        }
+
+        let claims = response
+            .json::<Map<String, Value>>()
+            .await
+            .map_err(|e| format!("解析 userinfo 响应失败: {}", e))?;
+
+        if claims.get("sub").and_then(Value::as_str) != Some(subject) {
+            return Err("userinfo 的 sub 与 ID 令牌不一致".to_string());
+        }
+
+        Ok(claims)
+    }
+
+    /// 读取缓存的发现文档和 JWKS，过期或 `refresh_jwks` 时重新获取
+    async fn load(
+        &self,
+        provider: &OidcProviderConfig,
+        refresh_jwks: bool,
+    ) -> Result<(ProviderMetadata, JwkSet), String> {
+        if let Some(cached) = self.cache.read().unwrap().get(&provider.name) {
+            let age = cached.fetched_at.elapsed();
+            let fresh = if refresh_jwks {
+                age < JWKS_REFRESH_INTERVAL
+            } else {
+                age < METADATA_TTL
+            };
+            if fresh {
+                return Ok((cached.metadata.clone(), cached.jwks.clone()));
+            }
+        }
+
+        let metadata = self.discover(provider).await?;
+        let jwks = self.fetch_json::<JwkSet>(&metadata.jwks_uri).await?;
+
+        self.cache.write().unwrap().insert(
+            provider.name.clone(),
+            CachedProvider {
+                metadata: metadata.clone(),
+                jwks: jwks.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+
+        Ok((metadata, jwks))
+    }
+
+    async fn discover(&self, provider: &OidcProviderConfig) -> Result<ProviderMetadata, String> {
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            provider.issuer.trim_end_matches('/')
+        );
+        let metadata = self.fetch_json::<ProviderMetadata>(&url).await?;
+
+        // -- 发现文档中的颁发者必须与配置完全一致，否则可能是被替换的文档
+        if metadata.issuer != provider.issuer {
+            return Err(format!(
+                "发现文档的颁发者 {} 与配置的 {} 不一致",
+                metadata.issuer, provider.issuer
+            ));
+        }
+
+        let endpoints = [
+            Some(&metadata.authorization_endpoint),
+            Some(&metadata.token_endpoint),
+            Some(&metadata.jwks_uri),
+            metadata.userinfo_endpoint.as_ref(),
+        ];
+        if let Some(endpoint) = endpoints
+            .into_iter()
+            .flatten()
+            .find(|url| !is_secure_url(url))
+        {
+            return Err(format!("端点必须使用 HTTPS: {}", endpoint));
+        }
+
+        Ok(metadata)
+    }
+
+    async fn fetch_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, String> {
+        if !is_secure_url(url) {
+            return Err(format!("端点必须使用 HTTPS: {}", url));
+        }
+
+        let response = self
+            .http
+            .get(url)
+            .header(ACCEPT, "application/json")
+            .send()
+            .await
+            .map_err(|e| format!("请求 {} 失败: {}", url, e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("{} 返回错误: {}", url, response.status()));
+        }
+
+        response
+            .json::<T>()
+            .await
+            .map_err(|e| format!("解析 {} 失败: {}", url, e))
+    }
+}
+
+/// 按提供方配置的声明名称映射用户资料
+///
+/// `extra` 为 userinfo 端点返回的声明，ID 令牌中没有的声明从这里补充。
+pub fn map_claims(
+    provider: &OidcProviderConfig,
+    claims: &Map<String, Value>,
+    extra: Option<&Map<String, Value>>,
+) -> Result<OidcUser, String> {
+    let claim = |name: &str| {
+        claims
+            .get(name)
+            .or_else(|| extra.and_then(|extra| extra.get(name)))
+            .and_then(Value::as_str)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    let subject = claim("sub").ok_or_else(|| "ID 令牌缺少 sub".to_string())?;
+
+    // -- 部分提供方把 email_verified 作为字符串返回
+    let email_verified = claims
+        .get("email_verified")
+        .or_else(|| extra.and_then(|extra| extra.get("email_verified")))
+        .is_some_and(|value| value.as_bool() == Some(true) || value.as_str() == Some("true"));
+
+    Ok(OidcUser {
+        subject,
+        email: claim(&provider.email_claim),
+        email_verified,
+        name: claim(&provider.name_claim).or_else(|| claim("preferred_username")),
+        picture: claim(&provider.picture_claim),
+    })
+}
+
+/// 按 `kid` 查找签名密钥；令牌未指定 `kid` 时只有在 JWKS 中只有一个密钥时才使用它
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+/// 只允许 HTTPS，本机地址例外，便于对接本地的模拟 IdP
+fn is_secure_url(url: &str) -> bool {
+    if url.starts_with("https://") {
+        return true;
+    }
+    let Some(rest) = url.strip_prefix("http://") else {
+        return false;
+    };
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use argon2::password_hash::rand_core::OsRng;
+    use axum::{Json, Router, extract::State, routing::get};
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use p256::{ecdsa::SigningKey, pkcs8::EncodePrivateKey};
+    use serde_json::json;
+
+    use super::*;
+
+    const CLIENT_ID: &str = "doc-editor";
+    const NONCE: &str = "test-nonce";
+
+    /// 测试用的签名密钥
+    struct TestKey {
+        kid: String,
+        key: SigningKey,
+    }
+
+    impl TestKey {
+        fn new(kid: &str) -> Self {
+            TestKey {
+                kid: kid.to_string(),
+                key: SigningKey::random(&mut OsRng),
+            }
+        }
+
+        fn jwk(&self) -> Value {
+            let point = self.key.verifying_key().to_encoded_point(false);
+            json!({
+                "kty": "EC",
+                "crv": "P-256",
+                "kid": self.kid,
+                "use": "sig",
+                "alg": "ES256",
+                "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+                "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+            })
+        }
+
+        fn sign(&self, claims: &Value) -> String {
+            let der = self.key.to_pkcs8_der().unwrap();
+            let mut header = Header::new(Algorithm::ES256);
+            header.kid = Some(self.kid.clone());
+            encode(&header, claims, &EncodingKey::from_ec_der(der.as_bytes())).unwrap()
+        }
+    }
+
+    /// 本地的模拟提供方，提供发现文档和 JWKS
+    struct TestProvider {
+        config: OidcProviderConfig,
+        keys: Arc<Mutex<Vec<Value>>>,
+        jwks_fetches: Arc<AtomicUsize>,
+    }
+
+    #[derive(Clone)]
+    struct ProviderState {
+        issuer: String,
+        keys: Arc<Mutex<Vec<Value>>>,
+        jwks_fetches: Arc<AtomicUsize>,
+    }
+
+    impl TestProvider {
+        async fn start(keys: &[&TestKey]) -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            let state = ProviderState {
+                issuer: issuer.clone(),
+                keys: Arc::new(Mutex::new(keys.iter().map(|key| key.jwk()).collect())),
+                jwks_fetches: Arc::new(AtomicUsize::new(0)),
+            };
+
+            let app = Router::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    get(|State(state): State<ProviderState>| async move {
+                        Json(json!({
+                            "issuer": state.issuer,
+                            "authorization_endpoint": format!("{}/authorize", state.issuer),
+                            "token_endpoint": format!("{}/token", state.issuer),
+                            "jwks_uri": format!("{}/jwks", state.issuer),
+                        }))
+                    }),
+                )
+                .route(
+                    "/jwks",
+                    get(|State(state): State<ProviderState>| async move {
+                        state.jwks_fetches.fetch_add(1, Ordering::SeqCst);
+                        Json(json!({ "keys": *state.keys.lock().unwrap() }))
+                    }),
+                )
+                .with_state(state.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            TestProvider {
+                config: provider_config(&issuer),
+                keys: state.keys,
+                jwks_fetches: state.jwks_fetches,
+            }
+        }
+
+        fn claims(&self) -> Value {
+            let now = chrono::Utc::now().timestamp();
+            json!({
+                "iss": self.config.issuer,
+                "aud": CLIENT_ID,
+                "sub": "user-1",
+                "iat": now,
+                "exp": now + 300,
+                "nonce": NONCE,
+            })
+        }
+    }
+
+    fn provider_config(issuer: &str) -> OidcProviderConfig {
+        OidcProviderConfig {
+            name: "acme".to_string(),
+            display_name: "Acme".to_string(),
+            issuer: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: "secret".to_string(),
+            redirect_url: "http://localhost/callback".to_string(),
+            scopes: vec!["openid".to_string()],
+            name_claim: "name".to_string(),
+            email_claim: "email".to_string(),
+            picture_claim: "picture".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_id_token() {
+        let key = TestKey::new("key-1");
+        let provider = TestProvider::start(&[&key]).await;
+        let client = OidcClient::new();
+
+        let claims = client
+            .verify_id_token(&provider.config, &key.sign(&provider.claims()), NONCE)
+            .await
+            .unwrap();
+        assert_eq!(claims["sub"], "user-1");
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_issuer_and_audience() {
+        let key = TestKey::new("key-1");
+        let provider = TestProvider::start(&[&key]).await;
+        let client = OidcClient::new();
+
+        let mut claims = provider.claims();
+        claims["iss"] = json!("https://evil.example.com");
+        assert!(
+            client
+                .verify_id_token(&provider.config, &key.sign(&claims), NONCE)
+                .await
+                .is_err()
+        );
+
+        let mut claims = provider.claims();
+        claims["aud"] = json!("another-client");
+        assert!(
+            client
+                .verify_id_token(&provider.config, &key.sign(&claims), NONCE)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn multiple_audiences_require_azp() {
+        let key = TestKey::new("key-1");
+        let provider = TestProvider::start(&[&key]).await;
+        let client = OidcClient::new();
+
+        let mut claims = provider.claims();
+        claims["aud"] = json!([CLIENT_ID, "another-client"]);
+        let err = client
+            .verify_id_token(&provider.config, &key.sign(&claims), NONCE)
+            .await
+            .unwrap_err();
+        assert!(err.contains("授权方"), "{}", err);
+
+        claims["azp"] = json!("another-client");
+        assert!(
+            client
+                .verify_id_token(&provider.config, &key.sign(&claims), NONCE)
+                .await
+                .is_err()
+        );
+
+        claims["azp"] = json!(CLIENT_ID);
+        assert!(
+            client
+                .verify_id_token(&provider.config, &key.sign(&claims), NONCE)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_nonce() {
+        let key = TestKey::new("key-1");
+        let provider = TestProvider::start(&[&key]).await;
+        let client = OidcClient::new();
+
+        let token = key.sign(&provider.claims());
+        let err = client
+            .verify_id_token(&provider.config, &token, "another-nonce")
+            .await
+            .unwrap_err();
+        assert!(err.contains("nonce"), "{}", err);
+
+        let mut claims = provider.claims();
+        claims.as_object_mut().unwrap().remove("nonce");
+        assert!(
+            client
+                .verify_id_token(&provider.config, &key.sign(&claims), NONCE)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_kid_refreshes_jwks() {
+        let old_key = TestKey::new("key-1");
+        let new_key = TestKey::new("key-2");
+        let provider = TestProvider::start(&[&old_key]).await;
+        let client = OidcClient::new();
+
+        client
+            .verify_id_token(&provider.config, &old_key.sign(&provider.claims()), NONCE)
+            .await
+            .unwrap();
+        assert_eq!(provider.jwks_fetches.load(Ordering::SeqCst), 1);
+
+        // -- 提供方轮换密钥，刚获取过 JWKS 时不重新获取
+        *provider.keys.lock().unwrap() = vec![new_key.jwk()];
+        let token = new_key.sign(&provider.claims());
+        assert!(
+            client
+                .verify_id_token(&provider.config, &token, NONCE)
+                .await
+                .is_err()
+        );
+        assert_eq!(provider.jwks_fetches.load(Ordering::SeqCst), 1);
+
+        // -- 超过刷新间隔后，未知的 kid 触发重新获取
+        if let Some(cached) = client.cache.write().unwrap().get_mut("acme") {
+            cached.fetched_at = Instant::now() - JWKS_REFRESH_INTERVAL;
+        }
+        let claims = client
+            .verify_id_token(&provider.config, &token, NONCE)
+            .await
+            .unwrap();
+        assert_eq!(claims["sub"], "user-1");
+        assert_eq!(provider.jwks_fetches.load(Ordering::SeqCst), 2);
+
+        // -- 旧密钥已不在 JWKS 中
+        assert!(
+            client
+                .verify_id_token(&provider.config, &old_key.sign(&provider.claims()), NONCE)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_none_and_symmetric_algorithms() {
+        let key = TestKey::new("key-1");
+        let provider = TestProvider::start(&[&key]).await;
+        let client = OidcClient::new();
+        let claims = provider.claims();
+
+        // -- alg 为 none 的未签名令牌
+        let unsigned = format!(
+            "{}.{}.",
+            URL_SAFE_NO_PAD.encode(json!({ "alg": "none", "kid": "key-1" }).to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string()),
+        );
+        assert!(
+            client
+                .verify_id_token(&provider.config, &unsigned, NONCE)
+                .await
+                .is_err()
+        );
+
+        // -- 用公开的 JWK 作为 HS256 密钥签名
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("key-1".to_string());
+        let secret = key.jwk().to_string();
+        let token = encode(
+            &header,
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap();
+        let err = client
+            .verify_id_token(&provider.config, &token, NONCE)
+            .await
+            .unwrap_err();
+        assert!(err.contains("不支持"), "{}", err);
+
+        // -- 算法检查在获取 JWKS 之前
+        assert_eq!(provider.jwks_fetches.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn secure_url() {
+        assert!(is_secure_url(
+            "https://idp.example.com/.well-known/jwks.json"
+        ));
+        assert!(is_secure_url("http://localhost/jwks"));
+        assert!(is_secure_url("http://127.0.0.1:8080/jwks"));
+        assert!(is_secure_url("http://[::1]:8080"));
+        assert!(is_secure_url("http://localhost?x=1"));
+
+        assert!(!is_secure_url("http://idp.example.com/jwks"));
+        assert!(!is_secure_url("http://localhost.example.com/jwks"));
+        assert!(!is_secure_url("http://localhost@example.com/jwks"));
+        assert!(!is_secure_url("http://127.0.0.1.example.com/jwks"));
+        assert!(!is_secure_url("http://localhost:80@example.com"));
+        assert!(!is_secure_url("ftp://localhost/jwks"));
+        assert!(!is_secure_url("//localhost/jwks"));
+    }
+
+    #[test]
+    fn maps_claims() {
+        let provider = provider_config("https://idp.example.com");
+        let claims = |value: Value| value.as_object().unwrap().clone();
+
+        let user = map_claims(
+            &provider,
+            &claims(json!({
+                "sub": "user-1",
+                "email": "alice@example.com",
+                "email_verified": "true",
+                "preferred_username": "alice",
+            })),
+            None,
+        )
+        .unwrap();
+        assert_eq!(user.subject, "user-1");
+        assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+        assert!(user.email_verified);
+        assert_eq!(user.name.as_deref(), Some("alice"));
+        assert_eq!(user.picture, None);
+
+        for verified in [json!(false), json!("false"), json!("yes"), json!(1)] {
+            let user = map_claims(
+                &provider,
+                &claims(json!({ "sub": "user-1", "email_verified": verified })),
+                None,
+            )
+            .unwrap();
+            assert!(!user.email_verified, "{:?}", verified);
+        }
+
+        // -- ID 令牌中没有的声明从 userinfo 补充，已有的不被覆盖
+        let userinfo = claims(json!({
+            "sub": "user-1",
+            "name": "Alice",
+            "email": "other@example.com",
+            "email_verified": true,
+            "picture": "https://idp.example.com/alice.png",
+        }));
+        let user = map_claims(
+            &provider,
+            &claims(json!({ "sub": "user-1", "email": "alice@example.com" })),
+            Some(&userinfo),
+        )
+        .unwrap();
+        assert_eq!(user.email.as_deref(), Some("alice@example.com"));
+        assert!(user.email_verified);
+        assert_eq!(user.name.as_deref(), Some("Alice"));
+        assert_eq!(
+            user.picture.as_deref(),
+            Some("https://idp.example.com/alice.png")
+        );
+
+        assert!(map_claims(&provider, &claims(json!({ "sub": "" })), None).is_err());
+        assert!(map_claims(&provider, &claims(json!({})), None).is_err());
+    }
+
+    #[test]
+    fn finds_key() {
+        let jwks = |keys: Vec<Value>| -> JwkSet {
+            serde_json::from_value(json!({ "keys": keys })).unwrap()
+        };
+        let one = TestKey::new("key-1").jwk();
+        let two = TestKey::new("key-2").jwk();
+
+        let single = jwks(vec![one.clone()]);
+        assert!(find_key(&single, Some("key-1")).is_some());
+        assert!(find_key(&single, Some("key-2")).is_none());
+        assert!(find_key(&single, None).is_some());
+
+        // -- 有多个密钥时令牌必须指定 kid
+        let both = jwks(vec![one, two]);
+        assert_eq!(
+            find_key(&both, Some("key-2")).and_then(|jwk| jwk.common.key_id.as_deref()),
+            Some("key-2")
+        );
+        assert!(find_key(&both, None).is_none());
+        assert!(find_key(&jwks(vec![]), None).is_none());
+    }
+}
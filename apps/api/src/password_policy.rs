@@ -0,0 +1,271 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use uuid::Uuid;
+
+use crate::{
+    config::PasswordPolicyConfig,
+    db::{DBClient, PasswordHistoryExt},
+    error::HttpError,
+    utils::{password, password_strength},
+};
+
+/// 泄露密码列表中每条 SHA-1 摘要的长度
+const DIGEST_LEN: u64 = 20;
+/// 检查是否包含用户名或邮箱时忽略的过短片段
+const MIN_PERSONAL_INFO_LEN: usize = 3;
+
+/// 密码策略规则
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordRule {
+    MinLength,
+    Strength,
+    PersonalInfo,
+    Reused,
+    Breached,
+}
+
+/// 一条未满足的规则，作为错误响应中 `errors` 的元素
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordViolation {
+    pub rule: PasswordRule,
+    pub message: String,
+}
+
+/// 设置密码的用户，注册时还没有 `id` 和旧密码
+pub struct PasswordOwner<'a> {
+    pub id: Option<Uuid>,
+    pub name: &'a str,
+    pub email: &'a str,
+    /// 当前密码的哈希
+    pub current_hash: Option<&'a str>,
+}
+
+/// 本地的泄露密码列表
+///
+/// 文件由按升序排列的 20 字节 SHA-1 摘要组成，查询时在文件上二分查找，不需要载入内存。
+struct BreachedPasswords {
+    file: Mutex<File>,
+    count: u64,
+}
+
+impl BreachedPasswords {
+    fn open(path: &Path) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|e| format!("打开泄露密码列表 {:?} 失败: {}", path, e))?;
+        let len = file
+            .metadata()
+            .map_err(|e| format!("读取泄露密码列表 {:?} 失败: {}", path, e))?
+            .len();
+
+        if len % DIGEST_LEN != 0 {
+            return Err(format!(
+                "泄露密码列表 {:?} 的长度不是 {} 字节的整数倍",
+                path, DIGEST_LEN
+            ));
+        }
+
+        Ok(BreachedPasswords {
+            file: Mutex::new(file),
+            count: len / DIGEST_LEN,
+        })
+    }
+
+    fn contains(&self, password: &str) -> io::Result<bool> {
+        let digest: [u8; DIGEST_LEN as usize] = Sha1::digest(password.as_bytes()).into();
+        let mut file = self.file.lock().unwrap();
+        let mut entry = [0u8; DIGEST_LEN as usize];
+
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            file.seek(SeekFrom::Start(mid * DIGEST_LEN))?;
+            file.read_exact(&mut entry)?;
+
+            match entry.cmp(&digest) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Ok(true),
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// 注册、重置和修改密码时检查新密码
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+    breached: Option<BreachedPasswords>,
+}
+
+impl PasswordPolicy {
+    pub fn new(config: &PasswordPolicyConfig) -> Result<Self, String> {
+        let breached = match &config.breached_list_path {
+            Some(path) => {
+                let list = BreachedPasswords::open(Path::new(path))?;
+                tracing::info!("已加载泄露密码列表 {}，共 {} 条", path, list.count);
+                Some(list)
+            }
+            None => None,
+        };
+
+        Ok(PasswordPolicy {
+            config: config.clone(),
+            breached,
+        })
+    }
+
+    /// 检查新密码，未满足的规则全部列在错误响应的 `errors` 中
+    pub async fn check(
+        &self,
+        db_client: &DBClient,
+        new_password: &str,
+        owner: PasswordOwner<'_>,
+    ) -> Result<(), HttpError> {
+        let mut violations = Vec::new();
+        let mut violate = |rule, message: String| {
+            violations.push(PasswordViolation { rule, message });
+        };
+
+        if new_password.chars().count() < self.config.min_length {
+            violate(
+                PasswordRule::MinLength,
+                format!(
+                    "Password must be at least {} characters",
+                    self.config.min_length
+                ),
+            );
+        }
+
+        if self.config.min_score > 0 && self.score(new_password).await? < self.config.min_score {
+            violate(
+                PasswordRule::Strength,
+                "Password is too easy to guess, avoid common words, sequences and repeated characters"
+                    .to_string(),
+            );
+        }
+
+        if self.config.forbid_personal_info && contains_personal_info(new_password, &owner) {
+            violate(
+                PasswordRule::PersonalInfo,
+                "Password must not contain your name or email".to_string(),
+            );
+        }
+
+        if self.is_reused(db_client, new_password, &owner).await? {
+            violate(
+                PasswordRule::Reused,
+                format!(
+                    "Password must differ from your last {} passwords",
+                    self.config.history_size
+                ),
+            );
+        }
+
+        if let Some(breached) = &self.breached {
+            match breached.contains(new_password) {
+                Ok(true) => violate(
+                    PasswordRule::Breached,
+                    "Password has appeared in a data breach, please choose another one".to_string(),
+                ),
+                Ok(false) => {}
+                // -- 列表读取失败时不阻止设置密码
+                Err(e) => tracing::error!("查询泄露密码列表失败: {}", e),
+            }
+        }
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            "新密码不符合密码策略: {:?}",
+            violations.iter().map(|v| v.rule).collect::<Vec<_>>()
+        );
+
+        Err(
+            HttpError::bad_request("Password does not meet the password policy")
+                .with_errors(serde_json::to_value(&violations).unwrap_or_default()),
+        )
+    }
+
+    /// 密码修改成功后记住被替换的密码，供之后的重复使用检查
+    pub async fn remember(
+        &self,
+        db_client: &DBClient,
+        user_id: Uuid,
+        old_hash: &str,
+    ) -> Result<(), HttpError> {
+        // -- 当前密码单独检查，历史中只需保留更早的 N - 1 个
+        let keep = self.config.history_size - 1;
+        if keep > 0 {
+            db_client
+                .record_password_history(user_id, old_hash, keep)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 强度评分，估算在阻塞线程池中进行，不占用异步运行时的工作线程
+    async fn score(&self, new_password: &str) -> Result<u8, HttpError> {
+        let new_password = new_password.to_string();
+        tokio::task::spawn_blocking(move || password_strength::estimate(&new_password).score)
+            .await
+            .map_err(|e| HttpError::server_error(e.to_string()))
+    }
+
+    /// 新密码与当前密码或最近使用过的密码相同
+    ///
+    /// 每次比较都要计算一次 Argon2 哈希，在阻塞线程池中进行。
+    async fn is_reused(
+        &self,
+        db_client: &DBClient,
+        new_password: &str,
+        owner: &PasswordOwner<'_>,
+    ) -> Result<bool, HttpError> {
+        if self.config.history_size == 0 {
+            return Ok(false);
+        }
+
+        let mut hashes: Vec<String> = owner.current_hash.map(str::to_string).into_iter().collect();
+        if let Some(user_id) = owner.id
+            && self.config.history_size > 1
+        {
+            hashes.extend(
+                db_client
+                    .get_password_history(user_id, self.config.history_size - 1)
+                    .await?,
+            );
+        }
+
+        let new_password = new_password.to_string();
+        tokio::task::spawn_blocking(move || {
+            hashes
+                .iter()
+                .any(|hash| password::compare(&new_password, hash).unwrap_or(false))
+        })
+        .await
+        .map_err(|e| HttpError::server_error(e.to_string()))
+    }
+}
+
+/// 密码包含用户名、用户名中的单词或邮箱的用户名部分（不区分大小写）
+fn contains_personal_info(new_password: &str, owner: &PasswordOwner<'_>) -> bool {
+    let new_password = new_password.to_lowercase();
+    let name = owner.name.to_lowercase();
+    let email = owner.email.to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+
+    std::iter::once(name.as_str())
+        .chain(name.split(|c: char| !c.is_alphanumeric()))
+        .chain([email.as_str(), local_part])
+        .chain(local_part.split(|c: char| !c.is_alphanumeric()))
+        .filter(|part| part.chars().count() >= MIN_PERSONAL_INFO_LEN)
+        .any(|part| new_password.contains(part))
+}
@@ -0,0 +1,482 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use axum::{
+    Extension,
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState, audit,
+    config::{RateLimitConfig, RateLimitStoreKind, RouteRateLimit, SlidingWindow},
+    db::{DBClient, DbResult, RateLimitExt},
+    error::HttpError,
+};
+
+/// 内存中最多保存的计数键数量，超出时先清理过期的计数，再淘汰最久没有请求的
+const MAX_MEMORY_KEYS: usize = 100_000;
+/// Postgres 存储清理过期计数的间隔
+const PURGE_INTERVAL: Duration = Duration::from_secs(600);
+/// 为查找邮箱读取请求体时允许的最大长度
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// 需要限流的接口
+#[derive(Debug, Clone, Copy)]
+pub enum RateLimitRoute {
+    Login,
+    Register,
+    ForgotPassword,
+    ResendVerification,
+    AccessRequest,
+    EthereumNonce,
+}
+
+impl RateLimitRoute {
+    fn name(self) -> &'static str {
+        match self {
+            RateLimitRoute::Login => "login",
+            RateLimitRoute::Register => "register",
+            RateLimitRoute::ForgotPassword => "forgot_password",
+            RateLimitRoute::ResendVerification => "resend_verification",
+            RateLimitRoute::AccessRequest => "access_request",
+            RateLimitRoute::EthereumNonce => "ethereum_nonce",
+        }
+    }
+
+    fn rules(self, config: &RateLimitConfig) -> RouteRateLimit {
+        match self {
+            RateLimitRoute::Login => config.login,
+            RateLimitRoute::Register => config.register,
+            RateLimitRoute::ForgotPassword => config.forgot_password,
+            RateLimitRoute::ResendVerification => config.resend_verification,
+            RateLimitRoute::AccessRequest => config.access_request,
+            RateLimitRoute::EthereumNonce => config.ethereum_nonce,
+        }
+    }
+}
+
+/// 限流计数的存储
+#[async_trait]
+trait RateLimitStore: Send + Sync {
+    /// 在滑动窗口中记录一次请求，超出限制时返回需要等待的时长
+    ///
+    /// 被拒绝的请求不计数，否则持续重试的客户端永远无法恢复。
+    async fn hit(&self, key: &str, window: SlidingWindow) -> DbResult<Option<Duration>>;
+}
+
+/// 内存存储，记录窗口内每次请求的时间
+struct MemoryStore {
+    hits: Mutex<HashMap<String, (Duration, VecDeque<Instant>)>>,
+    capacity: usize,
+}
+
+impl MemoryStore {
+    fn new(capacity: usize) -> Self {
+        MemoryStore {
+            hits: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    fn record(&self, key: &str, window: SlidingWindow) -> Option<Duration> {
+        let now = Instant::now();
+        let length = Duration::from_secs(window.window_seconds);
+
+        let mut hits = self.hits.lock().unwrap();
+        if hits.len() >= self.capacity && !hits.contains_key(key) {
+            hits.retain(|_, (length, times)| times.back().is_some_and(|at| at.elapsed() < *length));
+            // -- 清理后仍然过多时淘汰最久没有请求的计数，留出十分之一的空间，避免每次都要整理
+            let keep = self.capacity - self.capacity / 10;
+            if hits.len() > keep {
+                let mut last_hits: Vec<Option<Instant>> = hits
+                    .values()
+                    .map(|(_, times)| times.back().copied())
+                    .collect();
+                let evict = hits.len() - keep;
+                let (_, cutoff, _) = last_hits.select_nth_unstable(evict - 1);
+                let cutoff = *cutoff;
+                hits.retain(|_, (_, times)| times.back().copied() > cutoff);
+            }
+        }
+
+        let (stored_length, times) = hits
+            .entry(key.to_string())
+            .or_insert_with(|| (length, VecDeque::new()));
+        *stored_length = length;
+
+        while times
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= length)
+        {
+            times.pop_front();
+        }
+
+        if times.len() >= window.limit as usize {
+            let waited = times
+                .front()
+                .map_or(Duration::ZERO, |at| now.duration_since(*at));
+            return Some(length.saturating_sub(waited));
+        }
+
+        times.push_back(now);
+        None
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn hit(&self, key: &str, window: SlidingWindow) -> DbResult<Option<Duration>> {
+        Ok(self.record(key, window))
+    }
+}
+
+/// Postgres 存储，多个实例共享同一份计数
+struct PostgresStore {
+    db_client: DBClient,
+    /// 最长的窗口，更早的计数可以清理
+    max_window: Duration,
+    last_purge: Mutex<Instant>,
+}
+
+#[async_trait]
+impl RateLimitStore for PostgresStore {
+    async fn hit(&self, key: &str, window: SlidingWindow) -> DbResult<Option<Duration>> {
+        let purge = {
+            let mut last_purge = self.last_purge.lock().unwrap();
+            let due = last_purge.elapsed() >= PURGE_INTERVAL;
+            if due {
+                *last_purge = Instant::now();
+            }
+            due
+        };
+        if purge {
+            let before = Utc::now()
+                - chrono::Duration::from_std(self.max_window).unwrap_or(chrono::Duration::days(1));
+            let purged = self.db_client.purge_rate_limit_hits(before).await?;
+            tracing::debug!("清理了 {} 条过期的限流计数", purged);
+        }
+
+        let retry_at = self
+            .db_client
+            .record_rate_limit_hit(key, window.limit as i64, window.window_seconds as f64)
+            .await?;
+
+        Ok(retry_at.map(|retry_at| (retry_at - Utc::now()).to_std().unwrap_or(Duration::ZERO)))
+    }
+}
+
+/// 按 IP 和目标邮箱限流
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    store: Box<dyn RateLimitStore>,
+    /// 存储出错时改用本实例的内存计数，限流不会因为数据库故障而失效
+    fallback: MemoryStore,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig, db_client: DBClient) -> Self {
+        let store: Box<dyn RateLimitStore> = match config.store {
+            RateLimitStoreKind::Memory => Box::new(MemoryStore::new(MAX_MEMORY_KEYS)),
+            RateLimitStoreKind::Postgres => {
+                let max_window = [
+                    config.login,
+                    config.register,
+                    config.forgot_password,
+                    config.resend_verification,
+                    config.access_request,
+                    config.ethereum_nonce,
+                ]
+                .iter()
+                .flat_map(|rules| [rules.by_ip, rules.by_email])
+                .flatten()
+                .map(|window| window.window_seconds)
+                .max()
+                .unwrap_or(0);
+
+                Box::new(PostgresStore {
+                    db_client,
+                    max_window: Duration::from_secs(max_window),
+                    last_purge: Mutex::new(Instant::now()),
+                })
+            }
+        };
+
+        RateLimiter {
+            config: config.clone(),
+            store,
+            fallback: MemoryStore::new(MAX_MEMORY_KEYS),
+        }
+    }
+
+    /// 检查请求是否超出接口的限制，超出时返回带 `Retry-After` 的 429 错误
+    ///
+    /// 存储出错时改用本实例的内存计数，多实例部署时限制暂时按实例分别计算。
+    pub async fn check(
+        &self,
+        route: RateLimitRoute,
+        ip_address: Option<IpAddr>,
+        email: Option<&str>,
+    ) -> Result<(), HttpError> {
+        let rules = route.rules(&self.config);
+        let ip_address = ip_address.map(|ip| ip.to_canonical().to_string());
+        // -- 邮箱取 SHA-256 摘要作为计数键，长度固定，计数表中也不保存明文邮箱
+        let email_digest = email.map(|email| hex::encode(Sha256::digest(email.as_bytes())));
+        let checks = [
+            (
+                rules.by_ip,
+                "ip",
+                ip_address.as_deref(),
+                ip_address.as_deref(),
+            ),
+            (rules.by_email, "email", email, email_digest.as_deref()),
+        ];
+
+        for (window, kind, value, key) in checks {
+            let (Some(window), Some(value), Some(key)) = (window, value, key) else {
+                continue;
+            };
+
+            let key = format!("{}:{}:{}", route.name(), kind, key);
+            let retry_after = match self.store.hit(&key, window).await {
+                Ok(retry_after) => retry_after,
+                Err(e) => {
+                    tracing::error!("限流计数失败，改用本实例的内存计数: {}", e);
+                    self.fallback.record(&key, window)
+                }
+            };
+
+            if let Some(retry_after) = retry_after {
+                tracing::warn!("请求过于频繁，接口: {}, {}: {}", route.name(), kind, value);
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                return Err(HttpError::too_many_requests(
+                    "请求过于频繁，请稍后再试",
+                    seconds.max(1),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct EmailField {
+    email: Option<String>,
+}
+
+/// 中间件 -- 按客户端 IP 和请求体中的 `email` 字段限流
+///
+/// 用法：`.layer(middleware::from_fn_with_state(RateLimitRoute::Login, rate_limit))`
+pub async fn rate_limit(
+    State(route): State<RateLimitRoute>,
+    Extension(app_state): Extension<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, HttpError> {
+    let ip_address = audit::current_context().ip();
+
+    // -- 需要按邮箱限流时读出请求体，再原样放回请求
+    let (req, email) = if route.rules(&app_state.env.rate_limit).by_email.is_some() {
+        let (parts, body) = req.into_parts();
+        let bytes = to_bytes(body, MAX_BODY_BYTES)
+            .await
+            .map_err(|_| HttpError::bad_request("请求体过大".to_string()))?;
+
+        let email = serde_json::from_slice::<EmailField>(&bytes)
+            .ok()
+            .and_then(|field| field.email)
+            .map(|email| email.trim().to_lowercase())
+            .filter(|email| !email.is_empty());
+
+        (Request::from_parts(parts, Body::from(bytes)), email)
+    } else {
+        (req, None)
+    };
+
+    app_state
+        .rate_limiter
+        .check(route, ip_address, email.as_deref())
+        .await?;
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::DbError;
+
+    const WINDOW: SlidingWindow = SlidingWindow {
+        limit: 2,
+        window_seconds: 60,
+    };
+
+    fn config(store: RateLimitStoreKind) -> RateLimitConfig {
+        let route = RouteRateLimit {
+            by_ip: Some(WINDOW),
+            by_email: Some(WINDOW),
+        };
+        RateLimitConfig {
+            store,
+            login: route,
+            register: route,
+            forgot_password: route,
+            resend_verification: route,
+            access_request: route,
+            ethereum_nonce: route,
+            lockout_threshold: 5,
+            lockout_base_seconds: 60,
+            lockout_max_seconds: 3600,
+        }
+    }
+
+    /// 总是出错的存储，模拟数据库故障
+    struct FailingStore;
+
+    #[async_trait]
+    impl RateLimitStore for FailingStore {
+        async fn hit(&self, _key: &str, _window: SlidingWindow) -> DbResult<Option<Duration>> {
+            Err(DbError::NotFound("rate_limit_hits".to_string()))
+        }
+    }
+
+    fn assert_limited(result: Result<(), HttpError>) {
+        let err = result.unwrap_err();
+        assert_eq!(err.status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(
+            err.retry_after
+                .is_some_and(|seconds| (1..=60).contains(&seconds))
+        );
+    }
+
+    #[test]
+    fn memory_store_counts_sliding_window() {
+        let store = MemoryStore::new(MAX_MEMORY_KEYS);
+
+        assert!(store.record("login:ip:10.0.0.1", WINDOW).is_none());
+        assert!(store.record("login:ip:10.0.0.1", WINDOW).is_none());
+        let retry_after = store.record("login:ip:10.0.0.1", WINDOW).unwrap();
+        assert!(retry_after > Duration::from_secs(59) && retry_after <= Duration::from_secs(60));
+
+        // -- 被拒绝的请求不计数，其他键不受影响
+        assert_eq!(store.hits.lock().unwrap()["login:ip:10.0.0.1"].1.len(), 2);
+        assert!(store.record("login:ip:10.0.0.2", WINDOW).is_none());
+    }
+
+    #[test]
+    fn memory_store_is_bounded() {
+        let store = MemoryStore::new(100);
+
+        for i in 0..1000 {
+            store.record(&format!("login:ip:{}", i), WINDOW);
+            assert!(store.hits.lock().unwrap().len() <= 100);
+        }
+
+        // -- 最近的计数保留，最早的被淘汰
+        let hits = store.hits.lock().unwrap();
+        assert!(hits.contains_key("login:ip:999"));
+        assert!(!hits.contains_key("login:ip:0"));
+    }
+
+    #[test]
+    fn memory_store_prunes_expired_keys_first() {
+        let store = MemoryStore::new(100);
+        let expired = SlidingWindow {
+            limit: 2,
+            window_seconds: 0,
+        };
+
+        for i in 0..100 {
+            store.record(&format!("login:ip:{}", i), expired);
+        }
+        store.record("login:ip:new", WINDOW);
+
+        let hits = store.hits.lock().unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits.contains_key("login:ip:new"));
+    }
+
+    #[sqlx::test]
+    async fn store_errors_fall_back_to_memory(pool: PgPool) {
+        let mut limiter =
+            RateLimiter::new(&config(RateLimitStoreKind::Postgres), DBClient::new(pool));
+        limiter.store = Box::new(FailingStore);
+        let ip = "203.0.113.7".parse().ok();
+
+        assert!(limiter.check(RateLimitRoute::Login, ip, None).await.is_ok());
+        assert!(limiter.check(RateLimitRoute::Login, ip, None).await.is_ok());
+        assert_limited(limiter.check(RateLimitRoute::Login, ip, None).await);
+    }
+
+    #[sqlx::test]
+    async fn ip_keys_use_canonical_address(pool: PgPool) {
+        let limiter = RateLimiter::new(&config(RateLimitStoreKind::Memory), DBClient::new(pool));
+        let ipv4 = "203.0.113.7".parse().ok();
+        let mapped = "::ffff:203.0.113.7".parse().ok();
+
+        assert!(
+            limiter
+                .check(RateLimitRoute::Login, ipv4, None)
+                .await
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check(RateLimitRoute::Login, mapped, None)
+                .await
+                .is_ok()
+        );
+        assert_limited(limiter.check(RateLimitRoute::Login, ipv4, None).await);
+    }
+
+    #[sqlx::test]
+    async fn long_emails_fit_postgres_keys(pool: PgPool) {
+        let limiter = RateLimiter::new(
+            &config(RateLimitStoreKind::Postgres),
+            DBClient::new(pool.clone()),
+        );
+        let email = format!("{}@example.com", "a".repeat(1000));
+
+        assert!(
+            limiter
+                .check(RateLimitRoute::Register, None, Some(&email))
+                .await
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check(RateLimitRoute::Register, None, Some(&email))
+                .await
+                .is_ok()
+        );
+        assert_limited(
+            limiter
+                .check(RateLimitRoute::Register, None, Some(&email))
+                .await,
+        );
+
+        // -- 计数保存在 Postgres 中，而不是存储出错后的内存计数
+        let keys: Vec<String> = sqlx::query_scalar("SELECT key FROM rate_limit_hits")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(keys.len(), 2);
+        assert!(
+            keys.iter()
+                .all(|key| key.len() == "register:email:".len() + 64)
+        );
+        assert!(limiter.fallback.hits.lock().unwrap().is_empty());
+    }
+}
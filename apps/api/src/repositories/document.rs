@@ -3,8 +3,17 @@ use chrono::{DateTime, Utc};
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::db::{DBClient, DbError, DbResult, DocumentExt};
-use crate::models::{Document, DocumentPermission, PermissionLevel};
+use crate::audit::{self, AuditEvent};
+use crate::db::{
+    CommentExt, DBClient, DbError, DbResult, DocumentExt, NotificationExt, UserExt, WebhookExt,
+};
+use crate::events::{DocumentEventKind, EventHub, NewDocumentEvent};
+use crate::mail::mails::send_mention_email;
+use crate::models::{
+    AuditAction, Document, DocumentPermission, NotificationType, PermissionLevel, WebhookEvent,
+};
+use crate::utils::mention::{self, Mention};
+use crate::webhooks;
 
 /// Document repository interface
 ///
@@ -90,14 +99,355 @@ pub trait DocumentRepository: Send + Sync {
 }
 
 /// Document repository implementation using the database client
+///
+/// Every successful write is published to the [`EventHub`] so that
+/// connected clients receive live updates.
 pub struct DbDocumentRepository {
     db_client: Arc<DBClient>,
+    event_hub: Arc<EventHub>,
 }
 
 impl DbDocumentRepository {
-    /// Create a new document repository with the given database client
-    pub fn new(db_client: Arc<DBClient>) -> Self {
-        Self { db_client }
+    /// Create a new document repository with the given database client and event hub
+    pub fn new(db_client: Arc<DBClient>, event_hub: Arc<EventHub>) -> Self {
+        Self {
+            db_client,
+            event_hub,
+        }
+    }
+
+    /// Publish a document event to the given recipients
+    fn publish(
+        &self,
+        kind: DocumentEventKind,
+        document: &Document,
+        actor_id: Uuid,
+        permission: Option<&DocumentPermission>,
+        recipients: Vec<Uuid>,
+    ) {
+        self.event_hub.publish(NewDocumentEvent {
+            kind,
+            document_id: document.id,
+            actor_id,
+            title: Some(document.title.clone()),
+            user_id: permission.map(|p| p.user_id),
+            permission: permission.map(|p| p.permission_level.clone()),
+            recipients,
+        });
+    }
+
+    /// Members of a document who receive its live events
+    ///
+    /// Events are best-effort: when the members cannot be loaded the event reaches nobody
+    /// instead of failing a write that has already been committed.
+    async fn event_recipients(&self, document_id: Uuid) -> Vec<Uuid> {
+        match self.db_client.get_document_member_ids(document_id).await {
+            Ok(recipients) => recipients,
+            Err(e) => {
+                tracing::error!("获取文档 {} 的成员失败: {}", document_id, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Publish a share event for a permission that has just been granted or changed
+    /// and notify the user whose access changed
+    ///
+    /// Failures are logged only, the permission change is already committed.
+    async fn publish_permission_event(
+        &self,
+        kind: DocumentEventKind,
+        notification_type: NotificationType,
+        permission: &DocumentPermission,
+        actor_id: Uuid,
+    ) {
+        let result = self
+            .try_publish_permission_event(kind, notification_type, permission, actor_id)
+            .await;
+
+        if let Err(e) = result {
+            tracing::error!("发布文档 {} 的共享事件失败: {}", permission.document_id, e);
+        }
+    }
+
+    async fn try_publish_permission_event(
+        &self,
+        kind: DocumentEventKind,
+        notification_type: NotificationType,
+        permission: &DocumentPermission,
+        actor_id: Uuid,
+    ) -> DbResult<()> {
+        let document = self
+            .db_client
+            .get_document(permission.document_id, Some(actor_id))
+            .await?
+            .ok_or(DbError::DocumentNotFound)?;
+        let recipients = self.event_recipients(permission.document_id).await;
+
+        self.publish(kind, &document, actor_id, Some(permission), recipients);
+
+        self.db_client
+            .create_notification(
+                permission.user_id,
+                Some(actor_id),
+                notification_type,
+                Some(document.id),
+                serde_json::json!({
+                    "title": document.title,
+                    "permission": permission.permission_level.to_str(),
+                }),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl DbDocumentRepository {
+    /// Record a document operation in the audit log
+    async fn audit_document(
+        &self,
+        action: AuditAction,
+        document_id: Uuid,
+        actor_id: Uuid,
+        details: serde_json::Value,
+    ) {
+        audit::record(
+            &self.db_client,
+            AuditEvent::new(action)
+                .actor_id(actor_id)
+                .target("document", document_id)
+                .details(details),
+        )
+        .await;
+    }
+
+    /// Queue webhook deliveries for a document event
+    ///
+    /// The payload carries document metadata only, never the document content.
+    /// Failures are logged only, the event itself is already committed.
+    pub async fn dispatch_webhooks(
+        &self,
+        event: WebhookEvent,
+        document: &Document,
+        actor_id: Uuid,
+        extra: Option<serde_json::Value>,
+    ) {
+        let mut data = serde_json::json!({
+            "document": {
+                "id": document.id,
+                "title": document.title,
+                "owner_id": document.owner_id,
+                "is_public": document.is_public,
+                "revision": document.revision,
+            },
+        });
+        if let (Some(data), Some(serde_json::Value::Object(extra))) = (data.as_object_mut(), extra)
+        {
+            data.extend(extra);
+        }
+
+        let result = self
+            .db_client
+            .enqueue_webhook_deliveries(
+                event,
+                document.id,
+                webhooks::event_payload(event, actor_id, data),
+            )
+            .await;
+
+        if let Err(e) = result {
+            tracing::error!(
+                "为文档 {} 创建 {} Webhook 投递失败: {}",
+                document.id,
+                event.to_str(),
+                e
+            );
+        }
+    }
+
+    /// Notify users newly mentioned in a document or one of its comments
+    ///
+    /// Mentions never grant access: only mentioned users who can already read
+    /// the document receive an in-app notification and an email.
+    ///
+    /// # Arguments
+    /// * `document_id` - Document the mentions belong to
+    /// * `actor_id` - User who wrote the mentions
+    /// * `mentions` - Mentions that were not present in the previous version
+    /// * `thread_id` - Comment thread, when the mentions come from a comment
+    /// * `excerpt` - Comment text shown in the notification
+    ///
+    /// Failures are logged only, the text carrying the mentions is already saved.
+    pub async fn notify_mentions(
+        &self,
+        document_id: Uuid,
+        actor_id: Uuid,
+        mentions: Vec<Mention>,
+        thread_id: Option<Uuid>,
+        excerpt: Option<String>,
+    ) {
+        let result = self
+            .try_notify_mentions(document_id, actor_id, mentions, thread_id, excerpt)
+            .await;
+
+        if let Err(e) = result {
+            tracing::error!("发送文档 {} 的提及通知失败: {}", document_id, e);
+        }
+    }
+
+    async fn try_notify_mentions(
+        &self,
+        document_id: Uuid,
+        actor_id: Uuid,
+        mentions: Vec<Mention>,
+        thread_id: Option<Uuid>,
+        excerpt: Option<String>,
+    ) -> DbResult<()> {
+        let targets = mention::split_mentions(mentions);
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let users = self
+            .db_client
+            .get_mentioned_users(&targets.user_ids, &targets.handles)
+            .await?;
+        if users.is_empty() {
+            return Ok(());
+        }
+
+        let document = self
+            .db_client
+            .get_document(document_id, Some(actor_id))
+            .await?
+            .ok_or(DbError::DocumentNotFound)?;
+        let actor = self
+            .db_client
+            .get_user(Some(actor_id), None, None, None)
+            .await?
+            .ok_or(DbError::UserNotFound)?;
+        let excerpt: Option<String> = excerpt.map(|text| text.chars().take(200).collect());
+
+        for user in users {
+            if user.id == actor_id {
+                continue;
+            }
+
+            let has_access = self
+                .db_client
+                .check_document_permission(document_id, user.id, PermissionLevel::Read)
+                .await?;
+            if !has_access {
+                continue;
+            }
+
+            let notification = self
+                .db_client
+                .create_notification(
+                    user.id,
+                    Some(actor_id),
+                    NotificationType::Mention,
+                    Some(document_id),
+                    serde_json::json!({
+                        "title": document.title,
+                        "thread_id": thread_id,
+                        "excerpt": excerpt,
+                    }),
+                )
+                .await?;
+
+            // -- 用户关闭了提及通知时同样不发送邮件
+            if notification.is_none() {
+                continue;
+            }
+
+            let actor_name = actor.name.clone();
+            let title = document.title.clone();
+            let excerpt = excerpt.clone();
+            tokio::spawn(async move {
+                let result = send_mention_email(
+                    &user.email,
+                    &user.name,
+                    &actor_name,
+                    &document_id.to_string(),
+                    &title,
+                    excerpt.as_deref(),
+                )
+                .await;
+
+                if let Err(e) = result {
+                    tracing::error!("发送提及邮件给用户 {} 失败: {}", user.email, e);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Side effects of a committed document update: comment anchors, mentions,
+    /// webhooks, audit log and live events
+    ///
+    /// Every step is best-effort and only logs failures, the update itself must not be
+    /// reported as failed once it has been committed.
+    ///
+    /// # Arguments
+    /// * `previous` - Document before the update, tells which mentions are new and
+    ///   whether the document got published
+    /// * `document` - Document after the update
+    /// * `content_changed` - Whether the update wrote new content
+    /// * `user_id` - User who made the update
+    pub async fn document_updated(
+        &self,
+        previous: Option<&Document>,
+        document: &Document,
+        content_changed: bool,
+        user_id: Uuid,
+    ) {
+        if content_changed {
+            // Keep comment anchors pointing at the text they were made on
+            if let Err(e) = self
+                .db_client
+                .remap_comment_anchors(document.id, &document.content)
+                .await
+            {
+                tracing::error!("重新定位文档 {} 的评论锚点失败: {}", document.id, e);
+            }
+
+            let previous_mentions = previous
+                .map(|previous| mention::document_mentions(&previous.content))
+                .unwrap_or_default();
+            let mentions = mention::new_mentions(
+                &previous_mentions,
+                mention::document_mentions(&document.content),
+            );
+            self.notify_mentions(document.id, user_id, mentions, None, None)
+                .await;
+        }
+
+        self.dispatch_webhooks(WebhookEvent::DocumentUpdated, document, user_id, None)
+            .await;
+        let was_public = previous.is_some_and(|previous| previous.is_public);
+        if document.is_public && !was_public {
+            self.dispatch_webhooks(WebhookEvent::DocumentPublished, document, user_id, None)
+                .await;
+            self.audit_document(
+                AuditAction::DocumentPublished,
+                document.id,
+                user_id,
+                serde_json::json!({}),
+            )
+            .await;
+        }
+
+        let recipients = self.event_recipients(document.id).await;
+        self.publish(
+            DocumentEventKind::Updated,
+            document,
+            user_id,
+            None,
+            recipients,
+        );
     }
 }
 
@@ -129,9 +479,34 @@ impl DocumentRepository for DbDocumentRepository {
         owner_id: Uuid,
         is_public: bool,
     ) -> DbResult<Document> {
-        self.db_client
+        let document = self
+            .db_client
             .create_document(title, content, owner_id, is_public)
-            .await
+            .await?;
+
+        self.publish(
+            DocumentEventKind::Created,
+            &document,
+            owner_id,
+            None,
+            vec![owner_id],
+        );
+
+        self.dispatch_webhooks(WebhookEvent::DocumentCreated, &document, owner_id, None)
+            .await;
+        if document.is_public {
+            self.dispatch_webhooks(WebhookEvent::DocumentPublished, &document, owner_id, None)
+                .await;
+            self.audit_document(
+                AuditAction::DocumentPublished,
+                document.id,
+                owner_id,
+                serde_json::json!({}),
+            )
+            .await;
+        }
+
+        Ok(document)
     }
 
     async fn update_document(
@@ -142,13 +517,52 @@ impl DocumentRepository for DbDocumentRepository {
         is_public: Option<bool>,
         user_id: Uuid,
     ) -> DbResult<Document> {
-        self.db_client
+        let content_changed = content.is_some();
+        // The previous version tells which mentions are new and whether the document gets published
+        let previous = self
+            .db_client
+            .get_document(document_id, Some(user_id))
+            .await?;
+
+        let document = self
+            .db_client
             .update_document(document_id, title, content, is_public, user_id)
-            .await
+            .await?;
+
+        self.document_updated(previous.as_ref(), &document, content_changed, user_id)
+            .await;
+
+        Ok(document)
     }
 
     async fn delete_document(&self, document_id: Uuid, user_id: Uuid) -> DbResult<()> {
-        self.db_client.delete_document(document_id, user_id).await
+        // Resolve the audience before the permissions are cascaded away
+        let document = self
+            .db_client
+            .get_document(document_id, Some(user_id))
+            .await?
+            .ok_or(DbError::DocumentNotFound)?;
+        let recipients = self.event_recipients(document_id).await;
+
+        self.db_client.delete_document(document_id, user_id).await?;
+
+        self.audit_document(
+            AuditAction::DocumentDeleted,
+            document_id,
+            user_id,
+            serde_json::json!({ "title": document.title }),
+        )
+        .await;
+
+        self.publish(
+            DocumentEventKind::Deleted,
+            &document,
+            user_id,
+            None,
+            recipients,
+        );
+
+        Ok(())
     }
 
     async fn get_user_document_count(&self, user_id: Uuid) -> DbResult<i64> {
@@ -162,9 +576,49 @@ impl DocumentRepository for DbDocumentRepository {
         permission_level: PermissionLevel,
         owner_id: Uuid,
     ) -> DbResult<DocumentPermission> {
-        self.db_client
+        let permission = self
+            .db_client
             .share_document(document_id, user_id, permission_level, owner_id)
+            .await?;
+
+        self.publish_permission_event(
+            DocumentEventKind::Shared,
+            NotificationType::DocumentShared,
+            &permission,
+            owner_id,
+        )
+        .await;
+
+        self.audit_document(
+            AuditAction::DocumentShared,
+            permission.document_id,
+            owner_id,
+            serde_json::json!({
+                "user_id": permission.user_id,
+                "permission": permission.permission_level.to_str(),
+            }),
+        )
+        .await;
+
+        if let Ok(Some(document)) = self
+            .db_client
+            .get_document(permission.document_id, Some(owner_id))
             .await
+        {
+            let permission_data = serde_json::json!({
+                "user_id": permission.user_id,
+                "permission": permission.permission_level.to_str(),
+            });
+            self.dispatch_webhooks(
+                WebhookEvent::DocumentShared,
+                &document,
+                owner_id,
+                Some(permission_data),
+            )
+            .await;
+        }
+
+        Ok(permission)
     }
 
     async fn update_document_permission(
@@ -173,9 +627,31 @@ impl DocumentRepository for DbDocumentRepository {
         permission_level: PermissionLevel,
         owner_id: Uuid,
     ) -> DbResult<DocumentPermission> {
-        self.db_client
+        let permission = self
+            .db_client
             .update_document_permission(permission_id, permission_level, owner_id)
-            .await
+            .await?;
+
+        self.audit_document(
+            AuditAction::PermissionChanged,
+            permission.document_id,
+            owner_id,
+            serde_json::json!({
+                "user_id": permission.user_id,
+                "permission": permission.permission_level.to_str(),
+            }),
+        )
+        .await;
+
+        self.publish_permission_event(
+            DocumentEventKind::Shared,
+            NotificationType::PermissionChanged,
+            &permission,
+            owner_id,
+        )
+        .await;
+
+        Ok(permission)
     }
 
     async fn remove_document_permission(
@@ -183,9 +659,44 @@ impl DocumentRepository for DbDocumentRepository {
         permission_id: Uuid,
         owner_id: Uuid,
     ) -> DbResult<()> {
+        let permission = self
+            .db_client
+            .get_document_permission(permission_id)
+            .await?
+            .ok_or(DbError::NotFound("Permission not found".to_string()))?;
+
+        // The unshared user is still a member here, so they learn about the removal
+        let document = self
+            .db_client
+            .get_document(permission.document_id, Some(owner_id))
+            .await?
+            .ok_or(DbError::DocumentNotFound)?;
+        let recipients = self.event_recipients(permission.document_id).await;
+
         self.db_client
             .remove_document_permission(permission_id, owner_id)
-            .await
+            .await?;
+
+        self.audit_document(
+            AuditAction::DocumentUnshared,
+            permission.document_id,
+            owner_id,
+            serde_json::json!({
+                "user_id": permission.user_id,
+                "permission": permission.permission_level.to_str(),
+            }),
+        )
+        .await;
+
+        self.publish(
+            DocumentEventKind::Unshared,
+            &document,
+            owner_id,
+            Some(&permission),
+            recipients,
+        );
+
+        Ok(())
     }
 
     async fn check_document_permission(
@@ -4,9 +4,16 @@ use axum::{Extension, Router, middleware};
 use tower_http::trace::TraceLayer;
 
 use crate::{
-    AppState,
-    handlers::{auth::auth_handler, users::users_handler},
-    middleware::auth,
+    AppState, audit,
+    handlers::{
+        access_tokens::access_tokens_handler, account::account_handler, audit::audit_handler,
+        auth::auth_handler, comments::comments_handler, events::events_handler,
+        identities::identities_handler, notifications::notifications_handler,
+        passkeys::passkeys_handler, sessions::sessions_handler, suggestions::suggestions_handler,
+        two_factor::two_factor_handler, users::users_handler, webhooks::webhooks_handler,
+        well_known::well_known_handler,
+    },
+    middleware::{api_auth, auth},
 };
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
@@ -14,14 +21,79 @@ pub fn create_router(app_state: Arc<AppState>) -> Router {
         .nest("/auth", auth_handler())
         // -- users 路由请求执行流程：
         // -- 1. 请求首先进入 users 路由
-        // -- 2. 经过认证中间件 auth 检查请求中的 token
+        // -- 2. 经过认证中间件 api_auth 检查请求中的 token（登录会话或个人访问令牌）
         // -- 3. token 验证通过后，请求传递给具体的用户处理函数
-        .nest("/users", users_handler().layer(middleware::from_fn(auth)))
+        .nest(
+            "/users",
+            users_handler().layer(middleware::from_fn(api_auth)),
+        )
+        // -- 文档事件流 (SSE)，同样需要登录
+        .nest(
+            "/events",
+            events_handler().layer(middleware::from_fn(api_auth)),
+        )
+        .nest(
+            "/comments",
+            comments_handler().layer(middleware::from_fn(api_auth)),
+        )
+        .nest(
+            "/notifications",
+            notifications_handler().layer(middleware::from_fn(api_auth)),
+        )
+        // -- 登录会话 (设备) 管理
+        .nest(
+            "/sessions",
+            sessions_handler().layer(middleware::from_fn(auth)),
+        )
+        // -- 关联的登录方式 (本地密码、Google、GitHub)
+        .nest(
+            "/identities",
+            identities_handler().layer(middleware::from_fn(auth)),
+        )
+        // -- 通行密钥 (WebAuthn) 管理
+        .nest(
+            "/passkeys",
+            passkeys_handler().layer(middleware::from_fn(auth)),
+        )
+        // -- 个人访问令牌管理，只允许登录会话访问
+        .nest(
+            "/access-tokens",
+            access_tokens_handler().layer(middleware::from_fn(auth)),
+        )
+        // -- 个人数据导出与账户注销，只允许登录会话访问
+        .nest(
+            "/account",
+            account_handler().layer(middleware::from_fn(auth)),
+        )
+        // -- 两步验证设置与角色策略
+        .nest(
+            "/two-factor",
+            two_factor_handler().layer(middleware::from_fn(auth)),
+        )
+        .nest(
+            "/suggestions",
+            suggestions_handler().layer(middleware::from_fn(api_auth)),
+        )
+        .nest(
+            "/webhooks",
+            webhooks_handler().layer(middleware::from_fn(api_auth)),
+        )
+        .nest(
+            "/audit-logs",
+            audit_handler().layer(middleware::from_fn(api_auth)),
+        )
+        // -- 记录客户端 IP 和 User-Agent，供审计日志使用
+        .layer(middleware::from_fn(audit::request_context))
         // -- 4. TraceLayer 记录整个请求的处理过程，包括耗时、状态等信息
         .layer(TraceLayer::new_for_http())
         // -- 5. Extension 中间件使处理函数能够访问应用状态（如数据库连接）
-        .layer(Extension(app_state));
+        .layer(Extension(app_state.clone()));
+
+    // -- 公开的 JWKS 等标准发现文档，不需要登录
+    let well_known_route = well_known_handler().layer(Extension(app_state));
 
     // -- 创建根路由，并嵌套 API 路由
-    Router::new().nest("/api", api_route)
+    Router::new()
+        .nest("/api", api_route)
+        .nest("/.well-known", well_known_route)
 }
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::models::User;
+
+/// 超过该数量时清理过期的缓存项
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// 已验证会话的缓存
+///
+/// 认证中间件每个请求都要确认会话未被吊销并读取用户，缓存命中时不再查询数据库。
+/// 本实例吊销的会话和修改的用户会立即移出缓存；其他实例的修改最多在 `ttl` 之后生效。
+#[derive(Debug)]
+pub struct SessionCache {
+    ttl: Duration,
+    verified: RwLock<HashMap<Uuid, (Instant, User)>>,
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration) -> Self {
+        SessionCache {
+            ttl,
+            verified: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 会话在 `ttl` 内验证过时返回验证时读取的用户
+    pub fn get_user(&self, session_id: Uuid) -> Option<User> {
+        self.verified
+            .read()
+            .unwrap()
+            .get(&session_id)
+            .filter(|(verified_at, _)| verified_at.elapsed() < self.ttl)
+            .map(|(_, user)| user.clone())
+    }
+
+    /// 记录会话刚刚通过数据库验证
+    pub fn mark_verified(&self, session_id: Uuid, user: User) {
+        let mut verified = self.verified.write().unwrap();
+        if verified.len() >= PRUNE_THRESHOLD {
+            verified.retain(|_, (verified_at, _)| verified_at.elapsed() < self.ttl);
+        }
+        verified.insert(session_id, (Instant::now(), user));
+    }
+
+    /// 会话被吊销后移出缓存
+    pub fn evict(&self, session_ids: &[Uuid]) {
+        let mut verified = self.verified.write().unwrap();
+        for session_id in session_ids {
+            verified.remove(session_id);
+        }
+    }
+
+    /// 用户资料或角色变更后移出该用户的全部会话，下次请求重新读取
+    pub fn evict_user(&self, user_id: Uuid) {
+        self.verified
+            .write()
+            .unwrap()
+            .retain(|_, (_, user)| user.id != user_id);
+    }
+}
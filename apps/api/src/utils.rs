@@ -1,5 +1,13 @@
+pub mod anchor;
+pub mod jwt_keys;
+pub mod mention;
 pub mod password;
+pub mod password_strength;
+pub mod siwe;
+pub mod suggestion;
 pub mod token;
+pub mod totp;
+pub mod webauthn;
 
 use chrono::{Local, Timelike};
 use std::path::{Path, PathBuf};
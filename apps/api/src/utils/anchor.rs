@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::models::CommentThread;
+
+/// 评论锚点在文档更新后的解析结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnchorResolution {
+    /// 锚定的文本仍在原位置
+    Unchanged,
+    /// 锚定的文本移动到了新的偏移量
+    Moved { start_offset: i32, end_offset: i32 },
+    /// 锚定的文本已被删除
+    Orphaned,
+}
+
+/// 从 Tiptap JSON 中提取的文档文本，用于重新定位评论锚点
+pub struct DocumentText {
+    /// 块 ID -> 块内纯文本
+    blocks: HashMap<String, String>,
+    /// 全文纯文本，顶层块之间以换行分隔
+    full_text: String,
+}
+
+impl DocumentText {
+    /// 解析 Tiptap JSON 文档
+    ///
+    /// 文档内容不是 Tiptap JSON 时返回 `None`，此时无法判断锚点状态，调用方应保持锚点不变。
+    pub fn parse(content: &str) -> Option<Self> {
+        let root: Value = serde_json::from_str(content).ok()?;
+        if root.get("type").and_then(Value::as_str) != Some("doc") {
+            return None;
+        }
+
+        let mut blocks = HashMap::new();
+        let top_level = root
+            .get("content")
+            .and_then(Value::as_array)
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .map(|node| collect_text(node, &mut blocks))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        Some(DocumentText {
+            blocks,
+            full_text: top_level.join("\n"),
+        })
+    }
+
+    /// 根据当前文档内容重新定位评论锚点
+    ///
+    /// - 块锚点：原偏移处的文本未变则保持不变；否则在同一块内查找距离原位置最近的
+    ///   相同文本并更新偏移量；块被删除或找不到文本时标记为孤立。
+    /// - CRDT 锚点：位置由客户端解析，服务端只在引用文本从全文中消失时标记为孤立。
+    pub fn resolve(&self, thread: &CommentThread) -> AnchorResolution {
+        let (Some(block_id), Some(start), Some(end)) =
+            (&thread.block_id, thread.start_offset, thread.end_offset)
+        else {
+            return if self.full_text.contains(&thread.quoted_text) {
+                AnchorResolution::Unchanged
+            } else {
+                AnchorResolution::Orphaned
+            };
+        };
+
+        let Some(block_text) = self.blocks.get(block_id) else {
+            return AnchorResolution::Orphaned;
+        };
+
+        let current: String = block_text
+            .chars()
+            .skip(start.max(0) as usize)
+            .take((end - start).max(0) as usize)
+            .collect();
+        if current == thread.quoted_text {
+            return AnchorResolution::Unchanged;
+        }
+
+        if thread.quoted_text.is_empty() {
+            return AnchorResolution::Orphaned;
+        }
+
+        let quoted_len = thread.quoted_text.chars().count() as i32;
+        block_text
+            .match_indices(thread.quoted_text.as_str())
+            .map(|(byte_index, _)| block_text[..byte_index].chars().count() as i32)
+            .min_by_key(|offset| (offset - start).abs())
+            .map(|offset| AnchorResolution::Moved {
+                start_offset: offset,
+                end_offset: offset + quoted_len,
+            })
+            .unwrap_or(AnchorResolution::Orphaned)
+    }
+}
+
+/// 递归收集节点文本，并记录带有 `attrs.id` 的块
+fn collect_text(node: &Value, blocks: &mut HashMap<String, String>) -> String {
+    let mut text = node
+        .get("text")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    if let Some(children) = node.get("content").and_then(Value::as_array) {
+        for child in children {
+            text.push_str(&collect_text(child, blocks));
+        }
+    }
+
+    if let Some(block_id) = node
+        .get("attrs")
+        .and_then(|attrs| attrs.get("id"))
+        .and_then(Value::as_str)
+    {
+        blocks.insert(block_id.to_string(), text.clone());
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn thread(block_id: Option<&str>, start: i32, end: i32, quoted_text: &str) -> CommentThread {
+        CommentThread {
+            id: uuid::Uuid::nil(),
+            document_id: uuid::Uuid::nil(),
+            author_id: uuid::Uuid::nil(),
+            block_id: block_id.map(str::to_string),
+            start_offset: block_id.map(|_| start),
+            end_offset: block_id.map(|_| end),
+            crdt_position: block_id.is_none().then(|| "crdt".to_string()),
+            quoted_text: quoted_text.to_string(),
+            orphaned: false,
+            resolved: false,
+            resolved_by: None,
+            resolved_at: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn document(paragraphs: &[(&str, &str)]) -> DocumentText {
+        let content: Vec<Value> = paragraphs
+            .iter()
+            .map(|(id, text)| {
+                json!({
+                    "type": "paragraph",
+                    "attrs": { "id": id },
+                    "content": [{ "type": "text", "text": text }]
+                })
+            })
+            .collect();
+
+        DocumentText::parse(&json!({ "type": "doc", "content": content }).to_string()).unwrap()
+    }
+
+    #[test]
+    fn unchanged_text() {
+        let document = document(&[("p1", "Hello world")]);
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 6, 11, "world")),
+            AnchorResolution::Unchanged
+        );
+    }
+
+    #[test]
+    fn text_moved_inside_block() {
+        let document = document(&[("p1", "Oh, Hello world")]);
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 6, 11, "world")),
+            AnchorResolution::Moved {
+                start_offset: 10,
+                end_offset: 15
+            }
+        );
+    }
+
+    #[test]
+    fn text_deleted() {
+        let document = document(&[("p1", "Hello there")]);
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 6, 11, "world")),
+            AnchorResolution::Orphaned
+        );
+    }
+
+    #[test]
+    fn block_removed() {
+        // -- 文本还在，但已经移到了另一个块中
+        let document = document(&[("p2", "Hello world")]);
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 6, 11, "world")),
+            AnchorResolution::Orphaned
+        );
+    }
+
+    #[test]
+    fn nearest_of_duplicate_occurrences() {
+        let document = document(&[("p1", "ab world, cd world, ef world")]);
+
+        // -- 引用的文本出现多次时选择距离原位置最近的一处
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 10, 15, "world")),
+            AnchorResolution::Moved {
+                start_offset: 13,
+                end_offset: 18
+            }
+        );
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 25, 30, "world")),
+            AnchorResolution::Moved {
+                start_offset: 23,
+                end_offset: 28
+            }
+        );
+    }
+
+    #[test]
+    fn offsets_count_characters() {
+        let document = document(&[("p1", "你好，世界和世界")]);
+        assert_eq!(
+            document.resolve(&thread(Some("p1"), 2, 4, "世界")),
+            AnchorResolution::Moved {
+                start_offset: 3,
+                end_offset: 5
+            }
+        );
+    }
+
+    #[test]
+    fn crdt_anchor_checks_full_text() {
+        let document = document(&[("p1", "Hello"), ("p2", "world")]);
+        assert_eq!(
+            document.resolve(&thread(None, 0, 0, "world")),
+            AnchorResolution::Unchanged
+        );
+        assert_eq!(
+            document.resolve(&thread(None, 0, 0, "there")),
+            AnchorResolution::Orphaned
+        );
+    }
+
+    #[test]
+    fn non_tiptap_content() {
+        assert!(DocumentText::parse("plain text").is_none());
+        assert!(DocumentText::parse(r#"{"type":"paragraph"}"#).is_none());
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode,
+    errors::{Error, ErrorKind},
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs8::{
+        DecodePrivateKey, DecodePublicKey, ObjectIdentifier, PrivateKeyInfo,
+        SubjectPublicKeyInfoRef, der::pem,
+    },
+    traits::PublicKeyParts,
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+const RSA_ENCRYPTION_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const ED25519_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+/// RSA 密钥的最小长度（位）
+const MIN_RSA_BITS: usize = 2048;
+
+/// 访问令牌的签名和校验密钥
+///
+/// 非对称模式下每个密钥用 `kid` 标识：新令牌使用当前密钥签名，
+/// 已退役的密钥只用于校验，直到它签发的令牌全部过期。
+/// HS256 模式只有一个不带 `kid` 的共享密钥，仅适合开发环境。
+pub struct JwtKeys {
+    /// 签发新令牌的密钥 ID，HS256 模式为空
+    active_kid: Option<String>,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    /// 按 `kid` 索引的校验密钥，HS256 模式下键为空字符串
+    decoding_keys: HashMap<String, (Algorithm, DecodingKey)>,
+    /// 公开的校验密钥，HS256 模式为空
+    jwks: JwkSet,
+    /// 签发和校验时的 `iss`
+    issuer: String,
+    /// 签发和校验时的 `aud`
+    audience: String,
+}
+
+impl JwtKeys {
+    /// HS256 共享密钥
+    pub fn shared_secret(secret: &[u8]) -> Self {
+        JwtKeys {
+            active_kid: None,
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_keys: HashMap::from([(
+                String::new(),
+                (Algorithm::HS256, DecodingKey::from_secret(secret)),
+            )]),
+            jwks: JwkSet { keys: Vec::new() },
+            issuer: String::new(),
+            audience: String::new(),
+        }
+    }
+
+    /// 从目录加载密钥，每个 `<kid>.pem` 文件是一个密钥
+    ///
+    /// 文件可以是 PKCS#8 私钥 (`PRIVATE KEY`) 或公钥 (`PUBLIC KEY`)，支持 RSA (RS256)
+    /// 和 Ed25519 (EdDSA)。已退役的密钥只保留公钥即可。
+    ///
+    /// # 参数
+    /// * `dir` - 密钥目录
+    /// * `active_kid` - 签发新令牌的密钥，必须是私钥；目录中只有一个私钥时可以省略
+    pub fn load(dir: &Path, active_kid: Option<&str>) -> Result<Self, String> {
+        let entries =
+            fs::read_dir(dir).map_err(|e| format!("读取 JWT 密钥目录 {:?} 失败: {}", dir, e))?;
+
+        let mut signing_keys = HashMap::new();
+        let mut decoding_keys = HashMap::new();
+        let mut jwks = Vec::new();
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+                continue;
+            }
+            let Some(kid) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let text = fs::read_to_string(&path)
+                .map_err(|e| format!("读取 JWT 密钥 {:?} 失败: {}", path, e))?;
+            let key =
+                load_key(kid, &text).map_err(|e| format!("JWT 密钥 {:?} 无效: {}", path, e))?;
+
+            let decoding_key = DecodingKey::from_jwk(&key.jwk)
+                .map_err(|e| format!("JWT 密钥 {:?} 无效: {}", path, e))?;
+            decoding_keys.insert(kid.to_string(), (key.algorithm, decoding_key));
+            if let Some(encoding_key) = key.encoding_key {
+                signing_keys.insert(kid.to_string(), (key.algorithm, encoding_key));
+            }
+            jwks.push(key.jwk);
+        }
+
+        let active_kid = match active_kid {
+            Some(kid) => kid.to_string(),
+            None if signing_keys.len() == 1 => signing_keys.keys().next().unwrap().clone(),
+            None => {
+                return Err(format!(
+                    "JWT 密钥目录 {:?} 中有 {} 个私钥，请用 JWT_ACTIVE_KEY_ID 指定签名密钥",
+                    dir,
+                    signing_keys.len()
+                ));
+            }
+        };
+        let (algorithm, encoding_key) = signing_keys
+            .remove(&active_kid)
+            .ok_or_else(|| format!("找不到签名密钥 {}.pem，签名密钥必须是私钥", active_kid))?;
+
+        // -- 按 kid 排序，JWKS 的内容不随目录遍历顺序变化
+        jwks.sort_by(|a: &Jwk, b: &Jwk| a.common.key_id.cmp(&b.common.key_id));
+
+        Ok(JwtKeys {
+            active_kid: Some(active_kid),
+            algorithm,
+            encoding_key,
+            decoding_keys,
+            jwks: JwkSet { keys: jwks },
+            issuer: String::new(),
+            audience: String::new(),
+        })
+    }
+
+    /// 设置令牌的签发者和受众，校验时两者都必须一致
+    pub fn with_issuer(mut self, issuer: &str, audience: &str) -> Self {
+        self.issuer = issuer.to_string();
+        self.audience = audience.to_string();
+        self
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn audience(&self) -> &str {
+        &self.audience
+    }
+
+    /// 使用当前密钥签名
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.active_kid.clone();
+        encode(&header, claims, &self.encoding_key)
+    }
+
+    /// 按令牌头部的 `kid` 选择密钥校验令牌，算法必须与密钥一致
+    ///
+    /// 同时校验 `exp`、`iss` 和 `aud`，令牌类型由调用方检查。
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, Error> {
+        let header = decode_header(token)?;
+        let (algorithm, key) = self
+            .decoding_keys
+            .get(header.kid.as_deref().unwrap_or_default())
+            .ok_or_else(|| Error::from(ErrorKind::InvalidToken))?;
+
+        let mut validation = Validation::new(*algorithm);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        decode::<T>(token, key, &validation).map(|data| data.claims)
+    }
+
+    /// 公开的校验密钥 (JWKS)
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+/// 从 PEM 文件解析出的密钥
+struct LoadedKey {
+    algorithm: Algorithm,
+    /// 私钥才能签名，公钥为空
+    encoding_key: Option<EncodingKey>,
+    jwk: Jwk,
+}
+
+fn load_key(kid: &str, text: &str) -> Result<LoadedKey, String> {
+    let (label, der) = pem::decode_vec(text.as_bytes()).map_err(|e| e.to_string())?;
+
+    match label {
+        "PRIVATE KEY" => {
+            let info = PrivateKeyInfo::try_from(der.as_slice()).map_err(|e| e.to_string())?;
+
+            if info.algorithm.oid == RSA_ENCRYPTION_OID {
+                let key = RsaPrivateKey::from_pkcs8_der(&der).map_err(|e| e.to_string())?;
+                let public_key = key.to_public_key();
+                check_rsa_size(&public_key)?;
+                let encoding_key =
+                    EncodingKey::from_rsa_pem(text.as_bytes()).map_err(|e| e.to_string())?;
+
+                Ok(LoadedKey {
+                    algorithm: Algorithm::RS256,
+                    encoding_key: Some(encoding_key),
+                    jwk: rsa_jwk(kid, &public_key),
+                })
+            } else if info.algorithm.oid == ED25519_OID {
+                let pair =
+                    Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).map_err(|e| e.to_string())?;
+
+                Ok(LoadedKey {
+                    algorithm: Algorithm::EdDSA,
+                    encoding_key: Some(EncodingKey::from_ed_der(&der)),
+                    jwk: ed25519_jwk(kid, pair.public_key().as_ref()),
+                })
+            } else {
+                Err(format!("不支持的密钥类型 {}", info.algorithm.oid))
+            }
+        }
+        "PUBLIC KEY" => {
+            let info =
+                SubjectPublicKeyInfoRef::try_from(der.as_slice()).map_err(|e| e.to_string())?;
+
+            if info.algorithm.oid == RSA_ENCRYPTION_OID {
+                let public_key =
+                    RsaPublicKey::from_public_key_der(&der).map_err(|e| e.to_string())?;
+                check_rsa_size(&public_key)?;
+
+                Ok(LoadedKey {
+                    algorithm: Algorithm::RS256,
+                    encoding_key: None,
+                    jwk: rsa_jwk(kid, &public_key),
+                })
+            } else if info.algorithm.oid == ED25519_OID {
+                let public_key = info
+                    .subject_public_key
+                    .as_bytes()
+                    .filter(|bytes| bytes.len() == 32)
+                    .ok_or("Ed25519 公钥长度无效")?;
+
+                Ok(LoadedKey {
+                    algorithm: Algorithm::EdDSA,
+                    encoding_key: None,
+                    jwk: ed25519_jwk(kid, public_key),
+                })
+            } else {
+                Err(format!("不支持的密钥类型 {}", info.algorithm.oid))
+            }
+        }
+        _ => Err(format!(
+            "不支持的 PEM 类型 {}，请使用 PKCS#8 私钥或公钥",
+            label
+        )),
+    }
+}
+
+fn check_rsa_size(key: &RsaPublicKey) -> Result<(), String> {
+    let bits = key.size() * 8;
+    if bits < MIN_RSA_BITS {
+        return Err(format!(
+            "RSA 密钥只有 {} 位，至少需要 {} 位",
+            bits, MIN_RSA_BITS
+        ));
+    }
+    Ok(())
+}
+
+fn common_parameters(kid: &str, algorithm: KeyAlgorithm) -> CommonParameters {
+    CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_algorithm: Some(algorithm),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    }
+}
+
+fn rsa_jwk(kid: &str, key: &RsaPublicKey) -> Jwk {
+    Jwk {
+        common: common_parameters(kid, KeyAlgorithm::RS256),
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+        }),
+    }
+}
+
+fn ed25519_jwk(kid: &str, public_key: &[u8]) -> Jwk {
+    Jwk {
+        common: common_parameters(kid, KeyAlgorithm::EdDSA),
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(public_key),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use argon2::password_hash::rand_core::OsRng;
+    use ring::rand::SystemRandom;
+    use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// 测试结束时删除的临时密钥目录
+    struct KeyDir(PathBuf);
+
+    impl KeyDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("jwt-keys-{}", uuid::Uuid::new_v4()));
+            fs::create_dir(&dir).unwrap();
+            KeyDir(dir)
+        }
+
+        fn write(&self, kid: &str, pem: &str) -> &Self {
+            fs::write(self.0.join(format!("{}.pem", kid)), pem).unwrap();
+            self
+        }
+
+        fn load(&self, active_kid: Option<&str>) -> Result<JwtKeys, String> {
+            JwtKeys::load(&self.0, active_kid)
+                .map(|keys| keys.with_issuer("doc-editor", "doc-editor-api"))
+        }
+    }
+
+    impl Drop for KeyDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn rsa_key(bits: usize) -> (String, String) {
+        let key = RsaPrivateKey::new(&mut OsRng, bits).unwrap();
+        let private_pem = key.to_pkcs8_pem(LineEnding::LF).unwrap().to_string();
+        let public_pem = key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        (private_pem, public_pem)
+    }
+
+    fn ed25519_key() -> (String, String) {
+        let der = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(der.as_ref()).unwrap();
+
+        // -- SubjectPublicKeyInfo 的固定前缀 + 32 字节公钥
+        let mut public_der = vec![
+            0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+        ];
+        public_der.extend_from_slice(pair.public_key().as_ref());
+
+        (
+            pem::encode_string("PRIVATE KEY", LineEnding::LF, der.as_ref()).unwrap(),
+            pem::encode_string("PUBLIC KEY", LineEnding::LF, &public_der).unwrap(),
+        )
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "user",
+            "iss": "doc-editor",
+            "aud": "doc-editor-api",
+            "exp": chrono::Utc::now().timestamp() + 600,
+        })
+    }
+
+    #[test]
+    fn rejects_short_rsa_keys() {
+        let (private_pem, public_pem) = rsa_key(1024);
+
+        let dir = KeyDir::new();
+        dir.write("short", &private_pem);
+        assert!(dir.load(None).err().unwrap().contains("至少需要 2048 位"));
+
+        let dir = KeyDir::new();
+        dir.write("short", &public_pem)
+            .write("current", &ed25519_key().0);
+        assert!(dir.load(Some("current")).is_err());
+    }
+
+    #[test]
+    fn rsa_and_ed25519_round_trip() {
+        let dir = KeyDir::new();
+        dir.write("rsa", &rsa_key(MIN_RSA_BITS).0)
+            .write("ed", &ed25519_key().0);
+
+        for kid in ["rsa", "ed"] {
+            let keys = dir.load(Some(kid)).unwrap();
+            let token = keys.encode(&claims()).unwrap();
+            assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some(kid));
+            assert_eq!(keys.decode::<Value>(&token).unwrap()["sub"], "user");
+        }
+
+        let keys = dir.load(Some("ed")).unwrap();
+        assert_eq!(keys.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn retired_public_key_still_verifies() {
+        let (old_private, old_public) = ed25519_key();
+
+        let before = KeyDir::new();
+        before.write("old", &old_private);
+        let token = before.load(None).unwrap().encode(&claims()).unwrap();
+
+        // -- 轮换后旧密钥只保留公钥
+        let after = KeyDir::new();
+        after
+            .write("old", &old_public)
+            .write("new", &ed25519_key().0);
+        let keys = after.load(None).unwrap();
+
+        assert_eq!(keys.decode::<Value>(&token).unwrap()["sub"], "user");
+        let new_token = keys.encode(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some("new")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_kid() {
+        let other = KeyDir::new();
+        other.write("other", &ed25519_key().0);
+        let token = other.load(None).unwrap().encode(&claims()).unwrap();
+
+        let dir = KeyDir::new();
+        dir.write("current", &ed25519_key().0);
+        let err = dir.load(None).unwrap().decode::<Value>(&token).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidToken);
+    }
+
+    #[test]
+    fn rejects_token_without_kid() {
+        let shared = JwtKeys::shared_secret(b"secret").with_issuer("doc-editor", "doc-editor-api");
+        let token = shared.encode(&claims()).unwrap();
+        assert!(decode_header(&token).unwrap().kid.is_none());
+
+        let dir = KeyDir::new();
+        dir.write("current", &ed25519_key().0);
+        let err = dir.load(None).unwrap().decode::<Value>(&token).unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidToken);
+    }
+}
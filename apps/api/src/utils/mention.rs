@@ -0,0 +1,208 @@
+use serde_json::Value;
+use uuid::Uuid;
+
+/// 文档或评论中提及的用户
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Mention {
+    /// Tiptap mention 节点中携带的用户 ID
+    UserId(Uuid),
+    /// `@` 后的用户名或邮箱，需要解析为用户
+    Handle(String),
+}
+
+/// 已解析的提及，按用户 ID 与用户名/邮箱分组，便于批量查询
+#[derive(Debug, Default)]
+pub struct MentionTargets {
+    pub user_ids: Vec<Uuid>,
+    pub handles: Vec<String>,
+}
+
+impl MentionTargets {
+    pub fn is_empty(&self) -> bool {
+        self.user_ids.is_empty() && self.handles.is_empty()
+    }
+}
+
+/// 提取 Tiptap JSON 文档中的 mention 节点
+///
+/// 节点格式为 `{"type": "mention", "attrs": {"id": "...", "label": "..."}}`，
+/// `id` 是用户 ID 时直接使用，否则按 `label`（或 `id`）作为用户名解析。
+/// 内容不是 Tiptap JSON 时按纯文本查找 `@用户名`。
+pub fn document_mentions(content: &str) -> Vec<Mention> {
+    let Ok(root) = serde_json::from_str::<Value>(content) else {
+        return text_mentions(content);
+    };
+    if root.get("type").and_then(Value::as_str) != Some("doc") {
+        return text_mentions(content);
+    }
+
+    let mut mentions = Vec::new();
+    collect_mention_nodes(&root, &mut mentions);
+    dedup(mentions)
+}
+
+/// 提取纯文本（如评论）中的 `@用户名` 或 `@邮箱`
+pub fn text_mentions(text: &str) -> Vec<Mention> {
+    let mut mentions = Vec::new();
+    let mut previous: Option<char> = None;
+
+    for (index, ch) in text.char_indices() {
+        // -- `@` 前面是 ASCII 字母数字时视为邮箱的一部分，例如 foo@example.com；请@张三 仍是提及
+        let starts_mention = ch == '@'
+            && !previous.is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        previous = Some(ch);
+        if !starts_mention {
+            continue;
+        }
+
+        let rest = &text[index + ch.len_utf8()..];
+        let end = rest
+            .char_indices()
+            .find(|(_, c)| !is_handle_char(*c) && *c != '@')
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        let handle = rest[..end].trim_end_matches(['.', '-', '_', '@']);
+
+        if !handle.is_empty() {
+            mentions.push(Mention::Handle(handle.to_lowercase()));
+        }
+    }
+
+    dedup(mentions)
+}
+
+/// 返回 `current` 中新增的提及，只有新提及才需要通知
+pub fn new_mentions(previous: &[Mention], current: Vec<Mention>) -> Vec<Mention> {
+    current
+        .into_iter()
+        .filter(|mention| !previous.contains(mention))
+        .collect()
+}
+
+/// 按类型拆分提及
+pub fn split_mentions(mentions: Vec<Mention>) -> MentionTargets {
+    let mut targets = MentionTargets::default();
+    for mention in mentions {
+        match mention {
+            Mention::UserId(id) => targets.user_ids.push(id),
+            Mention::Handle(handle) => targets.handles.push(handle),
+        }
+    }
+    targets
+}
+
+fn collect_mention_nodes(node: &Value, out: &mut Vec<Mention>) {
+    if node.get("type").and_then(Value::as_str) == Some("mention") {
+        let attrs = node.get("attrs");
+        let id = attrs.and_then(|a| a.get("id")).and_then(Value::as_str);
+        let label = attrs.and_then(|a| a.get("label")).and_then(Value::as_str);
+
+        if let Some(user_id) = id.and_then(|id| Uuid::parse_str(id).ok()) {
+            out.push(Mention::UserId(user_id));
+        } else if let Some(handle) = label.or(id) {
+            let handle = handle.trim_start_matches('@').trim();
+            if !handle.is_empty() {
+                out.push(Mention::Handle(handle.to_lowercase()));
+            }
+        }
+    }
+
+    if let Some(children) = node.get("content").and_then(Value::as_array) {
+        for child in children {
+            collect_mention_nodes(child, out);
+        }
+    }
+}
+
+fn is_handle_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '.' | '_' | '-')
+}
+
+fn dedup(mentions: Vec<Mention>) -> Vec<Mention> {
+    let mut unique = Vec::with_capacity(mentions.len());
+    for mention in mentions {
+        if !unique.contains(&mention) {
+            unique.push(mention);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn handles(names: &[&str]) -> Vec<Mention> {
+        names
+            .iter()
+            .map(|name| Mention::Handle(name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tiptap_mention_nodes() {
+        let user_id = Uuid::new_v4();
+        let content = json!({
+            "type": "doc",
+            "content": [{
+                "type": "paragraph",
+                "content": [
+                    { "type": "text", "text": "请 @ignored 看一下 " },
+                    { "type": "mention", "attrs": { "id": user_id.to_string(), "label": "Alice" } },
+                    { "type": "mention", "attrs": { "id": "bob", "label": "@Bob" } },
+                    { "type": "mention", "attrs": { "id": "carol" } },
+                    { "type": "mention", "attrs": { "id": user_id.to_string() } },
+                    { "type": "mention", "attrs": { "label": " " } }
+                ]
+            }]
+        })
+        .to_string();
+
+        // -- 文本节点中的 @ 不算提及，重复的提及只保留一次
+        assert_eq!(
+            document_mentions(&content),
+            vec![
+                Mention::UserId(user_id),
+                Mention::Handle("bob".to_string()),
+                Mention::Handle("carol".to_string()),
+            ]
+        );
+
+        // -- 不是 Tiptap JSON 时按纯文本处理
+        assert_eq!(document_mentions("hi @dave"), handles(&["dave"]));
+    }
+
+    #[test]
+    fn handle_boundaries_in_text() {
+        assert_eq!(
+            text_mentions("Hi @Alice, (@bob) and @carol. Thanks @dave's"),
+            handles(&["alice", "bob", "carol", "dave"])
+        );
+
+        // -- 邮箱地址不是提及，@ 后面的邮箱是提及
+        assert!(text_mentions("mail foo@example.com or bar.baz@example.com").is_empty());
+        assert_eq!(
+            text_mentions("cc @eve@example.com."),
+            handles(&["eve@example.com"])
+        );
+
+        // -- 中文紧挨着 @ 时仍然是提及，中文标点结束用户名
+        assert_eq!(text_mentions("请@张三，看一下"), handles(&["张三"]));
+
+        assert!(text_mentions("@ @@ @. email@").is_empty());
+    }
+
+    #[test]
+    fn only_new_mentions_trigger() {
+        let previous = text_mentions("@alice @bob");
+        let current = text_mentions("@Bob @carol @alice @dave");
+
+        assert_eq!(
+            new_mentions(&previous, current),
+            handles(&["carol", "dave"])
+        );
+        assert!(new_mentions(&previous, text_mentions("@alice")).is_empty());
+    }
+}
@@ -5,7 +5,8 @@ use argon2::{
 
 use crate::error::ErrorMessage;
 
-const MAX_PASSWORD_LENGTH: usize = 64;
+/// 与请求体中密码字段的长度上限一致
+const MAX_PASSWORD_LENGTH: usize = 128;
 
 /// 对密码进行哈希处理。
 ///
@@ -0,0 +1,464 @@
+//! 密码强度估算，思路与 zxcvbn 相同
+//!
+//! 把密码拆成若干片段：常见密码和单词、重复字符、连续字符、键盘相邻字符、年份，
+//! 其余字符按暴力破解计算。选择猜测次数最少的拆分方式，再按猜测次数换算成 0 到 4 的评分。
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+/// 常见密码和单词，按常见程度排序，排名越靠前越容易被猜到
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "password",
+    "12345678",
+    "qwerty",
+    "123456789",
+    "12345",
+    "1234",
+    "111111",
+    "1234567",
+    "dragon",
+    "123123",
+    "baseball",
+    "abc123",
+    "football",
+    "monkey",
+    "letmein",
+    "696969",
+    "shadow",
+    "master",
+    "666666",
+    "qwertyuiop",
+    "123321",
+    "mustang",
+    "1234567890",
+    "michael",
+    "654321",
+    "superman",
+    "1qaz2wsx",
+    "7777777",
+    "121212",
+    "000000",
+    "qazwsx",
+    "123qwe",
+    "killer",
+    "trustno1",
+    "jordan",
+    "jennifer",
+    "zxcvbnm",
+    "asdfgh",
+    "hunter",
+    "buster",
+    "soccer",
+    "harley",
+    "batman",
+    "andrew",
+    "tigger",
+    "sunshine",
+    "iloveyou",
+    "2000",
+    "charlie",
+    "robert",
+    "thomas",
+    "hockey",
+    "ranger",
+    "daniel",
+    "starwars",
+    "klaster",
+    "112233",
+    "george",
+    "computer",
+    "michelle",
+    "jessica",
+    "pepper",
+    "1111",
+    "zxcvbn",
+    "555555",
+    "11111111",
+    "131313",
+    "freedom",
+    "777777",
+    "pass",
+    "maggie",
+    "159753",
+    "aaaaaa",
+    "ginger",
+    "princess",
+    "joshua",
+    "cheese",
+    "amanda",
+    "summer",
+    "love",
+    "ashley",
+    "nicole",
+    "chelsea",
+    "biteme",
+    "matthew",
+    "access",
+    "yankees",
+    "987654321",
+    "dallas",
+    "austin",
+    "thunder",
+    "taylor",
+    "matrix",
+    "admin",
+    "welcome",
+    "login",
+    "passw0rd",
+    "secret",
+    "hello",
+    "flower",
+    "whatever",
+    "qwerty123",
+    "monday",
+    "lovely",
+    "football1",
+    "abcdef",
+    "abcd1234",
+    "changeme",
+    "default",
+    "guest",
+    "root",
+    "test",
+    "user",
+    "temp",
+    "google",
+    "internet",
+    "samsung",
+    "apple",
+    "orange",
+    "banana",
+    "china",
+    "wang",
+    "zhang",
+    "woaini",
+    "5201314",
+    "1314520",
+    "88888888",
+    "66666666",
+    "asdfghjkl",
+    "qweasd",
+    "zaq12wsx",
+    "starwars1",
+    "dragon1",
+    "monkey1",
+    "letmein1",
+    "password1",
+    "password123",
+    "admin123",
+    "welcome1",
+    "iloveyou1",
+    "sunshine1",
+    "princess1",
+    "secret1",
+    "spring",
+    "autumn",
+    "winter",
+    "january",
+    "february",
+    "march",
+    "april",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+    "red",
+    "blue",
+    "green",
+    "black",
+    "white",
+    "yellow",
+    "purple",
+    "silver",
+    "golden",
+    "house",
+    "horse",
+    "battery",
+    "staple",
+    "correct",
+    "money",
+    "family",
+    "friend",
+    "happy",
+    "lucky",
+    "magic",
+    "angel",
+    "devil",
+    "heaven",
+    "super",
+    "power",
+    "star",
+    "king",
+    "queen",
+    "prince",
+    "boss",
+    "cool",
+    "baby",
+    "honey",
+    "sweet",
+    "kitty",
+    "tiger",
+    "lion",
+    "eagle",
+    "wolf",
+    "bear",
+    "dog",
+    "cat",
+    "fish",
+    "bird",
+    "snake",
+];
+
+/// 键盘上相邻的字符
+const KEYBOARD_ROWS: &[&str] = &[
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    "1qaz2wsx3edc4rfv5tgb6yhn7ujm8ik,9ol.0p;/",
+];
+
+/// 参与估算的最大字符数
+const MAX_SCORED_CHARS: usize = 100;
+
+/// 不匹配任何模式的字符，每个按 10 种可能计算，与 zxcvbn 相同
+const BRUTE_FORCE_CARDINALITY: f64 = 10.0;
+
+/// 常见的字母替换，例如 `p@ssw0rd`
+const L33T: &[(char, char)] = &[
+    ('4', 'a'),
+    ('@', 'a'),
+    ('8', 'b'),
+    ('3', 'e'),
+    ('6', 'g'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('0', 'o'),
+    ('5', 's'),
+    ('$', 's'),
+    ('7', 't'),
+    ('2', 'z'),
+];
+
+static RANKS: LazyLock<HashMap<&'static str, usize>> = LazyLock::new(|| {
+    let mut ranks = HashMap::new();
+    for (index, word) in COMMON_PASSWORDS.iter().enumerate() {
+        ranks.entry(*word).or_insert(index + 1);
+    }
+    ranks
+});
+
+/// 估算结果
+#[derive(Debug, Clone, Copy)]
+pub struct Strength {
+    /// 猜中密码所需次数的对数 (log10)
+    pub guesses_log10: f64,
+    /// 0 到 4，0 最弱
+    pub score: u8,
+}
+
+/// 估算密码强度
+///
+/// 拆分的耗时随长度的三次方增长，只估算前 `MAX_SCORED_CHARS` 个字符，超出部分不计入评分。
+pub fn estimate(password: &str) -> Strength {
+    let chars: Vec<char> = password.chars().take(MAX_SCORED_CHARS).collect();
+    let n = chars.len();
+
+    // -- best[j] 是前 j 个字符的最少猜测次数 (log10)
+    let mut best = vec![f64::INFINITY; n + 1];
+    best[0] = 0.0;
+
+    for i in 0..n {
+        if best[i].is_infinite() {
+            continue;
+        }
+
+        let brute_force = best[i] + BRUTE_FORCE_CARDINALITY.log10();
+        if brute_force < best[i + 1] {
+            best[i + 1] = brute_force;
+        }
+
+        for j in (i + 2)..=n {
+            if let Some(guesses_log10) = pattern_guesses(&chars[i..j]) {
+                let total = best[i] + guesses_log10;
+                if total < best[j] {
+                    best[j] = total;
+                }
+            }
+        }
+    }
+
+    let guesses_log10 = best[n];
+    let score = match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    };
+
+    Strength {
+        guesses_log10,
+        score,
+    }
+}
+
+/// 片段匹配某种模式时的最少猜测次数 (log10)，不匹配时返回 `None`
+fn pattern_guesses(chars: &[char]) -> Option<f64> {
+    [
+        dictionary_guesses(chars),
+        repeat_guesses(chars),
+        sequence_guesses(chars),
+        keyboard_guesses(chars),
+        year_guesses(chars),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(f64::min)
+}
+
+/// 常见密码或单词，大小写变化和字母替换只增加少量猜测次数
+fn dictionary_guesses(chars: &[char]) -> Option<f64> {
+    let lower: String = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+
+    let mut substitutions = 0;
+    let unleeted: String = lower
+        .chars()
+        .map(|c| match L33T.iter().find(|(from, _)| *from == c) {
+            Some((_, to)) => {
+                substitutions += 1;
+                *to
+            }
+            None => c,
+        })
+        .collect();
+
+    let (rank, substitutions) = match (RANKS.get(lower.as_str()), RANKS.get(unleeted.as_str())) {
+        (Some(rank), _) => (*rank, 0),
+        (None, Some(rank)) => (*rank, substitutions),
+        (None, None) => return None,
+    };
+
+    let uppercase = chars.iter().filter(|c| c.is_uppercase()).count();
+    let case_variations = match uppercase {
+        0 => 1.0,
+        _ if uppercase == chars.len() => 2.0,
+        1 if chars[0].is_uppercase() => 2.0,
+        _ => 2f64.powi(uppercase.min(chars.len() - uppercase) as i32 + 1),
+    };
+
+    Some((rank as f64 * case_variations * 2f64.powi(substitutions)).log10())
+}
+
+/// 同一个字符重复，例如 `aaaa`
+fn repeat_guesses(chars: &[char]) -> Option<f64> {
+    if chars.len() < 3 || chars.iter().any(|c| *c != chars[0]) {
+        return None;
+    }
+    Some((cardinality(chars[0]) * chars.len() as f64).log10())
+}
+
+/// 步长相同的连续字符，例如 `abcd`、`9876`、`2468`
+fn sequence_guesses(chars: &[char]) -> Option<f64> {
+    if chars.len() < 3 {
+        return None;
+    }
+
+    let delta = chars[1] as i64 - chars[0] as i64;
+    if delta == 0 || delta.abs() > 5 {
+        return None;
+    }
+    if chars
+        .windows(2)
+        .any(|pair| pair[1] as i64 - pair[0] as i64 != delta)
+    {
+        return None;
+    }
+
+    // -- 从最容易想到的字符开始时猜测次数最少
+    let start = match chars[0] {
+        'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9' => 4.0,
+        c if c.is_ascii_digit() => 10.0,
+        _ => 26.0,
+    };
+    let direction = if delta > 0 { 1.0 } else { 2.0 };
+
+    Some((start * chars.len() as f64 * direction * delta.abs() as f64).log10())
+}
+
+/// 键盘上相邻的字符，例如 `qwerty`、`asdf`、`1qaz2wsx`
+fn keyboard_guesses(chars: &[char]) -> Option<f64> {
+    if chars.len() < 4 {
+        return None;
+    }
+
+    let lower: String = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    let reversed: String = lower.chars().rev().collect();
+    if !KEYBOARD_ROWS
+        .iter()
+        .any(|row| row.contains(&lower) || row.contains(&reversed))
+    {
+        return None;
+    }
+
+    Some((47.0 * chars.len() as f64).log10())
+}
+
+/// 1900 到 2039 年的年份
+fn year_guesses(chars: &[char]) -> Option<f64> {
+    if chars.len() != 4 || !chars.iter().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let year: u32 = chars.iter().collect::<String>().parse().ok()?;
+    (1900..2040).contains(&year).then(|| 140f64.log10())
+}
+
+/// 字符所属字符类的大小
+fn cardinality(c: char) -> f64 {
+    match c {
+        'a'..='z' | 'A'..='Z' => 26.0,
+        '0'..='9' => 10.0,
+        c if c.is_ascii() => 33.0,
+        _ => 100.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn scores_common_patterns_low() {
+        for password in [
+            "password",
+            "P@ssw0rd",
+            "qwerty123",
+            "aaaaaaaa",
+            "abcdefgh",
+            "19841984",
+        ] {
+            assert!(estimate(password).score <= 1, "{}", password);
+        }
+        assert!(estimate("Tr0ub4dor&3-horse-staple").score >= 3);
+    }
+
+    #[test]
+    fn long_passwords_are_cut_off() {
+        let long: String = "x7#Kq2!vR9".repeat(1000);
+
+        let started = Instant::now();
+        let strength = estimate(&long);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let prefix: String = long.chars().take(MAX_SCORED_CHARS).collect();
+        assert_eq!(strength.guesses_log10, estimate(&prefix).guesses_log10);
+        assert_eq!(strength.score, 4);
+    }
+}
@@ -0,0 +1,555 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use sha3::{Digest, Keccak256};
+
+/// 消息第一行的固定后缀
+const PREAMBLE_SUFFIX: &str = " wants you to sign in with your Ethereum account:";
+/// 目前唯一的消息版本
+const VERSION: &str = "1";
+/// 随机数长度（字节），编码为十六进制后满足规范要求的至少 8 个字母数字字符
+const NONCE_BYTES: usize = 16;
+/// 允许的客户端时钟偏差
+const CLOCK_SKEW_SECONDS: i64 = 300;
+
+/// 解析后的 EIP-4361 登录消息
+pub struct SiweMessage {
+    /// 请求签名的站点，可能带端口，例如 `docs.example.com`
+    pub domain: String,
+    /// 签名的钱包地址
+    pub address: [u8; 20],
+    pub statement: Option<String>,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub request_id: Option<String>,
+    pub resources: Vec<String>,
+}
+
+impl SiweMessage {
+    /// 校验消息的作用域和有效期
+    ///
+    /// # 参数
+    /// * `domain` - 本服务的域名，必须与消息中的完全一致
+    /// * `chain_ids` - 允许的链 ID
+    /// * `now` - 当前时间
+    pub fn validate(
+        &self,
+        domain: &str,
+        chain_ids: &[u64],
+        now: DateTime<Utc>,
+    ) -> Result<(), String> {
+        if self.version != VERSION {
+            return Err(format!("不支持的消息版本 {}", self.version));
+        }
+        if self.domain != domain {
+            return Err(format!("消息的域名 {} 与本站不符", self.domain));
+        }
+        if !chain_ids.contains(&self.chain_id) {
+            return Err(format!("不支持链 ID {}", self.chain_id));
+        }
+
+        let skew = Duration::seconds(CLOCK_SKEW_SECONDS);
+        if self.issued_at > now + skew {
+            return Err("消息的签发时间无效".to_string());
+        }
+        if self
+            .expiration_time
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return Err("消息已过期".to_string());
+        }
+        if self
+            .not_before
+            .is_some_and(|not_before| not_before > now + skew)
+        {
+            return Err("消息尚未生效".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// 生成随机数，返回十六进制编码
+pub fn generate_nonce() -> String {
+    let mut nonce = [0u8; NONCE_BYTES];
+    OsRng.fill_bytes(&mut nonce);
+    hex::encode(nonce)
+}
+
+/// 解析 EIP-4361 消息
+///
+/// 地址必须使用 EIP-55 大小写校验格式，否则视为无效消息。
+pub fn parse(message: &str) -> Result<SiweMessage, String> {
+    let invalid = |field: &str| format!("登录消息格式无效: {}", field);
+    let mut lines = message.split('\n').peekable();
+
+    let preamble = lines.next().ok_or_else(|| invalid("缺少首行"))?;
+    let origin = preamble
+        .strip_suffix(PREAMBLE_SUFFIX)
+        .ok_or_else(|| invalid("首行"))?;
+    // -- 域名前可以带协议，例如 `https://docs.example.com`
+    let domain = origin
+        .split_once("://")
+        .map_or(origin, |(_, domain)| domain);
+    if domain.is_empty() || domain.contains(char::is_whitespace) {
+        return Err(invalid("域名"));
+    }
+
+    let address_text = lines.next().ok_or_else(|| invalid("缺少地址"))?;
+    let address = parse_address(address_text)?;
+
+    if lines.next() != Some("") {
+        return Err(invalid("地址后应为空行"));
+    }
+
+    // -- 声明是可选的，声明后同样跟一个空行
+    let mut statement = None;
+    if let Some(line) = lines.next_if(|line| !line.starts_with("URI: "))
+        && !line.is_empty()
+    {
+        statement = Some(line.to_string());
+        if lines.next() != Some("") {
+            return Err(invalid("声明后应为空行"));
+        }
+    }
+
+    let mut field = |name: &str| {
+        lines
+            .next()
+            .and_then(|line| line.strip_prefix(name))
+            .and_then(|line| line.strip_prefix(": "))
+            .map(str::to_string)
+            .ok_or_else(|| invalid(name))
+    };
+
+    let uri = field("URI")?;
+    let version = field("Version")?;
+    let chain_id = field("Chain ID")?
+        .parse()
+        .map_err(|_| invalid("Chain ID"))?;
+    let nonce = field("Nonce")?;
+    if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid("Nonce"));
+    }
+    let issued_at = parse_time(&field("Issued At")?).ok_or_else(|| invalid("Issued At"))?;
+
+    let mut optional = |name: &str| {
+        let prefix = format!("{}: ", name);
+        lines
+            .next_if(|line| line.starts_with(&prefix))
+            .map(|line| line[prefix.len()..].to_string())
+    };
+
+    let expiration_time = match optional("Expiration Time") {
+        Some(value) => Some(parse_time(&value).ok_or_else(|| invalid("Expiration Time"))?),
+        None => None,
+    };
+    let not_before = match optional("Not Before") {
+        Some(value) => Some(parse_time(&value).ok_or_else(|| invalid("Not Before"))?),
+        None => None,
+    };
+    let request_id = optional("Request ID");
+
+    let mut resources = Vec::new();
+    if lines.next_if_eq(&"Resources:").is_some() {
+        while let Some(line) = lines.next_if(|line| line.starts_with("- ")) {
+            resources.push(line[2..].to_string());
+        }
+    }
+
+    if lines.next().is_some() {
+        return Err(invalid("多余的内容"));
+    }
+
+    Ok(SiweMessage {
+        domain: domain.to_string(),
+        address,
+        statement,
+        uri,
+        version,
+        chain_id,
+        nonce,
+        issued_at,
+        expiration_time,
+        not_before,
+        request_id,
+        resources,
+    })
+}
+
+/// 从 `personal_sign` (EIP-191) 签名中恢复签名者的地址
+///
+/// 签名为 65 字节的十六进制 `r || s || v`，`v` 可以是 0/1 或 27/28。
+/// 按以太坊的规则只接受低 s 值的签名。
+pub fn recover_address(message: &str, signature: &str) -> Result<[u8; 20], String> {
+    let bytes = hex::decode(signature.strip_prefix("0x").unwrap_or(signature))
+        .map_err(|_| "签名编码无效".to_string())?;
+    if bytes.len() != 65 {
+        return Err("签名长度无效".to_string());
+    }
+
+    let recovery_id = match bytes[64] {
+        v @ (0 | 1) => v,
+        v @ (27 | 28) => v - 27,
+        _ => return Err("签名的恢复标识无效".to_string()),
+    };
+    let recovery_id = RecoveryId::from_byte(recovery_id).ok_or("签名的恢复标识无效")?;
+
+    let signature = Signature::from_slice(&bytes[..64]).map_err(|_| "签名无效".to_string())?;
+    if signature.normalize_s().is_some() {
+        return Err("签名无效".to_string());
+    }
+
+    let hash = Keccak256::new()
+        .chain_update(format!("\x19Ethereum Signed Message:\n{}", message.len()))
+        .chain_update(message)
+        .finalize();
+
+    let key = VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id)
+        .map_err(|_| "签名无效".to_string())?;
+
+    Ok(public_key_address(&key))
+}
+
+/// EIP-55 大小写校验格式的地址
+pub fn to_checksum_address(address: &[u8; 20]) -> String {
+    let lower = hex::encode(address);
+    let hash = Keccak256::digest(lower.as_bytes());
+
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    format!("0x{}", checksummed)
+}
+
+/// 以太坊地址为未压缩公钥（不含前缀字节）Keccak-256 哈希的后 20 字节
+fn public_key_address(key: &VerifyingKey) -> [u8; 20] {
+    let point = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+fn parse_address(text: &str) -> Result<[u8; 20], String> {
+    let invalid = || "登录消息中的地址无效".to_string();
+    let hex_part = text.strip_prefix("0x").ok_or_else(invalid)?;
+
+    let mut address = [0u8; 20];
+    hex::decode_to_slice(hex_part, &mut address).map_err(|_| invalid())?;
+
+    if to_checksum_address(&address) != text {
+        return Err("登录消息中的地址必须使用 EIP-55 校验格式".to_string());
+    }
+
+    Ok(address)
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::ecdsa::SigningKey;
+
+    use super::*;
+
+    /// EIP-4361 中的示例消息
+    const EIP4361_MESSAGE: &str = "service.invalid wants you to sign in with your Ethereum account:
+0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2
+
+I accept the ServiceOrg Terms of Service: https://service.invalid/tos
+
+URI: https://service.invalid/login
+Version: 1
+Chain ID: 1
+Nonce: 32891756
+Issued At: 2021-09-30T16:25:24Z
+Resources:
+- ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/
+- https://example.com/my-web2-claim.json";
+
+    /// web3.js 文档中 `eth.accounts.sign` 的示例私钥、地址和签名
+    const KNOWN_PRIVATE_KEY: &str =
+        "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const KNOWN_ADDRESS: &str = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+    const KNOWN_MESSAGE: &str = "Some data";
+    const KNOWN_SIGNATURE: &str = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+    fn known_key() -> SigningKey {
+        SigningKey::from_slice(&hex::decode(KNOWN_PRIVATE_KEY).unwrap()).unwrap()
+    }
+
+    fn personal_sign(key: &SigningKey, message: &str) -> String {
+        let hash = Keccak256::new()
+            .chain_update(format!("\x19Ethereum Signed Message:\n{}", message.len()))
+            .chain_update(message)
+            .finalize();
+        let (signature, recovery_id) = key.sign_prehash_recoverable(&hash).unwrap();
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(27 + recovery_id.to_byte());
+        format!("0x{}", hex::encode(bytes))
+    }
+
+    fn message(address: &str, now: DateTime<Utc>) -> String {
+        format!(
+            "docs.example.com wants you to sign in with your Ethereum account:
+{}
+
+Sign in to Doc Editor
+
+URI: https://docs.example.com
+Version: 1
+Chain ID: 1
+Nonce: {}
+Issued At: {}
+Expiration Time: {}",
+            address,
+            generate_nonce(),
+            now.to_rfc3339(),
+            (now + Duration::minutes(5)).to_rfc3339()
+        )
+    }
+
+    #[test]
+    fn parses_eip4361_example() {
+        let message = parse(EIP4361_MESSAGE).unwrap();
+
+        assert_eq!(message.domain, "service.invalid");
+        assert_eq!(
+            to_checksum_address(&message.address),
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+        );
+        assert_eq!(
+            message.statement.as_deref(),
+            Some("I accept the ServiceOrg Terms of Service: https://service.invalid/tos")
+        );
+        assert_eq!(message.uri, "https://service.invalid/login");
+        assert_eq!(message.version, "1");
+        assert_eq!(message.chain_id, 1);
+        assert_eq!(message.nonce, "32891756");
+        assert_eq!(message.issued_at.to_rfc3339(), "2021-09-30T16:25:24+00:00");
+        assert!(message.expiration_time.is_none());
+        assert!(message.not_before.is_none());
+        assert!(message.request_id.is_none());
+        assert_eq!(
+            message.resources,
+            [
+                "ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/",
+                "https://example.com/my-web2-claim.json"
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_optional_fields() {
+        let text = "https://docs.example.com:8443 wants you to sign in with your Ethereum account:
+0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2
+
+URI: https://docs.example.com:8443/login
+Version: 1
+Chain ID: 137
+Nonce: abcdef0123456789
+Issued At: 2024-01-01T00:00:00Z
+Expiration Time: 2024-01-01T00:05:00Z
+Not Before: 2024-01-01T00:00:00Z
+Request ID: request-1";
+        let message = parse(text).unwrap();
+
+        assert_eq!(message.domain, "docs.example.com:8443");
+        assert!(message.statement.is_none());
+        assert_eq!(message.chain_id, 137);
+        assert!(message.expiration_time.is_some());
+        assert!(message.not_before.is_some());
+        assert_eq!(message.request_id.as_deref(), Some("request-1"));
+        assert!(message.resources.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let replace = |from: &str, to: &str| EIP4361_MESSAGE.replacen(from, to, 1);
+
+        // -- 地址必须使用 EIP-55 校验格式
+        assert!(parse(&replace("0xC02aaA39b", "0xc02aaa39b")).is_err());
+        assert!(parse(&replace("0xC02aaA39b223FE8D", "0xC02aaA39b223Fe8D")).is_err());
+        assert!(parse(&replace("0xC02aaA39b", "C02aaA39b")).is_err());
+        // -- 随机数至少 8 个字母数字字符
+        assert!(parse(&replace("Nonce: 32891756", "Nonce: 1234567")).is_err());
+        assert!(parse(&replace("Nonce: 32891756", "Nonce: 3289-1756")).is_err());
+        assert!(parse(&replace("Chain ID: 1", "Chain ID: one")).is_err());
+        assert!(
+            parse(&replace(
+                "Issued At: 2021-09-30T16:25:24Z",
+                "Issued At: yesterday"
+            ))
+            .is_err()
+        );
+        assert!(
+            parse(&replace(
+                " wants you to sign in",
+                " would like you to sign in"
+            ))
+            .is_err()
+        );
+        assert!(parse(&replace("\n\nURI", "\nURI")).is_err());
+        assert!(parse(&format!("{}\nextra", EIP4361_MESSAGE)).is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn eip55_checksum_addresses() {
+        // -- EIP-55 中的测试向量
+        for address in [
+            "0x52908400098527886E0F7030069857D2E4169EE7",
+            "0x8617E340B3D01FA5F11F306F4090FD50E238070D",
+            "0xde709f2102306220921060314715629080e2fb77",
+            "0x27b1fdb04752bbc536007a920d24acb045561c26",
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let mut bytes = [0u8; 20];
+            hex::decode_to_slice(address[2..].to_ascii_lowercase(), &mut bytes).unwrap();
+            assert_eq!(to_checksum_address(&bytes), address);
+            assert_eq!(parse_address(address).unwrap(), bytes);
+        }
+
+        assert!(parse_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_err());
+        assert!(parse_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA").is_err());
+    }
+
+    #[test]
+    fn recovers_known_signature() {
+        assert_eq!(
+            to_checksum_address(&public_key_address(known_key().verifying_key())),
+            KNOWN_ADDRESS
+        );
+
+        let address = recover_address(KNOWN_MESSAGE, KNOWN_SIGNATURE).unwrap();
+        assert_eq!(to_checksum_address(&address), KNOWN_ADDRESS);
+
+        // -- v 也可以是 0/1，签名可以不带 0x 前缀
+        let mut bytes = hex::decode(&KNOWN_SIGNATURE[2..]).unwrap();
+        bytes[64] -= 27;
+        let address = recover_address(KNOWN_MESSAGE, &hex::encode(&bytes)).unwrap();
+        assert_eq!(to_checksum_address(&address), KNOWN_ADDRESS);
+
+        // -- 消息被改动后恢复出的是另一个地址
+        let address = recover_address("Some data!", KNOWN_SIGNATURE).unwrap();
+        assert_ne!(to_checksum_address(&address), KNOWN_ADDRESS);
+
+        assert_eq!(personal_sign(&known_key(), KNOWN_MESSAGE), KNOWN_SIGNATURE);
+    }
+
+    #[test]
+    fn rejects_high_s_signatures() {
+        let bytes = hex::decode(&KNOWN_SIGNATURE[2..]).unwrap();
+        let signature = Signature::from_slice(&bytes[..64]).unwrap();
+        let (r, s) = signature.split_scalars();
+
+        // -- (r, n - s) 是同一消息的另一个有效签名，恢复标识相应翻转
+        let high_s = Signature::from_scalars(r.to_bytes(), (-*s).to_bytes()).unwrap();
+        let mut malleated = high_s.to_bytes().to_vec();
+        malleated.push(if bytes[64] == 27 { 28 } else { 27 });
+
+        assert!(recover_address(KNOWN_MESSAGE, &hex::encode(malleated)).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_signatures() {
+        let bytes = hex::decode(&KNOWN_SIGNATURE[2..]).unwrap();
+
+        assert!(recover_address(KNOWN_MESSAGE, "0xzz").is_err());
+        assert!(recover_address(KNOWN_MESSAGE, &hex::encode(&bytes[..64])).is_err());
+
+        let mut bad_v = bytes.clone();
+        bad_v[64] = 29;
+        assert!(recover_address(KNOWN_MESSAGE, &hex::encode(bad_v)).is_err());
+
+        assert!(recover_address(KNOWN_MESSAGE, &hex::encode([0u8; 65])).is_err());
+    }
+
+    #[test]
+    fn signed_message_round_trip() {
+        let key = SigningKey::random(&mut OsRng);
+        let address = to_checksum_address(&public_key_address(key.verifying_key()));
+        let now = Utc::now();
+        let text = message(&address, now);
+
+        let message = parse(&text).unwrap();
+        message.validate("docs.example.com", &[1], now).unwrap();
+
+        let signer = recover_address(&text, &personal_sign(&key, &text)).unwrap();
+        assert_eq!(signer, message.address);
+    }
+
+    #[test]
+    fn validates_scope_and_lifetime() {
+        let now = Utc::now();
+        let text = message(KNOWN_ADDRESS, now);
+        let message = parse(&text).unwrap();
+
+        assert!(message.validate("docs.example.com", &[1, 137], now).is_ok());
+
+        // -- 域名必须完全一致，包括端口
+        assert!(message.validate("evil.example.com", &[1], now).is_err());
+        assert!(
+            message
+                .validate("docs.example.com:8443", &[1], now)
+                .is_err()
+        );
+        // -- 不支持的链 ID
+        assert!(message.validate("docs.example.com", &[137], now).is_err());
+        // -- 已过期，以及签发时间在允许的时钟偏差之后
+        assert!(
+            message
+                .validate("docs.example.com", &[1], now + Duration::minutes(5))
+                .is_err()
+        );
+        assert!(
+            message
+                .validate(
+                    "docs.example.com",
+                    &[1],
+                    now - Duration::seconds(CLOCK_SKEW_SECONDS + 1)
+                )
+                .is_err()
+        );
+
+        let not_yet = parse(&format!(
+            "{}\nNot Before: {}",
+            text,
+            (now + Duration::minutes(10)).to_rfc3339()
+        ))
+        .unwrap();
+        assert!(not_yet.validate("docs.example.com", &[1], now).is_err());
+
+        let other_version = parse(&text.replace("Version: 1", "Version: 2")).unwrap();
+        assert!(
+            other_version
+                .validate("docs.example.com", &[1], now)
+                .is_err()
+        );
+    }
+}
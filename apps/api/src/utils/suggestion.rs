@@ -0,0 +1,332 @@
+use serde_json::Value;
+
+use crate::models::Suggestion;
+
+/// 建议无法应用到当前文档时的原因
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SuggestionConflict {
+    #[error("Document content is not a Tiptap document")]
+    UnsupportedContent,
+
+    #[error("The suggested block no longer exists")]
+    BlockNotFound,
+
+    #[error("The suggested text has changed since the suggestion was made")]
+    TextChanged,
+}
+
+/// 将建议应用到 Tiptap JSON 文档，返回新的文档内容
+///
+/// # 冲突处理
+/// - 原偏移处的文本与建议记录的原文一致时直接应用
+/// - 文档在建议之后被修改过：在同一块内查找距离原位置最近的原文后应用；
+///   纯插入类建议没有原文可供定位，只能应用在未被修改的文档上
+/// - 其他情况视为冲突
+pub fn apply_suggestion(
+    content: &str,
+    suggestion: &Suggestion,
+    current_revision: i64,
+) -> Result<String, SuggestionConflict> {
+    let mut root: Value =
+        serde_json::from_str(content).map_err(|_| SuggestionConflict::UnsupportedContent)?;
+    if root.get("type").and_then(Value::as_str) != Some("doc") {
+        return Err(SuggestionConflict::UnsupportedContent);
+    }
+
+    let block =
+        find_block_mut(&mut root, &suggestion.block_id).ok_or(SuggestionConflict::BlockNotFound)?;
+
+    let block_text = block_text(block);
+    let (start, end) = locate_range(&block_text, suggestion, current_revision)?;
+
+    let mut text_nodes = Vec::new();
+    collect_text_nodes(block, &mut text_nodes);
+
+    if text_nodes.is_empty() {
+        // -- 空块只可能接受插入
+        if !suggestion.text.is_empty() {
+            block["content"] = serde_json::json!([{ "type": "text", "text": suggestion.text }]);
+        }
+    } else {
+        replace_range(&mut text_nodes, start, end, &suggestion.text);
+        prune_empty_text_nodes(block);
+    }
+
+    serde_json::to_string(&root).map_err(|_| SuggestionConflict::UnsupportedContent)
+}
+
+/// 确定建议在当前块文本中的字符范围
+fn locate_range(
+    block_text: &str,
+    suggestion: &Suggestion,
+    current_revision: i64,
+) -> Result<(usize, usize), SuggestionConflict> {
+    let start = suggestion.start_offset.max(0) as usize;
+    let end = suggestion.end_offset.max(suggestion.start_offset).max(0) as usize;
+
+    let current: String = block_text.chars().skip(start).take(end - start).collect();
+    let length = block_text.chars().count();
+
+    if current == suggestion.original_text && end <= length {
+        if suggestion.original_text.is_empty() && current_revision != suggestion.base_revision {
+            return Err(SuggestionConflict::TextChanged);
+        }
+        return Ok((start, end));
+    }
+
+    if suggestion.original_text.is_empty() {
+        return Err(SuggestionConflict::TextChanged);
+    }
+
+    let original_length = suggestion.original_text.chars().count();
+    block_text
+        .match_indices(suggestion.original_text.as_str())
+        .map(|(byte_index, _)| block_text[..byte_index].chars().count())
+        .min_by_key(|offset| offset.abs_diff(start))
+        .map(|offset| (offset, offset + original_length))
+        .ok_or(SuggestionConflict::TextChanged)
+}
+
+/// 查找 `attrs.id` 等于 `block_id` 的节点
+fn find_block_mut<'a>(node: &'a mut Value, block_id: &str) -> Option<&'a mut Value> {
+    let is_target = node
+        .get("attrs")
+        .and_then(|attrs| attrs.get("id"))
+        .and_then(Value::as_str)
+        == Some(block_id);
+    if is_target {
+        return Some(node);
+    }
+
+    node.get_mut("content")
+        .and_then(Value::as_array_mut)?
+        .iter_mut()
+        .find_map(|child| find_block_mut(child, block_id))
+}
+
+fn block_text(node: &Value) -> String {
+    let mut text = node
+        .get("text")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    if let Some(children) = node.get("content").and_then(Value::as_array) {
+        for child in children {
+            text.push_str(&block_text(child));
+        }
+    }
+
+    text
+}
+
+/// 按文档顺序收集块内的文本节点
+fn collect_text_nodes<'a>(node: &'a mut Value, out: &mut Vec<&'a mut Value>) {
+    if node.get("text").is_some() {
+        out.push(node);
+        return;
+    }
+
+    if let Some(children) = node.get_mut("content").and_then(Value::as_array_mut) {
+        for child in children {
+            collect_text_nodes(child, out);
+        }
+    }
+}
+
+/// 删除 `[start, end)` 范围内的字符并在 `start` 处插入新文本，保留各文本节点的样式标记
+fn replace_range(text_nodes: &mut [&mut Value], start: usize, end: usize, insert: &str) {
+    let mut node_start = 0;
+    let mut inserted = insert.is_empty();
+
+    for node in text_nodes.iter_mut() {
+        let text: Vec<char> = node
+            .get("text")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .chars()
+            .collect();
+        let node_end = node_start + text.len();
+
+        let mut new_text = String::with_capacity(text.len() + insert.len());
+        for (index, ch) in text.iter().enumerate() {
+            let offset = node_start + index;
+            if offset == start && !inserted {
+                new_text.push_str(insert);
+                inserted = true;
+            }
+            if offset < start || offset >= end {
+                new_text.push(*ch);
+            }
+        }
+        // -- 插入点位于该节点末尾
+        if !inserted && start == node_end {
+            new_text.push_str(insert);
+            inserted = true;
+        }
+
+        node["text"] = Value::String(new_text);
+        node_start = node_end;
+    }
+}
+
+/// Tiptap 不允许空文本节点，删除后需要清理
+fn prune_empty_text_nodes(node: &mut Value) {
+    if let Some(children) = node.get_mut("content").and_then(Value::as_array_mut) {
+        children.retain(|child| child.get("text").and_then(Value::as_str) != Some(""));
+        for child in children {
+            prune_empty_text_nodes(child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::{SuggestionKind, SuggestionStatus};
+
+    fn suggestion(
+        kind: SuggestionKind,
+        block_id: &str,
+        start_offset: i32,
+        end_offset: i32,
+        original_text: &str,
+        text: &str,
+    ) -> Suggestion {
+        Suggestion {
+            id: uuid::Uuid::nil(),
+            document_id: uuid::Uuid::nil(),
+            author_id: uuid::Uuid::nil(),
+            kind,
+            block_id: block_id.to_string(),
+            start_offset,
+            end_offset,
+            original_text: original_text.to_string(),
+            text: text.to_string(),
+            base_revision: 1,
+            status: SuggestionStatus::Pending,
+            reviewed_by: None,
+            reviewed_at: None,
+            applied_revision: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn paragraph(id: &str, content: Value) -> String {
+        json!({
+            "type": "doc",
+            "content": [{ "type": "paragraph", "attrs": { "id": id }, "content": content }]
+        })
+        .to_string()
+    }
+
+    fn block_content(content: &str) -> Value {
+        let root: Value = serde_json::from_str(content).unwrap();
+        root["content"][0]["content"].clone()
+    }
+
+    #[test]
+    fn insert_delete_and_replace_in_one_node() {
+        let content = paragraph("p1", json!([{ "type": "text", "text": "Hello world" }]));
+
+        let insert = suggestion(SuggestionKind::Insert, "p1", 5, 5, "", ",");
+        assert_eq!(
+            block_content(&apply_suggestion(&content, &insert, 1).unwrap()),
+            json!([{ "type": "text", "text": "Hello, world" }])
+        );
+
+        let delete = suggestion(SuggestionKind::Delete, "p1", 5, 11, " world", "");
+        assert_eq!(
+            block_content(&apply_suggestion(&content, &delete, 1).unwrap()),
+            json!([{ "type": "text", "text": "Hello" }])
+        );
+
+        let replace = suggestion(SuggestionKind::Replace, "p1", 6, 11, "world", "there");
+        assert_eq!(
+            block_content(&apply_suggestion(&content, &replace, 1).unwrap()),
+            json!([{ "type": "text", "text": "Hello there" }])
+        );
+    }
+
+    #[test]
+    fn range_spanning_marked_nodes() {
+        let bold = json!([{ "type": "bold" }]);
+        let content = paragraph(
+            "p1",
+            json!([
+                { "type": "text", "text": "Hello " },
+                { "type": "text", "text": "bold", "marks": bold },
+                { "type": "text", "text": " world" }
+            ]),
+        );
+
+        // -- 删除跨越两个节点的文本，剩余部分保留各自的样式
+        let delete = suggestion(SuggestionKind::Delete, "p1", 3, 8, "lo bo", "");
+        assert_eq!(
+            block_content(&apply_suggestion(&content, &delete, 1).unwrap()),
+            json!([
+                { "type": "text", "text": "Hel" },
+                { "type": "text", "text": "ld", "marks": bold },
+                { "type": "text", "text": " world" }
+            ])
+        );
+
+        // -- 整个加粗节点被替换掉后不留下空文本节点
+        let replace = suggestion(SuggestionKind::Replace, "p1", 3, 12, "lo bold w", "p, w");
+        assert_eq!(
+            block_content(&apply_suggestion(&content, &replace, 1).unwrap()),
+            json!([
+                { "type": "text", "text": "Help, w" },
+                { "type": "text", "text": "orld" }
+            ])
+        );
+    }
+
+    #[test]
+    fn relocates_moved_text() {
+        let content = paragraph("p1", json!([{ "type": "text", "text": "Oh, Hello world" }]));
+
+        // -- 文档在建议之后被修改，原文向后移动了 4 个字符
+        let replace = suggestion(SuggestionKind::Replace, "p1", 6, 11, "world", "there");
+        assert_eq!(
+            block_content(&apply_suggestion(&content, &replace, 2).unwrap()),
+            json!([{ "type": "text", "text": "Oh, Hello there" }])
+        );
+    }
+
+    #[test]
+    fn stale_original_text_conflicts() {
+        let content = paragraph("p1", json!([{ "type": "text", "text": "Hello everyone" }]));
+
+        let replace = suggestion(SuggestionKind::Replace, "p1", 6, 11, "world", "there");
+        assert_eq!(
+            apply_suggestion(&content, &replace, 2),
+            Err(SuggestionConflict::TextChanged)
+        );
+
+        // -- 纯插入没有原文可以定位，文档修改后无法应用
+        let insert = suggestion(SuggestionKind::Insert, "p1", 5, 5, "", ",");
+        assert_eq!(
+            apply_suggestion(&content, &insert, 2),
+            Err(SuggestionConflict::TextChanged)
+        );
+    }
+
+    #[test]
+    fn unknown_block_conflicts() {
+        let content = paragraph("p1", json!([{ "type": "text", "text": "Hello world" }]));
+
+        let replace = suggestion(SuggestionKind::Replace, "p2", 6, 11, "world", "there");
+        assert_eq!(
+            apply_suggestion(&content, &replace, 1),
+            Err(SuggestionConflict::BlockNotFound)
+        );
+        assert_eq!(
+            apply_suggestion("plain text", &replace, 1),
+            Err(SuggestionConflict::UnsupportedContent)
+        );
+    }
+}
@@ -1,20 +1,49 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
 use axum::http::StatusCode;
 use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::error::{ErrorMessage, HttpError};
+use super::jwt_keys::JwtKeys;
+use crate::{
+    error::{ErrorMessage, HttpError},
+    models::UserRole,
+};
+
+/// JWT 的类型，写入 `typ` 声明，防止一种令牌被当作另一种使用
+///
+/// 刷新令牌是不透明的随机令牌，不是 JWT，因此没有对应的类型。
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    /// 访问令牌
+    Access,
+    /// 登录挑战令牌
+    Challenge,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String,
+    /// 登录会话 ID，注销或吊销会话后令牌立即失效
+    pub sid: String,
+    /// 签发时的用户角色，角色变更在令牌过期后才会体现
+    pub role: UserRole,
+    pub typ: TokenType,
+    pub iss: String,
+    pub aud: String,
     pub iat: usize,
     pub exp: usize,
 }
 
+/// 签发访问令牌，使用当前的签名密钥
 pub fn create_token(
     user_id: &str,
-    secret: &[u8],
+    session_id: &str,
+    role: UserRole,
+    keys: &JwtKeys,
     expires_in_seconds: i64,
 ) -> Result<String, jsonwebtoken::errors::Error> {
     if user_id.is_empty() {
@@ -26,10 +55,121 @@ pub fn create_token(
     let exp = (now + Duration::minutes(expires_in_seconds)).timestamp() as usize;
     let claims = TokenClaims {
         sub: user_id.to_string(),
+        sid: session_id.to_string(),
+        role,
+        typ: TokenType::Access,
+        iss: keys.issuer().to_string(),
+        aud: keys.audience().to_string(),
         iat,
         exp,
     };
 
+    keys.encode(&claims)
+}
+
+/// 校验访问令牌，当前密钥和已退役的密钥签发的令牌都可以通过
+pub fn decode_token<T: Into<String>>(token: T, keys: &JwtKeys) -> Result<TokenClaims, HttpError> {
+    keys.decode::<TokenClaims>(&token.into())
+        .ok()
+        .filter(|claims| claims.typ == TokenType::Access)
+        .ok_or_else(|| {
+            HttpError::new(
+                ErrorMessage::InvalidToken.to_string(),
+                StatusCode::UNAUTHORIZED,
+            )
+        })
+}
+
+/// 登录挑战令牌的用途
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengePurpose {
+    /// 已开启两步验证，需要提交验证码
+    TwoFactor,
+    /// 角色要求两步验证但尚未设置，需要先完成设置
+    TwoFactorSetup,
+}
+
+/// 密码等第一步验证通过后签发的短期挑战令牌
+///
+/// 类型为 `challenge` 且不包含 `sid`，不能当作访问令牌使用。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String,
+    pub purpose: ChallengePurpose,
+    /// 第一步使用的登录方式，写入审计日志
+    pub method: String,
+    pub typ: TokenType,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
+    pub exp: usize,
+}
+
+pub fn create_challenge_token(
+    user_id: &str,
+    purpose: ChallengePurpose,
+    method: &str,
+    keys: &JwtKeys,
+    expires_in_minutes: i64,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = ChallengeClaims {
+        sub: user_id.to_string(),
+        purpose,
+        method: method.to_string(),
+        typ: TokenType::Challenge,
+        iss: keys.issuer().to_string(),
+        aud: keys.audience().to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(expires_in_minutes)).timestamp() as usize,
+    };
+
+    keys.encode(&claims)
+}
+
+pub fn decode_challenge_token(
+    token: &str,
+    purpose: ChallengePurpose,
+    keys: &JwtKeys,
+) -> Result<ChallengeClaims, HttpError> {
+    keys.decode::<ChallengeClaims>(token)
+        .ok()
+        .filter(|claims| claims.typ == TokenType::Challenge && claims.purpose == purpose)
+        .ok_or_else(|| {
+            HttpError::new(
+                ErrorMessage::InvalidToken.to_string(),
+                StatusCode::UNAUTHORIZED,
+            )
+        })
+}
+
+/// OAuth 授权流程的状态，签名后存入 cookie，回调时校验
+///
+/// 字段与访问令牌和挑战令牌都不同，不能互相冒用。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthStateClaims {
+    /// OAuth 提供方，例如 `google`、`github`
+    pub provider: String,
+    /// 发给提供方的 `state` 参数
+    pub state: String,
+    /// PKCE 校验码，换取令牌时提交
+    pub pkce_verifier: String,
+    /// OIDC 的 `nonce` 参数，必须与 ID 令牌中的一致
+    pub nonce: String,
+    /// 登录完成后返回的前端路径
+    pub return_to: Option<String>,
+    /// 已登录用户发起关联时的用户 ID，为空表示登录流程
+    #[serde(default)]
+    pub link_user_id: Option<String>,
+    pub iat: usize,
+    pub exp: usize,
+}
+
+pub fn create_oauth_state_token(
+    claims: OAuthStateClaims,
+    secret: &[u8],
+) -> Result<String, jsonwebtoken::errors::Error> {
     encode(
         &Header::default(),
         &claims,
@@ -37,18 +177,75 @@ pub fn create_token(
     )
 }
 
-pub fn decode_token<T: Into<String>>(token: T, secret: &[u8]) -> Result<String, HttpError> {
-    let decode = decode::<TokenClaims>(
-        &token.into(),
+pub fn decode_oauth_state_token(token: &str, secret: &[u8]) -> Option<OAuthStateClaims> {
+    decode::<OAuthStateClaims>(
+        token,
         &DecodingKey::from_secret(secret),
         &Validation::new(Algorithm::HS256),
-    );
-
-    match decode {
-        Ok(token) => Ok(token.claims.sub),
-        Err(_) => Err(HttpError::new(
-            ErrorMessage::InvalidToken.to_string(),
-            StatusCode::UNAUTHORIZED,
-        )),
+    )
+    .ok()
+    .map(|token| token.claims)
+}
+
+/// 生成不透明的随机令牌 (32 字节随机数的十六进制)，用于刷新令牌、登录链接等
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// 不透明令牌只以 SHA-256 哈希保存，数据库泄露时无法直接使用
+pub fn hash_opaque_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// 个人访问令牌的前缀，便于认证中间件区分令牌类型，也方便密钥扫描工具识别
+pub const ACCESS_TOKEN_PREFIX: &str = "pat_";
+/// 列表中展示的令牌开头长度（含前缀）
+const ACCESS_TOKEN_DISPLAY_LENGTH: usize = 12;
+
+/// 生成个人访问令牌，返回令牌和用于展示的开头部分
+pub fn generate_access_token() -> (String, String) {
+    let token = format!("{}{}", ACCESS_TOKEN_PREFIX, generate_opaque_token());
+    let display_prefix = token[..ACCESS_TOKEN_DISPLAY_LENGTH].to_string();
+    (token, display_prefix)
+}
+
+/// 生成六位数字登录验证码
+pub fn generate_login_code() -> String {
+    // -- 拒绝采样，避免取模带来的分布偏差
+    const LIMIT: u32 = u32::MAX - u32::MAX % 1_000_000;
+    loop {
+        let value = OsRng.next_u32();
+        if value < LIMIT {
+            return format!("{:06}", value % 1_000_000);
+        }
     }
 }
+
+/// 计算登录验证码的 HMAC
+///
+/// 六位验证码的取值空间很小，普通哈希可以被穷举，因此使用服务端密钥计算 HMAC，
+/// 并把邮箱一起纳入计算。
+pub fn hash_login_code(secret: &[u8], email: &str, code: &str) -> String {
+    hex::encode(login_code_mac(secret, email, code).finalize().into_bytes())
+}
+
+/// 以常量时间比较验证码与保存的 HMAC
+pub fn verify_login_code(secret: &[u8], email: &str, code: &str, code_hash: &str) -> bool {
+    let Ok(expected) = hex::decode(code_hash) else {
+        return false;
+    };
+
+    login_code_mac(secret, email, code)
+        .verify_slice(&expected)
+        .is_ok()
+}
+
+fn login_code_mac(secret: &[u8], email: &str, code: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(email.to_lowercase().as_bytes());
+    mac.update(b":");
+    mac.update(code.as_bytes());
+    mac
+}
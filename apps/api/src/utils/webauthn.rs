@@ -0,0 +1,642 @@
+use std::io::Cursor;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use ciborium::Value;
+use p256::ecdsa::{DerSignature, VerifyingKey as P256VerifyingKey, signature::Verifier};
+use rsa::{
+    BigUint, RsaPublicKey,
+    pkcs1v15::{Signature as RsaSignature, VerifyingKey as RsaVerifyingKey},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// COSE 算法：ECDSA P-256 + SHA-256
+pub const ES256: i64 = -7;
+/// COSE 算法：RSASSA-PKCS1-v1_5 + SHA-256 (Windows Hello)
+pub const RS256: i64 = -257;
+/// 支持的公钥算法，按优先级排列
+pub const SUPPORTED_ALGORITHMS: [i64; 2] = [ES256, RS256];
+
+/// 挑战长度，WebAuthn 规范要求至少 16 字节
+const CHALLENGE_BYTES: usize = 32;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+/// 依赖方（本服务）的标识，用于校验凭证的作用域
+pub struct RelyingParty<'a> {
+    /// 依赖方 ID，通常是站点的域名
+    pub id: &'a str,
+    /// 允许发起认证的前端源，例如 `https://docs.example.com`
+    pub origin: &'a str,
+}
+
+/// 注册成功后需要保存的凭证
+pub struct RegisteredCredential {
+    /// Base64url 编码的凭证 ID
+    pub credential_id: String,
+    /// COSE 编码的公钥
+    pub public_key: Vec<u8>,
+    pub algorithm: i64,
+    pub sign_count: u32,
+}
+
+/// 认证器返回的断言校验通过后的结果
+pub struct VerifiedAssertion {
+    pub sign_count: u32,
+    /// 认证器是否验证了用户身份（PIN、生物识别等）
+    pub user_verified: bool,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData {
+    flags: u8,
+    sign_count: u32,
+    /// 注册时认证器附带的凭证 ID 和公钥
+    attested_credential: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// 生成随机挑战，返回 Base64url 编码
+pub fn generate_challenge() -> String {
+    let mut challenge = [0u8; CHALLENGE_BYTES];
+    OsRng.fill_bytes(&mut challenge);
+    encode(&challenge)
+}
+
+/// Base64url 编码（不带填充），WebAuthn 中二进制字段统一使用这种编码
+pub fn encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Base64url 解码，兼容带填充的输入
+pub fn decode(value: &str) -> Result<Vec<u8>, String> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| "Base64url 编码无效".to_string())
+}
+
+/// 校验注册 (`navigator.credentials.create`) 的结果
+///
+/// 只接受 `none` 证明方式，不校验认证器的型号；凭证公钥必须使用支持的算法。
+pub fn verify_registration(
+    rp: &RelyingParty,
+    challenge: &str,
+    client_data_json: &str,
+    attestation_object: &str,
+) -> Result<RegisteredCredential, String> {
+    verify_client_data(&decode(client_data_json)?, "webauthn.create", challenge, rp)?;
+
+    let attestation: Value = ciborium::from_reader(decode(attestation_object)?.as_slice())
+        .map_err(|_| "attestationObject 格式无效".to_string())?;
+    let auth_data = map_get(&attestation, "authData")
+        .and_then(Value::as_bytes)
+        .ok_or_else(|| "attestationObject 缺少 authData".to_string())?;
+
+    let data = parse_authenticator_data(auth_data, rp)?;
+    let (credential_id, public_key) = data
+        .attested_credential
+        .ok_or_else(|| "认证器未返回凭证公钥".to_string())?;
+    let algorithm = cose_algorithm(&public_key)?;
+
+    Ok(RegisteredCredential {
+        credential_id: encode(&credential_id),
+        public_key,
+        algorithm,
+        sign_count: data.sign_count,
+    })
+}
+
+/// 校验认证 (`navigator.credentials.get`) 的断言签名
+///
+/// 签名内容为 `authenticatorData || SHA-256(clientDataJSON)`。
+pub fn verify_assertion(
+    rp: &RelyingParty,
+    challenge: &str,
+    public_key: &[u8],
+    client_data_json: &str,
+    authenticator_data: &str,
+    signature: &str,
+) -> Result<VerifiedAssertion, String> {
+    let client_data = decode(client_data_json)?;
+    verify_client_data(&client_data, "webauthn.get", challenge, rp)?;
+
+    let auth_data = decode(authenticator_data)?;
+    let data = parse_authenticator_data(&auth_data, rp)?;
+
+    let mut message = auth_data;
+    message.extend_from_slice(&Sha256::digest(&client_data));
+    verify_signature(public_key, &message, &decode(signature)?)?;
+
+    Ok(VerifiedAssertion {
+        sign_count: data.sign_count,
+        user_verified: data.flags & FLAG_USER_VERIFIED != 0,
+    })
+}
+
+fn verify_client_data(
+    client_data_json: &[u8],
+    ceremony: &str,
+    challenge: &str,
+    rp: &RelyingParty,
+) -> Result<(), String> {
+    let client_data: ClientData = serde_json::from_slice(client_data_json)
+        .map_err(|_| "clientDataJSON 格式无效".to_string())?;
+
+    if client_data.ceremony != ceremony {
+        return Err("clientDataJSON 类型不匹配".to_string());
+    }
+    if client_data.challenge.trim_end_matches('=') != challenge {
+        return Err("挑战不匹配".to_string());
+    }
+    if client_data.origin != rp.origin {
+        return Err(format!("不允许的来源: {}", client_data.origin));
+    }
+
+    Ok(())
+}
+
+/// 解析认证器数据，校验依赖方 ID 哈希和用户在场标志
+fn parse_authenticator_data(bytes: &[u8], rp: &RelyingParty) -> Result<AuthenticatorData, String> {
+    if bytes.len() < 37 {
+        return Err("authenticatorData 长度无效".to_string());
+    }
+    if bytes[..32] != Sha256::digest(rp.id.as_bytes())[..] {
+        return Err("依赖方 ID 不匹配".to_string());
+    }
+
+    let flags = bytes[32];
+    if flags & FLAG_USER_PRESENT == 0 {
+        return Err("用户不在场".to_string());
+    }
+    let sign_count = u32::from_be_bytes([bytes[33], bytes[34], bytes[35], bytes[36]]);
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+        // -- AAGUID (16) + 凭证 ID 长度 (2) + 凭证 ID + COSE 公钥
+        let rest = bytes
+            .get(37..)
+            .filter(|rest| rest.len() >= 18)
+            .ok_or("凭证数据长度无效")?;
+        let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+        let credential_id = rest
+            .get(18..18 + id_len)
+            .ok_or("凭证 ID 长度无效")?
+            .to_vec();
+
+        // -- 公钥后面可能还有扩展数据，只读取一个 CBOR 值
+        let key_bytes = &rest[18 + id_len..];
+        let mut cursor = Cursor::new(key_bytes);
+        let _: Value =
+            ciborium::from_reader(&mut cursor).map_err(|_| "凭证公钥格式无效".to_string())?;
+        let public_key = key_bytes[..cursor.position() as usize].to_vec();
+
+        Some((credential_id, public_key))
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData {
+        flags,
+        sign_count,
+        attested_credential,
+    })
+}
+
+/// 检查 COSE 公钥是否完整且使用支持的算法，返回算法标识
+fn cose_algorithm(public_key: &[u8]) -> Result<i64, String> {
+    let key = parse_cose_key(public_key)?;
+    match key {
+        CoseKey::Es256(_) => Ok(ES256),
+        CoseKey::Rs256(_) => Ok(RS256),
+    }
+}
+
+enum CoseKey {
+    Es256(P256VerifyingKey),
+    Rs256(RsaPublicKey),
+}
+
+fn parse_cose_key(public_key: &[u8]) -> Result<CoseKey, String> {
+    let key: Value =
+        ciborium::from_reader(public_key).map_err(|_| "凭证公钥格式无效".to_string())?;
+    let alg = cose_param(&key, 3)
+        .and_then(Value::as_integer)
+        .and_then(|alg| i64::try_from(alg).ok())
+        .ok_or("凭证公钥缺少算法")?;
+    let bytes = |label| {
+        cose_param(&key, label)
+            .and_then(Value::as_bytes)
+            .ok_or("凭证公钥参数缺失")
+    };
+
+    match alg {
+        ES256 => {
+            // -- 未压缩的 SEC1 点：0x04 || x || y
+            let mut point = vec![0x04];
+            point.extend_from_slice(bytes(-2)?);
+            point.extend_from_slice(bytes(-3)?);
+            P256VerifyingKey::from_sec1_bytes(&point)
+                .map(CoseKey::Es256)
+                .map_err(|_| "P-256 公钥无效".to_string())
+        }
+        RS256 => RsaPublicKey::new(
+            BigUint::from_bytes_be(bytes(-1)?),
+            BigUint::from_bytes_be(bytes(-2)?),
+        )
+        .map(CoseKey::Rs256)
+        .map_err(|_| "RSA 公钥无效".to_string()),
+        _ => Err(format!("不支持的公钥算法: {}", alg)),
+    }
+}
+
+fn verify_signature(public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), String> {
+    let verified = match parse_cose_key(public_key)? {
+        CoseKey::Es256(key) => DerSignature::try_from(signature)
+            .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        CoseKey::Rs256(key) => RsaSignature::try_from(signature).is_ok_and(|signature| {
+            RsaVerifyingKey::<Sha256>::new(key)
+                .verify(message, &signature)
+                .is_ok()
+        }),
+    };
+
+    if verified {
+        Ok(())
+    } else {
+        Err("签名无效".to_string())
+    }
+}
+
+/// 读取以文本为键的 CBOR map 字段
+fn map_get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    value
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// 读取以整数为键的 COSE 参数
+fn cose_param(value: &Value, label: i64) -> Option<&Value> {
+    value
+        .as_map()?
+        .iter()
+        .find(|(k, _)| {
+            k.as_integer()
+                .and_then(|k| i64::try_from(k).ok())
+                .is_some_and(|k| k == label)
+        })
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::{Signature as P256Signature, SigningKey as P256SigningKey};
+    use rsa::{
+        RsaPrivateKey,
+        pkcs1v15::SigningKey as RsaSigningKey,
+        signature::{SignatureEncoding, Signer},
+        traits::PublicKeyParts,
+    };
+
+    use super::*;
+
+    const RP: RelyingParty = RelyingParty {
+        id: "docs.example.com",
+        origin: "https://docs.example.com",
+    };
+    const CREDENTIAL_ID: &[u8] = b"test-credential";
+
+    /// 测试用的认证器
+    enum TestKey {
+        Es256(P256SigningKey),
+        Rs256(Box<RsaPrivateKey>),
+    }
+
+    impl TestKey {
+        fn es256() -> Self {
+            TestKey::Es256(P256SigningKey::random(&mut OsRng))
+        }
+
+        fn rs256() -> Self {
+            TestKey::Rs256(Box::new(RsaPrivateKey::new(&mut OsRng, 1024).unwrap()))
+        }
+
+        fn cose_public_key(&self) -> Vec<u8> {
+            let int = |value: i64| Value::Integer(value.into());
+            let params = match self {
+                TestKey::Es256(key) => {
+                    let point = key.verifying_key().to_encoded_point(false);
+                    vec![
+                        (int(1), int(2)),
+                        (int(3), int(ES256)),
+                        (int(-1), int(1)),
+                        (int(-2), Value::Bytes(point.x().unwrap().to_vec())),
+                        (int(-3), Value::Bytes(point.y().unwrap().to_vec())),
+                    ]
+                }
+                TestKey::Rs256(key) => vec![
+                    (int(1), int(3)),
+                    (int(3), int(RS256)),
+                    (int(-1), Value::Bytes(key.n().to_bytes_be())),
+                    (int(-2), Value::Bytes(key.e().to_bytes_be())),
+                ],
+            };
+
+            let mut bytes = Vec::new();
+            ciborium::into_writer(&Value::Map(params), &mut bytes).unwrap();
+            bytes
+        }
+
+        fn sign(&self, message: &[u8]) -> Vec<u8> {
+            match self {
+                TestKey::Es256(key) => {
+                    let signature: P256Signature = key.sign(message);
+                    signature.to_der().as_bytes().to_vec()
+                }
+                TestKey::Rs256(key) => RsaSigningKey::<Sha256>::new(key.as_ref().clone())
+                    .sign(message)
+                    .to_vec(),
+            }
+        }
+    }
+
+    fn client_data(ceremony: &str, challenge: &str, origin: &str) -> String {
+        encode(
+            serde_json::json!({
+                "type": ceremony,
+                "challenge": challenge,
+                "origin": origin,
+                "crossOrigin": false,
+            })
+            .to_string()
+            .as_bytes(),
+        )
+    }
+
+    fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn attested_data(key: &TestKey, flags: u8) -> Vec<u8> {
+        let mut data = authenticator_data(RP.id, flags | FLAG_ATTESTED_CREDENTIAL, 0);
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&(CREDENTIAL_ID.len() as u16).to_be_bytes());
+        data.extend_from_slice(CREDENTIAL_ID);
+        data.extend_from_slice(&key.cose_public_key());
+        data
+    }
+
+    fn attestation_object(auth_data: Vec<u8>) -> String {
+        let attestation = Value::Map(vec![
+            (Value::Text("fmt".into()), Value::Text("none".into())),
+            (Value::Text("attStmt".into()), Value::Map(vec![])),
+            (Value::Text("authData".into()), Value::Bytes(auth_data)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&attestation, &mut bytes).unwrap();
+        encode(&bytes)
+    }
+
+    /// 对认证器数据和 clientDataJSON 签名，返回断言的三个字段
+    fn assertion(key: &TestKey, client_data_json: String, auth_data: Vec<u8>) -> [String; 3] {
+        let mut message = auth_data.clone();
+        message.extend_from_slice(&Sha256::digest(decode(&client_data_json).unwrap()));
+        let signature = key.sign(&message);
+        [client_data_json, encode(&auth_data), encode(&signature)]
+    }
+
+    fn verify(
+        key: &TestKey,
+        challenge: &str,
+        [client, data, sig]: [String; 3],
+    ) -> Result<VerifiedAssertion, String> {
+        verify_assertion(&RP, challenge, &key.cose_public_key(), &client, &data, &sig)
+    }
+
+    #[test]
+    fn registers_p256_and_rsa_credentials() {
+        for (key, algorithm) in [(TestKey::es256(), ES256), (TestKey::rs256(), RS256)] {
+            let challenge = generate_challenge();
+            let credential = verify_registration(
+                &RP,
+                &challenge,
+                &client_data("webauthn.create", &challenge, RP.origin),
+                &attestation_object(attested_data(&key, FLAG_USER_PRESENT)),
+            )
+            .unwrap();
+
+            assert_eq!(credential.credential_id, encode(CREDENTIAL_ID));
+            assert_eq!(credential.public_key, key.cose_public_key());
+            assert_eq!(credential.algorithm, algorithm);
+            assert_eq!(credential.sign_count, 0);
+        }
+    }
+
+    #[test]
+    fn verifies_p256_and_rsa_assertions() {
+        for key in [TestKey::es256(), TestKey::rs256()] {
+            let challenge = generate_challenge();
+            let flags = FLAG_USER_PRESENT | FLAG_USER_VERIFIED;
+            let signed = assertion(
+                &key,
+                client_data("webauthn.get", &challenge, RP.origin),
+                authenticator_data(RP.id, flags, 7),
+            );
+
+            let verified = verify(&key, &challenge, signed).unwrap();
+            assert_eq!(verified.sign_count, 7);
+            assert!(verified.user_verified);
+        }
+    }
+
+    #[test]
+    fn rejects_registration_problems() {
+        let key = TestKey::es256();
+        let challenge = generate_challenge();
+        let register = |client_data_json: String, auth_data: Vec<u8>| {
+            verify_registration(
+                &RP,
+                &challenge,
+                &client_data_json,
+                &attestation_object(auth_data),
+            )
+        };
+        let valid_client = || client_data("webauthn.create", &challenge, RP.origin);
+
+        // -- 类型、挑战和来源
+        assert!(
+            register(
+                client_data("webauthn.get", &challenge, RP.origin),
+                attested_data(&key, FLAG_USER_PRESENT)
+            )
+            .is_err()
+        );
+        assert!(
+            register(
+                client_data("webauthn.create", "other", RP.origin),
+                attested_data(&key, FLAG_USER_PRESENT)
+            )
+            .is_err()
+        );
+        assert!(
+            register(
+                client_data("webauthn.create", &challenge, "https://evil.example.com"),
+                attested_data(&key, FLAG_USER_PRESENT)
+            )
+            .is_err()
+        );
+
+        // -- 依赖方 ID 哈希
+        let mut wrong_rp = attested_data(&key, FLAG_USER_PRESENT);
+        wrong_rp[..32].copy_from_slice(&Sha256::digest(b"evil.example.com"));
+        assert!(register(valid_client(), wrong_rp).is_err());
+
+        // -- 用户不在场
+        assert!(register(valid_client(), attested_data(&key, 0)).is_err());
+
+        // -- 没有凭证数据
+        assert!(
+            register(
+                valid_client(),
+                authenticator_data(RP.id, FLAG_USER_PRESENT, 0)
+            )
+            .is_err()
+        );
+
+        // -- 截断的凭证数据：AAGUID、凭证 ID 长度、凭证 ID、公钥
+        let full = attested_data(&key, FLAG_USER_PRESENT);
+        for len in [
+            37,
+            37 + 10,
+            37 + 18,
+            37 + 18 + 5,
+            37 + 18 + CREDENTIAL_ID.len(),
+            full.len() - 1,
+        ] {
+            assert!(
+                register(valid_client(), full[..len].to_vec()).is_err(),
+                "length {}",
+                len
+            );
+        }
+
+        // -- 不支持的算法
+        let mut unsupported =
+            authenticator_data(RP.id, FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL, 0);
+        unsupported.extend_from_slice(&[0u8; 16]);
+        unsupported.extend_from_slice(&(CREDENTIAL_ID.len() as u16).to_be_bytes());
+        unsupported.extend_from_slice(CREDENTIAL_ID);
+        let mut cose = Vec::new();
+        let int = |value: i64| Value::Integer(value.into());
+        ciborium::into_writer(
+            &Value::Map(vec![(int(1), int(1)), (int(3), int(-8))]),
+            &mut cose,
+        )
+        .unwrap();
+        unsupported.extend_from_slice(&cose);
+        assert!(register(valid_client(), unsupported).is_err());
+    }
+
+    #[test]
+    fn rejects_assertion_problems() {
+        let key = TestKey::es256();
+        let challenge = generate_challenge();
+        let signed = |client_data_json: String, auth_data: Vec<u8>| {
+            verify(
+                &key,
+                &challenge,
+                assertion(&key, client_data_json, auth_data),
+            )
+        };
+        let valid_data = || authenticator_data(RP.id, FLAG_USER_PRESENT, 1);
+
+        // -- 注册的 clientDataJSON 不能用于认证
+        assert!(
+            signed(
+                client_data("webauthn.create", &challenge, RP.origin),
+                valid_data()
+            )
+            .is_err()
+        );
+        assert!(
+            signed(
+                client_data("webauthn.get", "other", RP.origin),
+                valid_data()
+            )
+            .is_err()
+        );
+        assert!(
+            signed(
+                client_data("webauthn.get", &challenge, "https://evil.example.com"),
+                valid_data()
+            )
+            .is_err()
+        );
+        assert!(
+            signed(
+                client_data("webauthn.get", &challenge, RP.origin),
+                authenticator_data("evil.example.com", FLAG_USER_PRESENT, 1)
+            )
+            .is_err()
+        );
+        assert!(
+            signed(
+                client_data("webauthn.get", &challenge, RP.origin),
+                authenticator_data(RP.id, FLAG_USER_VERIFIED, 1)
+            )
+            .is_err()
+        );
+        assert!(
+            signed(
+                client_data("webauthn.get", &challenge, RP.origin),
+                valid_data()[..36].to_vec()
+            )
+            .is_err()
+        );
+
+        // -- 签名后修改计数器
+        let [client, _, signature] = assertion(
+            &key,
+            client_data("webauthn.get", &challenge, RP.origin),
+            valid_data(),
+        );
+        let tampered = encode(&authenticator_data(RP.id, FLAG_USER_PRESENT, 2));
+        assert!(
+            verify(
+                &key,
+                &challenge,
+                [client.clone(), tampered, signature.clone()]
+            )
+            .is_err()
+        );
+
+        // -- 其他认证器的签名
+        let other = TestKey::es256();
+        let forged = assertion(
+            &other,
+            client_data("webauthn.get", &challenge, RP.origin),
+            valid_data(),
+        );
+        assert!(verify(&key, &challenge, forged).is_err());
+
+        // -- 用户未验证身份时结果中标记
+        let verified = signed(
+            client_data("webauthn.get", &challenge, RP.origin),
+            valid_data(),
+        )
+        .unwrap();
+        assert!(!verified.user_verified);
+    }
+}
@@ -0,0 +1,705 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::{DBClient, WebhookAttemptResult, WebhookExt},
+    models::{Webhook, WebhookDelivery, WebhookDeliveryStatus, WebhookEvent},
+};
+
+/// 签名请求头，格式为 `sha256=<hex>`，签名内容为 `{timestamp}.{body}`
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// 轮询待发送投递的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// 每次轮询最多发送的投递数量
+const BATCH_SIZE: i64 = 20;
+/// 重试间隔上限
+const MAX_RETRY_DELAY_SECONDS: u64 = 6 * 60 * 60;
+/// 响应日志中保留的响应体长度
+const MAX_RESPONSE_BODY: usize = 1024;
+
+/// 构造 Webhook 请求体
+pub fn event_payload(
+    event: WebhookEvent,
+    actor_id: Uuid,
+    data: serde_json::Value,
+) -> serde_json::Value {
+    serde_json::json!({
+        "event": event.to_str(),
+        "actor_id": actor_id,
+        "created_at": Utc::now(),
+        "data": data,
+    })
+}
+
+/// 计算请求签名，接收方用同一个 secret 对 `{timestamp}.{body}` 计算 HMAC-SHA256 后比对
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// 生成 Webhook 签名密钥
+pub fn generate_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// 投递设置
+#[derive(Debug, Clone, Copy)]
+pub struct DeliverySettings {
+    pub max_attempts: i32,
+    pub retry_base_seconds: u64,
+    pub timeout: Duration,
+    /// 允许投递到内网地址
+    pub allow_private_networks: bool,
+}
+
+impl DeliverySettings {
+    pub fn from_config(config: &Config) -> Self {
+        DeliverySettings {
+            max_attempts: config.webhook_max_attempts,
+            retry_base_seconds: config.webhook_retry_base_seconds,
+            timeout: Duration::from_secs(config.webhook_timeout_seconds),
+            allow_private_networks: config.webhook_allow_private_networks,
+        }
+    }
+
+    /// 认领的投递在租约到期前不会被再次认领，租约覆盖一整批请求的超时时间
+    fn lease_seconds(&self) -> i64 {
+        self.timeout.as_secs() as i64 * BATCH_SIZE + 60
+    }
+}
+
+/// 启动后台投递任务
+///
+/// 投递记录保存在数据库中，服务重启后未完成的投递会继续发送。
+/// 失败的投递按指数退避重试：`base * 2^(attempts - 1)`，达到最大次数后标记为失败。
+pub fn spawn_delivery_worker(db_client: DBClient, config: &Config) {
+    let settings = DeliverySettings::from_config(config);
+
+    tokio::spawn(async move {
+        let client = match delivery_client(&settings) {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!("创建 Webhook HTTP 客户端失败: {}", e);
+                return;
+            }
+        };
+
+        loop {
+            deliver_due(&client, &db_client, &settings).await;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// 创建投递使用的 HTTP 客户端
+///
+/// 不跟随重定向，否则公网地址可以 302 到内网地址；域名解析结果中的内网地址会被丢弃，
+/// 防止注册后通过 DNS 重新绑定指向内网。也不使用环境变量中的代理，代理会替我们解析域名。
+pub fn delivery_client(settings: &DeliverySettings) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .timeout(settings.timeout);
+
+    if !settings.allow_private_networks {
+        builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+
+    builder.build()
+}
+
+/// 认领并发送一批到期的投递，返回发送的数量
+pub async fn deliver_due(
+    client: &reqwest::Client,
+    db_client: &DBClient,
+    settings: &DeliverySettings,
+) -> usize {
+    let deliveries = match db_client
+        .claim_due_webhook_deliveries(BATCH_SIZE, settings.lease_seconds())
+        .await
+    {
+        Ok(deliveries) => deliveries,
+        Err(e) => {
+            tracing::error!("获取待发送的 Webhook 投递失败: {}", e);
+            return 0;
+        }
+    };
+
+    let count = deliveries.len();
+    for delivery in deliveries {
+        deliver(client, db_client, delivery, settings).await;
+    }
+    count
+}
+
+/// 检查 Webhook 地址：只允许 http/https，且主机不能解析到内网地址
+///
+/// 注册和修改 Webhook 时检查一次，每次投递前再检查一次。
+pub async fn check_url(url: &str, allow_private_networks: bool) -> Result<(), String> {
+    let url = reqwest::Url::parse(url).map_err(|_| "Webhook URL is invalid".to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Webhook URL must use http or https".to_string());
+    }
+
+    // -- IPv6 地址带方括号，例如 `[::1]`
+    let host = url
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| "Webhook URL must have a host".to_string())?;
+
+    if allow_private_networks {
+        return Ok(());
+    }
+
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| format!("Webhook host {} cannot be resolved", host))?
+        .collect();
+
+    if addresses.is_empty() || addresses.iter().any(|address| is_private_ip(address.ip())) {
+        return Err("Webhook URL must not point to a private network address".to_string());
+    }
+
+    Ok(())
+}
+
+/// 只返回公网地址的域名解析器
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|address| !is_private_ip(address.ip()))
+                .collect();
+
+            if addresses.is_empty() {
+                return Err(
+                    format!("{} does not resolve to a public address", name.as_str()).into(),
+                );
+            }
+
+            Ok(Box::new(addresses.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// 回环、私有网络、链路本地、唯一本地 (ULA)、未指定等不能从公网访问的地址
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_private_ipv4(ip);
+            }
+            is_private_ipv6(ip)
+        }
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8
+        || a == 0
+        // 运营商级 NAT 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // IETF 协议分配 192.0.0.0/24
+        || ip.octets()[..3] == [192, 0, 0]
+        // 基准测试 198.18.0.0/15 和保留地址 240.0.0.0/4
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // 已废弃的站点本地地址 fec0::/10
+        || (first & 0xffc0) == 0xfec0
+        // 文档地址 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8)
+        // 兼容 IPv4 的地址 ::/96 和 NAT64 64:ff9b::/96 指向的 IPv4 地址
+        || ip.to_ipv4().is_some_and(is_private_ipv4)
+        || (ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+            && is_private_ipv4(Ipv4Addr::from_bits(ip.to_bits() as u32)))
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    db_client: &DBClient,
+    delivery: WebhookDelivery,
+    settings: &DeliverySettings,
+) {
+    let webhook = match db_client.get_webhook(delivery.webhook_id).await {
+        Ok(Some(webhook)) => webhook,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("获取 Webhook {} 失败: {}", delivery.webhook_id, e);
+            return;
+        }
+    };
+
+    // -- 域名可能在注册后被改为指向内网地址
+    let result = match check_url(&webhook.url, settings.allow_private_networks).await {
+        Ok(()) => send(client, &webhook, &delivery).await,
+        Err(e) => WebhookAttemptResult {
+            response_status: None,
+            response_body: None,
+            error: Some(e),
+            duration_ms: 0,
+        },
+    };
+    let succeeded = result
+        .response_status
+        .is_some_and(|status| (200..300).contains(&status));
+
+    let attempts = delivery.attempts + 1;
+    let (status, next_attempt_at) = if succeeded {
+        (WebhookDeliveryStatus::Succeeded, Utc::now())
+    } else if attempts >= settings.max_attempts {
+        (WebhookDeliveryStatus::Failed, Utc::now())
+    } else {
+        let delay = retry_delay_seconds(settings.retry_base_seconds, attempts);
+        (
+            WebhookDeliveryStatus::Pending,
+            Utc::now() + chrono::Duration::seconds(delay as i64),
+        )
+    };
+
+    tracing::info!(
+        "Webhook 投递 {} ({}) 第 {} 次尝试，响应状态: {:?}",
+        delivery.id,
+        delivery.event,
+        attempts,
+        result.response_status
+    );
+
+    if let Err(e) = db_client
+        .record_webhook_attempt(delivery.id, result, status, next_attempt_at)
+        .await
+    {
+        tracing::error!("记录 Webhook 投递 {} 结果失败: {}", delivery.id, e);
+    }
+}
+
+/// 第 `attempts` 次失败后的重试间隔：`base * 2^(attempts - 1)`，不超过上限
+fn retry_delay_seconds(retry_base_seconds: u64, attempts: i32) -> u64 {
+    retry_base_seconds
+        .saturating_mul(1 << (attempts - 1).clamp(0, 20))
+        .min(MAX_RETRY_DELAY_SECONDS)
+}
+
+async fn send(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+) -> WebhookAttemptResult {
+    let body = delivery.payload.to_string();
+    let timestamp = Utc::now().timestamp();
+    let started = Instant::now();
+
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) => {
+            let status = response.status().as_u16() as i32;
+            let body = response.text().await.unwrap_or_default();
+
+            WebhookAttemptResult {
+                response_status: Some(status),
+                response_body: Some(body.chars().take(MAX_RESPONSE_BODY).collect()),
+                error: None,
+                duration_ms: started.elapsed().as_millis() as i64,
+            }
+        }
+        Err(e) => WebhookAttemptResult {
+            response_status: None,
+            response_body: None,
+            error: Some(e.to_string()),
+            duration_ms: started.elapsed().as_millis() as i64,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use axum::{Router, http::HeaderMap, http::StatusCode, routing::post};
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::db::{DocumentExt, UserExt};
+
+    /// 本地接收端收到的请求
+    #[derive(Debug, Clone)]
+    struct Received {
+        headers: HeaderMap,
+        body: String,
+    }
+
+    /// 启动本地接收端，依次返回 `statuses` 中的状态码，之后一律返回 200
+    async fn start_receiver(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let received = received.clone();
+                move |headers: HeaderMap, body: String| async move {
+                    received.lock().unwrap().push(Received { headers, body });
+                    let status = statuses.lock().unwrap().pop_front().unwrap_or(200);
+                    StatusCode::from_u16(status).unwrap()
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}/hook", address), received)
+    }
+
+    /// `#[sqlx::test]` 运行在 async-std 上，本地接收端和 reqwest 需要 Tokio 运行时
+    fn on_tokio<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    fn settings() -> DeliverySettings {
+        DeliverySettings {
+            max_attempts: 3,
+            retry_base_seconds: 30,
+            timeout: Duration::from_secs(5),
+            allow_private_networks: true,
+        }
+    }
+
+    /// 注册一个订阅 document.updated 的 Webhook 并触发一次投递
+    async fn queue_delivery(db_client: &DBClient, url: &str, secret: &str) -> serde_json::Value {
+        let user = db_client
+            .save_user("owner", "owner@example.com", "hash", "token", Utc::now())
+            .await
+            .unwrap();
+        let document = db_client
+            .create_document("Doc", "{}", user.id, false)
+            .await
+            .unwrap();
+        db_client
+            .create_webhook(
+                user.id,
+                None,
+                url.to_string(),
+                secret.to_string(),
+                vec![WebhookEvent::DocumentUpdated.to_str().to_string()],
+            )
+            .await
+            .unwrap();
+
+        let payload = event_payload(
+            WebhookEvent::DocumentUpdated,
+            user.id,
+            serde_json::json!({ "document": { "id": document.id } }),
+        );
+        let queued = db_client
+            .enqueue_webhook_deliveries(WebhookEvent::DocumentUpdated, document.id, payload.clone())
+            .await
+            .unwrap();
+        assert_eq!(queued, 1);
+
+        payload
+    }
+
+    async fn only_delivery(pool: &PgPool) -> WebhookDelivery {
+        sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT id, webhook_id, event, payload, status as "status: WebhookDeliveryStatus",
+                   attempts, next_attempt_at, last_attempt_at, response_status,
+                   created_at, updated_at
+            FROM webhook_deliveries
+            "#
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn signs_deliveries_and_retries_server_errors(pool: PgPool) {
+        on_tokio(async move {
+            let db_client = DBClient::new(pool.clone());
+            let (url, received) = start_receiver(vec![503]).await;
+            let secret = generate_secret();
+            let payload = queue_delivery(&db_client, &url, &secret).await;
+
+            let settings = settings();
+            let client = delivery_client(&settings).unwrap();
+
+            // -- 第一次投递收到 503，按退避间隔重新排期
+            assert_eq!(deliver_due(&client, &db_client, &settings).await, 1);
+
+            let request = received.lock().unwrap()[0].clone();
+            let header = |name: &str| request.headers[name].to_str().unwrap().to_string();
+            let timestamp: i64 = header(TIMESTAMP_HEADER).parse().unwrap();
+
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+            mac.update(format!("{}.{}", timestamp, request.body).as_bytes());
+            let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+            assert_eq!(header(SIGNATURE_HEADER), expected);
+            assert_eq!(header(EVENT_HEADER), "document.updated");
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&request.body).unwrap(),
+                payload
+            );
+
+            let delivery = only_delivery(&pool).await;
+            assert_eq!(header(DELIVERY_HEADER), delivery.id.to_string());
+            assert_eq!(delivery.status, WebhookDeliveryStatus::Pending);
+            assert_eq!(delivery.attempts, 1);
+            assert_eq!(delivery.response_status, Some(503));
+            let delay = (delivery.next_attempt_at - Utc::now()).num_seconds();
+            assert!((25..=30).contains(&delay), "retry in {} seconds", delay);
+
+            // -- 退避期间不会重发
+            assert_eq!(deliver_due(&client, &db_client, &settings).await, 0);
+            assert_eq!(received.lock().unwrap().len(), 1);
+
+            // -- 到期后重试成功
+            sqlx::query!("UPDATE webhook_deliveries SET next_attempt_at = NOW()")
+                .execute(&pool)
+                .await
+                .unwrap();
+            assert_eq!(deliver_due(&client, &db_client, &settings).await, 1);
+
+            let delivery = only_delivery(&pool).await;
+            assert_eq!(delivery.status, WebhookDeliveryStatus::Succeeded);
+            assert_eq!(delivery.attempts, 2);
+            assert_eq!(received.lock().unwrap().len(), 2);
+
+            let attempts = db_client
+                .get_webhook_delivery_attempts(delivery.id)
+                .await
+                .unwrap();
+            let statuses: Vec<_> = attempts.iter().map(|a| a.response_status).collect();
+            assert_eq!(statuses, vec![Some(503), Some(200)]);
+        });
+    }
+
+    #[sqlx::test]
+    async fn gives_up_after_max_attempts(pool: PgPool) {
+        on_tokio(async move {
+            let db_client = DBClient::new(pool.clone());
+            let (url, received) = start_receiver(vec![500, 500, 500]).await;
+            queue_delivery(&db_client, &url, &generate_secret()).await;
+
+            let settings = settings();
+            let client = delivery_client(&settings).unwrap();
+
+            for _ in 0..settings.max_attempts {
+                sqlx::query!("UPDATE webhook_deliveries SET next_attempt_at = NOW()")
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                assert_eq!(deliver_due(&client, &db_client, &settings).await, 1);
+            }
+
+            let delivery = only_delivery(&pool).await;
+            assert_eq!(delivery.status, WebhookDeliveryStatus::Failed);
+            assert_eq!(delivery.attempts, 3);
+            assert_eq!(received.lock().unwrap().len(), 3);
+        });
+    }
+
+    #[sqlx::test]
+    async fn concurrent_workers_deliver_once(pool: PgPool) {
+        on_tokio(async move {
+            let db_client = DBClient::new(pool.clone());
+            let (url, received) = start_receiver(vec![]).await;
+            queue_delivery(&db_client, &url, &generate_secret()).await;
+
+            let settings = settings();
+            let client = delivery_client(&settings).unwrap();
+
+            let (first, second, third) = tokio::join!(
+                deliver_due(&client, &db_client, &settings),
+                deliver_due(&client, &db_client, &settings),
+                deliver_due(&client, &db_client, &settings),
+            );
+
+            assert_eq!(first + second + third, 1);
+            assert_eq!(received.lock().unwrap().len(), 1);
+            assert_eq!(
+                only_delivery(&pool).await.status,
+                WebhookDeliveryStatus::Succeeded
+            );
+        });
+    }
+
+    #[sqlx::test]
+    async fn claimed_deliveries_are_leased(pool: PgPool) {
+        let db_client = DBClient::new(pool.clone());
+        queue_delivery(&db_client, "https://example.com/hook", "secret").await;
+
+        let claimed = db_client
+            .claim_due_webhook_deliveries(10, 60)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 1);
+
+        // -- 租约到期前不会被其他实例认领
+        let again = db_client
+            .claim_due_webhook_deliveries(10, 60)
+            .await
+            .unwrap();
+        assert!(again.is_empty());
+
+        // -- 认领后崩溃的实例在租约到期后由其他实例接手
+        sqlx::query!("UPDATE webhook_deliveries SET next_attempt_at = NOW()")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let reclaimed = db_client
+            .claim_due_webhook_deliveries(10, 60)
+            .await
+            .unwrap();
+        assert_eq!(reclaimed.len(), 1);
+        assert_eq!(reclaimed[0].id, claimed[0].id);
+    }
+
+    #[sqlx::test]
+    async fn refuses_private_addresses_at_delivery(pool: PgPool) {
+        on_tokio(async move {
+            let db_client = DBClient::new(pool.clone());
+            let (url, received) = start_receiver(vec![]).await;
+            queue_delivery(&db_client, &url, &generate_secret()).await;
+
+            let settings = DeliverySettings {
+                allow_private_networks: false,
+                ..settings()
+            };
+            let client = delivery_client(&settings).unwrap();
+
+            assert_eq!(deliver_due(&client, &db_client, &settings).await, 1);
+            assert!(received.lock().unwrap().is_empty());
+
+            let delivery = only_delivery(&pool).await;
+            assert_eq!(delivery.status, WebhookDeliveryStatus::Pending);
+            assert_eq!(delivery.response_status, None);
+        });
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_limit() {
+        assert_eq!(retry_delay_seconds(30, 1), 30);
+        assert_eq!(retry_delay_seconds(30, 2), 60);
+        assert_eq!(retry_delay_seconds(30, 4), 240);
+        assert_eq!(retry_delay_seconds(30, 30), MAX_RETRY_DELAY_SECONDS);
+    }
+
+    #[test]
+    fn signature_matches_known_value() {
+        // echo -n '1700000000.{"a":1}' | openssl dgst -sha256 -hmac whsec_test
+        assert_eq!(
+            sign("whsec_test", 1_700_000_000, r#"{"a":1}"#),
+            "sha256=38877139021993b830af32feea6e18a8da83eb2f6e49ee50bd9e4cf4ca4d3789"
+        );
+    }
+
+    #[test]
+    fn detects_private_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{} is private", ip);
+        }
+
+        for ip in [
+            "93.184.216.34",
+            "8.8.8.8",
+            "2606:4700:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{} is public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_private_webhook_urls() {
+        for url in [
+            "http://127.0.0.1:5432/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://10.0.0.1/hook",
+            "http://[::1]:8080/hook",
+            "http://[fd00::1]/hook",
+            "http://localhost/hook",
+            "ftp://example.com/hook",
+            "not a url",
+        ] {
+            assert!(check_url(url, false).await.is_err(), "{} is rejected", url);
+        }
+
+        assert!(check_url("http://127.0.0.1:8080/hook", true).await.is_ok());
+        assert!(check_url("ftp://127.0.0.1/hook", true).await.is_err());
+    }
+}